@@ -0,0 +1,157 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager};
+use tracing::{info, warn};
+
+/// Keyring service name under which account passwords are stored
+const KEYRING_SERVICE: &str = "softphone-app";
+
+/// Accounts file inside the app config dir
+const ACCOUNTS_FILE: &str = "accounts.json";
+
+/// A saved SIP account. The password is kept in the OS keyring; only the
+/// keyring entry name is written to disk.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AccountProfile {
+    /// Stable identifier, `username@server`
+    #[serde(default)]
+    pub id: String,
+    pub server: String,
+    pub username: String,
+    #[serde(default)]
+    pub outbound_proxy: Option<String>,
+    #[serde(default = "default_prefer_srtp")]
+    pub prefer_srtp: bool,
+    /// Preferred transport ("udp", "tcp", "tls", "ws", "wss"); `None` lets the server URI decide
+    #[serde(default)]
+    pub transport: Option<String>,
+    /// Keyring entry (user name under `KEYRING_SERVICE`) holding the password, if one was saved
+    #[serde(default)]
+    pub password_ref: Option<String>,
+}
+
+fn default_prefer_srtp() -> bool {
+    true
+}
+
+impl AccountProfile {
+    pub fn account_id(username: &str, server: &str) -> String {
+        format!("{}@{}", username, server)
+    }
+
+    /// Server string for `sip_register`, with the preferred transport applied
+    /// unless the server already specifies one.
+    pub fn server_uri(&self) -> String {
+        match self.transport.as_deref() {
+            Some(transport)
+                if !self.server.contains("transport=")
+                    && !self.server.starts_with("ws://")
+                    && !self.server.starts_with("wss://") =>
+            {
+                format!("{};transport={}", self.server, transport)
+            }
+            _ => self.server.clone(),
+        }
+    }
+}
+
+fn accounts_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app_handle
+        .path()
+        .app_config_dir()
+        .map_err(|e| format!("Failed to resolve config dir: {}", e))?;
+    Ok(dir.join(ACCOUNTS_FILE))
+}
+
+fn keyring_entry(password_ref: &str) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYRING_SERVICE, password_ref).map_err(|e| format!("Keyring error: {}", e))
+}
+
+/// Load all saved accounts. A missing file means no accounts yet.
+pub fn load_accounts(app_handle: &AppHandle) -> Result<Vec<AccountProfile>, String> {
+    let path = accounts_path(app_handle)?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let data = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    serde_json::from_str(&data).map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+}
+
+fn write_accounts(app_handle: &AppHandle, accounts: &[AccountProfile]) -> Result<(), String> {
+    let path = accounts_path(app_handle)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let data = serde_json::to_string_pretty(accounts)
+        .map_err(|e| format!("Failed to serialize accounts: {}", e))?;
+    std::fs::write(&path, data).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Save (insert or replace) an account. When `password` is given it is stored
+/// in the OS keyring; otherwise any previously stored password is kept.
+pub fn save_account(
+    app_handle: &AppHandle,
+    mut profile: AccountProfile,
+    password: Option<&str>,
+) -> Result<AccountProfile, String> {
+    profile.id = AccountProfile::account_id(&profile.username, &profile.server);
+
+    let mut accounts = load_accounts(app_handle)?;
+    let existing = accounts.iter().position(|a| a.id == profile.id);
+
+    match password {
+        Some(password) => {
+            keyring_entry(&profile.id)?
+                .set_password(password)
+                .map_err(|e| format!("Failed to store password in keyring: {}", e))?;
+            profile.password_ref = Some(profile.id.clone());
+        }
+        None => {
+            profile.password_ref = existing.and_then(|i| accounts[i].password_ref.clone());
+        }
+    }
+
+    match existing {
+        Some(i) => accounts[i] = profile.clone(),
+        None => accounts.push(profile.clone()),
+    }
+    write_accounts(app_handle, &accounts)?;
+
+    info!(account = %profile.id, "Account saved");
+    Ok(profile)
+}
+
+/// Delete an account and its keyring entry.
+pub fn delete_account(app_handle: &AppHandle, id: &str) -> Result<(), String> {
+    let mut accounts = load_accounts(app_handle)?;
+    let index = accounts
+        .iter()
+        .position(|a| a.id == id)
+        .ok_or_else(|| format!("Account not found: {}", id))?;
+    let profile = accounts.remove(index);
+
+    if let Some(ref password_ref) = profile.password_ref {
+        match keyring_entry(password_ref)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => {}
+            Err(e) => warn!(account = %id, error = %e, "Failed to delete keyring entry"),
+        }
+    }
+
+    write_accounts(app_handle, &accounts)?;
+    info!(account = %id, "Account deleted");
+    Ok(())
+}
+
+/// Read an account's password from the keyring (`None` if none was saved).
+pub fn account_password(profile: &AccountProfile) -> Result<Option<String>, String> {
+    let Some(ref password_ref) = profile.password_ref else {
+        return Ok(None);
+    };
+    match keyring_entry(password_ref)?.get_password() {
+        Ok(password) => Ok(Some(password)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(format!("Failed to read password from keyring: {}", e)),
+    }
+}
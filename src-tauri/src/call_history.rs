@@ -0,0 +1,171 @@
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+use tracing::{debug, warn};
+
+/// Call log inside the app data dir, one JSON record per line
+const HISTORY_FILE: &str = "call_history.jsonl";
+
+/// Serializes appends and clearing of the log file
+static FILE_LOCK: Mutex<()> = Mutex::new(());
+
+/// One entry of the call log. Times are Unix timestamps in milliseconds.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CallRecord {
+    pub call_id: String,
+    pub account_id: String,
+    /// "inbound" or "outbound"
+    pub direction: String,
+    /// Caller of an inbound call, callee of an outbound one
+    pub remote_party: String,
+    pub start_time: i64,
+    /// When the call was reported as "connected"; `None` if it never was
+    #[serde(default)]
+    pub answer_time: Option<i64>,
+    #[serde(default)]
+    pub end_time: Option<i64>,
+    /// `reason` of the "ended" call state, e.g. "canceled", "rejected", "hangup"
+    #[serde(default)]
+    pub end_reason: Option<String>,
+    #[serde(default)]
+    pub codec: Option<String>,
+    /// An inbound call that ended without being answered
+    #[serde(default)]
+    pub missed: bool,
+}
+
+impl CallRecord {
+    /// Start the record of a call that is ringing or being dialed.
+    pub fn new(call_id: &str, account_id: &str, direction: &str, remote_party: &str) -> Self {
+        Self {
+            call_id: call_id.to_string(),
+            account_id: account_id.to_string(),
+            direction: direction.to_string(),
+            remote_party: remote_party.to_string(),
+            start_time: chrono::Utc::now().timestamp_millis(),
+            answer_time: None,
+            end_time: None,
+            end_reason: None,
+            codec: None,
+            missed: false,
+        }
+    }
+
+    /// Note the "connected" transition.
+    pub fn answered(&mut self, codec: Option<&str>) {
+        self.answer_time = Some(chrono::Utc::now().timestamp_millis());
+        self.codec = codec.map(str::to_string);
+    }
+}
+
+fn history_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve data dir: {}", e))?;
+    Ok(dir.join(HISTORY_FILE))
+}
+
+/// Close a call's record with the "ended" transition and append it to the log.
+/// Failures are only logged: they must not get in the way of the call teardown.
+pub fn record_call(app_handle: &AppHandle, mut record: CallRecord, reason: &str) {
+    record.end_time = Some(chrono::Utc::now().timestamp_millis());
+    record.end_reason = Some(reason.to_string());
+    record.missed = record.direction == "inbound" && record.answer_time.is_none();
+
+    if let Err(e) = append_record(app_handle, &record) {
+        warn!(call_id = %record.call_id, error = %e, "Failed to write call history");
+        return;
+    }
+    debug!(call_id = %record.call_id, reason = %reason, missed = record.missed, "Call recorded in history");
+}
+
+fn append_record(app_handle: &AppHandle, record: &CallRecord) -> Result<(), String> {
+    let path = history_path(app_handle)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let mut line = serde_json::to_string(record)
+        .map_err(|e| format!("Failed to serialize call record: {}", e))?;
+    line.push('\n');
+
+    let _guard = FILE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// The most recent calls first, at most `limit` of them. A missing file means
+/// no calls yet.
+pub fn load_call_history(
+    app_handle: &AppHandle,
+    limit: Option<usize>,
+) -> Result<Vec<CallRecord>, String> {
+    let path = history_path(app_handle)?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let data = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Ok(parse_history(&data, limit))
+}
+
+/// Delete the whole call log.
+pub fn clear_call_history(app_handle: &AppHandle) -> Result<(), String> {
+    let path = history_path(app_handle)?;
+    let _guard = FILE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    match std::fs::remove_file(&path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(format!("Failed to delete {}: {}", path.display(), e)),
+    }
+}
+
+/// Records of a JSON-lines log, newest first. Unreadable lines (e.g. one cut
+/// short by a crash) are skipped.
+fn parse_history(data: &str, limit: Option<usize>) -> Vec<CallRecord> {
+    data.lines()
+        .rev()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str(line) {
+            Ok(record) => Some(record),
+            Err(e) => {
+                warn!(error = %e, "Skipping unreadable call history entry");
+                None
+            }
+        })
+        .take(limit.unwrap_or(usize::MAX))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_history_newest_first_with_limit() {
+        let record = |id: &str| {
+            serde_json::to_string(&CallRecord::new(id, "alice@pbx", "inbound", "bob")).unwrap()
+        };
+        let data = format!(
+            "{}\n{}\n{{\"call_id\":\n{}\n",
+            record("a"),
+            record("b"),
+            record("c")
+        );
+
+        let all = parse_history(&data, None);
+        let ids: Vec<_> = all.iter().map(|r| r.call_id.as_str()).collect();
+        assert_eq!(ids, ["c", "b", "a"]);
+
+        let latest = parse_history(&data, Some(2));
+        assert_eq!(latest.len(), 2);
+        assert_eq!(latest[0].call_id, "c");
+    }
+}
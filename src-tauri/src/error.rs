@@ -0,0 +1,193 @@
+//! Errors returned by the Tauri commands.
+//!
+//! Serialized as `{ "code": "...", "message": "..." }` so the frontend can
+//! branch on `code` and show `message` to the user. Errors caused by a SIP
+//! response also carry its status code as `"status"`.
+
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+
+#[derive(Debug)]
+pub enum AppError {
+    /// No account is registered, or not the requested one
+    NotRegistered(Option<String>),
+    AlreadyRegistered,
+    /// There is no call to act on
+    NoActiveCall,
+    /// The given call_id does not belong to a call
+    CallNotFound(String),
+    DeviceNotFound(String),
+    /// The registrar answered REGISTER with a non-200 status
+    RegistrationFailed(rsip::StatusCode),
+    /// The registrar rejected the credentials (401/407 after authenticating, or 403)
+    AuthenticationFailed(rsip::StatusCode),
+    /// REGISTER got no answer (408, including the local transaction timeout)
+    RegistrationTimeout,
+    /// DNS, socket or WebSocket failure
+    Transport(String),
+    /// Our side could not connect a call's media (no ICE candidates, RTP
+    /// connection timeout); a fresh attempt may get past it
+    MediaFailed(String),
+    /// The remote answered our INVITE with a non-2xx final response
+    CallRejected(rsip::StatusCode),
+    Other(String),
+}
+
+impl AppError {
+    /// Machine-readable identifier of the error kind
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::NotRegistered(_) => "not_registered",
+            AppError::AlreadyRegistered => "already_registered",
+            AppError::NoActiveCall => "no_active_call",
+            AppError::CallNotFound(_) => "call_not_found",
+            AppError::DeviceNotFound(_) => "device_not_found",
+            AppError::RegistrationFailed(_) => "registration_failed",
+            AppError::AuthenticationFailed(_) => "auth_failed",
+            AppError::RegistrationTimeout => "timeout",
+            AppError::Transport(_) => "transport",
+            AppError::MediaFailed(_) => "media_failed",
+            AppError::CallRejected(_) => "call_rejected",
+            AppError::Other(_) => "error",
+        }
+    }
+
+    /// Error for a non-200 final response to REGISTER
+    pub fn from_register_status(status: rsip::StatusCode) -> Self {
+        match status {
+            rsip::StatusCode::Unauthorized
+            | rsip::StatusCode::ProxyAuthenticationRequired
+            | rsip::StatusCode::Forbidden => AppError::AuthenticationFailed(status),
+            rsip::StatusCode::RequestTimeout => AppError::RegistrationTimeout,
+            _ => AppError::RegistrationFailed(status),
+        }
+    }
+
+    /// SIP status code of the response behind the error, if any
+    pub fn status_code(&self) -> Option<u16> {
+        match self {
+            AppError::RegistrationFailed(status)
+            | AppError::AuthenticationFailed(status)
+            | AppError::CallRejected(status) => Some(status.code()),
+            AppError::RegistrationTimeout => Some(408),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AppError::NotRegistered(Some(id)) => write!(f, "Account not registered: {}", id),
+            AppError::NotRegistered(None) => write!(f, "Not registered"),
+            AppError::AlreadyRegistered => write!(f, "Already registered"),
+            AppError::NoActiveCall => write!(f, "No active call"),
+            AppError::CallNotFound(id) => write!(f, "No active call found for call_id: {}", id),
+            AppError::DeviceNotFound(id) => write!(f, "Audio device not found: {}", id),
+            AppError::RegistrationFailed(status) => write!(f, "Registration failed: {}", status),
+            AppError::AuthenticationFailed(status) => write!(
+                f,
+                "Authentication failed: {} (check the username and password)",
+                status
+            ),
+            AppError::RegistrationTimeout => {
+                write!(f, "Registration timed out: no answer from the server")
+            }
+            AppError::Transport(msg) => write!(f, "Network error: {}", msg),
+            AppError::MediaFailed(msg) => write!(f, "Media connection failed: {}", msg),
+            AppError::CallRejected(status) => write!(f, "Call rejected: {}", status),
+            AppError::Other(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("AppError", 3)?;
+        s.serialize_field("code", self.code())?;
+        s.serialize_field("message", &self.to_string())?;
+        match self.status_code() {
+            Some(status) => s.serialize_field("status", &status)?,
+            None => s.skip_field("status")?,
+        }
+        s.end()
+    }
+}
+
+impl From<String> for AppError {
+    fn from(msg: String) -> Self {
+        AppError::Other(msg)
+    }
+}
+
+impl From<&str> for AppError {
+    fn from(msg: &str) -> Self {
+        AppError::Other(msg.to_string())
+    }
+}
+
+/// Lets code that still reports plain strings use `?` on `AppError`s
+impl From<AppError> for String {
+    fn from(e: AppError) -> Self {
+        e.to_string()
+    }
+}
+
+impl From<rsipstack::Error> for AppError {
+    fn from(e: rsipstack::Error) -> Self {
+        match e {
+            rsipstack::Error::DnsResolutionError(msg) => AppError::Transport(msg),
+            rsipstack::Error::TransportLayerError(msg, addr) => {
+                AppError::Transport(format!("{}: {}", msg, addr))
+            }
+            rsipstack::Error::IoError(e) => AppError::Transport(e.to_string()),
+            rsipstack::Error::WebSocketError(e) => AppError::Transport(e.to_string()),
+            // Plain messages, without the "Error: " prefix of their Display
+            rsipstack::Error::Error(msg) => AppError::Other(msg),
+            other => AppError::Other(other.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialize_code_and_message() {
+        let json = serde_json::to_value(AppError::NoActiveCall).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({ "code": "no_active_call", "message": "No active call" })
+        );
+
+        let json = serde_json::to_value(AppError::RegistrationFailed(rsip::StatusCode::Forbidden))
+            .unwrap();
+        assert_eq!(json["code"], "registration_failed");
+        assert_eq!(json["status"], 403);
+    }
+
+    #[test]
+    fn test_register_status_kinds() {
+        let e = AppError::from_register_status(rsip::StatusCode::Forbidden);
+        assert_eq!(e.code(), "auth_failed");
+        let e = AppError::from_register_status(rsip::StatusCode::Unauthorized);
+        assert_eq!(e.code(), "auth_failed");
+        let e = AppError::from_register_status(rsip::StatusCode::RequestTimeout);
+        assert_eq!(e.code(), "timeout");
+        assert_eq!(e.status_code(), Some(408));
+        let e = AppError::from_register_status(rsip::StatusCode::ServiceUnavailable);
+        assert_eq!(e.code(), "registration_failed");
+        assert_eq!(e.status_code(), Some(503));
+    }
+
+    #[test]
+    fn test_from_rsipstack_error() {
+        let e: AppError = rsipstack::Error::Error("Transfer rejected: 603".to_string()).into();
+        assert_eq!(e.to_string(), "Transfer rejected: 603");
+        let e: AppError = rsipstack::Error::DnsResolutionError("pbx.example".to_string()).into();
+        assert_eq!(e.code(), "transport");
+    }
+}
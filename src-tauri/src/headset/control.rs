@@ -0,0 +1,163 @@
+//! Headset button actions and LED updates while call control is enabled.
+
+use super::descriptor;
+use super::hidraw;
+use crate::error::AppError;
+use crate::sip;
+use crate::sip::state::SipAppState;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+/// How often the LEDs are brought in line with the calls
+const LED_REFRESH_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// A button change reported by a headset
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HidButton {
+    /// New hook state, `true` = off-hook
+    HookSwitch(bool),
+    Mute,
+}
+
+/// Payload of `sip://hid-button`
+#[derive(Clone, Serialize)]
+pub struct HidButtonPayload {
+    /// "hook-switch" or "mute"
+    pub button: String,
+    /// Hook state reported by the headset (hook switch only)
+    pub off_hook: Option<bool>,
+    /// What the button did: "answer", "reject", "hangup", "mute", or nothing
+    pub action: Option<String>,
+    pub call_id: Option<String>,
+}
+
+pub fn start(app_handle: &AppHandle) -> Result<CancellationToken, String> {
+    let cancel_token = CancellationToken::new();
+    let (leds_tx, leds_rx) = std::sync::mpsc::channel();
+    let (buttons_tx, mut buttons_rx) = tokio::sync::mpsc::unbounded_channel();
+
+    let watcher_token = cancel_token.clone();
+    std::thread::Builder::new()
+        .name("headset-hid".to_string())
+        .spawn(move || hidraw::watch(watcher_token, leds_rx, buttons_tx))
+        .map_err(|e| format!("Failed to start headset control: {}", e))?;
+
+    let app_handle = app_handle.clone();
+    let task_token = cancel_token.clone();
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(LED_REFRESH_INTERVAL);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        let mut sent = None;
+        loop {
+            tokio::select! {
+                Some(button) = buttons_rx.recv() => on_button(&app_handle, button).await,
+                _ = ticker.tick() => {
+                    let leds = current_leds(&app_handle.state::<SipAppState>()).await;
+                    if sent != Some(leds) {
+                        if leds_tx.send(leds).is_err() {
+                            break;
+                        }
+                        sent = Some(leds);
+                    }
+                }
+                _ = task_token.cancelled() => break,
+            }
+        }
+    });
+    Ok(cancel_token)
+}
+
+/// LEDs matching the calls: ringing while a call waits to be answered,
+/// off-hook while a call is up, mute while its microphone is muted
+async fn current_leds(state: &SipAppState) -> descriptor::Leds {
+    let handles: Vec<_> = state.handles.lock().await.values().cloned().collect();
+    let mut leds = descriptor::Leds::default();
+    for handle in handles {
+        leds.ring |= !handle.pending_incoming.lock().await.is_empty();
+        let active = handle.active_calls.lock().await;
+        leds.off_hook |= !active.is_empty();
+        if let Some(session) = active.current().and_then(|c| c.webrtc_session.as_ref()) {
+            leds.mute |= session.mute_state().0;
+        }
+    }
+    leds
+}
+
+async fn on_button(app_handle: &AppHandle, button: HidButton) {
+    let state = app_handle.state::<SipAppState>();
+    let (action, call_id) = match act_on_button(&state, button).await {
+        Ok(Some((action, call_id))) => (Some(action.to_string()), Some(call_id)),
+        Ok(None) => (None, None),
+        Err(e) => {
+            warn!(?button, error = %e, "Headset button action failed");
+            (None, None)
+        }
+    };
+    info!(?button, ?action, "Headset button");
+    let (name, off_hook) = match button {
+        HidButton::HookSwitch(off_hook) => ("hook-switch", Some(off_hook)),
+        HidButton::Mute => ("mute", None),
+    };
+    let _ = app_handle.emit(
+        "sip://hid-button",
+        HidButtonPayload {
+            button: name.to_string(),
+            off_hook,
+            action,
+            call_id,
+        },
+    );
+}
+
+/// Carry out what `button` means for the calls, returning the action taken
+/// and the call it applied to.
+async fn act_on_button(
+    state: &SipAppState,
+    button: HidButton,
+) -> Result<Option<(&'static str, String)>, AppError> {
+    let (current, ringing) = find_calls(state).await;
+    match (button, current, ringing) {
+        (HidButton::HookSwitch(true), _, Some(call_id)) => {
+            crate::answer_call(state, call_id.clone(), None, false).await?;
+            Ok(Some(("answer", call_id)))
+        }
+        (HidButton::HookSwitch(false), Some(call_id), _) => {
+            let handle = crate::call_handle(state, Some(&call_id)).await?;
+            sip::handle_hangup(&handle, Some(call_id.clone())).await?;
+            Ok(Some(("hangup", call_id)))
+        }
+        (HidButton::HookSwitch(false), None, Some(call_id)) => {
+            let handle = crate::call_handle(state, Some(&call_id)).await?;
+            sip::handle_reject_call(&handle, call_id.clone(), None).await?;
+            Ok(Some(("reject", call_id)))
+        }
+        (HidButton::Mute, Some(call_id), _) => {
+            let handle = crate::call_handle(state, Some(&call_id)).await?;
+            sip::handle_toggle_mic_mute(&handle).await?;
+            Ok(Some(("mute", call_id)))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// The current call and the first call waiting to be answered, on any account
+async fn find_calls(state: &SipAppState) -> (Option<String>, Option<String>) {
+    let handles: Vec<_> = state.handles.lock().await.values().cloned().collect();
+    let (mut current, mut ringing) = (None, None);
+    for handle in handles {
+        if current.is_none() {
+            current = handle
+                .active_calls
+                .lock()
+                .await
+                .current()
+                .map(|c| c.call_id.clone());
+        }
+        if ringing.is_none() {
+            ringing = handle.pending_incoming.lock().await.keys().next().cloned();
+        }
+    }
+    (current, ringing)
+}
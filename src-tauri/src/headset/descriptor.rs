@@ -0,0 +1,288 @@
+//! HID report descriptor parsing (HID 1.11 §6.2.2), limited to what headset
+//! call control needs: where the Hook Switch and Phone Mute buttons sit in the
+//! input reports and where the Off-Hook, Ring and Mute LEDs sit in the output
+//! reports. Only variable (bit field) items are located; array items are not.
+
+use std::collections::{BTreeMap, HashMap};
+
+const USAGE_PAGE_LED: u32 = 0x08;
+const USAGE_PAGE_TELEPHONY: u32 = 0x0B;
+
+const HOOK_SWITCH: u32 = USAGE_PAGE_TELEPHONY << 16 | 0x20;
+const PHONE_MUTE: u32 = USAGE_PAGE_TELEPHONY << 16 | 0x2F;
+const LED_MUTE: u32 = USAGE_PAGE_LED << 16 | 0x09;
+const LED_OFF_HOOK: u32 = USAGE_PAGE_LED << 16 | 0x17;
+const LED_RING: u32 = USAGE_PAGE_LED << 16 | 0x18;
+
+/// Main item tags
+const TAG_INPUT: u8 = 0x8;
+const TAG_OUTPUT: u8 = 0x9;
+
+/// Position of a control inside the reports with `report_id`, in bits after
+/// the report ID byte
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Field {
+    pub report_id: u8,
+    pub bit: u32,
+    pub size: u32,
+}
+
+/// LED states sent to the headset
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Leds {
+    pub off_hook: bool,
+    pub ring: bool,
+    pub mute: bool,
+}
+
+/// The telephony controls of one HID device
+#[derive(Debug, Default, PartialEq)]
+pub struct Layout {
+    /// Reports start with a report ID byte
+    pub uses_report_ids: bool,
+    pub hook_switch: Option<Field>,
+    pub phone_mute: Option<Field>,
+    pub led_off_hook: Option<Field>,
+    pub led_ring: Option<Field>,
+    pub led_mute: Option<Field>,
+    /// Size of each output report in bits, by report ID
+    output_bits: HashMap<u8, u32>,
+}
+
+impl Layout {
+    /// Whether the device has any call control button
+    pub fn is_telephony(&self) -> bool {
+        self.hook_switch.is_some() || self.phone_mute.is_some()
+    }
+
+    /// Whether `field` is set in the input `report`; `None` when the report
+    /// does not carry it.
+    pub fn read(&self, report: &[u8], field: Field) -> Option<bool> {
+        let data = if self.uses_report_ids {
+            let (&id, rest) = report.split_first()?;
+            if id != field.report_id {
+                return None;
+            }
+            rest
+        } else {
+            report
+        };
+        if (data.len() as u32) * 8 < field.bit + field.size {
+            return None;
+        }
+        Some(
+            (field.bit..field.bit + field.size)
+                .any(|bit| data[(bit / 8) as usize] >> (bit % 8) & 1 == 1),
+        )
+    }
+
+    /// Output reports that set the LEDs to `leds`, each starting with its
+    /// report ID (0 when the device has none), as hidraw expects.
+    pub fn led_reports(&self, leds: Leds) -> Vec<Vec<u8>> {
+        let fields = [
+            (self.led_off_hook, leds.off_hook),
+            (self.led_ring, leds.ring),
+            (self.led_mute, leds.mute),
+        ];
+        let mut reports: BTreeMap<u8, Vec<u8>> = BTreeMap::new();
+        for (field, on) in fields {
+            let Some(field) = field else { continue };
+            let report = reports.entry(field.report_id).or_insert_with(|| {
+                let bits = self.output_bits.get(&field.report_id).copied().unwrap_or(0);
+                let mut report = vec![0u8; 1 + bits.div_ceil(8) as usize];
+                report[0] = field.report_id;
+                report
+            });
+            if on {
+                for bit in field.bit..field.bit + field.size {
+                    report[1 + (bit / 8) as usize] |= 1 << (bit % 8);
+                }
+            }
+        }
+        reports.into_values().collect()
+    }
+
+    fn assign(&mut self, tag: u8, usage: u32, field: Field) {
+        let slot = match (tag, usage) {
+            (TAG_INPUT, HOOK_SWITCH) => &mut self.hook_switch,
+            (TAG_INPUT, PHONE_MUTE) => &mut self.phone_mute,
+            (TAG_OUTPUT, LED_OFF_HOOK) => &mut self.led_off_hook,
+            (TAG_OUTPUT, LED_RING) => &mut self.led_ring,
+            (TAG_OUTPUT, LED_MUTE) => &mut self.led_mute,
+            _ => return,
+        };
+        slot.get_or_insert(field);
+    }
+}
+
+/// Global items that Push and Pop save and restore
+#[derive(Clone, Copy, Default)]
+struct Globals {
+    usage_page: u32,
+    report_size: u32,
+    report_count: u32,
+    report_id: u8,
+}
+
+pub fn parse(descriptor: &[u8]) -> Layout {
+    let mut layout = Layout::default();
+    let mut globals = Globals::default();
+    let mut stack = Vec::new();
+    let mut usages: Vec<u32> = Vec::new();
+    let mut usage_min = None;
+    let mut input_bits: HashMap<u8, u32> = HashMap::new();
+
+    let mut i = 0;
+    while i < descriptor.len() {
+        let prefix = descriptor[i];
+        if prefix == 0xFE {
+            // Long item: size byte, tag byte, data
+            i += 3 + descriptor.get(i + 1).copied().unwrap_or(0) as usize;
+            continue;
+        }
+        let size = match prefix & 0x03 {
+            3 => 4,
+            n => n as usize,
+        };
+        let Some(data) = descriptor.get(i + 1..i + 1 + size) else {
+            break;
+        };
+        let value = data.iter().rev().fold(0u32, |v, &b| v << 8 | b as u32);
+        // A 4-byte usage carries its own page
+        let usage = |page: u32| if size == 4 { value } else { page << 16 | value };
+        i += 1 + size;
+
+        let tag = prefix >> 4;
+        match (prefix >> 2) & 0x03 {
+            // Main
+            0 => {
+                if tag == TAG_INPUT || tag == TAG_OUTPUT {
+                    let bits = if tag == TAG_INPUT {
+                        &mut input_bits
+                    } else {
+                        &mut layout.output_bits
+                    };
+                    let offset = *bits.entry(globals.report_id).or_insert(0);
+                    let total = globals.report_size * globals.report_count;
+                    bits.insert(globals.report_id, offset + total);
+
+                    // Data (not constant), variable (not array)
+                    if value & 0x03 == 0x02 {
+                        for n in 0..globals.report_count {
+                            let usage = match usage_min {
+                                Some(min) => Some(min + n),
+                                None => usages.get(n as usize).or(usages.last()).copied(),
+                            };
+                            let field = Field {
+                                report_id: globals.report_id,
+                                bit: offset + n * globals.report_size,
+                                size: globals.report_size,
+                            };
+                            if let Some(usage) = usage {
+                                layout.assign(tag, usage, field);
+                            }
+                        }
+                    }
+                }
+                usages.clear();
+                usage_min = None;
+            }
+            // Global
+            1 => match tag {
+                0x0 => globals.usage_page = value,
+                0x7 => globals.report_size = value,
+                0x8 => {
+                    globals.report_id = value as u8;
+                    layout.uses_report_ids = true;
+                }
+                0x9 => globals.report_count = value,
+                0xA => stack.push(globals),
+                0xB => globals = stack.pop().unwrap_or_default(),
+                _ => {}
+            },
+            // Local
+            2 => match tag {
+                0x0 => usages.push(usage(globals.usage_page)),
+                0x1 => usage_min = Some(usage(globals.usage_page)),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+    layout
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Telephony headset with hook switch and mute buttons in input report 1
+    /// and off-hook, ring and mute LEDs in output report 2.
+    const HEADSET: &[u8] = &[
+        0x05, 0x0B, // Usage Page (Telephony)
+        0x09, 0x05, // Usage (Headset)
+        0xA1, 0x01, // Collection (Application)
+        0x85, 0x01, //   Report ID (1)
+        0x15, 0x00, //   Logical Minimum (0)
+        0x25, 0x01, //   Logical Maximum (1)
+        0x75, 0x01, //   Report Size (1)
+        0x95, 0x02, //   Report Count (2)
+        0x09, 0x20, //   Usage (Hook Switch)
+        0x09, 0x2F, //   Usage (Phone Mute)
+        0x81, 0x06, //   Input (Data, Variable, Relative)
+        0x95, 0x06, //   Report Count (6)
+        0x81, 0x01, //   Input (Constant)
+        0x85, 0x02, //   Report ID (2)
+        0x05, 0x08, //   Usage Page (LED)
+        0x95, 0x03, //   Report Count (3)
+        0x09, 0x17, //   Usage (Off-Hook)
+        0x09, 0x18, //   Usage (Ring)
+        0x09, 0x09, //   Usage (Mute)
+        0x91, 0x02, //   Output (Data, Variable, Absolute)
+        0x95, 0x05, //   Report Count (5)
+        0x91, 0x01, //   Output (Constant)
+        0xC0, // End Collection
+    ];
+
+    #[test]
+    fn test_parse_headset_descriptor() {
+        let layout = parse(HEADSET);
+        assert!(layout.is_telephony());
+        let field = |report_id, bit| {
+            Some(Field {
+                report_id,
+                bit,
+                size: 1,
+            })
+        };
+        assert_eq!(layout.hook_switch, field(1, 0));
+        assert_eq!(layout.phone_mute, field(1, 1));
+        assert_eq!(layout.led_off_hook, field(2, 0));
+        assert_eq!(layout.led_ring, field(2, 1));
+        assert_eq!(layout.led_mute, field(2, 2));
+
+        let hook = layout.hook_switch.unwrap();
+        assert_eq!(layout.read(&[1, 0b01], hook), Some(true));
+        assert_eq!(layout.read(&[1, 0b10], hook), Some(false));
+        assert_eq!(layout.read(&[3, 0b01], hook), None);
+
+        let leds = Leds {
+            off_hook: true,
+            ring: false,
+            mute: true,
+        };
+        assert_eq!(layout.led_reports(leds), vec![vec![2, 0b101]]);
+    }
+
+    #[test]
+    fn test_parse_ignores_other_devices() {
+        // Keyboard LEDs (Num Lock, Caps Lock) without report IDs
+        let keyboard = [
+            0x05, 0x01, 0x09, 0x06, 0xA1, 0x01, 0x05, 0x08, 0x19, 0x01, 0x29, 0x02, 0x75, 0x01,
+            0x95, 0x02, 0x91, 0x02, 0xC0,
+        ];
+        let layout = parse(&keyboard);
+        assert!(!layout.is_telephony());
+        assert_eq!(layout.led_reports(Leds::default()), Vec::<Vec<u8>>::new());
+    }
+}
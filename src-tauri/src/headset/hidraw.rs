@@ -0,0 +1,216 @@
+//! Linux hidraw backend: finds telephony headsets among `/dev/hidraw*`, reads
+//! their button reports and writes their LED reports.
+//!
+//! Runs on its own thread, polling the devices and rescanning for newly
+//! plugged ones every few seconds, until the token is cancelled.
+
+use super::control::HidButton;
+use super::descriptor::{self, Layout, Leds};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+/// `HID_MAX_DESCRIPTOR_SIZE` from linux/hid.h
+const HID_MAX_DESCRIPTOR_SIZE: usize = 4096;
+/// `_IOR('H', 0x01, int)` and `_IOR('H', 0x02, struct hidraw_report_descriptor)`
+const HIDIOCGRDESCSIZE: u32 = 0x8004_4801;
+const HIDIOCGRDESC: u32 = 0x9004_4802;
+
+/// How long a poll waits for reports before LEDs and the token are checked
+const POLL_TIMEOUT_MS: i32 = 200;
+const RESCAN_INTERVAL: Duration = Duration::from_secs(5);
+
+/// `struct hidraw_report_descriptor` from linux/hidraw.h
+#[repr(C)]
+struct ReportDescriptor {
+    size: u32,
+    value: [u8; HID_MAX_DESCRIPTOR_SIZE],
+}
+
+struct Device {
+    path: PathBuf,
+    file: File,
+    layout: Layout,
+    /// Last hook switch and mute button states, to report changes only
+    off_hook: bool,
+    mute: bool,
+}
+
+impl Device {
+    fn open(path: PathBuf) -> io::Result<Option<Self>> {
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(&path)?;
+        let layout = descriptor::parse(&report_descriptor(&file)?);
+        if !layout.is_telephony() {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            path,
+            file,
+            layout,
+            off_hook: false,
+            mute: false,
+        }))
+    }
+
+    /// Read all pending reports, sending button changes. Returns false once
+    /// the device is gone.
+    fn read_reports(&mut self, buttons: &UnboundedSender<HidButton>) -> bool {
+        let mut report = [0u8; 64];
+        loop {
+            let len = match self.file.read(&mut report) {
+                Ok(0) => return false,
+                Ok(len) => len,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return true,
+                Err(e) => {
+                    debug!(path = %self.path.display(), error = %e, "Headset read failed");
+                    return false;
+                }
+            };
+            let report = &report[..len];
+            if let Some(off_hook) = self
+                .layout
+                .hook_switch
+                .and_then(|f| self.layout.read(report, f))
+            {
+                if off_hook != self.off_hook {
+                    self.off_hook = off_hook;
+                    let _ = buttons.send(HidButton::HookSwitch(off_hook));
+                }
+            }
+            if let Some(mute) = self
+                .layout
+                .phone_mute
+                .and_then(|f| self.layout.read(report, f))
+            {
+                // Phone Mute is a one-shot control: act on the press only
+                if mute && !self.mute {
+                    let _ = buttons.send(HidButton::Mute);
+                }
+                self.mute = mute;
+            }
+        }
+    }
+
+    fn write_leds(&mut self, leds: Leds) {
+        for report in self.layout.led_reports(leds) {
+            if let Err(e) = self.file.write_all(&report) {
+                debug!(path = %self.path.display(), error = %e, "Failed to set headset LEDs");
+            }
+        }
+    }
+}
+
+fn report_descriptor(file: &File) -> io::Result<Vec<u8>> {
+    let fd = file.as_raw_fd();
+    let mut size: libc::c_int = 0;
+    // SAFETY: HIDIOCGRDESCSIZE writes one int to the pointer
+    if unsafe { libc::ioctl(fd, HIDIOCGRDESCSIZE as _, &mut size) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut descriptor = ReportDescriptor {
+        size: size.clamp(0, HID_MAX_DESCRIPTOR_SIZE as libc::c_int) as u32,
+        value: [0; HID_MAX_DESCRIPTOR_SIZE],
+    };
+    // SAFETY: HIDIOCGRDESC fills at most `size` bytes of the struct's buffer
+    if unsafe { libc::ioctl(fd, HIDIOCGRDESC as _, &mut descriptor) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(descriptor.value[..descriptor.size as usize].to_vec())
+}
+
+/// Open telephony devices not open yet, setting their LEDs to `leds`
+fn scan(devices: &mut Vec<Device>, leds: Leds) {
+    let Ok(entries) = std::fs::read_dir("/dev") else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_hidraw = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with("hidraw"));
+        if !is_hidraw || devices.iter().any(|d| d.path == path) {
+            continue;
+        }
+        match Device::open(path.clone()) {
+            Ok(Some(mut device)) => {
+                info!(path = %path.display(), "Headset call control attached");
+                device.write_leds(leds);
+                devices.push(device);
+            }
+            Ok(None) => {}
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                debug!(path = %path.display(), "No access to HID device (needs a udev rule)");
+            }
+            Err(e) => debug!(path = %path.display(), error = %e, "Failed to open HID device"),
+        }
+    }
+}
+
+pub fn watch(
+    cancel_token: CancellationToken,
+    leds_rx: mpsc::Receiver<Leds>,
+    buttons: UnboundedSender<HidButton>,
+) {
+    let mut devices: Vec<Device> = Vec::new();
+    let mut leds = Leds::default();
+    let mut scanned_at: Option<Instant> = None;
+
+    while !cancel_token.is_cancelled() {
+        if scanned_at.is_none_or(|at| at.elapsed() >= RESCAN_INTERVAL) {
+            scan(&mut devices, leds);
+            scanned_at = Some(Instant::now());
+        }
+        if let Some(latest) = leds_rx.try_iter().last() {
+            leds = latest;
+            for device in &mut devices {
+                device.write_leds(leds);
+            }
+        }
+
+        if devices.is_empty() {
+            std::thread::sleep(Duration::from_millis(POLL_TIMEOUT_MS as u64));
+            continue;
+        }
+        let mut fds: Vec<libc::pollfd> = devices
+            .iter()
+            .map(|d| libc::pollfd {
+                fd: d.file.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            })
+            .collect();
+        // SAFETY: `fds` is a valid array of `fds.len()` pollfd structs
+        let ready =
+            unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, POLL_TIMEOUT_MS) };
+        if ready <= 0 {
+            continue;
+        }
+        let mut index = 0;
+        devices.retain_mut(|device| {
+            let revents = fds[index].revents;
+            index += 1;
+            if revents == 0 {
+                return true;
+            }
+            let attached =
+                revents & (libc::POLLERR | libc::POLLHUP) == 0 && device.read_reports(&buttons);
+            if !attached {
+                warn!(path = %device.path.display(), "Headset call control detached");
+            }
+            attached
+        });
+    }
+    debug!("Headset watcher stopped");
+}
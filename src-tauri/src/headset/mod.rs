@@ -0,0 +1,57 @@
+//! Call control from USB headset buttons (HID telephony usage page).
+//!
+//! Going off-hook answers a ringing call; going on-hook hangs up the current
+//! call, or rejects a ringing one; the mute button toggles the microphone.
+//! The headset's Off-Hook, Ring and Mute LEDs follow the calls, which many
+//! headsets also rely on to keep their hook state in step with the app. Every
+//! button is reported as `sip://hid-button`.
+//!
+//! Only Linux is supported, through hidraw; the user needs read/write access
+//! to the headset's `/dev/hidraw*` node, usually granted by a udev rule.
+
+#[cfg(target_os = "linux")]
+mod control;
+#[cfg(target_os = "linux")]
+mod descriptor;
+#[cfg(target_os = "linux")]
+mod hidraw;
+
+#[cfg(target_os = "linux")]
+use control::start;
+use tauri::AppHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+/// Headset call control, off until enabled with `set_headset_control`
+#[derive(Default)]
+pub struct HeadsetControl {
+    /// Stops the running watcher
+    running: std::sync::Mutex<Option<CancellationToken>>,
+}
+
+impl HeadsetControl {
+    pub fn is_enabled(&self) -> bool {
+        self.running.lock().unwrap().is_some()
+    }
+
+    pub fn set_enabled(&self, app_handle: &AppHandle, enabled: bool) -> Result<(), String> {
+        let mut running = self.running.lock().unwrap();
+        if !enabled {
+            if let Some(token) = running.take() {
+                token.cancel();
+                info!("Headset call control disabled");
+            }
+            return Ok(());
+        }
+        if running.is_none() {
+            *running = Some(start(app_handle)?);
+            info!("Headset call control enabled");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn start(_app_handle: &AppHandle) -> Result<CancellationToken, String> {
+    Err("Headset call control is only supported on Linux".to_string())
+}
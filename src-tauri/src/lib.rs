@@ -1,11 +1,22 @@
+mod accounts;
+mod call_history;
+mod error;
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+mod headset;
 mod logging;
+mod qos;
 mod sip;
 mod webrtc;
 
+use error::AppError;
 use rustls;
 use sip::state::SipAppState;
-use tauri::{Manager, State};
-use tracing::error;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use tauri::{Emitter, Manager, State};
+use tracing::{error, info};
+use webrtc::audio_bridge::AudioDirection;
 
 // ── Audio device enumeration via cpal ──
 
@@ -242,11 +253,11 @@ fn enumerate_audio_devices_cpal_fallback(host: &cpal::Host) -> Result<AudioDevic
 }
 
 #[tauri::command]
-fn enumerate_audio_devices() -> Result<AudioDevices, String> {
+fn enumerate_audio_devices() -> Result<AudioDevices, AppError> {
     // On Linux, use PulseAudio/PipeWire as primary source so device names match
     // GNOME Settings → Sound. Falls back to raw cpal ALSA if PA is unavailable.
     #[cfg(target_os = "linux")]
-    return enumerate_audio_devices_linux();
+    return Ok(enumerate_audio_devices_linux()?);
 
     // On macOS / Windows, cpal descriptions are already the correct system names.
     #[cfg(not(target_os = "linux"))]
@@ -283,6 +294,75 @@ fn enumerate_audio_devices() -> Result<AudioDevices, String> {
     }
 }
 
+/// One supported stream configuration: a sample rate range for a channel count and format.
+#[derive(serde::Serialize)]
+struct AudioConfigRange {
+    channels: u16,
+    min_sample_rate: u32,
+    max_sample_rate: u32,
+    sample_format: String,
+}
+
+#[derive(serde::Serialize)]
+struct DeviceCapabilities {
+    id: String,
+    description: String,
+    /// Empty when the device has no input (or output) side
+    inputs: Vec<AudioConfigRange>,
+    outputs: Vec<AudioConfigRange>,
+    /// The configuration calls use (sample rate fixed, so min = max)
+    default_input: Option<AudioConfigRange>,
+    default_output: Option<AudioConfigRange>,
+}
+
+fn probe_device_capabilities(id: &str) -> Result<DeviceCapabilities, AppError> {
+    use cpal::traits::DeviceTrait;
+
+    let device = webrtc::audio_bridge::find_device_by_id(&cpal::default_host(), id)?;
+    let range = |c: cpal::SupportedStreamConfigRange| AudioConfigRange {
+        channels: c.channels(),
+        min_sample_rate: c.min_sample_rate(),
+        max_sample_rate: c.max_sample_rate(),
+        sample_format: c.sample_format().to_string(),
+    };
+    let default = |c: cpal::SupportedStreamConfig| AudioConfigRange {
+        channels: c.channels(),
+        min_sample_rate: c.sample_rate(),
+        max_sample_rate: c.sample_rate(),
+        sample_format: c.sample_format().to_string(),
+    };
+
+    Ok(DeviceCapabilities {
+        id: id.to_string(),
+        description: device
+            .description()
+            .map(|d| d.to_string())
+            .unwrap_or_else(|_| id.to_string()),
+        inputs: device
+            .supported_input_configs()
+            .map(|configs| configs.map(range).collect())
+            .unwrap_or_default(),
+        outputs: device
+            .supported_output_configs()
+            .map(|configs| configs.map(range).collect())
+            .unwrap_or_default(),
+        default_input: device.default_input_config().ok().map(default),
+        default_output: device.default_output_config().ok().map(default),
+    })
+}
+
+/// Supported sample rates, channel counts and sample formats of a device
+/// (by the id from `enumerate_audio_devices`), for diagnosing bad audio.
+#[tauri::command]
+fn get_device_capabilities(id: String) -> Result<DeviceCapabilities, AppError> {
+    // Probing opens the device, which makes ALSA/JACK print to stderr like enumeration does
+    #[cfg(target_os = "linux")]
+    return with_suppressed_stderr(|| probe_device_capabilities(&id));
+
+    #[cfg(not(target_os = "linux"))]
+    probe_device_capabilities(&id)
+}
+
 /// Filter out ALSA virtual plugins and duplicates for the cpal fallback path.
 #[cfg(target_os = "linux")]
 fn is_useful_device(_local_id: &str) -> bool {
@@ -292,299 +372,1425 @@ fn is_useful_device(_local_id: &str) -> bool {
         return true;
     }
 
-    // On Linux, apply ALSA-specific filtering
-    #[cfg(target_os = "linux")]
-    {
-        if _local_id == "default" {
-            return true;
-        }
-        if let Some(rest) = _local_id.strip_prefix("plughw:") {
-            // Modern format: plughw:CARD=PCH,DEV=0
-            if let Some(card_val) = rest.strip_prefix("CARD=") {
-                let card_name = card_val.split(&[',', ':']).next().unwrap_or("");
-                return !card_name.chars().all(|c| c.is_ascii_digit());
-            }
-            // Legacy format: plughw:PCH,0
-            let card_name = rest.split(&[',', ':']).next().unwrap_or("");
-            return !card_name.is_empty() && !card_name.chars().all(|c| c.is_ascii_digit());
-        }
-        false
+    // On Linux, apply ALSA-specific filtering
+    #[cfg(target_os = "linux")]
+    {
+        if _local_id == "default" {
+            return true;
+        }
+        if let Some(rest) = _local_id.strip_prefix("plughw:") {
+            // Modern format: plughw:CARD=PCH,DEV=0
+            if let Some(card_val) = rest.strip_prefix("CARD=") {
+                let card_name = card_val.split(&[',', ':']).next().unwrap_or("");
+                return !card_name.chars().all(|c| c.is_ascii_digit());
+            }
+            // Legacy format: plughw:PCH,0
+            let card_name = rest.split(&[',', ':']).next().unwrap_or("");
+            return !card_name.is_empty() && !card_name.chars().all(|c| c.is_ascii_digit());
+        }
+        false
+    }
+}
+
+// ── SIP commands ──
+
+/// Devices for call audio: the pinned ones, or `None` (system default) for
+/// both while following the system default
+async fn call_devices(state: &SipAppState) -> (Option<String>, Option<String>) {
+    if *state.follow_system_default.lock().await {
+        return (None, None);
+    }
+    (
+        state.input_device.lock().await.clone(),
+        state.output_device.lock().await.clone(),
+    )
+}
+
+/// Snapshot the current media settings for a new call.
+pub(crate) async fn media_config(state: &SipAppState) -> webrtc::MediaConfig {
+    let (input_device, output_device) = call_devices(state).await;
+    webrtc::MediaConfig {
+        input_device,
+        output_device,
+        prefer_srtp: *state.prefer_srtp.lock().await,
+        noise_reduce: *state.noise_reduce.lock().await,
+        speaker_noise_reduce: *state.speaker_noise_reduce.lock().await,
+        noise_reduce_strength: *state.noise_reduce_strength.lock().await,
+        echo_cancel: *state.echo_cancel.lock().await,
+        speaker_volume: *state.speaker_volume.lock().await,
+        mic_gain: *state.mic_gain.lock().await,
+        jitter_buffer_ms: *state.jitter_buffer_ms.lock().await,
+        audio_buffer_ms: *state.audio_buffer_ms.lock().await,
+        plc: *state.plc.lock().await,
+        comfort_noise: *state.comfort_noise.lock().await,
+        l16: *state.l16.lock().await,
+        rtcp_mux: *state.rtcp_mux.lock().await,
+        external_ip: *state.external_ip.lock().await,
+        dscp: *state.dscp.lock().await,
+        opus: state.opus_config.lock().await.clone(),
+        ice_config: state.ice_config.lock().await.clone(),
+        ice_mode: *state.ice_mode.lock().await,
+        force_codec: None,
+        hold_music_path: state.hold_music_path.lock().await.clone(),
+        media_timeout_secs: *state.media_timeout_secs.lock().await,
+        vad: *state.vad.lock().await,
+        listen_only: false,
+    }
+}
+
+/// Handle of a registered account; `None` selects the first registered account.
+async fn account_handle(
+    state: &SipAppState,
+    account_id: Option<&str>,
+) -> Result<Arc<sip::state::ClientHandle>, AppError> {
+    let handles = state.handles.lock().await;
+    match account_id {
+        Some(id) => handles
+            .get(id)
+            .cloned()
+            .ok_or_else(|| AppError::NotRegistered(Some(id.to_string()))),
+        None => handles
+            .values()
+            .next()
+            .cloned()
+            .ok_or(AppError::NotRegistered(None)),
+    }
+}
+
+/// Handle for call-control commands.
+///
+/// With a `call_id` this is the account owning that call (established or
+/// ringing); otherwise the account with an established or in-progress call,
+/// falling back to the first registered account.
+pub(crate) async fn call_handle(
+    state: &SipAppState,
+    call_id: Option<&str>,
+) -> Result<Arc<sip::state::ClientHandle>, AppError> {
+    let handles: Vec<_> = state.handles.lock().await.values().cloned().collect();
+    if let Some(call_id) = call_id {
+        for handle in &handles {
+            if handle.active_calls.lock().await.contains(call_id)
+                || handle.pending_incoming.lock().await.contains_key(call_id)
+            {
+                return Ok(handle.clone());
+            }
+        }
+    }
+    handles
+        .iter()
+        .find(|h| !h.active_call_tokens.is_empty())
+        .or_else(|| handles.first())
+        .cloned()
+        .ok_or(AppError::NotRegistered(None))
+}
+
+#[tauri::command]
+async fn sip_is_registered(
+    state: State<'_, SipAppState>,
+    account_id: Option<String>,
+) -> Result<bool, AppError> {
+    let handles = state.handles.lock().await;
+    Ok(match account_id {
+        Some(id) => handles.contains_key(&id),
+        None => !handles.is_empty(),
+    })
+}
+
+/// Registered Contact, expires and seconds until the next refresh of an
+/// account (the first registered one without `account_id`)
+#[tauri::command]
+async fn sip_get_registration_info(
+    state: State<'_, SipAppState>,
+    account_id: Option<String>,
+) -> Result<sip::state::RegistrationDetails, AppError> {
+    let handle = account_handle(&state, account_id.as_deref()).await?;
+    Ok(sip::handle_get_registration_info(&handle).await)
+}
+
+#[tauri::command]
+async fn sip_register(
+    state: State<'_, SipAppState>,
+    app_handle: tauri::AppHandle,
+    server: String,
+    username: String,
+    password: String,
+    outbound_proxy: Option<String>,
+    account_id: Option<String>,
+) -> Result<(), AppError> {
+    let account_id =
+        account_id.unwrap_or_else(|| accounts::AccountProfile::account_id(&username, &server));
+    if state.handles.lock().await.contains_key(&account_id) {
+        return Err(AppError::AlreadyRegistered);
+    }
+
+    // Get SIP flow config
+    let sip_flow_config = state.sip_flow_config.lock().await.clone();
+    let max_attempts = *state.registration_max_attempts.lock().await;
+    let keepalive_secs = *state.keepalive_interval_secs.lock().await;
+    let crlf_keepalive_secs = *state.crlf_keepalive_secs.lock().await;
+    let tls_verification = state.tls_verification.lock().await.clone();
+
+    match sip::Client::connect(
+        app_handle,
+        account_id.clone(),
+        server,
+        username,
+        password,
+        outbound_proxy,
+        &sip_flow_config,
+        max_attempts,
+        keepalive_secs,
+        crlf_keepalive_secs,
+        &tls_verification,
+    )
+    .await
+    {
+        Ok(new_handle) => {
+            state
+                .handles
+                .lock()
+                .await
+                .insert(account_id, Arc::new(new_handle));
+            Ok(())
+        }
+        Err(e) => {
+            error!(error = ?e, "SIP registration failed");
+            Err(e)
+        }
+    }
+}
+
+/// Register with a saved account, reading its password from the OS keyring.
+#[tauri::command]
+async fn sip_register_account(
+    state: State<'_, SipAppState>,
+    app_handle: tauri::AppHandle,
+    account_id: String,
+) -> Result<(), AppError> {
+    let profile = accounts::load_accounts(&app_handle)?
+        .into_iter()
+        .find(|a| a.id == account_id)
+        .ok_or_else(|| format!("Account not found: {}", account_id))?;
+    let password = accounts::account_password(&profile)?
+        .ok_or_else(|| format!("No saved password for account: {}", account_id))?;
+
+    *state.prefer_srtp.lock().await = profile.prefer_srtp;
+    let server = profile.server_uri();
+    sip_register(
+        state,
+        app_handle,
+        server,
+        profile.username,
+        password,
+        profile.outbound_proxy,
+        Some(profile.id),
+    )
+    .await
+}
+
+/// Unregister one account, or all accounts when `account_id` is `None`.
+#[tauri::command]
+async fn sip_unregister(
+    state: State<'_, SipAppState>,
+    account_id: Option<String>,
+) -> Result<(), AppError> {
+    let removed: Vec<_> = {
+        let mut handles = state.handles.lock().await;
+        match account_id {
+            Some(id) => handles.remove(&id).into_iter().collect(),
+            None => std::mem::take(&mut *handles).into_values().collect(),
+        }
+    };
+
+    // Cancel each account's token - this will cascade to all child tokens (active calls)
+    for handle in &removed {
+        handle.cancel_token.cancel();
+    }
+    if !removed.is_empty() {
+        // Give child tokens time to propagate cancellation and clean up
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    }
+    Ok(())
+}
+
+/// How often each account's egress IP is checked for a network change
+const NETWORK_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Rebuild the transport and registration of accounts whose egress IP
+/// changed, so Contact and Via carry the new address. Calls of such an
+/// account are ended: their media went to the old address.
+async fn watch_network(app_handle: tauri::AppHandle) {
+    let mut ticker = tokio::time::interval(NETWORK_CHECK_INTERVAL);
+    loop {
+        ticker.tick().await;
+        let state = app_handle.state::<SipAppState>();
+        let handles: Vec<_> = state.handles.lock().await.values().cloned().collect();
+        for handle in handles {
+            if sip::handle_check_network(&handle).await.is_none() {
+                continue;
+            }
+
+            // Skip an account unregistered or re-registered in the meantime
+            {
+                let mut handles = state.handles.lock().await;
+                if !handles
+                    .get(&handle.account_id)
+                    .is_some_and(|h| Arc::ptr_eq(h, &handle))
+                {
+                    continue;
+                }
+                handles.remove(&handle.account_id);
+            }
+            handle.cancel_token.cancel();
+
+            let args = handle.register_args.clone();
+            // sip_register logs a failure and leaves the account unregistered
+            let _ = sip_register(
+                app_handle.state(),
+                app_handle.clone(),
+                args.server,
+                args.username,
+                args.password,
+                args.outbound_proxy,
+                Some(handle.account_id.clone()),
+            )
+            .await;
+        }
+    }
+}
+
+#[tauri::command]
+async fn sip_make_call(
+    state: State<'_, SipAppState>,
+    callee: String,
+    account_id: Option<String>,
+    headers: Option<HashMap<String, String>>,
+    force_codec: Option<String>,
+    listen_only: Option<bool>,
+) -> Result<(), AppError> {
+    let force_codec = force_codec
+        .map(|name| {
+            webrtc::codec::codec_from_name(&name).ok_or_else(|| format!("Unknown codec: {}", name))
+        })
+        .transpose()?;
+    stop_running_audio_test(&state).await;
+    let media = media_config(&state).await;
+    let support_100rel = *state.prack_enabled.lock().await;
+
+    // Clone Arc<ClientHandle> and release the lock immediately
+    // so that sip_hangup can also acquire the lock concurrently
+    let handle = account_handle(&state, account_id.as_deref()).await?;
+    let cancel_token = handle.cancel_token.clone();
+
+    sip::handle_make_call(
+        &handle,
+        callee,
+        cancel_token,
+        media,
+        support_100rel,
+        headers,
+        force_codec,
+        listen_only.unwrap_or(false),
+    )
+    .await
+    .map(|_| ())
+    .map_err(|e| {
+        error!(error = ?e, "Make call failed");
+        e
+    })
+}
+
+/// Monitor an extension's busy lamp (BLF) on the given account
+#[tauri::command]
+async fn subscribe_blf(
+    state: State<'_, SipAppState>,
+    extension: String,
+    account_id: Option<String>,
+) -> Result<(), AppError> {
+    let handle = account_handle(&state, account_id.as_deref()).await?;
+
+    sip::handle_subscribe_blf(&handle, extension)
+        .await
+        .map_err(|e| {
+            error!(error = ?e, "BLF subscribe failed");
+            AppError::from(e)
+        })
+}
+
+/// Stop monitoring an extension's busy lamp
+#[tauri::command]
+async fn unsubscribe_blf(
+    state: State<'_, SipAppState>,
+    extension: String,
+    account_id: Option<String>,
+) -> Result<(), AppError> {
+    let handle = account_handle(&state, account_id.as_deref()).await?;
+
+    sip::handle_unsubscribe_blf(&handle, &extension)
+        .await
+        .map_err(AppError::from)
+}
+
+/// Send a text MESSAGE to `target` (extension or SIP URI) from the given account
+#[tauri::command]
+async fn sip_send_message(
+    state: State<'_, SipAppState>,
+    target: String,
+    body: String,
+    account_id: Option<String>,
+) -> Result<(), AppError> {
+    let handle = account_handle(&state, account_id.as_deref()).await?;
+
+    sip::handle_send_message(&handle, target, body)
+        .await
+        .map_err(|e| {
+            error!(error = ?e, "Send message failed");
+            AppError::from(e)
+        })
+}
+
+#[tauri::command]
+async fn sip_hangup(
+    state: State<'_, SipAppState>,
+    call_id: Option<String>,
+) -> Result<(), AppError> {
+    let handle = call_handle(&state, call_id.as_deref()).await?;
+
+    sip::handle_hangup(&handle, call_id).await.map_err(|e| {
+        error!(error = ?e, "Hangup failed");
+        e
+    })
+}
+
+#[tauri::command]
+async fn sip_answer_call(
+    state: State<'_, SipAppState>,
+    call_id: String,
+    preferred_codec: Option<String>,
+    listen_only: Option<bool>,
+) -> Result<(), AppError> {
+    let preferred_codec = preferred_codec
+        .map(|name| {
+            webrtc::codec::codec_from_name(&name).ok_or_else(|| format!("Unknown codec: {}", name))
+        })
+        .transpose()?;
+    answer_call(
+        &state,
+        call_id,
+        preferred_codec,
+        listen_only.unwrap_or(false),
+    )
+    .await
+}
+
+/// Answer an incoming call, for `sip_answer_call` and headset buttons.
+/// `listen_only` answers receive-only, without a microphone.
+pub(crate) async fn answer_call(
+    state: &SipAppState,
+    call_id: String,
+    preferred_codec: Option<webrtc::codec::CodecType>,
+    listen_only: bool,
+) -> Result<(), AppError> {
+    stop_running_audio_test(state).await;
+    let media = media_config(state).await;
+
+    let handle = call_handle(state, Some(&call_id)).await?;
+    let cancel_token = handle.cancel_token.clone();
+
+    sip::handle_answer_call(
+        &handle,
+        call_id,
+        cancel_token,
+        media,
+        preferred_codec,
+        listen_only,
+    )
+    .await
+    .map_err(|e| {
+        error!(error = ?e, "Answer call failed");
+        AppError::from(e)
+    })
+}
+
+#[tauri::command]
+async fn sip_reject_call(
+    state: State<'_, SipAppState>,
+    call_id: String,
+    reason: Option<u16>,
+) -> Result<(), AppError> {
+    let handle = call_handle(&state, Some(&call_id)).await?;
+
+    sip::handle_reject_call(&handle, call_id, reason)
+        .await
+        .map_err(|e| {
+            error!(error = ?e, "Reject call failed");
+            AppError::from(e)
+        })
+}
+
+#[tauri::command]
+async fn sip_hold(state: State<'_, SipAppState>) -> Result<(), AppError> {
+    let handle = call_handle(&state, None).await?;
+
+    sip::handle_hold(&handle).await.map_err(|e| {
+        error!(error = ?e, "Hold failed");
+        e
+    })
+}
+
+#[tauri::command]
+async fn sip_resume(state: State<'_, SipAppState>) -> Result<(), AppError> {
+    let handle = call_handle(&state, None).await?;
+
+    sip::handle_resume(&handle).await.map_err(|e| {
+        error!(error = ?e, "Resume failed");
+        e
+    })
+}
+
+#[tauri::command]
+async fn sip_switch_call(state: State<'_, SipAppState>, call_id: String) -> Result<(), AppError> {
+    let handle = call_handle(&state, Some(&call_id)).await?;
+
+    sip::handle_switch_call(&handle, call_id)
+        .await
+        .map_err(|e| {
+            error!(error = ?e, "Switch call failed");
+            e
+        })
+}
+
+#[tauri::command]
+async fn sip_start_conference(
+    state: State<'_, SipAppState>,
+    call_ids: Vec<String>,
+) -> Result<(), AppError> {
+    let handle = call_handle(&state, call_ids.first().map(String::as_str)).await?;
+
+    sip::handle_start_conference(&handle, call_ids)
+        .await
+        .map_err(|e| {
+            error!(error = ?e, "Start conference failed");
+            e
+        })
+}
+
+#[tauri::command]
+async fn sip_end_conference(state: State<'_, SipAppState>) -> Result<(), AppError> {
+    let handle = call_handle(&state, None).await?;
+
+    sip::handle_end_conference(&handle).await.map_err(|e| {
+        error!(error = ?e, "End conference failed");
+        e
+    })
+}
+
+#[tauri::command]
+async fn sip_transfer(state: State<'_, SipAppState>, target: String) -> Result<(), AppError> {
+    let handle = call_handle(&state, None).await?;
+
+    sip::handle_transfer(&handle, target).await.map_err(|e| {
+        error!(error = ?e, "Transfer failed");
+        e
+    })
+}
+
+#[tauri::command]
+async fn sip_attended_transfer(
+    state: State<'_, SipAppState>,
+    a_call_id: String,
+    b_call_id: String,
+) -> Result<(), AppError> {
+    let handle = call_handle(&state, Some(&a_call_id)).await?;
+
+    sip::handle_attended_transfer(&handle, a_call_id, b_call_id)
+        .await
+        .map_err(|e| {
+            error!(error = ?e, "Attended transfer failed");
+            e
+        })
+}
+
+#[tauri::command]
+async fn sip_get_call_stats(state: State<'_, SipAppState>) -> Result<webrtc::CallStats, AppError> {
+    let handle = call_handle(&state, None).await?;
+
+    sip::handle_get_call_stats(&handle).await
+}
+
+/// Codec, clock rate, ptime and SRTP use of a call (`None` = current call)
+#[tauri::command]
+async fn sip_get_media_info(
+    state: State<'_, SipAppState>,
+    call_id: Option<String>,
+) -> Result<webrtc::MediaInfo, AppError> {
+    let handle = call_handle(&state, call_id.as_deref()).await?;
+
+    sip::handle_get_media_info(&handle, call_id).await
+}
+
+// ── Account profile commands ──
+
+#[tauri::command]
+async fn save_account(
+    app_handle: tauri::AppHandle,
+    server: String,
+    username: String,
+    password: Option<String>,
+    outbound_proxy: Option<String>,
+    prefer_srtp: Option<bool>,
+    transport: Option<String>,
+) -> Result<accounts::AccountProfile, AppError> {
+    let profile = accounts::AccountProfile {
+        id: String::new(),
+        server,
+        username,
+        outbound_proxy,
+        prefer_srtp: prefer_srtp.unwrap_or(true),
+        transport,
+        password_ref: None,
+    };
+    let saved = accounts::save_account(&app_handle, profile, password.as_deref())?;
+    Ok(saved)
+}
+
+#[tauri::command]
+async fn list_accounts(
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<accounts::AccountProfile>, AppError> {
+    Ok(accounts::load_accounts(&app_handle)?)
+}
+
+/// Saved accounts for the startup account picker (same data as `list_accounts`)
+#[tauri::command]
+async fn get_accounts(
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<accounts::AccountProfile>, AppError> {
+    Ok(accounts::load_accounts(&app_handle)?)
+}
+
+#[tauri::command]
+async fn delete_account(app_handle: tauri::AppHandle, account_id: String) -> Result<(), AppError> {
+    Ok(accounts::delete_account(&app_handle, &account_id)?)
+}
+
+// ── Call history commands ──
+
+/// Logged calls, most recent first; `limit` caps how many are returned
+#[tauri::command]
+async fn get_call_history(
+    app_handle: tauri::AppHandle,
+    limit: Option<usize>,
+) -> Result<Vec<call_history::CallRecord>, AppError> {
+    Ok(call_history::load_call_history(&app_handle, limit)?)
+}
+
+#[tauri::command]
+async fn clear_call_history(app_handle: tauri::AppHandle) -> Result<(), AppError> {
+    Ok(call_history::clear_call_history(&app_handle)?)
+}
+
+// ── Audio device commands ──
+
+/// Pin the microphone; this stops following the system default device
+#[tauri::command]
+async fn set_input_device(state: State<'_, SipAppState>, name: String) -> Result<(), AppError> {
+    *state.input_device.lock().await = Some(name.clone());
+    *state.follow_system_default.lock().await = false;
+
+    // Switch active calls over right away; new calls pick up the setting
+    let handles: Vec<_> = state.handles.lock().await.values().cloned().collect();
+    for handle in handles {
+        sip::handle_set_input_device(&handle, &name).await?;
+    }
+    Ok(())
+}
+
+/// Pin the speaker; this stops following the system default device
+#[tauri::command]
+async fn set_output_device(state: State<'_, SipAppState>, name: String) -> Result<(), AppError> {
+    *state.output_device.lock().await = Some(name.clone());
+    *state.follow_system_default.lock().await = false;
+
+    // Switch active calls over right away; new calls pick up the setting
+    let handles: Vec<_> = state.handles.lock().await.values().cloned().collect();
+    for handle in handles {
+        sip::handle_set_output_device(&handle, &name).await?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_follow_system_default(state: State<'_, SipAppState>) -> Result<bool, AppError> {
+    Ok(*state.follow_system_default.lock().await)
+}
+
+/// Use the system default microphone and speaker, moving calls over whenever
+/// the default changes (e.g. a headset is plugged in). Turning it off moves
+/// calls back to the pinned devices, if any.
+#[tauri::command]
+async fn set_follow_system_default(
+    state: State<'_, SipAppState>,
+    enabled: bool,
+) -> Result<(), AppError> {
+    *state.follow_system_default.lock().await = enabled;
+
+    let (input_device, output_device) = call_devices(&state).await;
+    let handles: Vec<_> = state.handles.lock().await.values().cloned().collect();
+    for handle in handles {
+        match input_device {
+            Some(ref name) => sip::handle_set_input_device(&handle, name).await?,
+            None => {
+                sip::handle_switch_to_default_device(&handle, AudioDirection::Mic).await?;
+            }
+        }
+        match output_device {
+            Some(ref name) => sip::handle_set_output_device(&handle, name).await?,
+            None => {
+                sip::handle_switch_to_default_device(&handle, AudioDirection::Speaker).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// How often the system default audio devices are checked for a change
+const DEFAULT_DEVICE_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// While following the system default devices, move call audio to a new
+/// default microphone or speaker and emit `sip://device-followed`.
+async fn watch_default_devices(app_handle: tauri::AppHandle) {
+    let mut ticker = tokio::time::interval(DEFAULT_DEVICE_CHECK_INTERVAL);
+    // Default device IDs at the previous check, by direction
+    let mut defaults: HashMap<AudioDirection, String> = HashMap::new();
+    loop {
+        ticker.tick().await;
+        let state = app_handle.state::<SipAppState>();
+        if !*state.follow_system_default.lock().await {
+            defaults.clear();
+            continue;
+        }
+        for direction in [AudioDirection::Mic, AudioDirection::Speaker] {
+            let current = tokio::task::spawn_blocking(move || {
+                webrtc::audio_bridge::default_device(direction)
+            })
+            .await
+            .ok()
+            .flatten();
+            let Some((device_id, device_name)) = current else {
+                continue;
+            };
+            let previous = defaults.insert(direction, device_id.clone());
+            if previous.is_none_or(|previous| previous == device_id) {
+                continue;
+            }
+
+            info!(?direction, device = %device_name, "System default audio device changed");
+            let handles: Vec<_> = state.handles.lock().await.values().cloned().collect();
+            let mut call_ids = Vec::new();
+            for handle in handles {
+                match sip::handle_switch_to_default_device(&handle, direction).await {
+                    Ok(moved) => call_ids.extend(moved),
+                    Err(e) => error!(?direction, error = %e, "Failed to follow the default device"),
+                }
+            }
+            let _ = app_handle.emit(
+                "sip://device-followed",
+                webrtc::DeviceFollowedPayload {
+                    direction,
+                    device_id,
+                    device_name,
+                    call_ids,
+                },
+            );
+        }
+    }
+}
+
+/// Get the incoming-call ringtone settings
+#[tauri::command]
+async fn get_ringtone_config(
+    state: State<'_, SipAppState>,
+) -> Result<webrtc::ringtone::RingtoneConfig, AppError> {
+    Ok(state.ringtone.config())
+}
+
+/// Set the ringtone WAV file; `None` restores the built-in ring
+#[tauri::command]
+async fn set_ringtone_path(
+    state: State<'_, SipAppState>,
+    path: Option<String>,
+) -> Result<(), AppError> {
+    Ok(state.ringtone.set_path(path)?)
+}
+
+#[tauri::command]
+async fn set_ringtone_enabled(
+    state: State<'_, SipAppState>,
+    enabled: bool,
+) -> Result<(), AppError> {
+    state.ringtone.set_enabled(enabled);
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_headset_control(state: State<'_, SipAppState>) -> Result<bool, AppError> {
+    Ok(state.headset.is_enabled())
+}
+
+/// Let USB headset buttons answer, hang up and mute calls, and drive the
+/// headset's LEDs (Linux only)
+#[tauri::command]
+async fn set_headset_control(
+    app_handle: tauri::AppHandle,
+    state: State<'_, SipAppState>,
+    enabled: bool,
+) -> Result<(), AppError> {
+    Ok(state.headset.set_enabled(&app_handle, enabled)?)
+}
+
+#[tauri::command]
+async fn get_noise_reduce(state: State<'_, SipAppState>) -> Result<bool, AppError> {
+    Ok(*state.noise_reduce.lock().await)
+}
+
+/// Turn microphone noise reduction on at full strength, or off: shorthand
+/// for `set_noise_reduce_strength` with 1.0 or 0.0.
+#[tauri::command]
+async fn set_noise_reduce(state: State<'_, SipAppState>, enabled: bool) -> Result<(), AppError> {
+    *state.noise_reduce.lock().await = enabled;
+    if enabled {
+        *state.noise_reduce_strength.lock().await = 1.0;
+    }
+
+    // Apply immediately to active calls on every account
+    let handles: Vec<_> = state.handles.lock().await.values().cloned().collect();
+    for handle in handles {
+        if enabled {
+            sip::handle_set_noise_reduce_strength(&handle, 1.0).await;
+        }
+        sip::handle_set_noise_reduce(&handle, enabled).await;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_noise_reduce_strength(state: State<'_, SipAppState>) -> Result<f32, AppError> {
+    Ok(*state.noise_reduce_strength.lock().await)
+}
+
+/// Set how much noise reduction is applied (0.0 = bypass, 1.0 = full
+/// denoise), blending the denoised audio with the original. Microphone noise
+/// reduction is on for any strength above 0.0; speaker noise reduction, when
+/// enabled, uses the same strength.
+#[tauri::command]
+async fn set_noise_reduce_strength(
+    state: State<'_, SipAppState>,
+    strength: f32,
+) -> Result<(), AppError> {
+    if !(0.0..=1.0).contains(&strength) {
+        return Err(format!("Noise reduction strength must be 0.0-1.0, got {}", strength).into());
+    }
+    let enabled = strength > 0.0;
+    *state.noise_reduce_strength.lock().await = strength;
+    *state.noise_reduce.lock().await = enabled;
+
+    let handles: Vec<_> = state.handles.lock().await.values().cloned().collect();
+    for handle in handles {
+        sip::handle_set_noise_reduce_strength(&handle, strength).await;
+        sip::handle_set_noise_reduce(&handle, enabled).await;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_speaker_noise_reduce(state: State<'_, SipAppState>) -> Result<bool, AppError> {
+    Ok(*state.speaker_noise_reduce.lock().await)
+}
+
+#[tauri::command]
+async fn set_speaker_noise_reduce(state: State<'_, SipAppState>, enabled: bool) -> Result<(), AppError> {
+    *state.speaker_noise_reduce.lock().await = enabled;
+
+    // Apply immediately to active calls on every account
+    let handles: Vec<_> = state.handles.lock().await.values().cloned().collect();
+    for handle in handles {
+        sip::handle_set_speaker_noise_reduce(&handle, enabled).await;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_echo_cancel(state: State<'_, SipAppState>) -> Result<bool, AppError> {
+    Ok(*state.echo_cancel.lock().await)
+}
+
+#[tauri::command]
+async fn set_echo_cancel(state: State<'_, SipAppState>, enabled: bool) -> Result<(), AppError> {
+    *state.echo_cancel.lock().await = enabled;
+
+    // Apply immediately to active calls on every account
+    let handles: Vec<_> = state.handles.lock().await.values().cloned().collect();
+    for handle in handles {
+        sip::handle_set_echo_cancel(&handle, enabled).await;
+    }
+    Ok(())
+}
+
+/// Set the software speaker volume (0.0-1.5, 1.0 = unchanged) for all calls
+#[tauri::command]
+async fn set_speaker_volume(state: State<'_, SipAppState>, volume: f32) -> Result<(), AppError> {
+    if !(0.0..=webrtc::audio_bridge::MAX_GAIN).contains(&volume) {
+        return Err(format!("Speaker volume must be 0.0-1.5, got {}", volume).into());
+    }
+    *state.speaker_volume.lock().await = volume;
+
+    let handles: Vec<_> = state.handles.lock().await.values().cloned().collect();
+    for handle in handles {
+        sip::handle_set_speaker_volume(&handle, volume).await;
+    }
+    Ok(())
+}
+
+/// Set the software microphone gain (0.0-1.5, 1.0 = unchanged) for all calls
+#[tauri::command]
+async fn set_mic_gain(state: State<'_, SipAppState>, gain: f32) -> Result<(), AppError> {
+    if !(0.0..=webrtc::audio_bridge::MAX_GAIN).contains(&gain) {
+        return Err(format!("Microphone gain must be 0.0-1.5, got {}", gain).into());
+    }
+    *state.mic_gain.lock().await = gain;
+
+    let handles: Vec<_> = state.handles.lock().await.values().cloned().collect();
+    for handle in handles {
+        sip::handle_set_mic_gain(&handle, gain).await;
+    }
+    Ok(())
+}
+
+/// Set the WAV file played to the remote while a call is on hold (`None` = silence).
+/// An unreadable file is reported with `sip://media-warning` when a hold starts.
+#[tauri::command]
+async fn set_hold_music_path(
+    state: State<'_, SipAppState>,
+    path: Option<String>,
+) -> Result<(), AppError> {
+    *state.hold_music_path.lock().await = path.clone();
+
+    let handles: Vec<_> = state.handles.lock().await.values().cloned().collect();
+    for handle in handles {
+        sip::handle_set_hold_music_path(&handle, path.clone()).await;
+    }
+    Ok(())
+}
+
+/// Get the current devices, gains and audio processing settings
+#[tauri::command]
+async fn get_audio_settings(
+    state: State<'_, SipAppState>,
+) -> Result<sip::state::AudioSettings, AppError> {
+    Ok(sip::state::AudioSettings {
+        input_device: state.input_device.lock().await.clone(),
+        output_device: state.output_device.lock().await.clone(),
+        follow_system_default: *state.follow_system_default.lock().await,
+        speaker_volume: *state.speaker_volume.lock().await,
+        mic_gain: *state.mic_gain.lock().await,
+        noise_reduce: *state.noise_reduce.lock().await,
+        speaker_noise_reduce: *state.speaker_noise_reduce.lock().await,
+        noise_reduce_strength: *state.noise_reduce_strength.lock().await,
+        echo_cancel: *state.echo_cancel.lock().await,
+        hold_music_path: state.hold_music_path.lock().await.clone(),
+    })
+}
+
+#[tauri::command]
+async fn get_jitter_buffer_ms(state: State<'_, SipAppState>) -> Result<u32, AppError> {
+    Ok(*state.jitter_buffer_ms.lock().await)
+}
+
+/// Set the playback jitter buffer target depth (20-500 ms)
+#[tauri::command]
+async fn set_jitter_buffer_ms(state: State<'_, SipAppState>, ms: u32) -> Result<(), AppError> {
+    if !(20..=500).contains(&ms) {
+        return Err(format!("Jitter buffer depth must be 20-500 ms, got {}", ms).into());
+    }
+    *state.jitter_buffer_ms.lock().await = ms;
+
+    // Apply immediately to active calls on every account
+    let handles: Vec<_> = state.handles.lock().await.values().cloned().collect();
+    for handle in handles {
+        sip::handle_set_jitter_buffer_ms(&handle, ms).await;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_audio_buffer_ms(state: State<'_, SipAppState>) -> Result<u32, AppError> {
+    Ok(*state.audio_buffer_ms.lock().await)
+}
+
+/// Set the capacity of the audio ring buffers next to the devices (40-500 ms).
+/// Smaller buffers cut latency but drop audio sooner when the system is busy;
+/// applies to new calls and reconnected audio.
+#[tauri::command]
+async fn set_audio_buffer_ms(state: State<'_, SipAppState>, ms: u32) -> Result<(), AppError> {
+    if !(40..=500).contains(&ms) {
+        return Err(format!("Audio buffer size must be 40-500 ms, got {}", ms).into());
+    }
+    *state.audio_buffer_ms.lock().await = ms;
+
+    let handles: Vec<_> = state.handles.lock().await.values().cloned().collect();
+    for handle in handles {
+        sip::handle_set_audio_buffer_ms(&handle, ms).await;
+    }
+    Ok(())
+}
+
+/// Switch between the low-latency audio buffer preset and the default size
+#[tauri::command]
+async fn set_low_latency_audio(
+    state: State<'_, SipAppState>,
+    enabled: bool,
+) -> Result<(), AppError> {
+    let ms = if enabled {
+        webrtc::audio_bridge::LOW_LATENCY_AUDIO_BUFFER_MS
+    } else {
+        webrtc::audio_bridge::DEFAULT_AUDIO_BUFFER_MS
+    };
+    set_audio_buffer_ms(state, ms).await
+}
+
+#[tauri::command]
+async fn get_plc(state: State<'_, SipAppState>) -> Result<bool, AppError> {
+    Ok(*state.plc.lock().await)
+}
+
+/// Enable or disable packet loss concealment on playback
+#[tauri::command]
+async fn set_plc(state: State<'_, SipAppState>, enabled: bool) -> Result<(), AppError> {
+    *state.plc.lock().await = enabled;
+
+    // Apply immediately to active calls on every account
+    let handles: Vec<_> = state.handles.lock().await.values().cloned().collect();
+    for handle in handles {
+        sip::handle_set_plc(&handle, enabled).await;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_comfort_noise(state: State<'_, SipAppState>) -> Result<bool, AppError> {
+    Ok(*state.comfort_noise.lock().await)
+}
+
+#[tauri::command]
+async fn get_l16(state: State<'_, SipAppState>) -> Result<bool, AppError> {
+    Ok(*state.l16.lock().await)
+}
+
+/// Offer uncompressed L16 (16 kHz, ~256 kbit/s) ahead of the other codecs.
+/// Meant for LAN intercoms; applies to new calls.
+#[tauri::command]
+async fn set_l16(state: State<'_, SipAppState>, enabled: bool) -> Result<(), AppError> {
+    *state.l16.lock().await = enabled;
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_rtcp_mux(state: State<'_, SipAppState>) -> Result<bool, AppError> {
+    Ok(*state.rtcp_mux.lock().await)
+}
+
+/// Keep `a=rtcp-mux` in answers to non-ICE peers so RTCP shares the RTP port
+/// instead of needing a second one through the firewall. Off by default for
+/// PBXs without rtcp-mux support; applies to new calls.
+#[tauri::command]
+async fn set_rtcp_mux(state: State<'_, SipAppState>, enabled: bool) -> Result<(), AppError> {
+    *state.rtcp_mux.lock().await = enabled;
+    Ok(())
+}
+
+/// Enable or disable comfort noise in receive gaps (the CN offer applies to new calls)
+#[tauri::command]
+async fn set_comfort_noise(state: State<'_, SipAppState>, enabled: bool) -> Result<(), AppError> {
+    *state.comfort_noise.lock().await = enabled;
+
+    // Apply immediately to active calls on every account
+    let handles: Vec<_> = state.handles.lock().await.values().cloned().collect();
+    for handle in handles {
+        sip::handle_set_comfort_noise(&handle, enabled).await;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_media_timeout(state: State<'_, SipAppState>) -> Result<u64, AppError> {
+    Ok(*state.media_timeout_secs.lock().await)
+}
+
+/// Set how long a call may go without received RTP before it is hung up
+/// (0 = never); applies to subsequent calls
+#[tauri::command]
+async fn set_media_timeout(state: State<'_, SipAppState>, secs: u64) -> Result<(), AppError> {
+    *state.media_timeout_secs.lock().await = secs;
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_opus_config(
+    state: State<'_, SipAppState>,
+) -> Result<webrtc::encoder::OpusConfig, AppError> {
+    Ok(state.opus_config.lock().await.clone())
+}
+
+/// Set the Opus encoder bitrate, in-band FEC and DTX
+#[tauri::command]
+async fn set_opus_config(
+    state: State<'_, SipAppState>,
+    config: webrtc::encoder::OpusConfig,
+) -> Result<(), AppError> {
+    config.validate()?;
+    *state.opus_config.lock().await = config.clone();
+
+    // Apply immediately to active calls on every account
+    let handles: Vec<_> = state.handles.lock().await.values().cloned().collect();
+    for handle in handles {
+        sip::handle_set_opus_config(&handle, &config).await;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_adaptive_bitrate(
+    state: State<'_, SipAppState>,
+) -> Result<sip::state::AdaptiveBitrateConfig, AppError> {
+    Ok(*state.adaptive_bitrate.lock().await)
+}
+
+/// Adapt the Opus bitrate of calls to the loss the remote reports, between
+/// `min_bitrate` and `max_bitrate` bps. Active calls follow within seconds;
+/// disabling it restores the configured bitrate.
+#[tauri::command]
+async fn set_adaptive_bitrate(
+    state: State<'_, SipAppState>,
+    enabled: bool,
+    min_bitrate: u32,
+    max_bitrate: u32,
+) -> Result<(), AppError> {
+    use webrtc::encoder::{OPUS_MAX_BITRATE, OPUS_MIN_BITRATE};
+    let limits = OPUS_MIN_BITRATE..=OPUS_MAX_BITRATE;
+    if !limits.contains(&min_bitrate) || !limits.contains(&max_bitrate) {
+        return Err(AppError::Other(format!(
+            "Adaptive bitrate bounds must be between {} and {} bps",
+            OPUS_MIN_BITRATE, OPUS_MAX_BITRATE
+        )));
     }
+    if min_bitrate > max_bitrate {
+        return Err(AppError::Other(
+            "Minimum bitrate must not exceed the maximum".to_string(),
+        ));
+    }
+    *state.adaptive_bitrate.lock().await = sip::state::AdaptiveBitrateConfig {
+        enabled,
+        min_bitrate,
+        max_bitrate,
+    };
+    Ok(())
 }
 
-// ── SIP commands ──
+#[tauri::command]
+async fn get_vad(state: State<'_, SipAppState>) -> Result<webrtc::vad::VadConfig, AppError> {
+    Ok(*state.vad.lock().await)
+}
 
+/// Enable or disable voice activity detection (silence is not sent)
 #[tauri::command]
-async fn sip_is_registered(state: State<'_, SipAppState>) -> Result<bool, String> {
-    Ok(state.handle.lock().await.is_some())
+async fn set_vad(state: State<'_, SipAppState>, enabled: bool) -> Result<(), AppError> {
+    let config = {
+        let mut vad = state.vad.lock().await;
+        vad.enabled = enabled;
+        *vad
+    };
+    apply_vad(&state, config).await;
+    Ok(())
 }
 
+/// Set how quiet audio may be and still count as speech (0 - 100)
 #[tauri::command]
-async fn sip_register(
+async fn set_vad_sensitivity(
     state: State<'_, SipAppState>,
-    app_handle: tauri::AppHandle,
-    server: String,
-    username: String,
-    password: String,
-    outbound_proxy: Option<String>,
-) -> Result<(), String> {
-    if state.handle.lock().await.is_some() {
-        return Err("Already registered".to_string());
+    sensitivity: u8,
+) -> Result<(), AppError> {
+    let config = {
+        let mut vad = state.vad.lock().await;
+        let config = webrtc::vad::VadConfig {
+            sensitivity,
+            ..*vad
+        };
+        config.validate()?;
+        *vad = config;
+        config
+    };
+    apply_vad(&state, config).await;
+    Ok(())
+}
+
+/// Apply VAD settings immediately to active calls on every account
+async fn apply_vad(state: &SipAppState, config: webrtc::vad::VadConfig) {
+    let handles: Vec<_> = state.handles.lock().await.values().cloned().collect();
+    for handle in handles {
+        sip::handle_set_vad(&handle, config).await;
     }
+}
 
-    // Get SIP flow config
-    let sip_flow_config = state.sip_flow_config.lock().await.clone();
+/// Stop the audio self-test, if running, so a call gets the devices.
+async fn stop_running_audio_test(state: &SipAppState) {
+    if let Some(test) = state.audio_test.lock().await.take() {
+        test.stop();
+    }
+}
 
-    match sip::Client::connect(
-        app_handle,
-        server,
-        username,
-        password,
-        outbound_proxy,
-        Some(sip_flow_config.enabled),
-        Some(sip_flow_config.log_dir),
-    )
-    .await
-    {
-        Ok((new_handle, cancel_token)) => {
-            *state.handle.lock().await = Some(std::sync::Arc::new(new_handle));
-            *state.cancel_token.lock().await = Some(cancel_token);
-            Ok(())
-        }
-        Err(e) => {
-            error!(error = ?e, "SIP registration failed");
-            Err(format!("Registration failed: {}", e))
+/// Audio self-tests need the devices to themselves.
+async fn ensure_no_active_call(state: &SipAppState) -> Result<(), AppError> {
+    let handles: Vec<_> = state.handles.lock().await.values().cloned().collect();
+    for handle in handles {
+        if !handle.active_calls.lock().await.is_empty() {
+            return Err("Cannot test audio during a call".into());
         }
     }
+    Ok(())
 }
 
+/// Play the microphone back on the speaker (with a short delay) to check the
+/// selected devices; levels are emitted as `sip://audio-level` with `call_id`
+/// `"audio-test"`. Not available during a call; restarts a running test.
 #[tauri::command]
-async fn sip_unregister(state: State<'_, SipAppState>) -> Result<(), String> {
-    // Cancel global token - this will cascade to all child tokens (active calls)
-    if let Some(token) = state.cancel_token.lock().await.take() {
-        token.cancel();
-        // Give child tokens time to propagate cancellation and clean up
-        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-    }
+async fn start_audio_test(
+    app_handle: tauri::AppHandle,
+    state: State<'_, SipAppState>,
+) -> Result<(), AppError> {
+    ensure_no_active_call(&state).await?;
 
-    state.handle.lock().await.take();
+    let media = media_config(&state).await;
+    let mut audio_test = state.audio_test.lock().await;
+    if let Some(test) = audio_test.take() {
+        test.stop();
+    }
+    *audio_test = Some(webrtc::audio_test::AudioTest::start(&media, app_handle)?);
     Ok(())
 }
 
+/// Play a sine test tone of `freq_hz` for `ms` on the selected output device at
+/// the speaker volume, e.g. to check the speaker before a call. Needs neither a
+/// registration nor a microphone; resolves once the tone has played and the
+/// device is released. Not available during a call.
 #[tauri::command]
-async fn sip_make_call(state: State<'_, SipAppState>, callee: String) -> Result<(), String> {
-    let input_device = state.input_device.lock().await.clone();
-    let output_device = state.output_device.lock().await.clone();
-    let prefer_srtp = *state.prefer_srtp.lock().await;
-    let noise_reduce = *state.noise_reduce.lock().await;
-    let speaker_noise_reduce = *state.speaker_noise_reduce.lock().await;
+async fn play_test_tone(
+    state: State<'_, SipAppState>,
+    freq_hz: u32,
+    ms: u32,
+) -> Result<(), AppError> {
+    ensure_no_active_call(&state).await?;
+    webrtc::audio_test::validate_test_tone(freq_hz, ms)?;
+    let media = media_config(&state).await;
+    Ok(webrtc::audio_test::play_test_tone(&media, freq_hz, ms).await?)
+}
 
-    // Clone Arc<ClientHandle> and release the lock immediately
-    // so that sip_hangup can also acquire the lock concurrently
-    let handle = {
-        let handle_guard = state.handle.lock().await;
-        handle_guard
-            .as_ref()
-            .ok_or_else(|| "Not registered".to_string())?
-            .clone()
-    };
+/// Stop the audio self-test and release the devices
+#[tauri::command]
+async fn stop_audio_test(state: State<'_, SipAppState>) -> Result<(), AppError> {
+    stop_running_audio_test(&state).await;
+    Ok(())
+}
 
-    let cancel_token = state
-        .cancel_token
-        .lock()
-        .await
-        .as_ref()
-        .ok_or_else(|| "No cancel token available".to_string())?
-        .clone();
+/// Re-open the audio devices of a call (`None` = current call)
+#[tauri::command]
+async fn reconnect_audio(
+    state: State<'_, SipAppState>,
+    call_id: Option<String>,
+) -> Result<(), AppError> {
+    let handle = call_handle(&state, call_id.as_deref()).await?;
 
-    sip::handle_make_call(&handle, callee, input_device, output_device, cancel_token, prefer_srtp, noise_reduce, speaker_noise_reduce)
-        .await
-        .map_err(|e| {
-            error!(error = ?e, "Make call failed");
-            e.to_string().trim_start_matches("Error: ").to_string()
-        })
+    sip::handle_reconnect_audio(&handle, call_id).await
 }
 
+/// Record a call (`None` = current call) to a WAV file; `mode` defaults to mixed mono
 #[tauri::command]
-async fn sip_hangup(state: State<'_, SipAppState>) -> Result<(), String> {
-    let handle = {
-        let handle_guard = state.handle.lock().await;
-        handle_guard
-            .as_ref()
-            .ok_or_else(|| "Not registered".to_string())?
-            .clone()
-    };
+async fn start_recording(
+    state: State<'_, SipAppState>,
+    path: String,
+    mode: Option<webrtc::recorder::RecordingMode>,
+    call_id: Option<String>,
+) -> Result<(), AppError> {
+    let handle = call_handle(&state, call_id.as_deref()).await?;
 
-    sip::handle_hangup(&handle).await.map_err(|e| {
-        error!(error = ?e, "Hangup failed");
-        format!("Hangup failed: {}", e)
-    })
+    sip::handle_start_recording(&handle, call_id, &path, mode.unwrap_or_default()).await
 }
 
+/// Stop recording a call; returns the path of the WAV file
 #[tauri::command]
-async fn sip_answer_call(state: State<'_, SipAppState>, call_id: String) -> Result<(), String> {
-    let input_device = state.input_device.lock().await.clone();
-    let output_device = state.output_device.lock().await.clone();
-    let noise_reduce = *state.noise_reduce.lock().await;
-    let speaker_noise_reduce = *state.speaker_noise_reduce.lock().await;
+async fn stop_recording(
+    state: State<'_, SipAppState>,
+    call_id: Option<String>,
+) -> Result<String, AppError> {
+    let handle = call_handle(&state, call_id.as_deref()).await?;
 
-    let handle = {
-        let handle_guard = state.handle.lock().await;
-        handle_guard
-            .as_ref()
-            .ok_or_else(|| "Not registered".to_string())?
-            .clone()
-    };
+    sip::handle_stop_recording(&handle, call_id).await
+}
 
-    let cancel_token = state
-        .cancel_token
-        .lock()
-        .await
-        .as_ref()
-        .ok_or_else(|| "No cancel token available".to_string())?
-        .clone();
+/// Send a WAV file to the far end of a call (`None` = current call) in place of
+/// the mic; the mic is live again when the file ends or on
+/// `stop_audio_into_call`, either way announced as `sip://playback-finished`
+#[tauri::command]
+async fn play_audio_into_call(
+    state: State<'_, SipAppState>,
+    path: String,
+    call_id: Option<String>,
+) -> Result<(), AppError> {
+    let handle = call_handle(&state, call_id.as_deref()).await?;
 
-    sip::handle_answer_call(&handle, call_id, input_device, output_device, cancel_token, noise_reduce, speaker_noise_reduce)
-        .await
-        .map_err(|e| {
-            error!(error = ?e, "Answer call failed");
-            format!("Answer failed: {}", e)
-        })
+    sip::handle_play_audio_into_call(&handle, call_id, &path).await
 }
 
+/// Stop the file sent into a call and return to the mic; returns whether one was playing
 #[tauri::command]
-async fn sip_reject_call(
+async fn stop_audio_into_call(
     state: State<'_, SipAppState>,
-    call_id: String,
-    reason: Option<u16>,
-) -> Result<(), String> {
-    let handle = {
-        let handle_guard = state.handle.lock().await;
-        handle_guard
-            .as_ref()
-            .ok_or_else(|| "Not registered".to_string())?
-            .clone()
-    };
+    call_id: Option<String>,
+) -> Result<bool, AppError> {
+    let handle = call_handle(&state, call_id.as_deref()).await?;
 
-    sip::handle_reject_call(&handle, call_id, reason)
-        .await
-        .map_err(|e| {
-            error!(error = ?e, "Reject call failed");
-            format!("Reject failed: {}", e)
-        })
+    sip::handle_stop_audio_into_call(&handle, call_id).await
 }
 
-// ── Audio device commands ──
-
 #[tauri::command]
-async fn set_input_device(state: State<'_, SipAppState>, name: String) -> Result<(), String> {
-    *state.input_device.lock().await = Some(name);
-    Ok(())
+async fn toggle_noise_reduce(state: State<'_, SipAppState>) -> Result<bool, AppError> {
+    let handle = call_handle(&state, None).await?;
+
+    sip::handle_toggle_noise_reduce(&handle).await
 }
 
 #[tauri::command]
-async fn set_output_device(state: State<'_, SipAppState>, name: String) -> Result<(), String> {
-    *state.output_device.lock().await = Some(name);
-    Ok(())
+async fn toggle_mic_mute(state: State<'_, SipAppState>) -> Result<bool, AppError> {
+    let handle = call_handle(&state, None).await?;
+
+    sip::handle_toggle_mic_mute(&handle).await
 }
 
 #[tauri::command]
-async fn get_noise_reduce(state: State<'_, SipAppState>) -> Result<bool, String> {
-    Ok(*state.noise_reduce.lock().await)
+async fn toggle_speaker_mute(state: State<'_, SipAppState>) -> Result<bool, AppError> {
+    let handle = call_handle(&state, None).await?;
+
+    sip::handle_toggle_speaker_mute(&handle).await
 }
 
 #[tauri::command]
-async fn set_noise_reduce(state: State<'_, SipAppState>, enabled: bool) -> Result<(), String> {
-    *state.noise_reduce.lock().await = enabled;
+async fn set_mic_mute(state: State<'_, SipAppState>, muted: bool) -> Result<(), AppError> {
+    let handle = call_handle(&state, None).await?;
 
-    // Apply immediately to the active call if one exists
-    let handle_opt = state.handle.lock().await.clone();
-    if let Some(handle) = handle_opt {
-        sip::handle_set_noise_reduce(&handle, enabled).await;
-    }
-    Ok(())
+    sip::handle_set_mic_mute(&handle, muted).await
 }
 
 #[tauri::command]
-async fn get_speaker_noise_reduce(state: State<'_, SipAppState>) -> Result<bool, String> {
-    Ok(*state.speaker_noise_reduce.lock().await)
+async fn set_speaker_mute(state: State<'_, SipAppState>, muted: bool) -> Result<(), AppError> {
+    let handle = call_handle(&state, None).await?;
+
+    sip::handle_set_speaker_mute(&handle, muted).await
 }
 
+/// Mic and speaker mute state of the active call, e.g. after the UI reloaded
 #[tauri::command]
-async fn set_speaker_noise_reduce(state: State<'_, SipAppState>, enabled: bool) -> Result<(), String> {
-    *state.speaker_noise_reduce.lock().await = enabled;
+async fn get_mute_state(state: State<'_, SipAppState>) -> Result<sip::state::MuteState, AppError> {
+    let handle = call_handle(&state, None).await?;
 
-    // Apply immediately to the active call if one exists
-    let handle_opt = state.handle.lock().await.clone();
-    if let Some(handle) = handle_opt {
-        sip::handle_set_speaker_noise_reduce(&handle, enabled).await;
-    }
-    Ok(())
+    sip::handle_get_mute_state(&handle).await
 }
 
 #[tauri::command]
-async fn toggle_noise_reduce(state: State<'_, SipAppState>) -> Result<bool, String> {
-    let handle = {
-        let handle_guard = state.handle.lock().await;
-        handle_guard
-            .as_ref()
-            .ok_or_else(|| "Not registered".to_string())?
-            .clone()
-    };
+async fn send_dtmf(state: State<'_, SipAppState>, digit: String) -> Result<(), AppError> {
+    let handle = call_handle(&state, None).await?;
 
-    sip::handle_toggle_noise_reduce(&handle).await
+    let mode = *state.dtmf_mode.lock().await;
+    let params = *state.dtmf_params.lock().await;
+    sip::handle_send_dtmf(&handle, digit, mode, params).await
 }
 
+/// Send a string of DTMF digits on the active call, `gap_ms` (default 80 ms)
+/// between digits. Invalid characters are skipped.
 #[tauri::command]
-async fn toggle_mic_mute(state: State<'_, SipAppState>) -> Result<bool, String> {
-    let handle = {
-        let handle_guard = state.handle.lock().await;
-        handle_guard
-            .as_ref()
-            .ok_or_else(|| "Not registered".to_string())?
-            .clone()
-    };
+async fn sip_send_dtmf_string(
+    state: State<'_, SipAppState>,
+    digits: String,
+    gap_ms: Option<u64>,
+) -> Result<(), AppError> {
+    let handle = call_handle(&state, None).await?;
+
+    let mode = *state.dtmf_mode.lock().await;
+    let params = *state.dtmf_params.lock().await;
+    let gap_ms = gap_ms.unwrap_or(sip::DEFAULT_DTMF_GAP_MS);
+    sip::handle_send_dtmf_string(&handle, digits, mode, params, gap_ms).await
+}
 
-    sip::handle_toggle_mic_mute(&handle).await
+/// Get the DTMF sending mode ("rfc2833", "info" or "inband")
+#[tauri::command]
+async fn get_dtmf_mode(state: State<'_, SipAppState>) -> Result<String, AppError> {
+    Ok(state.dtmf_mode.lock().await.as_str().to_string())
 }
 
+/// Set the DTMF sending mode ("rfc2833", "info" or "inband")
 #[tauri::command]
-async fn toggle_speaker_mute(state: State<'_, SipAppState>) -> Result<bool, String> {
-    let handle = {
-        let handle_guard = state.handle.lock().await;
-        handle_guard
-            .as_ref()
-            .ok_or_else(|| "Not registered".to_string())?
-            .clone()
-    };
+async fn set_dtmf_mode(state: State<'_, SipAppState>, mode: String) -> Result<(), AppError> {
+    *state.dtmf_mode.lock().await = sip::state::DtmfMode::parse(&mode)?;
+    Ok(())
+}
 
-    sip::handle_toggle_speaker_mute(&handle).await
+/// Get the DTMF tone duration and volume
+#[tauri::command]
+async fn get_dtmf_params(
+    state: State<'_, SipAppState>,
+) -> Result<sip::state::DtmfParams, AppError> {
+    Ok(*state.dtmf_params.lock().await)
 }
 
+/// Set the DTMF tone duration (40–1000 ms) and volume (0–36 -dBm0, 0 = loudest).
+/// The duration applies to RFC 4733 events and SIP INFO; in-band tones keep
+/// their fixed length.
 #[tauri::command]
-async fn send_dtmf(state: State<'_, SipAppState>, digit: String) -> Result<(), String> {
-    let handle = {
-        let handle_guard = state.handle.lock().await;
-        handle_guard
-            .as_ref()
-            .ok_or_else(|| "Not registered".to_string())?
-            .clone()
+async fn set_dtmf_params(
+    state: State<'_, SipAppState>,
+    duration_ms: u32,
+    volume_dbm0: u8,
+) -> Result<(), AppError> {
+    if !(40..=1000).contains(&duration_ms) {
+        return Err(AppError::Other(
+            "DTMF duration must be between 40 and 1000 ms".to_string(),
+        ));
+    }
+    if volume_dbm0 > 36 {
+        return Err(AppError::Other(
+            "DTMF volume must be between 0 and 36 dBm0".to_string(),
+        ));
+    }
+    *state.dtmf_params.lock().await = sip::state::DtmfParams {
+        duration_ms,
+        volume_dbm0,
     };
-
-    sip::handle_send_dtmf(&handle, digit).await
+    Ok(())
 }
 
 // ── SIP Flow config commands (unified interface, works before and after registration) ──
 
 /// Enable or disable SIP message flow logging
 #[tauri::command]
-async fn set_sip_flow_enabled(state: State<'_, SipAppState>, enabled: bool) -> Result<(), String> {
+async fn set_sip_flow_enabled(
+    state: State<'_, SipAppState>,
+    enabled: bool,
+) -> Result<(), AppError> {
     // Update stored config
     state.sip_flow_config.lock().await.enabled = enabled;
 
-    // If already registered, also update the running instance
-    let handle_guard = state.handle.lock().await;
-    if let Some(handle) = handle_guard.as_ref() {
+    // If already registered, also update the running instances
+    let handles = state.handles.lock().await;
+    for handle in handles.values() {
         if enabled {
             sip::handle_enable_sip_flow(handle)?;
         } else {
@@ -595,14 +1801,31 @@ async fn set_sip_flow_enabled(state: State<'_, SipAppState>, enabled: bool) -> R
 
 /// Set the SIP message log directory
 #[tauri::command]
-async fn set_sip_flow_dir(state: State<'_, SipAppState>, dir: String) -> Result<(), String> {
+async fn set_sip_flow_dir(state: State<'_, SipAppState>, dir: String) -> Result<(), AppError> {
     // Update stored config
     state.sip_flow_config.lock().await.log_dir = dir.clone();
 
-    // If already registered, also update the running instance
-    let handle_guard = state.handle.lock().await;
-    if let Some(handle) = handle_guard.as_ref() {
-        sip::handle_set_sip_flow_dir(handle, dir)?;
+    // If already registered, also update the running instances
+    let handles = state.handles.lock().await;
+    for handle in handles.values() {
+        sip::handle_set_sip_flow_dir(handle, dir.clone())?;
+    }
+
+    Ok(())
+}
+
+/// Log only the SIP messages of one Call-ID; `None` logs every message again
+#[tauri::command]
+async fn set_sip_flow_filter(
+    state: State<'_, SipAppState>,
+    call_id: Option<String>,
+) -> Result<(), AppError> {
+    let call_id = call_id.filter(|id| !id.trim().is_empty());
+    state.sip_flow_config.lock().await.filter = call_id.clone();
+
+    let handles = state.handles.lock().await;
+    for handle in handles.values() {
+        sip::handle_set_sip_flow_filter(handle, call_id.clone())?;
     }
 
     Ok(())
@@ -612,32 +1835,275 @@ async fn set_sip_flow_dir(state: State<'_, SipAppState>, dir: String) -> Result<
 #[tauri::command]
 async fn get_sip_flow_config(
     state: State<'_, SipAppState>,
-) -> Result<sip::state::FlowConfig, String> {
-    // Prefer live state from the registered handle when available
-    let handle_guard = state.handle.lock().await;
-    if let Some(handle) = handle_guard.as_ref() {
+) -> Result<sip::state::FlowConfig, AppError> {
+    // Prefer live state from a registered handle when available
+    let handles = state.handles.lock().await;
+    if let Some(handle) = handles.values().next() {
         let enabled = sip::handle_is_sip_flow_enabled(handle)?;
         let log_dir = sip::handle_get_sip_flow_dir(handle)?;
-        Ok(sip::state::FlowConfig { enabled, log_dir })
+        let filter = sip::handle_get_sip_flow_filter(handle)?;
+        Ok(sip::state::FlowConfig {
+            enabled,
+            log_dir,
+            filter,
+        })
     } else {
         // Otherwise return the stored config
         Ok(state.sip_flow_config.lock().await.clone())
     }
 }
 
+/// Write the SIP messages captured by the flow logger of every registered
+/// account to `path` as a PCAP file for Wireshark. Returns the number of
+/// messages written; only messages seen while logging was enabled are captured.
+#[tauri::command]
+async fn export_sip_flow_pcap(
+    state: State<'_, SipAppState>,
+    path: String,
+) -> Result<usize, AppError> {
+    let mut messages = Vec::new();
+    for handle in state.handles.lock().await.values() {
+        messages.extend(sip::handle_sip_flow_captures(handle)?);
+    }
+    messages.sort_by_key(|m| m.timestamp);
+
+    let mut file = std::io::BufWriter::new(
+        std::fs::File::create(&path)
+            .map_err(|e| AppError::Other(format!("Failed to create {}: {}", path, e)))?,
+    );
+    sip::pcap::write_pcap(&mut file, &messages)
+        .and_then(|_| std::io::Write::flush(&mut file))
+        .map_err(|e| AppError::Other(format!("Failed to write {}: {}", path, e)))?;
+    Ok(messages.len())
+}
+
 /// Get the SRTP preference setting
 #[tauri::command]
-async fn get_prefer_srtp(state: State<'_, SipAppState>) -> Result<bool, String> {
+async fn get_prefer_srtp(state: State<'_, SipAppState>) -> Result<bool, AppError> {
     Ok(*state.prefer_srtp.lock().await)
 }
 
 /// Set the SRTP preference setting
 #[tauri::command]
-async fn set_prefer_srtp(state: State<'_, SipAppState>, enabled: bool) -> Result<(), String> {
+async fn set_prefer_srtp(state: State<'_, SipAppState>, enabled: bool) -> Result<(), AppError> {
     *state.prefer_srtp.lock().await = enabled;
     Ok(())
 }
 
+/// Get whether outbound calls offer 100rel (reliable provisional responses / PRACK)
+#[tauri::command]
+async fn get_100rel_enabled(state: State<'_, SipAppState>) -> Result<bool, AppError> {
+    Ok(*state.prack_enabled.lock().await)
+}
+
+/// Enable or disable 100rel on subsequent outbound calls; some PBXes
+/// mishandle PRACK and need it turned off
+#[tauri::command]
+async fn set_100rel_enabled(state: State<'_, SipAppState>, enabled: bool) -> Result<(), AppError> {
+    *state.prack_enabled.lock().await = enabled;
+    Ok(())
+}
+
+/// Get how many REGISTER attempts are made before giving up (0 = unlimited)
+#[tauri::command]
+async fn get_registration_max_attempts(state: State<'_, SipAppState>) -> Result<u32, AppError> {
+    Ok(*state.registration_max_attempts.lock().await)
+}
+
+/// Set how many REGISTER attempts are made before giving up (0 = unlimited);
+/// applies to accounts registered afterwards
+#[tauri::command]
+async fn set_registration_max_attempts(
+    state: State<'_, SipAppState>,
+    attempts: u32,
+) -> Result<(), AppError> {
+    *state.registration_max_attempts.lock().await = attempts;
+    Ok(())
+}
+
+/// Get the OPTIONS keepalive interval in seconds (0 = disabled)
+#[tauri::command]
+async fn get_keepalive_interval(state: State<'_, SipAppState>) -> Result<u64, AppError> {
+    Ok(*state.keepalive_interval_secs.lock().await)
+}
+
+/// Get the CRLF keepalive interval in seconds on TCP/TLS/WS/WSS (0 = disabled)
+#[tauri::command]
+async fn get_crlf_keepalive_interval(state: State<'_, SipAppState>) -> Result<u64, AppError> {
+    Ok(*state.crlf_keepalive_secs.lock().await)
+}
+
+/// Set the OPTIONS keepalive interval in seconds (0 disables it) for all
+/// registered accounts; reachability is reported as `sip://server-reachable`.
+/// `crlf_secs`, when given, sets the double-CRLF keepalive interval on
+/// connection-oriented transports the same way.
+#[tauri::command]
+async fn set_keepalive_interval(
+    state: State<'_, SipAppState>,
+    secs: u64,
+    crlf_secs: Option<u64>,
+) -> Result<(), AppError> {
+    *state.keepalive_interval_secs.lock().await = secs;
+    if let Some(crlf_secs) = crlf_secs {
+        *state.crlf_keepalive_secs.lock().await = crlf_secs;
+    }
+    for handle in state.handles.lock().await.values() {
+        sip::handle_set_keepalive_interval(handle, secs);
+        if let Some(crlf_secs) = crlf_secs {
+            sip::handle_set_crlf_keepalive_interval(handle, crlf_secs);
+        }
+    }
+    Ok(())
+}
+
+/// Get how TLS/WSS server certificates are verified
+#[tauri::command]
+async fn get_tls_verification(
+    state: State<'_, SipAppState>,
+) -> Result<sip::tls::TlsVerificationConfig, AppError> {
+    Ok(state.tls_verification.lock().await.config())
+}
+
+/// Set how TLS/WSS server certificates are verified: `"strict"` (system CA
+/// certificates), `"skip"` (accept any certificate) or `"pinned"` (only the
+/// certificate with SHA-256 `fingerprint`); applies to accounts registered afterwards
+#[tauri::command]
+async fn set_tls_verification(
+    state: State<'_, SipAppState>,
+    mode: String,
+    fingerprint: Option<String>,
+) -> Result<(), AppError> {
+    let verification = sip::tls::TlsVerification::parse(&mode, fingerprint.as_deref())?;
+    *state.tls_verification.lock().await = verification;
+    Ok(())
+}
+
+/// Pin the TLS/WSS server certificate by its SHA-256 fingerprint (hex, with or
+/// without `:` separators): only that certificate is accepted, whatever its CA.
+/// Same as `set_tls_verification("pinned", sha256_hex)`
+#[tauri::command]
+async fn set_pinned_cert(
+    state: State<'_, SipAppState>,
+    sha256_hex: String,
+) -> Result<(), AppError> {
+    let verification = sip::tls::TlsVerification::parse("pinned", Some(&sha256_hex))?;
+    *state.tls_verification.lock().await = verification;
+    Ok(())
+}
+
+/// Get the Do Not Disturb setting
+#[tauri::command]
+async fn get_dnd(state: State<'_, SipAppState>) -> Result<sip::state::DndConfig, AppError> {
+    Ok(*state.dnd.lock().await)
+}
+
+/// Enable or disable Do Not Disturb. Incoming calls are then rejected with
+/// `reject_code` (4xx-6xx, default 486 Busy Here) and reported as `sip://call-rejected-dnd`.
+#[tauri::command]
+async fn set_dnd(
+    state: State<'_, SipAppState>,
+    enabled: bool,
+    reject_code: Option<u16>,
+) -> Result<(), AppError> {
+    let reject_code = reject_code.unwrap_or(486);
+    if !(400..700).contains(&reject_code) {
+        return Err(format!("Invalid reject code: {}", reject_code).into());
+    }
+    *state.dnd.lock().await = sip::state::DndConfig {
+        enabled,
+        reject_code,
+    };
+    Ok(())
+}
+
+/// Get the auto-answer setting
+#[tauri::command]
+async fn get_auto_answer(
+    state: State<'_, SipAppState>,
+) -> Result<sip::state::AutoAnswerConfig, AppError> {
+    Ok(*state.auto_answer.lock().await)
+}
+
+/// Answer incoming calls automatically after `delay_ms` of ringing.
+/// Only applies while no other call is in progress.
+#[tauri::command]
+async fn set_auto_answer(
+    state: State<'_, SipAppState>,
+    enabled: bool,
+    delay_ms: u64,
+) -> Result<(), AppError> {
+    *state.auto_answer.lock().await = sip::state::AutoAnswerConfig { enabled, delay_ms };
+    Ok(())
+}
+
+/// Get the configured STUN/TURN servers (empty means the built-in STUN list is used)
+#[tauri::command]
+async fn get_ice_servers(
+    state: State<'_, SipAppState>,
+) -> Result<Vec<webrtc::IceServerConfig>, AppError> {
+    Ok(state.ice_config.lock().await.servers.clone())
+}
+
+#[tauri::command]
+async fn get_dscp(state: State<'_, SipAppState>) -> Result<u8, AppError> {
+    Ok(*state.dscp.lock().await)
+}
+
+/// DSCP for outgoing RTP, e.g. 46 (EF); 0 leaves it unmarked. SIP signaling is
+/// always marked CS3. Best effort: Windows drops the mark without a QoS policy.
+/// Applies to new calls.
+#[tauri::command]
+async fn set_dscp(state: State<'_, SipAppState>, value: u8) -> Result<(), AppError> {
+    qos::validate(value)?;
+    *state.dscp.lock().await = value;
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_ice_mode(state: State<'_, SipAppState>) -> Result<webrtc::IceMode, AppError> {
+    Ok(*state.ice_mode.lock().await)
+}
+
+/// "full" gathers STUN/TURN candidates; "host-only" skips the ICE servers entirely,
+/// for LAN deployments where public STUN queries are unwanted. Applies to new calls.
+#[tauri::command]
+async fn set_ice_mode(
+    state: State<'_, SipAppState>,
+    mode: webrtc::IceMode,
+) -> Result<(), AppError> {
+    info!(mode = ?mode, "ICE mode set");
+    *state.ice_mode.lock().await = mode;
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_external_ip(state: State<'_, SipAppState>) -> Result<Option<IpAddr>, AppError> {
+    Ok(*state.external_ip.lock().await)
+}
+
+/// Advertise a fixed public IP (1:1 NAT) in SDP with the local RTP port, skipping
+/// STUN. `None` goes back to STUN discovery. Applies to new calls.
+#[tauri::command]
+async fn set_external_ip(
+    state: State<'_, SipAppState>,
+    ip: Option<IpAddr>,
+) -> Result<(), AppError> {
+    *state.external_ip.lock().await = ip;
+    Ok(())
+}
+
+/// Set the STUN/TURN servers used for subsequent calls
+#[tauri::command]
+async fn set_ice_servers(
+    state: State<'_, SipAppState>,
+    servers: Vec<webrtc::IceServerConfig>,
+) -> Result<(), AppError> {
+    let config = webrtc::IceConfig { servers };
+    config.validate()?;
+    *state.ice_config.lock().await = config;
+    Ok(())
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     // Install ring as the default rustls CryptoProvider before any TLS operations.
@@ -649,40 +2115,176 @@ pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
+        .setup(|app| {
+            tauri::async_runtime::spawn(watch_network(app.handle().clone()));
+            tauri::async_runtime::spawn(watch_default_devices(app.handle().clone()));
+            Ok(())
+        })
         .manage(SipAppState {
-            handle: tokio::sync::Mutex::new(None),
-            cancel_token: tokio::sync::Mutex::new(None),
+            handles: tokio::sync::Mutex::new(Default::default()),
             input_device: tokio::sync::Mutex::new(None),
             output_device: tokio::sync::Mutex::new(None),
+            follow_system_default: tokio::sync::Mutex::new(false), // default: pinned devices (None = default at call start)
             sip_flow_config: tokio::sync::Mutex::new(sip::state::FlowConfig::default()),
             prefer_srtp: tokio::sync::Mutex::new(true), // default: prefer SRTP
+            prack_enabled: tokio::sync::Mutex::new(true), // default: offer 100rel
+            registration_max_attempts: tokio::sync::Mutex::new(10), // default: give up after 10 attempts
+            keepalive_interval_secs: tokio::sync::Mutex::new(0), // default: OPTIONS keepalive disabled
+            crlf_keepalive_secs: tokio::sync::Mutex::new(15), // default: CRLF ping every 15 s on connection-oriented transports
+            tls_verification: tokio::sync::Mutex::new(sip::tls::TlsVerification::default()), // default: strict
+            dnd: tokio::sync::Mutex::new(sip::state::DndConfig::default()), // default: DND off, reject with 486
+            auto_answer: tokio::sync::Mutex::new(sip::state::AutoAnswerConfig::default()), // default: auto-answer off
             noise_reduce: tokio::sync::Mutex::new(false), // default: noise reduction disabled
             speaker_noise_reduce: tokio::sync::Mutex::new(false), // default: speaker noise reduction disabled
+            noise_reduce_strength: tokio::sync::Mutex::new(1.0), // default: full denoise when enabled
+            echo_cancel: tokio::sync::Mutex::new(false), // default: echo cancellation disabled
+            speaker_volume: tokio::sync::Mutex::new(1.0), // default: unchanged volume
+            mic_gain: tokio::sync::Mutex::new(1.0),      // default: unchanged gain
+            hold_music_path: tokio::sync::Mutex::new(None), // default: silent hold
+            jitter_buffer_ms: tokio::sync::Mutex::new(
+                webrtc::audio_bridge::DEFAULT_JITTER_BUFFER_MS,
+            ),
+            audio_buffer_ms: tokio::sync::Mutex::new(webrtc::audio_bridge::DEFAULT_AUDIO_BUFFER_MS), // default: 200 ms
+            plc: tokio::sync::Mutex::new(true), // default: packet loss concealment enabled
+            comfort_noise: tokio::sync::Mutex::new(true), // default: comfort noise enabled
+            l16: tokio::sync::Mutex::new(false), // default: L16 not offered
+            rtcp_mux: tokio::sync::Mutex::new(false), // default: rtcp-mux stripped for non-ICE peers
+            external_ip: tokio::sync::Mutex::new(None), // default: public address from STUN
+            dscp: tokio::sync::Mutex::new(0), // default: RTP unmarked; SIP signaling always marked CS3
+            ice_mode: tokio::sync::Mutex::new(webrtc::IceMode::default()), // default: full (STUN/TURN)
+            media_timeout_secs: tokio::sync::Mutex::new(30), // default: hang up after 30 s without RTP
+            opus_config: tokio::sync::Mutex::new(webrtc::encoder::OpusConfig::default()), // default: 32 kbps, FEC on, DTX off
+            adaptive_bitrate: tokio::sync::Mutex::new(sip::state::AdaptiveBitrateConfig::default()), // default: off, 12-64 kbps
+            vad: tokio::sync::Mutex::new(webrtc::vad::VadConfig::default()), // default: VAD off, sensitivity 50
+            dtmf_mode: tokio::sync::Mutex::new(sip::state::DtmfMode::default()), // default: RFC 4733 telephone-event
+            dtmf_params: tokio::sync::Mutex::new(sip::state::DtmfParams::default()), // default: 160 ms at -10 dBm0
+            ice_config: tokio::sync::Mutex::new(webrtc::IceConfig::default()), // default: built-in STUN servers
+            ringtone: webrtc::ringtone::RingtonePlayer::new(),
+            headset: headset::HeadsetControl::default(),
+            audio_test: tokio::sync::Mutex::new(None), // default: not running
         })
         .invoke_handler(tauri::generate_handler![
             enumerate_audio_devices,
+            get_device_capabilities,
             sip_is_registered,
+            sip_get_registration_info,
             sip_register,
+            sip_register_account,
             sip_unregister,
             sip_make_call,
+            sip_send_message,
+            subscribe_blf,
+            unsubscribe_blf,
             sip_hangup,
             sip_answer_call,
             sip_reject_call,
+            sip_hold,
+            sip_resume,
+            sip_switch_call,
+            sip_start_conference,
+            sip_end_conference,
+            sip_transfer,
+            sip_attended_transfer,
+            sip_get_call_stats,
+            sip_get_media_info,
             set_input_device,
             set_output_device,
+            get_follow_system_default,
+            set_follow_system_default,
+            get_ringtone_config,
+            set_ringtone_path,
+            set_ringtone_enabled,
+            get_headset_control,
+            set_headset_control,
             toggle_mic_mute,
             toggle_speaker_mute,
+            set_mic_mute,
+            set_speaker_mute,
+            get_mute_state,
             toggle_noise_reduce,
             get_noise_reduce,
             set_noise_reduce,
             get_speaker_noise_reduce,
             set_speaker_noise_reduce,
+            get_noise_reduce_strength,
+            set_noise_reduce_strength,
+            get_echo_cancel,
+            set_echo_cancel,
+            set_speaker_volume,
+            set_mic_gain,
+            set_hold_music_path,
+            start_audio_test,
+            stop_audio_test,
+            play_test_tone,
+            get_audio_settings,
+            get_jitter_buffer_ms,
+            set_jitter_buffer_ms,
+            get_audio_buffer_ms,
+            set_audio_buffer_ms,
+            set_low_latency_audio,
+            get_plc,
+            set_plc,
+            get_comfort_noise,
+            set_comfort_noise,
+            get_l16,
+            set_l16,
+            get_rtcp_mux,
+            set_rtcp_mux,
+            get_external_ip,
+            set_external_ip,
+            get_ice_mode,
+            set_ice_mode,
+            get_dscp,
+            set_dscp,
+            get_media_timeout,
+            set_media_timeout,
+            get_opus_config,
+            set_opus_config,
+            get_adaptive_bitrate,
+            set_adaptive_bitrate,
+            get_vad,
+            set_vad,
+            set_vad_sensitivity,
+            reconnect_audio,
+            start_recording,
+            stop_recording,
+            play_audio_into_call,
+            stop_audio_into_call,
             send_dtmf,
+            sip_send_dtmf_string,
+            get_dtmf_mode,
+            set_dtmf_mode,
+            get_dtmf_params,
+            set_dtmf_params,
             set_sip_flow_enabled,
             set_sip_flow_dir,
+            set_sip_flow_filter,
             get_sip_flow_config,
+            export_sip_flow_pcap,
             get_prefer_srtp,
             set_prefer_srtp,
+            get_100rel_enabled,
+            set_100rel_enabled,
+            get_registration_max_attempts,
+            set_registration_max_attempts,
+            get_keepalive_interval,
+            set_keepalive_interval,
+            get_crlf_keepalive_interval,
+            get_tls_verification,
+            set_tls_verification,
+            set_pinned_cert,
+            get_dnd,
+            set_dnd,
+            get_auto_answer,
+            set_auto_answer,
+            get_ice_servers,
+            set_ice_servers,
+            save_account,
+            list_accounts,
+            get_accounts,
+            delete_account,
+            get_call_history,
+            clear_call_history,
         ])
         .on_window_event(|window, event| {
             if let tauri::WindowEvent::CloseRequested { api, .. } = event {
@@ -692,12 +2294,14 @@ pub fn run() {
                 api.prevent_close();
                 let app = window.app_handle().clone();
                 let state = app.state::<SipAppState>();
-                if let Some(token) = state.cancel_token.blocking_lock().take() {
-                    token.cancel();
+                let handles = std::mem::take(&mut *state.handles.blocking_lock());
+                if !handles.is_empty() {
+                    for handle in handles.values() {
+                        handle.cancel_token.cancel();
+                    }
                     // Give registration_refresh_loop time to send UNREGISTER.
                     std::thread::sleep(std::time::Duration::from_millis(500));
                 }
-                state.handle.blocking_lock().take();
                 app.exit(0);
             }
         })
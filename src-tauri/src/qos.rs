@@ -0,0 +1,93 @@
+//! DSCP marking (RFC 4594) of outgoing packets: a configured value for RTP
+//! (usually EF, 46), CS3 for SIP.
+//!
+//! The DSCP is the upper six bits of the IPv4 TOS byte / IPv6 traffic class.
+//! Marking is best effort and failures are only logged. Platform limits:
+//! - Linux and macOS apply the mark directly, no privileges needed.
+//! - Windows ignores `IP_TOS` set by applications unless a QoS policy allows
+//!   it; reliable marking there needs the QWAVE API, which is not used. The
+//!   IPv6 traffic class cannot be set at all.
+//! - Relayed (TURN) media is sent from a socket owned by the TURN client and
+//!   stays unmarked.
+
+use std::io;
+use std::net::SocketAddr;
+use tokio::net::{TcpSocket, TcpStream, UdpSocket};
+use tracing::{debug, warn};
+
+/// Class Selector 3, for call signaling
+pub const DSCP_CS3: u8 = 24;
+
+pub fn validate(dscp: u8) -> Result<(), String> {
+    if dscp > 63 {
+        return Err(format!("DSCP must be between 0 and 63, got {}", dscp));
+    }
+    Ok(())
+}
+
+/// TOS / traffic class byte carrying `dscp` (ECN bits left clear)
+fn tos(dscp: u8) -> u32 {
+    (dscp as u32) << 2
+}
+
+/// Mark everything sent from `socket` with `dscp`.
+pub fn mark_udp(socket: &UdpSocket, dscp: u8) {
+    let result = socket.local_addr().and_then(|addr| match addr {
+        SocketAddr::V4(_) => socket.set_tos_v4(tos(dscp)),
+        SocketAddr::V6(_) => set_udp_tclass(socket, tos(dscp)),
+    });
+    report(result, "UDP", dscp);
+}
+
+/// Open a TCP connection to `addr` whose packets carry `dscp`.
+pub async fn connect_tcp(addr: SocketAddr, dscp: u8) -> io::Result<TcpStream> {
+    let (socket, result) = match addr {
+        SocketAddr::V4(_) => {
+            let socket = TcpSocket::new_v4()?;
+            let result = socket.set_tos_v4(tos(dscp));
+            (socket, result)
+        }
+        SocketAddr::V6(_) => {
+            let socket = TcpSocket::new_v6()?;
+            let result = set_tcp_tclass(&socket, tos(dscp));
+            (socket, result)
+        }
+    };
+    report(result, "TCP", dscp);
+    socket.connect(addr).await
+}
+
+fn report(result: io::Result<()>, transport: &str, dscp: u8) {
+    match result {
+        Ok(()) => debug!(transport, dscp, "DSCP marking applied"),
+        Err(e) => warn!(transport, dscp, error = %e, "Failed to set DSCP marking"),
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+fn set_udp_tclass(socket: &UdpSocket, tclass: u32) -> io::Result<()> {
+    socket.set_tclass_v6(tclass)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos")))]
+fn set_udp_tclass(_socket: &UdpSocket, _tclass: u32) -> io::Result<()> {
+    Err(tclass_unsupported())
+}
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+fn set_tcp_tclass(socket: &TcpSocket, tclass: u32) -> io::Result<()> {
+    socket.set_tclass_v6(tclass)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos")))]
+fn set_tcp_tclass(_socket: &TcpSocket, _tclass: u32) -> io::Result<()> {
+    Err(tclass_unsupported())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos")))]
+fn tclass_unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "IPv6 traffic class cannot be set on this platform",
+    )
+}
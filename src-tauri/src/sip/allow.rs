@@ -0,0 +1,93 @@
+//! `Allow` header (RFC 3261 §20.5) listing the methods we handle, so the far
+//! end knows it may change the session with UPDATE (RFC 3311) instead of a
+//! re-INVITE, transfer us with REFER, and so on.
+//!
+//! It goes on INVITE and REGISTER requests and on 2xx responses to INVITE and
+//! OPTIONS, replacing the stack's own list on REGISTER, which names every
+//! method rsip knows.
+
+use rsip::headers::UntypedHeader;
+use rsip::prelude::{HasHeaders, HeadersExt};
+use rsip::{Method, SipMessage};
+use rsipstack::{transaction::endpoint::MessageInspector, transport::SipAddr};
+
+/// Methods answered by the app, in and out of dialogs
+const ALLOWED_METHODS: &[Method] = &[
+    Method::Invite,
+    Method::Ack,
+    Method::Cancel,
+    Method::Bye,
+    Method::Options,
+    Method::Notify,
+    Method::Refer,
+    Method::Message,
+    Method::PRack,
+    Method::Update,
+];
+
+/// Adds `Allow` to outgoing messages that should carry it, then passes every
+/// message on to `inner`.
+pub struct AllowInspector<I> {
+    inner: I,
+}
+
+impl<I> AllowInspector<I> {
+    pub fn new(inner: I) -> Self {
+        Self { inner }
+    }
+}
+
+impl<I: MessageInspector> MessageInspector for AllowInspector<I> {
+    fn before_send(&self, mut msg: SipMessage, dest: Option<&SipAddr>) -> SipMessage {
+        if needs_allow(&msg) {
+            let allow = ALLOWED_METHODS
+                .iter()
+                .map(Method::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+            msg.headers_mut()
+                .unique_push(rsip::headers::Allow::new(allow).into());
+        }
+        self.inner.before_send(msg, dest)
+    }
+
+    fn after_received(&self, msg: SipMessage, from: &SipAddr) -> SipMessage {
+        self.inner.after_received(msg, from)
+    }
+}
+
+fn needs_allow(msg: &SipMessage) -> bool {
+    match msg {
+        SipMessage::Request(req) => matches!(req.method, Method::Invite | Method::Register),
+        SipMessage::Response(resp) => {
+            resp.status_code.kind() == rsip::StatusCodeKind::Successful
+                && resp
+                    .cseq_header()
+                    .and_then(|cseq| cseq.method())
+                    .is_ok_and(|method| matches!(method, Method::Invite | Method::Options))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rsip::StatusCode;
+
+    fn response(status: StatusCode, cseq: &str) -> SipMessage {
+        SipMessage::Response(rsip::Response {
+            status_code: status,
+            version: rsip::Version::V2,
+            headers: vec![rsip::headers::CSeq::new(cseq).into()].into(),
+            body: vec![],
+        })
+    }
+
+    #[test]
+    fn test_needs_allow() {
+        assert!(needs_allow(&response(StatusCode::OK, "1 INVITE")));
+        assert!(needs_allow(&response(StatusCode::OK, "7 OPTIONS")));
+        assert!(!needs_allow(&response(StatusCode::Ringing, "1 INVITE")));
+        assert!(!needs_allow(&response(StatusCode::OK, "2 BYE")));
+    }
+}
@@ -0,0 +1,172 @@
+use rsip::prelude::HasHeaders;
+use rsipstack::transaction::transaction::Transaction;
+use rsipstack::Result;
+use tauri::Emitter;
+use tracing::{debug, info};
+
+use crate::sip::state::BlfStatePayload;
+
+/// Event package and NOTIFY body type of BLF subscriptions (RFC 4235)
+pub const EVENT: &str = "dialog";
+pub const ACCEPT: &str = "application/dialog-info+xml";
+
+/// Busy lamp state of a monitored extension.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlfState {
+    Idle,
+    Ringing,
+    Confirmed,
+}
+
+impl BlfState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BlfState::Idle => "idle",
+            BlfState::Ringing => "ringing",
+            BlfState::Confirmed => "confirmed",
+        }
+    }
+}
+
+/// Extract the monitored user and its lamp state from a dialog-info document.
+///
+/// The user comes from the `entity` attribute of `<dialog-info>`. Any
+/// confirmed dialog makes the extension busy; otherwise any early dialog
+/// (trying / proceeding / early) makes it ringing; no live dialog is idle.
+pub fn parse_dialog_info(body: &str) -> Option<(String, BlfState)> {
+    let root_start = body.find("<dialog-info")?;
+    let root = &body[root_start..root_start + body[root_start..].find('>')?];
+    let entity = attribute(root, "entity")?;
+    let user = entity
+        .split_once(':')
+        .map_or(entity, |(_, rest)| rest)
+        .split('@')
+        .next()
+        .unwrap_or_default()
+        .to_string();
+
+    let mut state = BlfState::Idle;
+    let mut rest = body;
+    while let Some(start) = rest.find("<state") {
+        let after_tag = &rest[start..];
+        let Some(open_end) = after_tag.find('>') else {
+            break;
+        };
+        let content = &after_tag[open_end + 1..];
+        let Some(close) = content.find("</state>") else {
+            break;
+        };
+        match content[..close].trim() {
+            "confirmed" => state = BlfState::Confirmed,
+            "trying" | "proceeding" | "early" if state == BlfState::Idle => {
+                state = BlfState::Ringing
+            }
+            _ => {}
+        }
+        rest = &content[close..];
+    }
+    Some((user, state))
+}
+
+/// Value of `name="..."` (or single-quoted) inside an XML start tag.
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let pos = tag.find(&format!("{}=", name))? + name.len() + 1;
+    let quote = tag[pos..].chars().next()?;
+    if quote != '"' && quote != '\'' {
+        return None;
+    }
+    let value = &tag[pos + 1..];
+    Some(&value[..value.find(quote)?])
+}
+
+/// Check whether a request is a NOTIFY for the `dialog` event package.
+pub fn is_blf_notify(req: &rsip::Request) -> bool {
+    req.method == rsip::Method::Notify
+        && req.headers().iter().any(|h| match h {
+            rsip::Header::Event(e) => event_is_dialog(&e.to_string()),
+            rsip::Header::Other(name, value) => {
+                name.eq_ignore_ascii_case("event") && event_is_dialog(value)
+            }
+            _ => false,
+        })
+}
+
+/// `Event: dialog` possibly followed by parameters (`dialog;sla`)
+fn event_is_dialog(value: &str) -> bool {
+    value
+        .split(';')
+        .next()
+        .is_some_and(|package| package.trim().eq_ignore_ascii_case(EVENT))
+}
+
+/// Answer a dialog-package NOTIFY and emit the extension's lamp state as `sip://blf-state`.
+pub async fn process_notify(
+    tx: &mut Transaction,
+    account_id: &str,
+    app_handle: &tauri::AppHandle,
+) -> Result<()> {
+    tx.reply(rsip::StatusCode::OK).await?;
+
+    let body = String::from_utf8_lossy(&tx.original.body);
+    let Some((extension, state)) = parse_dialog_info(&body) else {
+        debug!(account = %account_id, "BLF NOTIFY without dialog-info");
+        return Ok(());
+    };
+    info!(account = %account_id, extension = %extension, state = state.as_str(), "BLF state");
+    let _ = app_handle.emit(
+        "sip://blf-state",
+        BlfStatePayload {
+            account_id: account_id.to_string(),
+            extension,
+            state: state.as_str().to_string(),
+        },
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dialog_info(states: &[&str]) -> String {
+        let dialogs: String = states
+            .iter()
+            .enumerate()
+            .map(|(i, s)| {
+                format!(
+                    "<dialog id=\"d{}\" direction=\"recipient\"><state>{}</state></dialog>",
+                    i, s
+                )
+            })
+            .collect();
+        format!(
+            "<?xml version=\"1.0\"?>\n<dialog-info xmlns=\"urn:ietf:params:xml:ns:dialog-info\" version=\"3\" state=\"full\" entity=\"sip:1001@pbx.example.com\">{}</dialog-info>",
+            dialogs
+        )
+    }
+
+    #[test]
+    fn test_parse_dialog_info_states() {
+        assert_eq!(
+            parse_dialog_info(&dialog_info(&[])),
+            Some(("1001".to_string(), BlfState::Idle))
+        );
+        assert_eq!(
+            parse_dialog_info(&dialog_info(&["early"])).map(|(_, s)| s),
+            Some(BlfState::Ringing)
+        );
+        assert_eq!(
+            parse_dialog_info(&dialog_info(&["terminated", "confirmed"])).map(|(_, s)| s),
+            Some(BlfState::Confirmed)
+        );
+        assert_eq!(
+            parse_dialog_info(&dialog_info(&["terminated"])).map(|(_, s)| s),
+            Some(BlfState::Idle)
+        );
+    }
+
+    #[test]
+    fn test_parse_dialog_info_rejects_other_bodies() {
+        assert_eq!(parse_dialog_info("Messages-Waiting: yes"), None);
+    }
+}
@@ -1,24 +1,36 @@
 use rsip::headers::UntypedHeader;
 use rsip::prelude::HeadersExt;
-use rsipstack::dialog::dialog::DialogStateSender;
+use rsipstack::dialog::dialog::{Dialog, DialogState, DialogStateSender};
 use rsipstack::dialog::dialog_layer::DialogLayer;
 use rsipstack::transaction::TransactionReceiver;
 use rsipstack::{Error, Result};
 use std::collections::HashMap;
 use std::sync::Arc;
-use tauri::Emitter;
+use std::time::Duration;
+use tauri::{Emitter, Manager};
 use tracing::{debug, info, warn};
 
-use crate::sip::state::{ActiveCall, IncomingCallPayload, PendingCall};
+use crate::call_history::{self, CallRecord};
+use crate::sip::state::{
+    ActiveCalls, CallRejectedDndPayload, IncomingCallPayload, IncomingMessagePayload, PendingCall,
+    SipAppState,
+};
 
+/// Ringtone volume when no other call is active
+const RINGTONE_VOLUME: f32 = 1.0;
+/// Ringtone volume for a call waiting behind an active call
+const WAITING_RINGTONE_VOLUME: f32 = 0.3;
+
+#[allow(clippy::too_many_arguments)]
 pub async fn process_incoming_request(
+    account_id: String,
     dialog_layer: Arc<DialogLayer>,
     mut incoming: TransactionReceiver,
     state_sender: DialogStateSender,
     contact: rsip::Uri,
     app_handle: tauri::AppHandle,
     pending_incoming: Arc<tokio::sync::Mutex<HashMap<String, PendingCall>>>,
-    active_call: Arc<tokio::sync::Mutex<Option<ActiveCall>>>,
+    active_calls: Arc<tokio::sync::Mutex<ActiveCalls>>,
 ) -> Result<()> {
     while let Some(mut tx) = incoming.recv().await {
         let method = tx.original.method.to_string();
@@ -30,6 +42,18 @@ pub async fn process_incoming_request(
 
         debug!(method = %method, call_id = %call_id, "Received incoming request");
 
+        // Voicemail NOTIFYs are accepted with or without a matching dialog:
+        // subscriptions are not dialogs and servers also send them unsolicited
+        if super::mwi::is_mwi_notify(&tx.original) {
+            super::mwi::process_notify(&mut tx, &account_id, &app_handle).await?;
+            continue;
+        }
+        // Likewise for BLF (dialog package) NOTIFYs of monitored extensions
+        if super::blf::is_blf_notify(&tx.original) {
+            super::blf::process_notify(&mut tx, &account_id, &app_handle).await?;
+            continue;
+        }
+
         match tx.original.to_header()?.tag()?.as_ref() {
             Some(_) => match dialog_layer.match_dialog(&tx) {
                 Some(mut d) => {
@@ -56,10 +80,8 @@ pub async fn process_incoming_request(
                 if tx.original.method == rsip::Method::Invite {
                     // Check if we already have an active call with this call_id
                     let already_active = {
-                        let active = active_call.lock().await;
-                        active
-                            .as_ref()
-                            .map_or(false, |call| call.call_id == call_id)
+                        let active = active_calls.lock().await;
+                        active.contains(&call_id)
                     };
 
                     if already_active {
@@ -78,6 +100,25 @@ pub async fn process_incoming_request(
                         continue;
                     }
 
+                    // An INVITE with Replaces (RFC 3891) takes the place of one of our
+                    // calls, e.g. as the target of an attended transfer
+                    let replaced_call = match super::transfer::replaces_header(&tx.original) {
+                        Some(replaces) => {
+                            let replaced = super::transfer::find_replaced_call(
+                                &*active_calls.lock().await,
+                                &replaces,
+                            );
+                            if replaced.is_none() {
+                                warn!(call_id = %call_id, replaces = %replaces, "Replaces names no active call, replying 481");
+                                tx.reply(rsip::StatusCode::CallTransactionDoesNotExist)
+                                    .await?;
+                                continue;
+                            }
+                            replaced
+                        }
+                        None => None,
+                    };
+
                     // Extract caller information — show only the user part (before @)
                     let caller = tx
                         .original
@@ -104,6 +145,37 @@ pub async fn process_incoming_request(
                                 .unwrap_or_else(|| uri.to_string())
                         });
 
+                    // Do Not Disturb: turn the call away without ringing
+                    let dnd = *app_handle.state::<SipAppState>().dnd.lock().await;
+                    if dnd.enabled && replaced_call.is_none() {
+                        info!(call_id = %call_id, caller = %caller, code = dnd.reject_code, "Rejecting incoming call (Do Not Disturb)");
+                        let status = rsip::StatusCode::from(dnd.reject_code);
+                        // Keep the transaction until the caller's ACK stops 486 retransmissions
+                        tokio::spawn(async move {
+                            if let Err(e) = tx.reply(status).await {
+                                warn!(error = ?e, "Failed to send DND rejection");
+                                return;
+                            }
+                            while tx.receive().await.is_some() {}
+                        });
+
+                        call_history::record_call(
+                            &app_handle,
+                            CallRecord::new(&call_id, &account_id, "inbound", &caller),
+                            "dnd",
+                        );
+                        let payload = CallRejectedDndPayload {
+                            account_id: account_id.clone(),
+                            call_id: call_id.clone(),
+                            caller,
+                            timestamp: chrono::Utc::now().timestamp_millis(),
+                        };
+                        if let Err(e) = app_handle.emit("sip://call-rejected-dnd", payload) {
+                            warn!(call_id = %call_id, error = ?e, "Failed to emit DND rejection event");
+                        }
+                        continue;
+                    }
+
                     // Extract SDP offer
                     let sdp_offer = String::from_utf8_lossy(&tx.original.body).to_string();
 
@@ -138,21 +210,40 @@ pub async fn process_incoming_request(
                                     dialog.clone(),
                                 ),
                                 sdp_offer: sdp_offer.clone(),
+                                history: CallRecord::new(&call_id, &account_id, "inbound", &caller),
                             },
                         );
                     }
 
                     // Spawn task to handle transaction - this is critical for SIP message handling
                     let mut dialog_for_handle = dialog;
+                    let ah = app_handle.clone();
+                    let pi = pending_incoming.clone();
+                    let pending_id = call_id.clone();
                     tokio::spawn(async move {
                         if let Err(e) = dialog_for_handle.handle(&mut tx).await {
                             warn!(error = ?e, "Failed to handle transaction");
                         }
+                        // The INVITE transaction is over. Answering or rejecting
+                        // removes the pending call first, so one still pending was
+                        // given up by the caller (CANCEL) or timed out
+                        let pending = pi.lock().await.remove(&pending_id);
+                        if let Some(pending) = pending {
+                            let reason = match dialog_for_handle.state() {
+                                DialogState::Terminated(_, reason) => {
+                                    super::dialog::ended_reason(&reason)
+                                }
+                                _ => "timeout".to_string(),
+                            };
+                            super::stop_ringtone_if_idle(&ah).await;
+                            super::report_missed_call(&ah, pending, &reason);
+                        }
                         Ok::<_, Error>(())
                     });
 
                     // Emit event to frontend
                     let payload = IncomingCallPayload {
+                        account_id: account_id.clone(),
                         call_id: call_id.clone(),
                         caller,
                         callee,
@@ -162,6 +253,40 @@ pub async fn process_incoming_request(
                         warn!(call_id = %call_id, error = ?e, "Failed to emit incoming call event");
                     }
 
+                    // The replacing call is answered without ringing
+                    if let Some(replaced_call) = replaced_call {
+                        tokio::spawn(answer_replacing_call(
+                            app_handle.clone(),
+                            account_id.clone(),
+                            call_id.clone(),
+                            replaced_call,
+                        ));
+                        continue;
+                    }
+
+                    // Ring locally; quietly if this is a waiting call during a conversation
+                    let state = app_handle.state::<SipAppState>();
+                    let output_device = state.output_device.lock().await.clone();
+                    let idle = active_calls.lock().await.is_empty();
+                    let volume = if idle {
+                        RINGTONE_VOLUME
+                    } else {
+                        WAITING_RINGTONE_VOLUME
+                    };
+                    state.ringtone.start(output_device.as_deref(), volume);
+
+                    // A waiting call is never auto-answered: that would put the current call on hold
+                    let auto_answer = *state.auto_answer.lock().await;
+                    if auto_answer.enabled && idle {
+                        tokio::spawn(auto_answer_call(
+                            app_handle.clone(),
+                            account_id.clone(),
+                            call_id.clone(),
+                            pending_incoming.clone(),
+                            auto_answer.delay_ms,
+                        ));
+                    }
+
                     continue;
                 }
                 // Handle ACK for pending calls
@@ -185,6 +310,31 @@ pub async fn process_incoming_request(
                     Ok::<_, Error>(())
                 });
             }
+            rsip::Method::Message => {
+                // Pager-mode instant message (RFC 3428); in-dialog MESSAGEs carry a
+                // To tag and were already routed to their dialog above
+                let from = tx
+                    .original
+                    .from_header()
+                    .ok()
+                    .and_then(|h| h.uri().ok())
+                    .map(|uri| uri.to_string())
+                    .unwrap_or_else(|| "Unknown".to_string());
+                let body = String::from_utf8_lossy(&tx.original.body).into_owned();
+
+                tx.reply(rsip::StatusCode::OK).await?;
+                info!(call_id = %call_id, from = %from, "Received MESSAGE");
+
+                let payload = IncomingMessagePayload {
+                    account_id: account_id.clone(),
+                    from,
+                    body,
+                    timestamp: chrono::Utc::now().timestamp_millis(),
+                };
+                if let Err(e) = app_handle.emit("sip://incoming-message", payload) {
+                    warn!(call_id = %call_id, error = ?e, "Failed to emit incoming message event");
+                }
+            }
             _ => {
                 debug!(method = %method, call_id = %call_id, "Replying 200 OK");
                 tx.reply(rsip::StatusCode::OK).await?;
@@ -193,3 +343,85 @@ pub async fn process_incoming_request(
     }
     Ok::<_, Error>(())
 }
+
+/// Answer a ringing call after `delay_ms`, unless it was answered, rejected or
+/// cancelled by the caller in the meantime.
+async fn auto_answer_call(
+    app_handle: tauri::AppHandle,
+    account_id: String,
+    call_id: String,
+    pending_incoming: Arc<tokio::sync::Mutex<HashMap<String, PendingCall>>>,
+    delay_ms: u64,
+) {
+    tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+
+    // Answering or rejecting removes the pending call; a caller CANCEL terminates its dialog
+    let ringing = match pending_incoming.lock().await.get(&call_id) {
+        Some(PendingCall {
+            dialog: Dialog::ServerInvite(dialog),
+            ..
+        }) => !dialog.state().is_terminated(),
+        _ => false,
+    };
+    if !ringing {
+        debug!(call_id = %call_id, "Call no longer ringing, skipping auto-answer");
+        return;
+    }
+
+    let state = app_handle.state::<SipAppState>();
+    let Some(handle) = state.handles.lock().await.get(&account_id).cloned() else {
+        return;
+    };
+    let media = crate::media_config(&state).await;
+    info!(call_id = %call_id, delay_ms = delay_ms, "Auto-answering incoming call");
+    let cancel_token = handle.cancel_token.clone();
+    if let Err(e) =
+        super::handle_answer_call(&handle, call_id.clone(), cancel_token, media, None, false).await
+    {
+        warn!(call_id = %call_id, error = ?e, "Auto-answer failed");
+    }
+}
+
+/// Answer an INVITE with Replaces at once, then hang up the call it replaces.
+async fn answer_replacing_call(
+    app_handle: tauri::AppHandle,
+    account_id: String,
+    call_id: String,
+    replaced_call_id: String,
+) {
+    let state = app_handle.state::<SipAppState>();
+    let Some(handle) = state.handles.lock().await.get(&account_id).cloned() else {
+        return;
+    };
+    let caller = match handle.pending_incoming.lock().await.get(&call_id) {
+        Some(pending) => pending.history.remote_party.clone(),
+        None => return,
+    };
+    let media = crate::media_config(&state).await;
+    info!(call_id = %call_id, replaced_call_id = %replaced_call_id, "Answering call that replaces an active call");
+    let cancel_token = handle.cancel_token.clone();
+    if let Err(e) =
+        super::handle_answer_call(&handle, call_id.clone(), cancel_token, media, None, false).await
+    {
+        warn!(call_id = %call_id, error = ?e, "Failed to answer replacing call");
+        return;
+    }
+
+    let replaced = {
+        let mut active = handle.active_calls.lock().await;
+        super::remove_active_call(&app_handle, &mut active, &replaced_call_id, "replaced")
+    };
+    if let Some(call) = replaced {
+        if let Err(e) = super::hang_up_call(&handle.active_call_tokens, call).await {
+            warn!(call_id = %replaced_call_id, error = ?e, "Failed to hang up replaced call");
+        }
+    }
+    super::transfer::emit_being_transferred(
+        &app_handle,
+        &replaced_call_id,
+        &caller,
+        "replaced",
+        Some(call_id),
+        None,
+    );
+}
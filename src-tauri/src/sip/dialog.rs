@@ -1,19 +1,94 @@
 use dashmap::DashMap;
-use rsipstack::dialog::dialog::{Dialog, DialogState, DialogStateReceiver};
+use rsipstack::dialog::dialog::{
+    Dialog, DialogState, DialogStateReceiver, TerminatedReason, TransactionHandle,
+};
 use rsipstack::dialog::dialog_layer::DialogLayer;
+use rsipstack::dialog::DialogId;
+use rsipstack::rsip_ext::{header_contains_token, parse_rseq_header};
 use rsipstack::Error;
+use std::collections::HashMap;
 use std::sync::Arc;
 use tauri::{AppHandle, Emitter};
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, info, warn};
 
-use crate::sip::state::CallStatePayload;
+use crate::sip::state::{ActiveCalls, CallStatePayload, PendingCall, RemoteHoldPayload};
+use crate::sip::transfer;
+
+/// Answer a re-INVITE or UPDATE (RFC 3311) from the remote, following its hold / resume.
+///
+/// The answer mirrors the offered direction (see `WebRtcSession::create_reinvite_answer`);
+/// when the hold state changes, playback is paused or resumed and `sip://remote-hold`
+/// is emitted. An UPDATE without SDP (e.g. a session refresh) is simply accepted.
+async fn process_reinvite(
+    id: DialogId,
+    req: rsip::Request,
+    tx_handle: TransactionHandle,
+    app_handle: AppHandle,
+    active_calls: Arc<tokio::sync::Mutex<ActiveCalls>>,
+) {
+    let offer = String::from_utf8_lossy(&req.body).to_string();
+    if req.method == rsip::Method::Update && offer.trim().is_empty() {
+        if let Err(e) = tx_handle.reply(rsip::StatusCode::OK).await {
+            warn!(dialog_id = %id, error = %e, "Failed to answer UPDATE");
+        }
+        return;
+    }
+    let mut active = active_calls.lock().await;
+    let session = active
+        .find_by_dialog_id(&id.to_string())
+        .and_then(|call_id| active.get_mut(&call_id))
+        .and_then(|call| Some((call.call_id.clone(), call.webrtc_session.as_mut()?)));
+    let Some((call_id, session)) = session else {
+        warn!(dialog_id = %id, "re-INVITE for a call without media, rejecting");
+        drop(active);
+        let _ = tx_handle.reply(rsip::StatusCode::ServerInternalError).await;
+        return;
+    };
+
+    let was_held = session.is_remote_hold();
+    let (answer, held) = session.create_reinvite_answer(&offer);
+    if held != was_held {
+        info!(call_id = %call_id, held, "Remote hold state changed");
+        session.set_remote_hold(held);
+        let _ = app_handle.emit(
+            "sip://remote-hold",
+            RemoteHoldPayload {
+                call_id: call_id.clone(),
+                on_hold: held,
+            },
+        );
+    }
+    drop(active);
+
+    let headers = vec![rsip::typed::ContentType(rsip::typed::MediaType::Sdp(vec![])).into()];
+    let body = Some(answer.into_bytes());
+    if let Err(e) = tx_handle
+        .respond(rsip::StatusCode::OK, Some(headers), body)
+        .await
+    {
+        warn!(call_id = %call_id, method = %req.method, error = %e, "Failed to answer re-INVITE");
+    }
+}
+
+/// `reason` of the "ended" call state: "canceled" for a call cancelled before it
+/// was answered (by us, or by the caller of an incoming call), else the stack's reason.
+pub(super) fn ended_reason(reason: &TerminatedReason) -> String {
+    match reason {
+        TerminatedReason::UacCancel
+        | TerminatedReason::UasOther(rsip::StatusCode::RequestTerminated) => "canceled".to_string(),
+        other => format!("{:?}", other),
+    }
+}
 
 pub async fn process_dialog(
+    account_id: String,
     dialog_layer: Arc<DialogLayer>,
     state_receiver: DialogStateReceiver,
     app_handle: AppHandle,
+    active_calls: Arc<tokio::sync::Mutex<ActiveCalls>>,
     active_call_tokens: Arc<DashMap<String, CancellationToken>>,
+    pending_incoming: Arc<tokio::sync::Mutex<HashMap<String, PendingCall>>>,
 ) -> Result<(), Error> {
     let mut state_receiver = state_receiver;
     while let Some(state) = state_receiver.recv().await {
@@ -47,27 +122,90 @@ pub async fn process_dialog(
                     }
                 }
             }
-            DialogState::Early(id, _resp) => {
-                debug!(dialog_id = %id, "Dialog entered Early state (ringing)");
+            DialogState::Trying(id) => {
+                debug!(dialog_id = %id, "Dialog received 100 Trying");
+                if let Some(Dialog::ClientInvite(_)) = dialog_layer.get_dialog(&id) {
+                    let _ = app_handle.emit(
+                        "sip://call-state",
+                        CallStatePayload {
+                            state: "trying".to_string(),
+                            call_id: Some(id.to_string()),
+                            reason: None,
+                        },
+                    );
+                }
+            }
+            DialogState::Early(id, resp) => {
+                debug!(dialog_id = %id, status = %resp.status_code, "Dialog entered Early state (ringing)");
+                // The dialog layer has already answered a reliable provisional
+                // response (`Require: 100rel`, RFC 3262) with PRACK before reporting it here
+                if header_contains_token(&resp.headers, "Require", "100rel") {
+                    debug!(dialog_id = %id, rseq = ?parse_rseq_header(&resp.headers), "Reliable provisional response acknowledged with PRACK");
+                }
 
                 // Only emit ringing state for outbound calls (ClientInvite)
                 // For inbound calls (ServerInvite), we don't change the state
-                // because the frontend should already be in 'incoming' state
+                // because the frontend should already be in 'incoming' state.
+                // 183 Session Progress (with or without SDP) means the call is
+                // still being connected, not that the callee is ringing.
                 let dialog = dialog_layer.get_dialog(&id);
                 if let Some(Dialog::ClientInvite(_)) = dialog {
+                    let state = if resp.status_code == rsip::StatusCode::SessionProgress {
+                        "progress"
+                    } else {
+                        "ringing"
+                    };
                     let _ = app_handle.emit(
                         "sip://call-state",
                         CallStatePayload {
-                            state: "ringing".to_string(),
+                            state: state.to_string(),
                             call_id: Some(id.to_string()),
                             reason: None,
                         },
                     );
                 }
             }
+            DialogState::Notify(id, req, tx_handle) => {
+                debug!(dialog_id = %id, "Dialog received NOTIFY");
+                // Handled in a separate task: a successful transfer sends BYE,
+                // which must not block this state loop
+                let ah = app_handle.clone();
+                let ac = active_calls.clone();
+                let tokens = active_call_tokens.clone();
+                tokio::spawn(transfer::process_notify(id, req, tx_handle, ah, ac, tokens));
+            }
+            DialogState::Refer(id, req, tx_handle) => {
+                debug!(dialog_id = %id, "Dialog received REFER");
+                // Handled in a separate task: it places a whole new call, whose
+                // dialog states come through this loop
+                let ah = app_handle.clone();
+                let ac = active_calls.clone();
+                let tokens = active_call_tokens.clone();
+                tokio::spawn(transfer::process_refer(
+                    id,
+                    req,
+                    tx_handle,
+                    ah,
+                    account_id.clone(),
+                    ac,
+                    tokens,
+                ));
+            }
+            DialogState::Updated(id, req, tx_handle)
+                if matches!(req.method, rsip::Method::Invite | rsip::Method::Update) =>
+            {
+                debug!(dialog_id = %id, method = %req.method, "Dialog received re-INVITE or UPDATE");
+                // Handled in a separate task: a hold re-INVITE of our own may be
+                // holding the call lock while it waits for its answer
+                let ah = app_handle.clone();
+                let ac = active_calls.clone();
+                tokio::spawn(process_reinvite(id, req, tx_handle, ah, ac));
+            }
             DialogState::Terminated(id, reason) => {
                 info!(dialog_id = %id, reason = ?reason, "Dialog terminated");
+                let inbound = matches!(dialog_layer.get_dialog(&id), Some(Dialog::ServerInvite(_)));
                 dialog_layer.remove_dialog(&id);
+                let reason = ended_reason(&reason);
 
                 // Cancel and remove the call's cancellation token to trigger cleanup
                 if let Some((_, token)) = active_call_tokens.remove(&id.to_string()) {
@@ -75,12 +213,46 @@ pub async fn process_dialog(
                     token.cancel();
                 }
 
+                // Release the call's media if it was still tracked (e.g. remote BYE).
+                // Done in a task since hangup may be holding the lock while sending BYE.
+                let ac = active_calls.clone();
+                let pi = pending_incoming.clone();
+                let ah = app_handle.clone();
+                let dialog_id = id.to_string();
+                let sip_call_id = id.call_id.clone();
+                let ended = reason.clone();
+                tokio::spawn(async move {
+                    let call = {
+                        let mut active = ac.lock().await;
+                        active.find_by_dialog_id(&dialog_id).and_then(|call_id| {
+                            super::remove_active_call(&ah, &mut active, &call_id, &ended)
+                        })
+                    };
+                    match call {
+                        Some(mut call) => {
+                            debug!(dialog_id = %dialog_id, call_id = %call.call_id, "Releasing terminated call");
+                            if let Some(ref mut session) = call.webrtc_session {
+                                session.close().await;
+                            }
+                        }
+                        // An incoming call that was never answered (e.g. caller CANCEL)
+                        None if inbound => {
+                            let pending = pi.lock().await.remove(&sip_call_id);
+                            super::stop_ringtone_if_idle(&ah).await;
+                            if let Some(pending) = pending {
+                                super::report_missed_call(&ah, pending, &ended);
+                            }
+                        }
+                        None => {}
+                    }
+                });
+
                 let _ = app_handle.emit(
                     "sip://call-state",
                     CallStatePayload {
                         state: "ended".to_string(),
                         call_id: Some(id.to_string()),
-                        reason: Some(format!("{:?}", reason)),
+                        reason: Some(reason),
                     },
                 );
             }
@@ -1,72 +1,22 @@
 use futures_util::StreamExt;
 use rsipstack::transport::tcp::TcpConnection;
 use rsipstack::transport::tls::TlsConnection;
-use rsipstack::transport::udp::UdpConnection;
+use rsipstack::transport::udp::{UdpConnection, UdpInner};
 use rsipstack::transport::websocket::{WebSocketConnection, WebSocketInner};
 use rsipstack::transport::{SipAddr, SipConnection};
 use rsipstack::Error;
-use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
-use rustls::crypto::{ring::default_provider, verify_tls12_signature, verify_tls13_signature};
-use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
-use rustls::{DigitallySignedStruct, SignatureScheme};
 use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 use tokio::sync::Mutex;
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::TlsConnector;
 use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::Connector;
 use tokio_util::sync::CancellationToken;
 use tracing::debug;
 
-/// TLS verifier that skips certificate chain validation (accepts self-signed certs).
-/// Signature verification is still performed to prevent MITM attacks.
-#[derive(Debug)]
-struct SkipCertVerifier;
-
-impl ServerCertVerifier for SkipCertVerifier {
-    fn verify_server_cert(
-        &self,
-        _end_entity: &CertificateDer<'_>,
-        _intermediates: &[CertificateDer<'_>],
-        _server_name: &ServerName<'_>,
-        _ocsp_response: &[u8],
-        _now: UnixTime,
-    ) -> Result<ServerCertVerified, rustls::Error> {
-        Ok(ServerCertVerified::assertion())
-    }
-
-    fn verify_tls12_signature(
-        &self,
-        message: &[u8],
-        cert: &CertificateDer<'_>,
-        dss: &DigitallySignedStruct,
-    ) -> Result<HandshakeSignatureValid, rustls::Error> {
-        verify_tls12_signature(
-            message,
-            cert,
-            dss,
-            &default_provider().signature_verification_algorithms,
-        )
-    }
-
-    fn verify_tls13_signature(
-        &self,
-        message: &[u8],
-        cert: &CertificateDer<'_>,
-        dss: &DigitallySignedStruct,
-    ) -> Result<HandshakeSignatureValid, rustls::Error> {
-        verify_tls13_signature(
-            message,
-            cert,
-            dss,
-            &default_provider().signature_verification_algorithms,
-        )
-    }
-
-    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
-        default_provider()
-            .signature_verification_algorithms
-            .supported_schemes()
-    }
-}
+use crate::qos::{self, DSCP_CS3};
+use crate::sip::tls::TlsVerification;
 
 /// Protocol enum to represent SIP transport protocols
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -108,19 +58,25 @@ impl From<Protocol> for rsip::transport::Transport {
     }
 }
 
+impl From<rsip::transport::Transport> for Protocol {
+    fn from(transport: rsip::transport::Transport) -> Self {
+        match transport {
+            rsip::transport::Transport::Udp => Protocol::Udp,
+            rsip::transport::Transport::Tcp => Protocol::Tcp,
+            rsip::transport::Transport::Tls => Protocol::Tls,
+            rsip::transport::Transport::TlsSctp => Protocol::TlsSctp,
+            rsip::transport::Transport::Sctp => Protocol::Sctp,
+            rsip::transport::Transport::Ws => Protocol::Ws,
+            rsip::transport::Transport::Wss => Protocol::Wss,
+        }
+    }
+}
+
 /// Extract transport protocol from SIP URI
 pub fn extract_protocol_from_uri(uri: &rsip::Uri) -> Protocol {
     for param in &uri.params {
         if let rsip::Param::Transport(transport) = param {
-            return match transport {
-                rsip::transport::Transport::Udp => Protocol::Udp,
-                rsip::transport::Transport::Tcp => Protocol::Tcp,
-                rsip::transport::Transport::Tls => Protocol::Tls,
-                rsip::transport::Transport::TlsSctp => Protocol::TlsSctp,
-                rsip::transport::Transport::Sctp => Protocol::Sctp,
-                rsip::transport::Transport::Ws => Protocol::Ws,
-                rsip::transport::Transport::Wss => Protocol::Wss,
-            };
+            return (*transport).into();
         }
     }
 
@@ -131,6 +87,41 @@ pub fn extract_protocol_from_uri(uri: &rsip::Uri) -> Protocol {
     Protocol::Udp
 }
 
+/// Locate the SIP server for a URI that names only a domain (RFC 3263).
+///
+/// When the URI has a domain host and neither a port nor a `transport` parameter,
+/// the `_sip._udp` and `_sip._tcp` SRV records (`_sips._tcp` for `sips:`) are
+/// queried and ordered by priority and weight; the transport comes from the
+/// record that matched. Without SRV records the domain's A/AAAA address is used
+/// on the default port. Returns `None` for URIs that already pin the address.
+pub async fn resolve_server_uri(uri: &rsip::Uri) -> rsipstack::Result<Option<SipAddr>> {
+    let rsip::Host::Domain(ref domain) = uri.host_with_port.host else {
+        return Ok(None);
+    };
+    let has_transport = uri
+        .params
+        .iter()
+        .any(|p| matches!(p, rsip::Param::Transport(_)));
+    if uri.host_with_port.port.is_some() || has_transport {
+        return Ok(None);
+    }
+
+    let secure = matches!(uri.scheme, Some(rsip::Scheme::Sips));
+    let targets = rsipstack::resolver::SipResolver::new()
+        .lookup(domain, None, None, secure)
+        .await
+        .map_err(|e| Error::Error(format!("DNS resolution failed for '{}': {}", domain, e)))?;
+    let target = targets
+        .into_iter()
+        .next()
+        .ok_or_else(|| Error::Error(format!("No address found for '{}'", domain)))?;
+    debug!(domain = %domain, addr = %target.addr, transport = %target.transport, "SIP server located via DNS");
+    Ok(Some(SipAddr {
+        r#type: Some(target.transport),
+        addr: target.addr.into(),
+    }))
+}
+
 /// Resolve the hostname in a SipAddr to an IP address via DNS.
 /// TCP/TLS connections require a resolved SocketAddr; UDP does not.
 async fn resolve_sip_addr(target: &SipAddr) -> rsipstack::Result<SipAddr> {
@@ -153,40 +144,63 @@ async fn resolve_sip_addr(target: &SipAddr) -> rsipstack::Result<SipAddr> {
     })
 }
 
-/// Create transport connection based on protocol
+/// Create transport connection based on protocol; `tls` selects how the
+/// server certificate is checked for TLS and WSS. Signaling is marked CS3.
 pub async fn create_transport_connection(
     local_addr: SocketAddr,
     target: SipAddr,
     cancel_token: CancellationToken,
     ws_path: Option<String>,
+    tls: &TlsVerification,
 ) -> rsipstack::Result<SipConnection> {
     match target.r#type {
         Some(rsip::transport::Transport::Udp) => {
-            let connection = UdpConnection::create_connection(
-                local_addr,
+            let conn = tokio::net::UdpSocket::bind(local_addr).await?;
+            qos::mark_udp(&conn, DSCP_CS3);
+            let addr = SipAddr {
+                r#type: Some(rsip::transport::Transport::Udp),
+                addr: SipConnection::resolve_bind_address(conn.local_addr()?).into(),
+            };
+            let connection = UdpConnection::attach(
+                UdpInner { conn, addr },
                 None,
                 Some(cancel_token.child_token()),
             )
-            .await?;
+            .await;
             Ok(SipConnection::Udp(connection))
         }
         Some(rsip::transport::Transport::Tcp) => {
             let resolve = resolve_sip_addr(&target).await?;
+            let stream = qos::connect_tcp(resolve.get_socketaddr()?, DSCP_CS3).await?;
+            let local = SipAddr {
+                r#type: Some(rsip::transport::Transport::Tcp),
+                addr: SipConnection::resolve_bind_address(stream.local_addr()?).into(),
+            };
             let connection =
-                TcpConnection::connect(&resolve, Some(cancel_token.child_token())).await?;
+                TcpConnection::from_stream(stream, local, Some(cancel_token.child_token()))?;
             Ok(SipConnection::Tcp(connection))
         }
         Some(rsip::transport::Transport::Tls) => {
             let resolve = resolve_sip_addr(&target).await?;
-            let verifier = Arc::new(SkipCertVerifier);
-            let connection =
-                TlsConnection::connect(&resolve, Some(verifier), Some(cancel_token.child_token())).await?;
+            let config = tls.client_config().map_err(Error::Error)?;
+            let server_name = ServerName::try_from(resolve.addr.host.to_string())
+                .map_err(|e| Error::Error(format!("Invalid TLS server name: {}", e)))?;
+            let stream = qos::connect_tcp(resolve.get_socketaddr()?, DSCP_CS3).await?;
+            let stream = TlsConnector::from(Arc::new(config))
+                .connect(server_name, stream)
+                .await?;
+            let connection = TlsConnection::from_client_stream(
+                stream,
+                resolve,
+                Some(cancel_token.child_token()),
+            )
+            .await?;
             Ok(SipConnection::Tls(connection))
         }
         Some(rsip::transport::Transport::Ws | rsip::transport::Transport::Wss) => {
             let resolve = resolve_sip_addr(&target).await?;
             let connection =
-                create_websocket_connection(&resolve, ws_path.as_deref(), Some(cancel_token.child_token())).await?;
+                create_websocket_connection(&resolve, ws_path.as_deref(), tls, Some(cancel_token.child_token())).await?;
             Ok(SipConnection::WebSocket(connection))
         }
         _ => Err(Error::TransportLayerError(
@@ -202,11 +216,15 @@ pub async fn create_transport_connection(
 async fn create_websocket_connection(
     remote: &SipAddr,
     ws_path: Option<&str>,
+    tls: &TlsVerification,
     cancel_token: Option<CancellationToken>,
 ) -> rsipstack::Result<WebSocketConnection> {
-    let scheme = match remote.r#type {
-        Some(rsip::transport::Transport::Wss) => "wss",
-        _ => "ws",
+    let (scheme, connector) = match remote.r#type {
+        Some(rsip::transport::Transport::Wss) => {
+            let config = tls.client_config().map_err(Error::Error)?;
+            ("wss", Connector::Rustls(Arc::new(config)))
+        }
+        _ => ("ws", Connector::Plain),
     };
 
     let host = match &remote.addr.host {
@@ -233,9 +251,11 @@ async fn create_websocket_connection(
         .headers_mut()
         .insert("sec-websocket-protocol", "sip".parse().unwrap());
 
-    let (ws_stream, _) = tokio_tungstenite::connect_async(request)
-        .await
-        .map_err(|e| Error::Error(format!("WebSocket connect failed: {}", e)))?;
+    let stream = qos::connect_tcp(remote.get_socketaddr()?, DSCP_CS3).await?;
+    let (ws_stream, _) =
+        tokio_tungstenite::client_async_tls_with_config(request, stream, None, Some(connector))
+            .await
+            .map_err(|e| Error::Error(format!("WebSocket connect failed: {}", e)))?;
     let (ws_sink, ws_read) = ws_stream.split();
 
     Ok(WebSocketConnection {
@@ -255,29 +275,20 @@ async fn create_websocket_connection(
 /// interface. The resulting `local_addr()` is the IP the kernel would actually use
 /// to reach the server (respects VPN, multiple NICs, policy routing, etc.).
 ///
-/// `server_addr` may be "host:port" or just "host" (port defaults to 5060).
+/// `server_addr` may be "host:port", "[v6]:port" or just the host (port defaults
+/// to 5060). The probe socket uses the address family of the resolved server, so
+/// IPv6-only networks get their IPv6 address.
 ///
-/// Falls back to the first non-loopback IPv4 interface if routing probe fails.
+/// Falls back to the first usable non-loopback interface if routing probe fails.
 pub fn get_local_outbound_ip(server_addr: &str) -> rsipstack::Result<IpAddr> {
-    use std::net::UdpSocket;
-
-    let target = if server_addr.contains(':') {
-        server_addr.to_string()
-    } else {
-        format!("{}:5060", server_addr)
-    };
-
-    match UdpSocket::bind("0.0.0.0:0")
-        .and_then(|s| s.connect(&target).map(|_| s))
-        .and_then(|s| s.local_addr())
-    {
-        Ok(addr) => {
-            debug!(ip = %addr.ip(), server = %target, "Detected local outbound IP via routing");
-            Ok(addr.ip())
+    match probe_outbound_ip(server_addr) {
+        Ok(ip) => {
+            debug!(ip = %ip, server = %server_addr, "Detected local outbound IP via routing");
+            Ok(ip)
         }
         Err(e) => {
             tracing::warn!(
-                error = %e, server = %target,
+                error = %e, server = %server_addr,
                 "UDP routing probe failed; falling back to interface enumeration"
             );
             get_first_non_loopback_interface()
@@ -285,14 +296,51 @@ pub fn get_local_outbound_ip(server_addr: &str) -> rsipstack::Result<IpAddr> {
     }
 }
 
+/// The routing probe of `get_local_outbound_ip`, without the fallback: fails
+/// when the server does not resolve or no route leads to it (e.g. offline).
+pub fn probe_outbound_ip(server_addr: &str) -> std::io::Result<IpAddr> {
+    use std::net::{ToSocketAddrs, UdpSocket};
+
+    let target = if server_addr.parse::<std::net::Ipv6Addr>().is_ok() {
+        format!("[{}]:5060", server_addr)
+    } else if server_addr.ends_with(']') || !server_addr.contains(':') {
+        format!("{}:5060", server_addr)
+    } else {
+        server_addr.to_string()
+    };
+
+    let remote = target.to_socket_addrs()?.next().ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::NotFound, "address did not resolve")
+    })?;
+    let unspecified = if remote.is_ipv6() {
+        "[::]:0"
+    } else {
+        "0.0.0.0:0"
+    };
+    let socket = UdpSocket::bind(unspecified)?;
+    socket.connect(remote)?;
+    Ok(socket.local_addr()?.ip())
+}
+
+/// First non-loopback IPv4 address, or failing that the first global IPv6 one.
+///
+/// Link-local IPv6 (fe80::/10) is skipped: it needs a scope id and is not
+/// reachable from a SIP server anyway.
 fn get_first_non_loopback_interface() -> rsipstack::Result<IpAddr> {
+    let mut ipv6 = None;
     for i in get_if_addrs::get_if_addrs()? {
-        if !i.is_loopback() {
-            match i.addr {
-                get_if_addrs::IfAddr::V4(ref addr) => return Ok(std::net::IpAddr::V4(addr.ip)),
-                _ => continue,
+        if i.is_loopback() {
+            continue;
+        }
+        match i.addr {
+            get_if_addrs::IfAddr::V4(ref addr) => return Ok(IpAddr::V4(addr.ip)),
+            get_if_addrs::IfAddr::V6(ref addr) => {
+                let link_local = addr.ip.segments()[0] & 0xffc0 == 0xfe80;
+                if ipv6.is_none() && !link_local {
+                    ipv6 = Some(IpAddr::V6(addr.ip));
+                }
             }
         }
     }
-    Err(Error::Error("No IPv4 interface found".to_string()))
+    ipv6.ok_or_else(|| Error::Error("No usable network interface found".to_string()))
 }
@@ -0,0 +1,172 @@
+use rsipstack::dialog::authenticate::{handle_client_authenticate, Credential};
+use rsipstack::transaction::endpoint::EndpointInnerRef;
+use rsipstack::transaction::key::{TransactionKey, TransactionRole};
+use rsipstack::transaction::make_tag;
+use rsipstack::transaction::transaction::Transaction;
+use rsipstack::Result;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+use tokio::select;
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::sip::state::ServerReachablePayload;
+
+/// How long to wait for the answer to an OPTIONS ping before calling the server unreachable
+const PING_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Periodic OPTIONS ping to the registrar.
+///
+/// Runs independently of the registration refresh so the UI can show the
+/// server's reachability between REGISTERs. The interval comes from a watch
+/// channel (`0` = disabled) so it can be changed while the account is registered.
+pub struct KeepAlive {
+    endpoint: EndpointInnerRef,
+    credential: Credential,
+    contact: rsip::Uri,
+    server: rsip::Uri,
+    app_handle: AppHandle,
+    account_id: String,
+    seq: u32,
+}
+
+impl KeepAlive {
+    pub fn new(
+        endpoint: EndpointInnerRef,
+        credential: Credential,
+        contact: rsip::Uri,
+        server: rsip::Uri,
+        app_handle: AppHandle,
+        account_id: String,
+    ) -> Self {
+        Self {
+            endpoint,
+            credential,
+            contact,
+            server,
+            app_handle,
+            account_id,
+            seq: 0,
+        }
+    }
+
+    /// Send one OPTIONS request; `Ok(true)` when the server answers 2xx.
+    async fn ping(&mut self) -> Result<bool> {
+        self.seq += 1;
+        let from = rsip::typed::From {
+            display_name: None,
+            uri: self.contact.clone(),
+            params: vec![],
+        }
+        .with_tag(make_tag());
+        let to = rsip::typed::To {
+            display_name: None,
+            uri: self.server.clone(),
+            params: vec![],
+        };
+        let via = self.endpoint.get_via(None, None)?;
+        let mut request = self.endpoint.make_request(
+            rsip::Method::Options,
+            self.server.clone(),
+            via,
+            from,
+            to,
+            self.seq,
+            Some(rsip::headers::CallId::from(Uuid::new_v4().to_string())),
+        );
+        request
+            .headers
+            .push(rsip::headers::ContentLength::from(0u32).into());
+
+        let key = TransactionKey::from_request(&request, TransactionRole::Client)?;
+        let mut tx = Transaction::new_client(key, request, self.endpoint.clone(), None);
+        tx.send().await?;
+
+        let mut auth_sent = false;
+        while let Some(msg) = tx.receive().await {
+            let rsip::SipMessage::Response(resp) = msg else {
+                continue;
+            };
+            match resp.status_code {
+                rsip::StatusCode::Unauthorized | rsip::StatusCode::ProxyAuthenticationRequired
+                    if !auth_sent =>
+                {
+                    self.seq += 1;
+                    tx = handle_client_authenticate(self.seq, &tx, resp, &self.credential).await?;
+                    tx.send().await?;
+                    auth_sent = true;
+                }
+                ref status if status.kind() == rsip::StatusCodeKind::Provisional => {}
+                status => {
+                    debug!(server = %self.server, status = %status, "OPTIONS answered");
+                    return Ok(status.kind() == rsip::StatusCodeKind::Successful);
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    /// Ping at the configured interval until cancelled, emitting
+    /// `sip://server-reachable` on the first result and whenever it changes.
+    pub async fn run(
+        mut self,
+        mut interval: watch::Receiver<u64>,
+        cancel_token: CancellationToken,
+    ) {
+        let mut reachable: Option<bool> = None;
+        loop {
+            let secs = *interval.borrow_and_update();
+            if secs == 0 {
+                // Disabled: wait until re-enabled, then report afresh
+                reachable = None;
+                select! {
+                    _ = cancel_token.cancelled() => break,
+                    changed = interval.changed() => {
+                        if changed.is_err() {
+                            break;
+                        }
+                        continue;
+                    }
+                }
+            }
+
+            select! {
+                _ = cancel_token.cancelled() => break,
+                // A new interval takes effect immediately
+                changed = interval.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                    continue;
+                }
+                _ = tokio::time::sleep(Duration::from_secs(secs)) => {}
+            }
+
+            let now_reachable = match tokio::time::timeout(PING_TIMEOUT, self.ping()).await {
+                Ok(Ok(ok)) => ok,
+                Ok(Err(e)) => {
+                    warn!(server = %self.server, error = ?e, "OPTIONS keepalive failed");
+                    false
+                }
+                Err(_) => {
+                    warn!(server = %self.server, "OPTIONS keepalive timed out");
+                    false
+                }
+            };
+            if reachable != Some(now_reachable) {
+                info!(server = %self.server, reachable = now_reachable, "Server reachability changed");
+                reachable = Some(now_reachable);
+                let _ = self.app_handle.emit(
+                    "sip://server-reachable",
+                    ServerReachablePayload {
+                        account_id: self.account_id.clone(),
+                        reachable: now_reachable,
+                    },
+                );
+            }
+        }
+        debug!(server = %self.server, "OPTIONS keepalive stopped");
+    }
+}
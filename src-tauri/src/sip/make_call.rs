@@ -1,106 +1,347 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
-use rsipstack::dialog::dialog::DialogStateSender;
+use rsip::prelude::*;
+use rsipstack::dialog::client_dialog::ClientInviteDialog;
+use rsipstack::dialog::dialog::{Dialog, DialogState, DialogStateSender};
 use rsipstack::dialog::dialog_layer::DialogLayer;
 use rsipstack::dialog::invitation::InviteOption;
+use rsipstack::dialog::DialogId;
 use rsipstack::Error;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::mpsc;
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, info, warn};
 use uuid::Uuid;
 
-use crate::webrtc::WebRtcSession;
+use crate::error::AppError;
+use crate::sip::state::{
+    CallRedirectedPayload, CallRetryPayload, CallStatePayload, EarlyMediaPayload,
+};
+use crate::webrtc::{self, MediaConfig, WebRtcSession};
+
+/// How long to wait for the final response (normally 487) after sending CANCEL
+const CANCEL_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Most 3xx redirects followed for one call
+const MAX_REDIRECTS: usize = 5;
+
+/// Headers the stack builds itself and that custom headers may not override
+/// (including their compact forms).
+const RESERVED_HEADERS: &[&str] = &[
+    "via",
+    "v",
+    "call-id",
+    "i",
+    "from",
+    "f",
+    "to",
+    "t",
+    "cseq",
+    "contact",
+    "m",
+    "max-forwards",
+    "content-type",
+    "c",
+    "content-length",
+    "l",
+    "route",
+    "record-route",
+    "authorization",
+    "proxy-authorization",
+    "supported",
+    "k",
+    "require",
+];
+
+/// Validate user-supplied headers for an outbound INVITE (e.g. `X-Account-Code`,
+/// `P-Asserted-Identity`) and turn them into SIP headers, sorted by name.
+///
+/// Names must be RFC 3261 tokens and values may not contain CR, LF or NUL, so
+/// a header cannot inject further headers or a body. Headers the stack sets
+/// itself (Via, Call-ID, From, To, CSeq, Contact, ...) cannot be overridden.
+pub fn custom_headers(headers: &HashMap<String, String>) -> Result<Vec<rsip::Header>, String> {
+    let mut names: Vec<&String> = headers.keys().collect();
+    names.sort();
+    names
+        .into_iter()
+        .map(|name| {
+            let value = &headers[name];
+            let is_token_char = |c: char| c.is_ascii_alphanumeric() || "-.!%*_+`'~".contains(c);
+            if name.is_empty() || !name.chars().all(is_token_char) {
+                return Err(format!("Invalid header name: {:?}", name));
+            }
+            if RESERVED_HEADERS.contains(&name.to_ascii_lowercase().as_str()) {
+                return Err(format!("Header {} cannot be overridden", name));
+            }
+            if value.contains(['\r', '\n', '\0']) {
+                return Err(format!("Invalid value for header {}", name));
+            }
+            Ok(rsip::Header::Other(name.clone(), value.trim().to_string()))
+        })
+        .collect()
+}
 
 /// Make an outbound call with internally-generated SDP (from rustrtc).
 /// Returns (Dialog, WebRtcSession) on success.
 ///
-/// SRTP negotiation is controlled by the prefer_srtp parameter.
+/// SRTP negotiation is controlled by `media.prefer_srtp`.
 /// If prefer_srtp=true and the remote returns 488 Not Acceptable, automatically falls back to RTP (with a new call_id).
+/// Custom headers in `invite_option.headers` are kept for the retry.
+///
+/// A provisional response with SDP (183 Session Progress) starts playback of the
+/// far end's early media and emits `sip://early-media`.
+///
+/// A 3xx response is followed to its Contact with the same SDP offer, up to
+/// `MAX_REDIRECTS` times, emitting `sip://call-redirected`. The 488 fallback
+/// then goes to the redirected target.
+///
+/// If the media cannot be connected (no ICE candidates, RTP connection timeout)
+/// the call is placed once more with a new call_id, emitting `sip://call-retry`.
+/// A call the remote rejected is not retried.
 pub async fn make_call(
     dialog_layer: Arc<DialogLayer>,
     mut invite_option: InviteOption,
     state_sender: DialogStateSender,
-    input_device: Option<String>,
-    output_device: Option<String>,
+    media: MediaConfig,
     cancel_token: CancellationToken,
-    prefer_srtp: bool,
-) -> rsipstack::Result<(rsipstack::dialog::dialog::Dialog, WebRtcSession)> {
+    app_handle: AppHandle,
+) -> Result<(rsipstack::dialog::dialog::Dialog, WebRtcSession), AppError> {
+    let prefer_srtp = media.prefer_srtp;
     let caller = invite_option.caller.to_string();
     let callee = invite_option.callee.to_string();
     let call_id = invite_option.call_id.clone().unwrap_or_default();
 
-    debug!(call_id = %call_id, caller = %caller, callee = %callee, prefer_srtp = prefer_srtp, "Preparing outbound call");
+    debug!(call_id = %call_id, caller = %caller, callee = %callee, prefer_srtp = prefer_srtp, support_100rel = invite_option.support_prack, "Preparing outbound call");
 
     // Attempt call with SRTP or RTP based on config
-    let result = try_call_with_mode(
+    let mut result = try_call_with_mode(
         &dialog_layer,
         &mut invite_option,
         state_sender.clone(),
-        &input_device,
-        &output_device,
+        &media,
         &call_id,
-        &callee,
         prefer_srtp,
         cancel_token.clone(),
+        &app_handle,
     )
     .await;
 
     // If SRTP was preferred and remote returned 488 Not Acceptable, retry with plain RTP
-    if prefer_srtp {
-        if let Err(Error::Error(ref msg)) = result {
-            if msg.contains("488") || msg.contains("NotAcceptableHere") {
-                warn!(call_id = %call_id, "Remote rejected SRTP (488), retrying with RTP");
-
-                // Check if cancellation was requested before retrying
-                if cancel_token.is_cancelled() {
-                    info!(call_id = %call_id, "Call cancelled before RTP retry");
-                    return Err(Error::Error("Call cancelled".to_string()));
-                }
+    let mut srtp = prefer_srtp;
+    if prefer_srtp
+        && matches!(
+            result,
+            Err(AppError::CallRejected(rsip::StatusCode::NotAcceptableHere))
+        )
+    {
+        warn!(call_id = %call_id, "Remote rejected SRTP (488), retrying with RTP");
 
-                // Generate a new call_id for the retry
-                let new_call_id = Uuid::new_v4().to_string();
-                invite_option.call_id = Some(new_call_id.clone());
-
-                info!(old_call_id = %call_id, new_call_id = %new_call_id, "Retrying with new call_id");
-
-                return try_call_with_mode(
-                    &dialog_layer,
-                    &mut invite_option,
-                    state_sender,
-                    &input_device,
-                    &output_device,
-                    &new_call_id,
-                    &callee,
-                    false, // prefer_srtp = false
-                    cancel_token,
-                )
-                .await;
-            }
+        // Check if cancellation was requested before retrying
+        if cancel_token.is_cancelled() {
+            info!(call_id = %call_id, "Call cancelled before RTP retry");
+            return Err("Call cancelled".into());
+        }
+
+        // Generate a new call_id for the retry
+        let new_call_id = Uuid::new_v4().to_string();
+        invite_option.call_id = Some(new_call_id.clone());
+
+        info!(old_call_id = %call_id, new_call_id = %new_call_id, "Retrying with new call_id");
+
+        srtp = false;
+        result = try_call_with_mode(
+            &dialog_layer,
+            &mut invite_option,
+            state_sender.clone(),
+            &media,
+            &new_call_id,
+            false, // prefer_srtp = false
+            cancel_token.clone(),
+            &app_handle,
+        )
+        .await;
+    }
+
+    // If our side failed to connect the media, place the call once more
+    if let Err(ref e @ AppError::MediaFailed(_)) = result {
+        if !cancel_token.is_cancelled() {
+            // The last attempt may have been a fallback or a redirect
+            let call_id = invite_option.call_id.clone().unwrap_or_default();
+            let new_call_id = Uuid::new_v4().to_string();
+            invite_option.call_id = Some(new_call_id.clone());
+            warn!(call_id = %call_id, new_call_id = %new_call_id, error = %e, "Retrying call after media failure");
+            let _ = app_handle.emit(
+                "sip://call-retry",
+                CallRetryPayload {
+                    call_id,
+                    new_call_id: new_call_id.clone(),
+                    reason: e.to_string(),
+                },
+            );
+
+            return match try_call_with_mode(
+                &dialog_layer,
+                &mut invite_option,
+                state_sender,
+                &media,
+                &new_call_id,
+                srtp,
+                cancel_token,
+                &app_handle,
+            )
+            .await
+            {
+                Err(AppError::MediaFailed(msg)) => {
+                    Err(AppError::MediaFailed(format!("{} (after one retry)", msg)))
+                }
+                result => result,
+            };
         }
     }
 
     result
 }
 
+/// Error for media setup that failed with `error`: `MediaFailed` when a fresh
+/// attempt may get past it.
+fn media_error(context: &str, error: String) -> AppError {
+    if webrtc::is_transient_media_error(&error) {
+        AppError::MediaFailed(error)
+    } else {
+        AppError::Other(format!("{}: {}", context, error))
+    }
+}
+
+/// Where a 3xx response sends the call: its first Contact.
+fn redirect_target(resp: &rsip::Response) -> Option<rsip::Uri> {
+    let contact: rsip::typed::Contact = resp.contact_header().ok()?.typed().ok()?;
+    Some(contact.uri)
+}
+
+/// Report an outbound call cancelled before it was answered, for when the
+/// dialog never reached its terminated state.
+fn emit_canceled(app_handle: &AppHandle, dialog_id: Option<&DialogId>) {
+    let _ = app_handle.emit(
+        "sip://call-state",
+        CallStatePayload {
+            state: "ended".to_string(),
+            call_id: dialog_id.map(|id| id.to_string()),
+            reason: Some("canceled".to_string()),
+        },
+    );
+}
+
+/// Send one INVITE and wait for its final response, playing early media
+/// from a 183 and sending CANCEL if `cancel_token` fires first.
+#[allow(clippy::too_many_arguments)]
+async fn send_invite(
+    dialog_layer: &Arc<DialogLayer>,
+    invite_option: &InviteOption,
+    state_sender: DialogStateSender,
+    session: &mut WebRtcSession,
+    early_media: &mut bool,
+    media: &MediaConfig,
+    call_id: &str,
+    cancel_token: &CancellationToken,
+    app_handle: &AppHandle,
+) -> rsipstack::Result<(ClientInviteDialog, Option<rsip::Response>)> {
+    info!(call_id = %call_id, callee = %invite_option.callee, srtp = media.prefer_srtp, "Sending INVITE");
+
+    // Relay the dialog states, picking out provisional responses that carry SDP
+    // and the ID of the dialog being set up (needed to CANCEL it)
+    let (early_sdp_tx, mut early_sdp_rx) = mpsc::unbounded_channel();
+    let (invite_state_tx, mut invite_state_rx) = mpsc::unbounded_channel();
+    let invite_dialog_id: Arc<std::sync::Mutex<Option<DialogId>>> = Default::default();
+    let relay_dialog_id = invite_dialog_id.clone();
+    tokio::spawn(async move {
+        while let Some(state) = invite_state_rx.recv().await {
+            if let DialogState::Calling(ref id) = state {
+                *relay_dialog_id.lock().unwrap() = Some(id.clone());
+            }
+            if let DialogState::Early(_, ref resp) = state {
+                if !resp.body().is_empty() {
+                    let sdp = String::from_utf8_lossy(resp.body()).to_string();
+                    let _ = early_sdp_tx.send(sdp);
+                }
+            }
+            if state_sender.send(state).is_err() {
+                break;
+            }
+        }
+    });
+
+    let invite = dialog_layer.do_invite(invite_option.clone(), invite_state_tx);
+    tokio::pin!(invite);
+    loop {
+        tokio::select! {
+            result = &mut invite => {
+                info!(call_id = %call_id, "do_invite returned");
+                return result;
+            },
+            Some(sdp) = early_sdp_rx.recv(), if !*early_media => {
+                // Transactions keep running while the media is set up; the
+                // final response is picked up on the next iteration
+                let output = media.output_device.as_deref();
+                match session.start_early_media(&sdp, output).await {
+                    Ok(()) => {
+                        *early_media = true;
+                        info!(call_id = %call_id, "Early media playing");
+                        let _ = app_handle.emit(
+                            "sip://early-media",
+                            EarlyMediaPayload {
+                                call_id: call_id.to_string(),
+                            },
+                        );
+                    }
+                    Err(e) => warn!(call_id = %call_id, error = %e, "Failed to start early media"),
+                }
+            },
+            _ = cancel_token.cancelled() => {
+                // RFC 3261 §9: an INVITE without a final response is ended with
+                // CANCEL; the far end then answers it with 487 Request Terminated
+                info!(call_id = %call_id, "Call cancelled by user (during INVITE), sending CANCEL");
+                session.close().await;
+                let dialog_id = invite_dialog_id.lock().unwrap().clone();
+                let dialog = dialog_id.as_ref().and_then(|id| dialog_layer.get_dialog(id));
+                if let Some(Dialog::ClientInvite(ref d)) = dialog {
+                    if let Err(e) = d.cancel().await {
+                        warn!(call_id = %call_id, error = ?e, "Failed to send CANCEL");
+                    }
+                }
+                match tokio::time::timeout(CANCEL_TIMEOUT, &mut invite).await {
+                    Ok(result) => return result,
+                    Err(_) => {
+                        warn!(call_id = %call_id, "No final response to CANCEL");
+                        emit_canceled(app_handle, dialog_id.as_ref());
+                        return Err(Error::Error("Call cancelled".to_string()));
+                    }
+                }
+            }
+        }
+    }
+}
+
 /// Internal helper: attempt call with specific transport mode
+#[allow(clippy::too_many_arguments)]
 async fn try_call_with_mode(
     dialog_layer: &Arc<DialogLayer>,
     invite_option: &mut InviteOption,
     state_sender: DialogStateSender,
-    input_device: &Option<String>,
-    output_device: &Option<String>,
+    media: &MediaConfig,
     call_id: &str,
-    callee: &str,
     prefer_srtp: bool,
     cancel_token: CancellationToken,
-) -> rsipstack::Result<(rsipstack::dialog::dialog::Dialog, WebRtcSession)> {
+    app_handle: &AppHandle,
+) -> Result<(rsipstack::dialog::dialog::Dialog, WebRtcSession), AppError> {
     // Create WebRTC session and generate SDP offer with ICE candidates
-    let (mut session, sdp_offer) = WebRtcSession::new_outbound(
-        input_device.as_deref(),
-        output_device.as_deref(),
+    let media = MediaConfig {
         prefer_srtp,
-    )
-    .await
-    .map_err(|e| Error::Error(e))?;
+        ..media.clone()
+    };
+    let (mut session, sdp_offer) = WebRtcSession::new_outbound(&media)
+        .await
+        .map_err(|e| media_error("Failed to create media session", e))?;
 
     debug!(
         call_id = %call_id,
@@ -112,23 +353,61 @@ async fn try_call_with_mode(
     // Set the SDP offer
     invite_option.offer = Some(sdp_offer.into_bytes());
 
-    // Send INVITE and wait for response (or cancellation)
-    info!(call_id = %call_id, srtp = prefer_srtp, "Sending INVITE");
+    // Send INVITE, following 3xx redirects to the Contact they name
+    let mut call_id = call_id.to_string();
+    let mut early_media = false;
+    let mut tried = vec![invite_option.callee.clone()];
+    let (dialog, resp) = loop {
+        let (dialog, resp) = send_invite(
+            dialog_layer,
+            invite_option,
+            state_sender.clone(),
+            &mut session,
+            &mut early_media,
+            &media,
+            &call_id,
+            &cancel_token,
+            app_handle,
+        )
+        .await?;
+        let resp = resp.ok_or(AppError::Other("No response from remote".to_string()))?;
 
-    let invite_result = tokio::select! {
-        result = dialog_layer.do_invite(invite_option.clone(), state_sender) => {
-            info!(call_id = %call_id, "do_invite returned");
-            result
-        },
-        _ = cancel_token.cancelled() => {
-            info!(call_id = %call_id, "Call cancelled by user (during INVITE)");
-            session.close().await;
-            return Err(Error::Error("Call cancelled".to_string()));
+        let redirected = matches!(resp.status_code.kind(), rsip::StatusCodeKind::Redirection);
+        if !redirected || cancel_token.is_cancelled() {
+            break (dialog, resp);
         }
+        let Some(target) = redirect_target(&resp) else {
+            warn!(call_id = %call_id, status = %resp.status_code, "Redirect without a Contact");
+            break (dialog, resp);
+        };
+        if tried.len() > MAX_REDIRECTS || tried.contains(&target) {
+            warn!(call_id = %call_id, target = %target, hops = tried.len() - 1, "Redirect loop or too many redirects");
+            break (dialog, resp);
+        }
+
+        // A new INVITE with the same offer; it gets its own Call-ID, as the
+        // SRTP fallback does
+        let new_call_id = Uuid::new_v4().to_string();
+        info!(call_id = %call_id, new_call_id = %new_call_id, status = %resp.status_code, target = %target, "Call redirected");
+        let _ = app_handle.emit(
+            "sip://call-redirected",
+            CallRedirectedPayload {
+                call_id: call_id.clone(),
+                new_call_id: new_call_id.clone(),
+                target: target.to_string(),
+            },
+        );
+        invite_option.callee = target.clone();
+        invite_option.call_id = Some(new_call_id.clone());
+        call_id = new_call_id;
+        tried.push(target);
     };
+    let callee = invite_option.callee.to_string();
 
-    let (dialog, resp) = invite_result?;
-    let resp = resp.ok_or(Error::Error("No response from remote".to_string()))?;
+    if resp.status_code != rsip::StatusCode::OK && cancel_token.is_cancelled() {
+        info!(call_id = %call_id, status = %resp.status_code, "Outbound call cancelled");
+        return Err("Call cancelled".into());
+    }
 
     if resp.status_code != rsip::StatusCode::OK {
         warn!(
@@ -138,7 +417,7 @@ async fn try_call_with_mode(
             "Call rejected by remote"
         );
         session.close().await;
-        return Err(Error::Error(format!("Call rejected: {}", resp.status_code)));
+        return Err(AppError::CallRejected(resp.status_code));
     }
 
     // Check if cancellation was requested during call setup (race condition handling)
@@ -149,7 +428,7 @@ async fn try_call_with_mode(
         if let Err(e) = dialog.bye().await {
             warn!(call_id = %call_id, error = ?e, "Failed to send BYE after cancellation");
         }
-        return Err(Error::Error("Call cancelled".to_string()));
+        return Err("Call cancelled".into());
     }
 
     info!(call_id = %call_id, callee = %callee, "Call answered (200 OK)");
@@ -157,14 +436,58 @@ async fn try_call_with_mode(
     let sdp_answer = String::from_utf8_lossy(resp.body()).to_string();
     debug!(call_id = %call_id, sdp_answer_len = sdp_answer.len(), "Received SDP answer");
 
-    // Apply SDP answer and start audio
-    session
-        .apply_answer(&sdp_answer, output_device.as_deref())
+    // Apply SDP answer and start audio; without media the answered call is hung up
+    if let Err(e) = session
+        .apply_answer(&sdp_answer, media.output_device.as_deref())
         .await
-        .map_err(|e| Error::Error(format!("Failed to apply SDP answer: {}", e)))?;
+    {
+        warn!(call_id = %call_id, error = %e, "Media setup failed, sending BYE");
+        session.close().await;
+        if let Err(e) = dialog.bye().await {
+            warn!(call_id = %call_id, error = ?e, "Failed to send BYE after media failure");
+        }
+        return Err(media_error("Failed to apply SDP answer", e));
+    }
 
     Ok((
         rsipstack::dialog::dialog::Dialog::ClientInvite(dialog),
         session,
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_custom_headers_sorted() {
+        let result = custom_headers(&headers(&[
+            ("X-Account-Code", "4711"),
+            ("P-Asserted-Identity", "<sip:1000@pbx.example.com>"),
+        ]))
+        .unwrap();
+        let names: Vec<String> = result.iter().map(|h| h.to_string()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "P-Asserted-Identity: <sip:1000@pbx.example.com>",
+                "X-Account-Code: 4711"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_custom_headers_rejects_injection_and_reserved() {
+        assert!(custom_headers(&headers(&[("X-Code", "1\r\nVia: evil")])).is_err());
+        assert!(custom_headers(&headers(&[("X Code", "1")])).is_err());
+        assert!(custom_headers(&headers(&[("Call-ID", "abc")])).is_err());
+        assert!(custom_headers(&headers(&[("i", "abc")])).is_err());
+    }
+}
@@ -1,19 +1,33 @@
 use rsip::{headers::UntypedHeader, prelude::HeadersExt, SipMessage};
 use rsipstack::{transaction::endpoint::MessageInspector, transport::SipAddr};
 use std::{
+    collections::VecDeque,
     fs::{self, OpenOptions},
     io::Write,
+    net::{Ipv4Addr, SocketAddr},
     path::PathBuf,
     sync::{Arc, Mutex},
+    time::SystemTime,
 };
 use tracing::{error, info};
 
+use crate::sip::pcap::CapturedMessage;
+
+/// Messages kept for the PCAP export; older ones are dropped first
+const MAX_CAPTURED_MESSAGES: usize = 5000;
+
 /// SIP message flow inspector with dynamic enable/disable of logging
 #[derive(Clone)]
 pub struct SipFlow {
     log_file: Arc<Mutex<Option<std::fs::File>>>,
     enabled: Arc<Mutex<bool>>,
     log_dir: Arc<Mutex<PathBuf>>,
+    /// Local end of the captured messages
+    local_addr: SocketAddr,
+    /// Messages logged so far, with their addresses, for `captured_messages`
+    captured: Arc<Mutex<VecDeque<CapturedMessage>>>,
+    /// Call-ID whose messages alone are logged, if set
+    filter: Arc<Mutex<Option<String>>>,
 }
 
 impl SipFlow {
@@ -22,7 +36,8 @@ impl SipFlow {
     /// # Parameters
     /// - `log_dir`: log directory; uses the system temp dir if None
     /// - `enabled`: whether to enable logging on creation
-    pub fn new(log_dir: Option<&str>, enabled: bool) -> Self {
+    /// - `local_addr`: local SIP address, recorded as one end of every message
+    pub fn new(log_dir: Option<&str>, enabled: bool, local_addr: SocketAddr) -> Self {
         // Resolve log directory
         let dir = log_dir.map(PathBuf::from).unwrap_or_else(|| {
             let mut temp = std::env::temp_dir();
@@ -40,6 +55,9 @@ impl SipFlow {
             log_file: Arc::new(Mutex::new(log_file)),
             enabled: Arc::new(Mutex::new(enabled)),
             log_dir: Arc::new(Mutex::new(dir)),
+            local_addr,
+            captured: Arc::new(Mutex::new(VecDeque::new())),
+            filter: Arc::new(Mutex::new(None)),
         }
     }
 
@@ -126,8 +144,57 @@ impl SipFlow {
         self.log_dir.lock().unwrap().clone()
     }
 
+    /// Log only the messages of one call (`Some(call_id)`) or all of them (`None`).
+    /// The change is marked in the log file so the gap in the flow is explained.
+    pub fn set_sip_flow_filter(&self, call_id: Option<String>) {
+        let mut filter = self.filter.lock().unwrap();
+        if *filter == call_id {
+            return;
+        }
+        let marker = match call_id {
+            Some(ref id) => format!("filter set: only Call-ID {}", id),
+            None => "filter cleared: all Call-IDs".to_string(),
+        };
+        if let Some(ref mut file) = *self.log_file.lock().unwrap() {
+            let timestamp_str = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S%.3f");
+            let _ = writeln!(file, "\n[{}] --- {} ---", timestamp_str, marker);
+            let _ = file.flush();
+        }
+        info!("SIP flow {}", marker);
+        *filter = call_id;
+    }
+
+    /// Call-ID the log is currently restricted to
+    pub fn sip_flow_filter(&self) -> Option<String> {
+        self.filter.lock().unwrap().clone()
+    }
+
+    /// Messages captured while logging was enabled, oldest first
+    pub fn captured_messages(&self) -> Vec<CapturedMessage> {
+        self.captured.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Keep a SIP message and its addresses for the PCAP export
+    fn capture(&self, outgoing: bool, msg: &SipMessage, peer: Option<&SipAddr>) {
+        // Domains and unknown peers get a placeholder address
+        let remote = peer
+            .and_then(|addr| addr.get_socketaddr().ok())
+            .unwrap_or_else(|| SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 5060));
+        let mut captured = self.captured.lock().unwrap();
+        if captured.len() >= MAX_CAPTURED_MESSAGES {
+            captured.pop_front();
+        }
+        captured.push_back(CapturedMessage {
+            timestamp: SystemTime::now(),
+            outgoing,
+            local: self.local_addr,
+            remote,
+            data: msg.to_string().into_bytes(),
+        });
+    }
+
     /// Record a SIP message to the log file
-    fn record(&self, direction: &str, msg: &SipMessage) {
+    fn record(&self, direction: &str, msg: &SipMessage, peer: Option<&SipAddr>) {
         // Skip if logging is disabled
         if !self.is_enabled() {
             return;
@@ -137,9 +204,16 @@ impl SipFlow {
             rsip::SipMessage::Request(req) => req.call_id_header(),
             rsip::SipMessage::Response(resp) => resp.call_id_header(),
         };
+        let call_id = call_id.map(|id| id.value().to_string()).ok();
+
+        if let Some(ref filter) = *self.filter.lock().unwrap() {
+            if call_id.as_ref() != Some(filter) {
+                return;
+            }
+        }
+        self.capture(direction == "OUTGOING", msg, peer);
 
-        if let Ok(id) = call_id {
-            let call_id_str = id.value().to_string();
+        if let Some(call_id_str) = call_id {
             let timestamp = chrono::Utc::now();
             let content = msg.to_string();
 
@@ -165,13 +239,13 @@ impl SipFlow {
 }
 
 impl MessageInspector for SipFlow {
-    fn before_send(&self, msg: SipMessage, _dest: Option<&SipAddr>) -> SipMessage {
-        self.record("OUTGOING", &msg);
+    fn before_send(&self, msg: SipMessage, dest: Option<&SipAddr>) -> SipMessage {
+        self.record("OUTGOING", &msg, dest);
         msg
     }
 
-    fn after_received(&self, msg: SipMessage, _from: &SipAddr) -> SipMessage {
-        self.record("INCOMING", &msg);
+    fn after_received(&self, msg: SipMessage, from: &SipAddr) -> SipMessage {
+        self.record("INCOMING", &msg, Some(from));
         msg
     }
 }
@@ -1,50 +1,95 @@
+use crate::call_history::{self, CallRecord};
+use crate::error::AppError;
 use crate::sip::helpers::{
     create_transport_connection, extract_protocol_from_uri, get_local_outbound_ip,
+    resolve_server_uri,
 };
 use crate::sip::message_inspector::SipFlow;
-use crate::sip::state::{ActiveCall, PendingCall, ClientHandle};
+use crate::sip::state::{
+    ActiveCall, ActiveCalls, ClientHandle, FlowConfig, PendingCall, SipAppState,
+};
 use dashmap::DashMap;
 use rsip::Uri;
-use rsipstack::dialog::authenticate::Credential;
+use rsipstack::dialog::authenticate::{handle_client_authenticate, Credential};
 use rsipstack::dialog::dialog_layer::DialogLayer;
 use rsipstack::dialog::invitation::InviteOption;
+use rsipstack::transaction::key::{TransactionKey, TransactionRole};
+use rsipstack::transaction::make_tag;
+use rsipstack::transaction::transaction::Transaction;
 use rsipstack::transport::TransportLayer;
 use rsipstack::EndpointBuilder;
 use std::collections::HashMap;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
-use tauri::{AppHandle, Emitter};
+use tauri::{AppHandle, Emitter, Manager};
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
-use crate::webrtc::WebRtcSession;
+use crate::webrtc::audio_bridge::{AudioDirection, DEFAULT_AUDIO_BUFFER_MS};
+use crate::webrtc::codec::{prefer_offered_codec, CodecType};
+use crate::webrtc::encoder::{adapt_opus_bitrate, OpusConfig};
+use crate::webrtc::recorder::RecordingMode;
+use crate::webrtc::vad::VadConfig;
+use crate::webrtc::{CallStats, MediaConfig, MediaInfo, WebRtcSession};
 
+mod allow;
+mod blf;
 mod coming_request;
 mod dialog;
 mod helpers;
+mod keepalive;
 mod make_call;
 pub mod message_inspector;
+mod mwi;
+mod outbound;
+pub mod pcap;
 mod registration;
 pub mod state;
+mod subscription;
+pub mod tls;
+mod transfer;
+mod transport_monitor;
 
 pub struct Client;
 
 impl Client {
     /// Connect to SIP server, perform registration, and return a handle for making calls.
     ///
+    /// Each account gets its own transport layer, cancel token and registration
+    /// refresh loop, so several accounts can be registered at the same time.
+    ///
     /// # Parameters
-    /// - `enable_sip_flow`: whether to enable SIP message flow logging (default: false)
-    /// - `sip_flow_log_dir`: directory for SIP flow log files (default: "logs")
+    /// - `account_id`: identifies the account in events (`sip://incoming-call`, ...)
+    /// - `sip_flow`: SIP message flow logging settings
+    /// - `max_attempts`: REGISTER attempts, with exponential backoff, before
+    ///   giving up (0 = unlimited); applies to the initial registration and to each refresh
+    /// - `keepalive_secs`: OPTIONS keepalive interval (0 = disabled)
+    /// - `crlf_keepalive_secs`: double-CRLF keepalive interval on TCP, TLS, WS
+    ///   and WSS (0 = disabled)
+    /// - `tls_verification`: server certificate check for TLS and WSS
+    #[allow(clippy::too_many_arguments)]
     pub async fn connect(
         app_handle: AppHandle,
+        account_id: String,
         server: String,
         username: String,
         password: String,
         outbound_proxy: Option<String>,
-        enable_sip_flow: Option<bool>,
-        sip_flow_log_dir: Option<String>,
-    ) -> rsipstack::Result<(ClientHandle, CancellationToken)> {
+        sip_flow: &FlowConfig,
+        max_attempts: u32,
+        keepalive_secs: u64,
+        crlf_keepalive_secs: u64,
+        tls_verification: &tls::TlsVerification,
+    ) -> Result<ClientHandle, AppError> {
+        let register_args = state::RegisterArgs {
+            server: server.clone(),
+            username: username.clone(),
+            password: password.clone(),
+            outbound_proxy: outbound_proxy.clone(),
+        };
+
         // Parse server URI - support both SIP URI (sip:host) and WebSocket URL (ws://host/path)
         let (server_uri, ws_path) = if server.starts_with("ws://") || server.starts_with("wss://") {
             let is_wss = server.starts_with("wss://");
@@ -86,6 +131,7 @@ impl Client {
         };
 
         info!(
+            account = %account_id,
             server = %server_uri,
             username = %username,
             "SIP client connecting"
@@ -93,66 +139,83 @@ impl Client {
 
         let cancel_token = CancellationToken::new();
 
+        // Determine protocol and target: an outbound proxy if configured, else the server.
+        // A bare domain is located via DNS SRV, which also selects the transport.
+        let target_uri = outbound_proxy_uri.as_ref().unwrap_or(&server_uri);
+        let located = if ws_path.is_none() {
+            resolve_server_uri(target_uri).await?
+        } else {
+            None
+        };
+        let located_via_dns = located.is_some();
+        let (protocol, target_sip_addr) = match located {
+            Some(sip_addr) => {
+                let protocol = sip_addr
+                    .r#type
+                    .map(helpers::Protocol::from)
+                    .unwrap_or(helpers::Protocol::Udp);
+                (protocol, sip_addr)
+            }
+            None => {
+                let protocol = extract_protocol_from_uri(target_uri);
+                (
+                    protocol,
+                    rsipstack::transport::SipAddr {
+                        r#type: Some(protocol.into()),
+                        addr: target_uri.host_with_port.clone(),
+                    },
+                )
+            }
+        };
+
+        debug!(protocol = %protocol.as_str(), target = %target_sip_addr.addr, "Transport protocol selected");
+
         // Get local IP — probe the OS routing table to find the actual egress interface
-        let local_ip = get_local_outbound_ip(&format!("{}", server_uri.host_with_port))?;
+        let route_target = target_sip_addr.addr.to_string();
+        let local_ip = get_local_outbound_ip(&route_target)?;
         debug!(ip = %local_ip, "Detected local outbound IP");
 
         // Create transport layer
         let mut transport_layer = TransportLayer::new(cancel_token.clone());
 
-        // Determine protocol
-        let (protocol, target_sip_addr) = if let Some(ref proxy) = outbound_proxy_uri {
-            let protocol = extract_protocol_from_uri(proxy);
-            (
-                protocol,
-                rsipstack::transport::SipAddr {
-                    r#type: Some(protocol.into()),
-                    addr: proxy.host_with_port.clone(),
-                },
-            )
-        } else {
-            let protocol = extract_protocol_from_uri(&server_uri);
-            (
-                protocol,
-                rsipstack::transport::SipAddr {
-                    r#type: Some(protocol.into()),
-                    addr: server_uri.host_with_port.clone(),
-                },
-            )
-        };
-
-        debug!(protocol = %protocol.as_str(), target = %target_sip_addr.addr, "Transport protocol selected");
-
-        // Configure outbound proxy
-        if let Some(ref proxy) = outbound_proxy_uri {
-            let sip_addr = rsipstack::transport::SipAddr {
-                r#type: Some(protocol.into()),
-                addr: proxy.host_with_port.clone(),
-            };
-            transport_layer.outbound = Some(sip_addr);
-            info!(proxy = %proxy.host_with_port, "Outbound proxy configured");
+        // Route all requests through the outbound proxy, or to the DNS-located server
+        if outbound_proxy_uri.is_some() || located_via_dns {
+            transport_layer.outbound = Some(target_sip_addr.clone());
+            info!(target = %target_sip_addr.addr, "Outbound target configured");
+        }
+        // Keep an SRV-selected transport on the server URI so calls and
+        // subscriptions built from it use the same connection
+        let mut server_uri = server_uri;
+        if located_via_dns && protocol != helpers::Protocol::Udp && outbound_proxy_uri.is_none() {
+            server_uri
+                .params
+                .push(rsip::Param::Transport(protocol.into()));
         }
 
         // Create transport connection and get local address
-        let local_addr: SocketAddr = format!("{}:0", local_ip).parse()?;
+        let local_addr = SocketAddr::new(local_ip, 0);
+
+        // The TCP/TLS/WS connection, watched and kept alive by the transport supervisor
+        let mut supervised = None;
 
         // Get local SipAddr for Contact/Via construction
         let local_sip_addr = match protocol {
             // For TCP: extract local addr from connection, use add_connection
             helpers::Protocol::Tcp => {
-                let connection = create_transport_connection(local_addr, target_sip_addr.clone(), cancel_token.clone(), None).await?;
+                let connection = create_transport_connection(local_addr, target_sip_addr.clone(), cancel_token.clone(), None, tls_verification).await?;
 
                 // Extract local address from TCP connection (inner is public for TCP)
                 let conn_local_addr = match &connection {
                     rsipstack::transport::SipConnection::Tcp(tcp) => tcp.inner.local_addr.clone(),
                     _ => {
-                        return Err(rsipstack::Error::Error(
+                        return Err(AppError::Other(
                             "Unexpected connection type for TCP protocol".to_string(),
                         ));
                     }
                 };
 
                 // Use add_connection for TCP (starts receive loop immediately)
+                supervised = Some(connection.clone());
                 transport_layer.add_connection(connection);
                 info!(local = %conn_local_addr, remote = %target_sip_addr, protocol = %protocol.as_str(), "TCP connection added via add_connection");
 
@@ -172,15 +235,17 @@ impl Client {
 
                 conn_local_addr
             }
-            // For TLS/WS/WSS: pre-create connection with custom TLS verifier (SkipCertVerifier),
-            // add_connection for receive loop + connection reuse (prevents rsipstack from
-            // auto-creating a new one with default TLS verifier that rejects self-signed certs).
+            // For TLS/WS/WSS: pre-create connection with the TLS verifier chosen by the account's
+            // `TlsVerification` mode (Strict by default, Skip or Pinned), add_connection for receive
+            // loop + connection reuse (prevents rsipstack from auto-creating a new one with its
+            // default TLS verifier, which ignores that mode).
             // Create a TcpListenerConnection with the correct transport type in `external` so
             // get_addrs() returns local_ip with the correct type (TLS/WS/WSS) for Via headers.
             helpers::Protocol::Tls | helpers::Protocol::Ws | helpers::Protocol::Wss | helpers::Protocol::TlsSctp => {
                 let transport_type: rsip::transport::Transport = protocol.into();
-                let connection = create_transport_connection(local_addr, target_sip_addr.clone(), cancel_token.clone(), ws_path.clone()).await?;
+                let connection = create_transport_connection(local_addr, target_sip_addr.clone(), cancel_token.clone(), ws_path.clone(), tls_verification).await?;
                 // Register in connections map (rsipstack will reuse this for sends) + start receive loop
+                supervised = Some(connection.clone());
                 transport_layer.add_connection(connection);
 
                 // Create a TcpListenerConnection as a "local address anchor" in listens.
@@ -210,7 +275,7 @@ impl Client {
             }
             // For UDP: use add_transport (listener mode)
             _ => {
-                let connection = create_transport_connection(local_addr, target_sip_addr.clone(), cancel_token.clone(), None).await?;
+                let connection = create_transport_connection(local_addr, target_sip_addr.clone(), cancel_token.clone(), None, tls_verification).await?;
                 let udp_addr = connection.get_addr().clone();
                 transport_layer.add_transport(connection);
                 info!(local = %udp_addr, protocol = %protocol.as_str(), "UDP transport added");
@@ -219,15 +284,35 @@ impl Client {
         };
 
         // Create SIP flow inspector
-        let enable_flow = enable_sip_flow.unwrap_or(false); // disabled by default
-        let sip_flow = Arc::new(SipFlow::new(sip_flow_log_dir.as_deref(), enable_flow));
+        let flow_local_addr = local_sip_addr
+            .get_socketaddr()
+            .unwrap_or_else(|_| SocketAddr::new(local_ip, 5060));
+        let flow_filter = sip_flow.filter.clone();
+        let sip_flow = Arc::new(SipFlow::new(
+            Some(&sip_flow.log_dir),
+            sip_flow.enabled,
+            flow_local_addr,
+        ));
+        if flow_filter.is_some() {
+            sip_flow.set_sip_flow_filter(flow_filter);
+        }
+
+        // REGISTERs advertise outbound (RFC 5626) until the first hop rejects it
+        let outbound_enabled = Arc::new(AtomicBool::new(true));
+        let flow_inspector = outbound::OutboundInspector::new(
+            allow::AllowInspector::new(sip_flow.as_ref().clone()),
+            outbound::load_instance_id(&app_handle),
+            outbound_enabled.clone(),
+        );
 
-        // Create endpoint with SIP flow inspector
+        // Create endpoint with SIP flow inspector and closed-connection reporting
+        let (closed_inspector, closed_rx) = transport_monitor::ClosedConnectionInspector::new();
         let endpoint = EndpointBuilder::new()
             .with_cancel_token(cancel_token.clone())
             .with_transport_layer(transport_layer)
             .with_user_agent("softphone-app/0.1.0")
-            .with_inspector(Box::new(sip_flow.as_ref().clone()))
+            .with_inspector(Box::new(flow_inspector))
+            .with_transport_inspector(Box::new(closed_inspector))
             .build();
 
         let credential = Credential {
@@ -259,9 +344,9 @@ impl Client {
         // Spawn background tasks BEFORE registration (endpoint.serve() must run to receive responses)
         let mut tasks = Vec::new();
 
-        // Initialize pending_incoming HashMap, active_call, and call cancellation tokens
+        // Initialize pending_incoming HashMap, active_calls, and call cancellation tokens
         let pending_incoming = Arc::new(tokio::sync::Mutex::new(HashMap::new()));
-        let active_call = Arc::new(tokio::sync::Mutex::new(None));
+        let active_calls = Arc::new(tokio::sync::Mutex::new(ActiveCalls::default()));
         let active_call_tokens = Arc::new(DashMap::new());
 
         // Task 1: endpoint.serve()
@@ -271,56 +356,62 @@ impl Client {
         }));
 
         // Task 2: process_incoming_request
+        let id = account_id.clone();
         let dl = dialog_layer.clone();
         let ss = state_sender.clone();
         let ct = contact.clone();
         let ah = app_handle.clone();
         let pi = pending_incoming.clone();
-        let ac = active_call.clone();
+        let ac = active_calls.clone();
         tasks.push(tokio::spawn(async move {
             if let Err(e) =
-                coming_request::process_incoming_request(dl, incoming, ss, ct, ah, pi, ac).await
+                coming_request::process_incoming_request(id, dl, incoming, ss, ct, ah, pi, ac)
+                    .await
             {
                 error!(error = ?e, "Incoming request loop error");
             }
         }));
 
         // Task 3: process_dialog (with app_handle for event emission and call tokens for cleanup)
+        let id = account_id.clone();
         let dl = dialog_layer.clone();
         let ah = app_handle.clone();
+        let ac = active_calls.clone();
         let tokens = active_call_tokens.clone();
+        let pi = pending_incoming.clone();
         tasks.push(tokio::spawn(async move {
-            if let Err(e) = dialog::process_dialog(dl, state_receiver, ah, tokens).await {
+            if let Err(e) = dialog::process_dialog(id, dl, state_receiver, ah, ac, tokens, pi).await
+            {
                 error!(error = ?e, "Dialog loop error");
             }
         }));
 
         // Perform initial registration (after endpoint.serve() is running)
+        let registration = Arc::new(tokio::sync::Mutex::new(state::RegistrationInfo::default()));
         let mut reg = registration::Registrant::new(
             endpoint_inner.clone(),
             credential.clone(),
             server_uri.clone(),
+            app_handle.clone(),
+            account_id.clone(),
+            max_attempts,
+            outbound_enabled,
+            registration.clone(),
         );
-        let initial_expires = reg.register_once().await?;
-
-        // Emit registration success event
-        let _ = app_handle.emit(
-            "sip://registration-status",
-            state::RegistrationStatusPayload {
-                status: "registered".to_string(),
-                message: None,
-            },
-        );
+        let initial_expires = reg.register(&cancel_token).await?;
 
         // Task 4: registration refresh loop.
-        // For connection-oriented transports (TCP/TLS/WS/WSS), cap the
-        // refresh interval at 25 s so the TCP session is kept alive by
-        // periodic REGISTER traffic.  rsipstack never auto-removes dead
-        // connections from its send map, so the only reliable protection
-        // against "socket already shut down" (OS 10058) on the first outbound
-        // INVITE is to prevent the server from closing the TCP connection in
-        // the first place.
+        // For connection-oriented transports (TCP/TLS/WS/WSS) without CRLF
+        // keepalive, cap the refresh interval at 25 s so the TCP session is
+        // kept alive by periodic REGISTER traffic.  rsipstack never
+        // auto-removes dead connections from its send map; the transport
+        // supervisor below replaces a connection the server closed anyway,
+        // but keeping it open avoids "socket already shut down" (OS 10058)
+        // on an INVITE sent before the replacement is in place.  With CRLF
+        // keepalive the supervisor's pings do this and REGISTER follows the
+        // server's expires.
         let tcp_keepalive = match protocol {
+            _ if crlf_keepalive_secs > 0 && supervised.is_some() => None,
             helpers::Protocol::Tcp
             | helpers::Protocol::Tls
             | helpers::Protocol::TlsSctp
@@ -328,57 +419,179 @@ impl Client {
             | helpers::Protocol::Wss => Some(25u64),
             helpers::Protocol::Udp | helpers::Protocol::Sctp => None,
         };
+        let reregister = Arc::new(tokio::sync::Notify::new());
         let ct = cancel_token.clone();
+        let rr = reregister.clone();
         tasks.push(tokio::spawn(async move {
-            if let Err(e) = reg.run_refresh_loop(initial_expires, ct, tcp_keepalive).await {
+            if let Err(e) = reg.run_refresh_loop(initial_expires, ct, tcp_keepalive, rr).await {
                 error!(error = ?e, "Registration refresh loop error");
             }
         }));
 
-        Ok((
-            ClientHandle {
-                app_handle,
-                dialog_layer,
-                state_sender,
-                contact,
-                credential,
-                server: server_uri,
-                active_call,
-                pending_incoming,
-                active_call_tokens,
-                sip_flow: Some(sip_flow),
-                _tasks: tasks,
-            },
+        // Task 4b: CRLF keepalive on the TCP/TLS/WS connection, rebuilt when
+        // the server closes it
+        let (crlf_keepalive_interval, crlf_keepalive_rx) =
+            tokio::sync::watch::channel(crlf_keepalive_secs);
+        if let Some(connection) = supervised {
+            let supervisor = transport_monitor::TransportSupervisor::new(
+                endpoint_inner.clone(),
+                local_addr,
+                target_sip_addr.clone(),
+                ws_path.clone(),
+                tls_verification.clone(),
+                connection,
+                reregister,
+                app_handle.clone(),
+                account_id.clone(),
+                max_attempts,
+            );
+            tasks.push(tokio::spawn(supervisor.run(
+                closed_rx,
+                crlf_keepalive_rx,
+                cancel_token.clone(),
+            )));
+        }
+
+        // Task 5: voicemail (message-summary) subscription and its refreshes
+        let mut mwi = subscription::Subscription::new(
+            endpoint_inner.clone(),
+            credential.clone(),
+            contact.clone(),
+            user_uri(&server_uri, &username),
+            mwi::EVENT,
+            mwi::ACCEPT,
+        );
+        let ct = cancel_token.clone();
+        tasks.push(tokio::spawn(async move {
+            match mwi.subscribe(subscription::SUBSCRIBE_EXPIRES).await {
+                Ok(expires) => mwi.run_refresh_loop(expires, ct).await,
+                Err(e) => warn!(error = ?e, "Voicemail subscription failed"),
+            }
+        }));
+
+        // Task 6: OPTIONS keepalive, independent of the registration refresh
+        let (keepalive_interval, keepalive_rx) = tokio::sync::watch::channel(keepalive_secs);
+        let keepalive = keepalive::KeepAlive::new(
+            endpoint_inner.clone(),
+            credential.clone(),
+            contact.clone(),
+            server_uri.clone(),
+            app_handle.clone(),
+            account_id.clone(),
+        );
+        let ct = cancel_token.clone();
+        tasks.push(tokio::spawn(keepalive.run(keepalive_rx, ct)));
+
+        Ok(ClientHandle {
+            account_id,
+            register_args,
+            local_ip,
+            route_target,
+            app_handle,
             cancel_token,
-        ))
+            dialog_layer,
+            state_sender,
+            contact,
+            credential,
+            server: server_uri,
+            registration,
+            active_calls,
+            pending_incoming,
+            active_call_tokens,
+            blf_subscriptions: Arc::new(DashMap::new()),
+            keepalive_interval,
+            crlf_keepalive_interval,
+            sip_flow: Some(sip_flow),
+            _tasks: tasks,
+        })
+    }
+}
+
+/// The egress IP towards the account's server, when it is no longer the one
+/// the transport was built on (moved between Wi-Fi, Ethernet or VPN).
+///
+/// Emits `sip://network-changed` on a change. Returns `None` while there is
+/// no route to the server, so going offline does not count as a change.
+pub async fn handle_check_network(handle: &ClientHandle) -> Option<IpAddr> {
+    let target = handle.route_target.clone();
+    let new_ip = tokio::task::spawn_blocking(move || helpers::probe_outbound_ip(&target))
+        .await
+        .ok()?
+        .ok()?;
+    if new_ip == handle.local_ip {
+        return None;
+    }
+
+    info!(account = %handle.account_id, old_ip = %handle.local_ip, new_ip = %new_ip, "Local network changed");
+    let _ = handle.app_handle.emit(
+        "sip://network-changed",
+        state::NetworkChangedPayload {
+            account_id: handle.account_id.clone(),
+            old_ip: handle.local_ip.to_string(),
+            new_ip: new_ip.to_string(),
+        },
+    );
+    Some(new_ip)
+}
+
+/// Contact, expires and time to the next refresh of the account's registration
+pub async fn handle_get_registration_info(handle: &ClientHandle) -> state::RegistrationDetails {
+    let info = handle.registration.lock().await;
+    state::RegistrationDetails {
+        account_id: handle.account_id.clone(),
+        contact: info.contact.clone(),
+        expires_secs: info.expires_secs,
+        refresh_in_secs: info.refresh_at.map(|at| {
+            at.saturating_duration_since(std::time::Instant::now())
+                .as_secs()
+        }),
     }
 }
 
-/// Make an outbound call using the ClientHandle
+/// Make an outbound call using the ClientHandle. Returns the call_id once the
+/// call is connected.
+///
+/// `callee` may be a full SIP URI or just an extension, which is called at the
+/// registrar. `headers` are extra INVITE headers (see `make_call::custom_headers`
+/// for what is accepted). `force_codec` offers only that codec for this call.
+/// `listen_only` places a receive-only call (`a=recvonly`) without a microphone.
+#[allow(clippy::too_many_arguments)]
 pub async fn handle_make_call(
     handle: &ClientHandle,
     callee: String,
-    input_device: Option<String>,
-    output_device: Option<String>,
     global_cancel_token: CancellationToken,
-    prefer_srtp: bool,
-    noise_reduce: bool,
-    speaker_noise_reduce: bool,
-) -> rsipstack::Result<()> {
+    media: MediaConfig,
+    support_100rel: bool,
+    headers: Option<HashMap<String, String>>,
+    force_codec: Option<CodecType>,
+    listen_only: bool,
+) -> Result<String, AppError> {
+    let headers = headers.map(|h| make_call::custom_headers(&h)).transpose()?;
     let call_id = Uuid::new_v4().to_string();
+    // Per-call overrides leave the configured media settings untouched
+    let media = MediaConfig {
+        force_codec,
+        listen_only,
+        ..media
+    };
 
-    info!(call_id = %call_id, callee = %callee, "Making outbound call");
+    info!(account = %handle.account_id, call_id = %call_id, callee = %callee, "Making outbound call");
 
-    let callee_uri = Uri {
-        scheme: Some(rsip::Scheme::Sip),
-        auth: Some(rsip::Auth {
-            user: callee.clone(),
-            password: None,
-        }),
-        host_with_port: handle.server.host_with_port.clone(),
-        // Preserve transport params (e.g. transport=TCP) so rsipstack uses the correct connection
-        params: handle.server.params.clone(),
-        ..Default::default()
+    let callee_uri = if callee.starts_with("sip:") || callee.starts_with("sips:") {
+        Uri::try_from(callee.as_str())
+            .map_err(|e| AppError::Other(format!("Invalid callee: {:?}", e)))?
+    } else {
+        Uri {
+            scheme: Some(rsip::Scheme::Sip),
+            auth: Some(rsip::Auth {
+                user: callee.clone(),
+                password: None,
+            }),
+            host_with_port: handle.server.host_with_port.clone(),
+            // Preserve transport params (e.g. transport=TCP) so rsipstack uses the correct connection
+            params: handle.server.params.clone(),
+            ..Default::default()
+        }
     };
 
     let invite_option = InviteOption {
@@ -387,9 +600,17 @@ pub async fn handle_make_call(
         contact: handle.contact.clone(),
         credential: Some(handle.credential.clone()),
         call_id: Some(call_id.clone()),
+        // Advertise `Supported: 100rel`; the dialog layer then PRACKs any
+        // provisional response carrying `Require: 100rel` and an RSeq
+        support_prack: support_100rel,
+        headers,
         ..Default::default()
     };
 
+    let media_timeout_secs = media.media_timeout_secs;
+    let audio_buffer_ms = media.audio_buffer_ms;
+    let mut history = CallRecord::new(&call_id, &handle.account_id, "outbound", &callee);
+
     // Create child token from global cancel token BEFORE making the call
     let call_cancel_token = global_cancel_token.child_token();
 
@@ -406,10 +627,9 @@ pub async fn handle_make_call(
         handle.dialog_layer.clone(),
         invite_option,
         handle.state_sender.clone(),
-        input_device,
-        output_device,
+        media,
         call_cancel_token.clone(),
-        prefer_srtp,
+        handle.app_handle.clone(),
     )
     .await;
 
@@ -418,6 +638,12 @@ pub async fn handle_make_call(
         Err(e) => {
             // Clean up on failure - remove placeholder token and cancel
             handle.active_call_tokens.remove(&dialog_id_placeholder);
+            let reason = if call_cancel_token.is_cancelled() {
+                "canceled".to_string()
+            } else {
+                e.to_string()
+            };
+            call_history::record_call(&handle.app_handle, history, &reason);
             call_cancel_token.cancel();
             return Err(e);
         }
@@ -439,15 +665,23 @@ pub async fn handle_make_call(
             }
             _ => {}
         }
-        return Err(rsipstack::Error::Error("Call cancelled".to_string()));
+        call_history::record_call(&handle.app_handle, history, "canceled");
+        return Err("Call cancelled".into());
     }
 
     // Call was successful and not cancelled - remove placeholder and create new token for active call
     handle.active_call_tokens.remove(&dialog_id_placeholder);
 
-    // Apply noise reduction settings before audio starts
-    webrtc_session.set_noise_reduce(noise_reduce);
-    webrtc_session.set_speaker_noise_reduce(speaker_noise_reduce);
+    warn_if_relayed(handle, &call_id, &webrtc_session).await;
+    webrtc_session.emit_media_info(&handle.app_handle, &call_id);
+    webrtc_session.forward_audio_levels(handle.app_handle.clone(), call_id.clone());
+    webrtc_session.forward_received_dtmf(handle.app_handle.clone(), call_id.clone());
+    webrtc_session.forward_audio_errors(handle.app_handle.clone(), call_id.clone());
+    webrtc_session.forward_audio_fallbacks(handle.app_handle.clone(), call_id.clone());
+    webrtc_session.forward_playback_finished(handle.app_handle.clone(), call_id.clone());
+    webrtc_session.watch_one_way_audio(handle.app_handle.clone(), call_id.clone());
+    webrtc_session.watch_rtp_latching(handle.app_handle.clone(), call_id.clone());
+    watch_audio_devices(handle, &call_id, &mut webrtc_session);
 
     let call_cancel_token = global_cancel_token.child_token();
 
@@ -462,13 +696,18 @@ pub async fn handle_make_call(
     debug!(call_id = %call_id, dialog_id = %dialog_id, "Registered call cancellation token (child of global)");
 
     // Store active call with WebRTC session
+    let connected_at = std::time::Instant::now();
+    history.answered(webrtc_session.codec_name());
     {
-        let mut active = handle.active_call.lock().await;
-        *active = Some(ActiveCall {
+        let mut active = handle.active_calls.lock().await;
+        active.insert(ActiveCall {
             call_id: call_id.clone(),
             dialog,
             webrtc_session: Some(webrtc_session),
-            cancel_token: call_cancel_token,
+            cancel_token: call_cancel_token.clone(),
+            transfer_peer: None,
+            connected_at,
+            history,
         });
     }
 
@@ -477,56 +716,629 @@ pub async fn handle_make_call(
         "sip://call-state",
         state::CallStatePayload {
             state: "connected".to_string(),
-            call_id: Some(call_id),
+            call_id: Some(call_id.clone()),
             reason: None,
         },
     );
+    spawn_call_duration_timer(
+        handle.app_handle.clone(),
+        call_id.clone(),
+        connected_at,
+        call_cancel_token.clone(),
+    );
+    spawn_media_watchdog(
+        handle,
+        call_id.clone(),
+        media_timeout_secs,
+        call_cancel_token.clone(),
+    );
+    spawn_call_stats_emitter(handle, call_id.clone(), call_cancel_token.clone());
+    spawn_adaptive_bitrate(handle, call_id.clone(), call_cancel_token.clone());
+    spawn_audio_buffer_monitor(handle, call_id.clone(), audio_buffer_ms, call_cancel_token);
 
-    Ok(())
+    Ok(call_id)
 }
 
-/// Hang up the active call
-pub async fn handle_hangup(handle: &ClientHandle) -> rsipstack::Result<()> {
-    let mut active = handle.active_call.lock().await;
-    if let Some(mut call) = active.take() {
-        info!(call_id = %call.call_id, "Hanging up call");
+/// Emit `sip://call-duration` once per second with the seconds elapsed since
+/// `connected_at`, until the call's cancel token fires (hangup, BYE, unregister).
+fn spawn_call_duration_timer(
+    app_handle: AppHandle,
+    call_id: String,
+    connected_at: std::time::Instant,
+    cancel_token: CancellationToken,
+) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(std::time::Duration::from_secs(1));
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        ticker.tick().await; // first tick fires immediately, skip it
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    let _ = app_handle.emit(
+                        "sip://call-duration",
+                        state::CallDurationPayload {
+                            call_id: call_id.clone(),
+                            seconds: connected_at.elapsed().as_secs(),
+                        },
+                    );
+                }
+                _ = cancel_token.cancelled() => {
+                    debug!(call_id = %call_id, "Call duration timer stopped");
+                    break;
+                }
+            }
+        }
+    });
+}
 
-        // Cancel the call token first to trigger cleanup
-        call.cancel_token.cancel();
+/// Emit `sip://call-stats` every second until the call ends, so the UI can
+/// graph call quality without polling `sip_get_call_stats`.
+fn spawn_call_stats_emitter(
+    handle: &ClientHandle,
+    call_id: String,
+    cancel_token: CancellationToken,
+) {
+    let app_handle = handle.app_handle.clone();
+    let active_calls = handle.active_calls.clone();
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(std::time::Duration::from_secs(1));
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        // Byte counters at the previous sample, for the bitrates
+        let mut previous = (0u64, 0u64, std::time::Instant::now());
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    let (source, codec) = {
+                        let active = active_calls.lock().await;
+                        let Some(session) = active.get(&call_id).and_then(|c| c.webrtc_session.as_ref()) else {
+                            break;
+                        };
+                        (session.stats_source(), session.codec_name())
+                    };
+                    let stats = source.get_stats().await;
+                    let (sent, received, at) = previous;
+                    let secs = at.elapsed().as_secs_f64().max(0.001);
+                    let kbps = |now: u64, before: u64| now.saturating_sub(before) as f64 * 8.0 / 1000.0 / secs;
+                    let payload = state::CallStatsPayload {
+                        call_id: call_id.clone(),
+                        codec: codec.map(str::to_string),
+                        send_bitrate_kbps: kbps(stats.bytes_sent, sent),
+                        receive_bitrate_kbps: kbps(stats.bytes_received, received),
+                        stats,
+                    };
+                    previous = (payload.stats.bytes_sent, payload.stats.bytes_received, std::time::Instant::now());
+                    let _ = app_handle.emit("sip://call-stats", payload);
+                }
+                _ = cancel_token.cancelled() => break,
+            }
+        }
+        debug!(call_id = %call_id, "Call stats emitter stopped");
+    });
+}
 
-        // Stop audio
-        if let Some(ref mut session) = call.webrtc_session {
-            session.close().await;
+/// Interval between adaptive bitrate decisions, a few RTCP report periods
+const ADAPTIVE_BITRATE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Adjust the Opus bitrate of an Opus call to the loss the remote reports,
+/// while adaptive bitrate is enabled (see `set_adaptive_bitrate`). When it is
+/// disabled mid-call the configured bitrate is put back.
+fn spawn_adaptive_bitrate(handle: &ClientHandle, call_id: String, cancel_token: CancellationToken) {
+    let app_handle = handle.app_handle.clone();
+    let active_calls = handle.active_calls.clone();
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(ADAPTIVE_BITRATE_INTERVAL);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        ticker.tick().await; // first tick fires immediately, skip it
+
+        // Bitrate last set by this task, None while the configured one applies
+        let mut adapted: Option<u32> = None;
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    let state = app_handle.state::<SipAppState>();
+                    let config = *state.adaptive_bitrate.lock().await;
+                    let configured = state.opus_config.lock().await.bitrate;
+
+                    let source = {
+                        let active = active_calls.lock().await;
+                        let Some(session) = active.get(&call_id).and_then(|c| c.webrtc_session.as_ref()) else {
+                            break;
+                        };
+                        if session.codec_name() != Some("opus") {
+                            continue;
+                        }
+                        if !config.enabled {
+                            if adapted.take().is_some() {
+                                session.set_opus_bitrate(configured);
+                            }
+                            continue;
+                        }
+                        session.stats_source()
+                    };
+                    let stats = source.get_stats().await;
+                    // Nothing reported by the remote yet
+                    if stats.packets_lost == 0 && stats.fraction_lost == 0.0 && stats.round_trip_time_ms == 0.0 {
+                        continue;
+                    }
+                    let current = adapted.unwrap_or(configured);
+                    let next = adapt_opus_bitrate(current, stats.fraction_lost, config.min_bitrate, config.max_bitrate);
+                    if adapted != Some(next) {
+                        info!(call_id = %call_id, fraction_lost = stats.fraction_lost, bitrate = next, "Adapting Opus bitrate");
+                        adapted = Some(next);
+                    }
+                    let active = active_calls.lock().await;
+                    let Some(session) = active.get(&call_id).and_then(|c| c.webrtc_session.as_ref()) else {
+                        break;
+                    };
+                    // Set every time: `set_opus_config` may have replaced it
+                    session.set_opus_bitrate(next);
+                }
+                _ = cancel_token.cancelled() => break,
+            }
         }
+        debug!(call_id = %call_id, "Adaptive bitrate stopped");
+    });
+}
 
-        // Get dialog ID before moving
-        let dialog_id = match &call.dialog {
-            rsipstack::dialog::dialog::Dialog::ClientInvite(d) => d.id().to_string(),
-            rsipstack::dialog::dialog::Dialog::ServerInvite(d) => d.id().to_string(),
-            _ => call.call_id.clone(),
-        };
+/// Hang up a call that received no RTP for `timeout_secs` (0 = never) while
+/// not on hold, e.g. after the far end crashed or a NAT mapping expired.
+/// Reported as `"ended"` with reason `"media-timeout"`.
+fn spawn_media_watchdog(
+    handle: &ClientHandle,
+    call_id: String,
+    timeout_secs: u64,
+    cancel_token: CancellationToken,
+) {
+    if timeout_secs == 0 {
+        return;
+    }
+    let timeout = std::time::Duration::from_secs(timeout_secs);
+    let app_handle = handle.app_handle.clone();
+    let active_calls = handle.active_calls.clone();
+    let active_call_tokens = handle.active_call_tokens.clone();
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(std::time::Duration::from_secs(1));
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    let call = {
+                        let mut active = active_calls.lock().await;
+                        let idle = active
+                            .get_mut(&call_id)
+                            .and_then(|c| c.webrtc_session.as_ref())
+                            .and_then(|s| s.media_idle());
+                        if idle.is_none_or(|idle| idle < timeout) {
+                            continue;
+                        }
+                        remove_active_call(&app_handle, &mut active, &call_id, "media-timeout")
+                    };
+                    let Some(call) = call else { break };
+
+                    warn!(call_id = %call_id, timeout_secs, "No RTP received, hanging up");
+                    let _ = app_handle.emit(
+                        "sip://call-state",
+                        state::CallStatePayload {
+                            state: "ended".to_string(),
+                            call_id: Some(call_id.clone()),
+                            reason: Some("media-timeout".to_string()),
+                        },
+                    );
+                    if let Err(e) = hang_up_call(&active_call_tokens, call).await {
+                        warn!(call_id = %call_id, error = ?e, "Failed to hang up after media timeout");
+                    }
+                    break;
+                }
+                _ = cancel_token.cancelled() => break,
+            }
+        }
+        debug!(call_id = %call_id, "Media watchdog stopped");
+    });
+}
 
-        // Remove from active_call_tokens
-        handle.active_call_tokens.remove(&dialog_id);
+/// Audio ring buffer glitches within `AUDIO_GLITCH_WINDOW` that count as a spike
+const AUDIO_GLITCH_LIMIT: u64 = 10;
+const AUDIO_GLITCH_WINDOW: std::time::Duration = std::time::Duration::from_secs(10);
 
-        match call.dialog {
-            rsipstack::dialog::dialog::Dialog::ClientInvite(d) => {
-                d.bye().await.map_err(|e| {
-                    error!(call_id = %call.call_id, error = ?e, "Failed to send BYE");
-                    rsipstack::Error::Error(format!("Failed to send BYE: {:?}", e))
-                })?;
+/// When a call runs with audio ring buffers below the default size, watch it
+/// for glitches and emit one `sip://media-warning` ("audio-buffer-glitches")
+/// if they spike, so the user knows to raise the buffer again.
+fn spawn_audio_buffer_monitor(
+    handle: &ClientHandle,
+    call_id: String,
+    buffer_ms: u32,
+    cancel_token: CancellationToken,
+) {
+    if buffer_ms >= DEFAULT_AUDIO_BUFFER_MS {
+        return;
+    }
+    let app_handle = handle.app_handle.clone();
+    let active_calls = handle.active_calls.clone();
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(AUDIO_GLITCH_WINDOW);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        let mut previous = 0;
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    let xruns = {
+                        let active = active_calls.lock().await;
+                        match active.get(&call_id).and_then(|c| c.webrtc_session.as_ref()) {
+                            Some(session) => session.audio_buffer_xruns(),
+                            None => break,
+                        }
+                    };
+                    if xruns - previous >= AUDIO_GLITCH_LIMIT {
+                        warn!(call_id = %call_id, buffer_ms, glitches = xruns - previous, "Audio glitches with a reduced ring buffer");
+                        let _ = app_handle.emit(
+                            "sip://media-warning",
+                            state::MediaWarningPayload {
+                                call_id: Some(call_id.clone()),
+                                warning: "audio-buffer-glitches".to_string(),
+                                message: format!("Audio keeps dropping out with a {} ms audio buffer; a larger buffer adds latency but plays more smoothly.", buffer_ms),
+                            },
+                        );
+                        break;
+                    }
+                    previous = xruns;
+                }
+                _ = cancel_token.cancelled() => break,
             }
-            rsipstack::dialog::dialog::Dialog::ServerInvite(d) => {
-                d.bye().await.map_err(|e| {
-                    error!(call_id = %call.call_id, error = ?e, "Failed to send BYE");
-                    rsipstack::Error::Error(format!("Failed to send BYE: {:?}", e))
-                })?;
+        }
+    });
+}
+
+/// Emit a `sip://media-warning` when the call's media goes through a TURN relay,
+/// so the user knows why latency or audio quality may differ.
+async fn warn_if_relayed(handle: &ClientHandle, call_id: &str, session: &WebRtcSession) {
+    if session.is_relayed().await {
+        warn!(call_id = %call_id, "Media is relayed through TURN (no direct connectivity)");
+        let _ = handle.app_handle.emit(
+            "sip://media-warning",
+            state::MediaWarningPayload {
+                call_id: Some(call_id.to_string()),
+                warning: "relay-only".to_string(),
+                message: "Audio is relayed through a TURN server because a direct path could not be established; latency may be higher.".to_string(),
+            },
+        );
+    }
+}
+
+/// Delay before re-opening audio after a device was lost, giving the OS time
+/// to settle on a new default device
+const AUDIO_RECONNECT_DELAY: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Watch the session's audio devices: when one disappears mid-call, emit
+/// `sip://audio-device-lost` and reconnect the call's audio to the current
+/// (default) devices. A failed reconnect is reported as a `sip://media-warning`.
+///
+/// The watcher ends when the session's audio bridge is dropped.
+fn watch_audio_devices(handle: &ClientHandle, call_id: &str, session: &mut WebRtcSession) {
+    let Some(mut loss_rx) = session.take_device_loss_receiver() else {
+        return;
+    };
+    let app_handle = handle.app_handle.clone();
+    let active_calls = handle.active_calls.clone();
+    let call_id = call_id.to_string();
+    tokio::spawn(async move {
+        while let Some(direction) = loss_rx.recv().await {
+            warn!(call_id = %call_id, ?direction, "Audio device lost, reconnecting");
+            let _ = app_handle.emit(
+                "sip://audio-device-lost",
+                crate::webrtc::AudioDeviceLostPayload {
+                    call_id: call_id.clone(),
+                    direction,
+                },
+            );
+            tokio::time::sleep(AUDIO_RECONNECT_DELAY).await;
+
+            let mut active = active_calls.lock().await;
+            let Some(session) = active
+                .get_mut(&call_id)
+                .and_then(|c| c.webrtc_session.as_mut())
+            else {
+                break;
+            };
+            if let Err(e) = session.reconnect_audio() {
+                error!(call_id = %call_id, error = %e, "Audio reconnect failed");
+                let _ = app_handle.emit(
+                    "sip://media-warning",
+                    state::MediaWarningPayload {
+                        call_id: Some(call_id.clone()),
+                        warning: "audio-device-lost".to_string(),
+                        message: format!("Audio device was lost and could not be reopened: {}", e),
+                    },
+                );
             }
-            _ => {
-                debug!(call_id = %call.call_id, "Other dialog type, skipping BYE");
+        }
+        debug!(call_id = %call_id, "Audio device watcher stopped");
+    });
+}
+
+/// Re-open the audio devices of a call (`None` = current call), e.g. after
+/// plugging a headset back in.
+pub async fn handle_reconnect_audio(
+    handle: &ClientHandle,
+    call_id: Option<String>,
+) -> Result<(), AppError> {
+    let mut active = handle.active_calls.lock().await;
+    let call = match call_id {
+        Some(ref id) => active.get_mut(id),
+        None => active.current_mut(),
+    };
+    let session = call
+        .and_then(|c| c.webrtc_session.as_mut())
+        .ok_or(AppError::NoActiveCall)?;
+    Ok(session.reconnect_audio()?)
+}
+
+/// Start recording a call (`None` = current call) to a WAV file
+pub async fn handle_start_recording(
+    handle: &ClientHandle,
+    call_id: Option<String>,
+    path: &str,
+    mode: RecordingMode,
+) -> Result<(), AppError> {
+    let active = handle.active_calls.lock().await;
+    let call = match call_id {
+        Some(ref id) => active.get(id),
+        None => active.current(),
+    };
+    let session = call
+        .and_then(|c| c.webrtc_session.as_ref())
+        .ok_or(AppError::NoActiveCall)?;
+    Ok(session.start_recording(path, mode)?)
+}
+
+/// Stop recording a call (`None` = current call); returns the recording's path
+pub async fn handle_stop_recording(
+    handle: &ClientHandle,
+    call_id: Option<String>,
+) -> Result<String, AppError> {
+    let active = handle.active_calls.lock().await;
+    let call = match call_id {
+        Some(ref id) => active.get(id),
+        None => active.current(),
+    };
+    let session = call
+        .and_then(|c| c.webrtc_session.as_ref())
+        .ok_or(AppError::NoActiveCall)?;
+    Ok(session.stop_recording()?)
+}
+
+/// Send a WAV file into a call (`None` = current call) in place of the mic
+pub async fn handle_play_audio_into_call(
+    handle: &ClientHandle,
+    call_id: Option<String>,
+    path: &str,
+) -> Result<(), AppError> {
+    let active = handle.active_calls.lock().await;
+    let call = match call_id {
+        Some(ref id) => active.get(id),
+        None => active.current(),
+    };
+    let session = call
+        .and_then(|c| c.webrtc_session.as_ref())
+        .ok_or(AppError::NoActiveCall)?;
+    Ok(session.play_announcement(path)?)
+}
+
+/// Stop the file sent into a call (`None` = current call); returns whether one was playing
+pub async fn handle_stop_audio_into_call(
+    handle: &ClientHandle,
+    call_id: Option<String>,
+) -> Result<bool, AppError> {
+    let active = handle.active_calls.lock().await;
+    let call = match call_id {
+        Some(ref id) => active.get(id),
+        None => active.current(),
+    };
+    let session = call
+        .and_then(|c| c.webrtc_session.as_ref())
+        .ok_or(AppError::NoActiveCall)?;
+    Ok(session.stop_announcement())
+}
+
+/// Negotiated media parameters of a call (`None` = current call)
+pub async fn handle_get_media_info(
+    handle: &ClientHandle,
+    call_id: Option<String>,
+) -> Result<MediaInfo, AppError> {
+    let active = handle.active_calls.lock().await;
+    let call = match call_id {
+        Some(ref id) => active.get(id),
+        None => active.current(),
+    };
+    let session = call
+        .and_then(|c| c.webrtc_session.as_ref())
+        .ok_or(AppError::NoActiveCall)?;
+    session
+        .media_info()
+        .cloned()
+        .ok_or_else(|| AppError::Other("Media is not established yet".to_string()))
+}
+
+/// Change (or disable with 0) the OPTIONS keepalive interval of an account
+pub fn handle_set_keepalive_interval(handle: &ClientHandle, secs: u64) {
+    handle.keepalive_interval.send_replace(secs);
+    debug!(account = %handle.account_id, secs, "Keepalive interval updated");
+}
+
+/// Change (or disable with 0) the CRLF keepalive interval of an account.
+/// The REGISTER refresh cap chosen at registration is kept until the next one.
+pub fn handle_set_crlf_keepalive_interval(handle: &ClientHandle, secs: u64) {
+    handle.crlf_keepalive_interval.send_replace(secs);
+    debug!(account = %handle.account_id, secs, "CRLF keepalive interval updated");
+}
+
+/// `sip:user@server`, keeping the server's transport params so rsipstack
+/// picks the account's connection.
+fn user_uri(server: &Uri, user: &str) -> Uri {
+    Uri {
+        scheme: Some(rsip::Scheme::Sip),
+        auth: Some(rsip::Auth {
+            user: user.to_string(),
+            password: None,
+        }),
+        host_with_port: server.host_with_port.clone(),
+        params: server.params.clone(),
+        ..Default::default()
+    }
+}
+
+/// Start monitoring `extension` (busy lamp field): SUBSCRIBE to its `dialog`
+/// events and keep the subscription refreshed until `handle_unsubscribe_blf`
+/// or until the account is unregistered. State changes arrive as `sip://blf-state`.
+pub async fn handle_subscribe_blf(
+    handle: &ClientHandle,
+    extension: String,
+) -> rsipstack::Result<()> {
+    if handle.blf_subscriptions.contains_key(&extension) {
+        return Ok(());
+    }
+    let mut blf = subscription::Subscription::new(
+        handle.dialog_layer.endpoint.clone(),
+        handle.credential.clone(),
+        handle.contact.clone(),
+        user_uri(&handle.server, &extension),
+        blf::EVENT,
+        blf::ACCEPT,
+    );
+    let expires = blf.subscribe(subscription::SUBSCRIBE_EXPIRES).await?;
+    info!(account = %handle.account_id, extension = %extension, expires, "BLF subscribed");
+
+    // Child of the account token: unregistering ends (and unsubscribes) every BLF
+    let token = handle.cancel_token.child_token();
+    handle.blf_subscriptions.insert(extension, token.clone());
+    tokio::spawn(blf.run_refresh_loop(expires, token));
+    Ok(())
+}
+
+/// Stop monitoring `extension`; the subscription is ended with `Expires: 0`.
+pub async fn handle_unsubscribe_blf(
+    handle: &ClientHandle,
+    extension: &str,
+) -> rsipstack::Result<()> {
+    let (_, token) = handle
+        .blf_subscriptions
+        .remove(extension)
+        .ok_or_else(|| rsipstack::Error::Error(format!("No BLF subscription for {}", extension)))?;
+    token.cancel();
+    Ok(())
+}
+
+/// Send a pager-mode MESSAGE (RFC 3428) with a `text/plain` body to `target`,
+/// an extension on the account's server or a full SIP URI.
+///
+/// Succeeds once the far end accepts it (200/202); any other final response is an error.
+pub async fn handle_send_message(
+    handle: &ClientHandle,
+    target: String,
+    body: String,
+) -> rsipstack::Result<()> {
+    let target_uri = if target.starts_with("sip:") || target.starts_with("sips:") {
+        Uri::try_from(target.as_str())
+            .map_err(|e| rsipstack::Error::Error(format!("Invalid message target: {:?}", e)))?
+    } else {
+        user_uri(&handle.server, &target)
+    };
+
+    let endpoint = handle.dialog_layer.endpoint.clone();
+    let from = rsip::typed::From {
+        display_name: None,
+        uri: handle.contact.clone(),
+        params: vec![],
+    }
+    .with_tag(make_tag());
+    let to = rsip::typed::To {
+        display_name: None,
+        uri: target_uri.clone(),
+        params: vec![],
+    };
+    let via = endpoint.get_via(None, None)?;
+    let mut seq = 1;
+    let mut request =
+        endpoint.make_request(rsip::Method::Message, target_uri, via, from, to, seq, None);
+    request
+        .headers
+        .push(rsip::Header::ContentType("text/plain;charset=UTF-8".into()));
+    request
+        .headers
+        .push(rsip::headers::ContentLength::from(body.len() as u32).into());
+    request.body = body.into_bytes();
+
+    info!(account = %handle.account_id, target = %target, "Sending MESSAGE");
+
+    let key = TransactionKey::from_request(&request, TransactionRole::Client)?;
+    let mut tx = Transaction::new_client(key, request, endpoint, None);
+    tx.send().await?;
+
+    let mut auth_sent = false;
+    while let Some(msg) = tx.receive().await {
+        let rsip::SipMessage::Response(resp) = msg else {
+            continue;
+        };
+        match resp.status_code {
+            rsip::StatusCode::Unauthorized | rsip::StatusCode::ProxyAuthenticationRequired
+                if !auth_sent =>
+            {
+                seq += 1;
+                tx = handle_client_authenticate(seq, &tx, resp, &handle.credential).await?;
+                tx.send().await?;
+                auth_sent = true;
+            }
+            ref status if status.kind() == rsip::StatusCodeKind::Provisional => {}
+            ref status if status.kind() == rsip::StatusCodeKind::Successful => {
+                info!(target = %target, status = %status, "MESSAGE delivered");
+                return Ok(());
+            }
+            status => {
+                warn!(target = %target, status = %status, "MESSAGE rejected");
+                return Err(rsipstack::Error::Error(format!(
+                    "Message rejected: {}",
+                    status
+                )));
             }
         }
-        info!(call_id = %call.call_id, "Call hung up");
+    }
+    Err(rsipstack::Error::Error(
+        "No final response to MESSAGE".to_string(),
+    ))
+}
+
+/// Hang up a call: `call_id` selects a specific established leg (`CallNotFound`
+/// if there is none), `None` aborts a call being dialed or hangs up the current call
+pub async fn handle_hangup(handle: &ClientHandle, call_id: Option<String>) -> Result<(), AppError> {
+    let mut active = handle.active_calls.lock().await;
+
+    // A given call_id only ever ends that call: a stale one must not fall
+    // through to the pending or current call
+    if let Some(id) = call_id {
+        let call = remove_active_call(&handle.app_handle, &mut active, &id, "hangup")
+            .ok_or(AppError::CallNotFound(id))?;
+        drop(active);
+        return Ok(hang_up_call(&handle.active_call_tokens, call).await?);
+    }
+
+    // An outbound call still being set up takes priority over established calls:
+    // with a call on hold, hanging up while dialing must abort the new call only
+    if let Some((_, token)) = handle.active_call_tokens.remove("pending_outbound") {
+        info!("Canceling pending outbound call");
+        token.cancel();
+        return Ok(());
+    }
+
+    let current = active.current().map(|c| c.call_id.clone());
+    if let Some(call) =
+        current.and_then(|id| remove_active_call(&handle.app_handle, &mut active, &id, "hangup"))
+    {
+        drop(active);
+        hang_up_call(&handle.active_call_tokens, call).await?;
     } else {
         // No active call, but cancel any pending call tokens (e.g. during calling/ringing state)
         let token_count = handle.active_call_tokens.len();
@@ -543,81 +1355,824 @@ pub async fn handle_hangup(handle: &ClientHandle) -> rsipstack::Result<()> {
     Ok(())
 }
 
-/// Toggle mic mute for the active call
-pub async fn handle_toggle_mic_mute(handle: &ClientHandle) -> Result<bool, String> {
-    let active = handle.active_call.lock().await;
-    if let Some(ref call) = *active {
-        if let Some(ref session) = call.webrtc_session {
-            Ok(session.toggle_mic_mute())
+/// Remove a call from `active`, recording it in the call history as ended with
+/// `reason` and reporting `sip://conference-state` if it was taking part in a conference.
+pub(crate) fn remove_active_call(
+    app_handle: &AppHandle,
+    active: &mut ActiveCalls,
+    call_id: &str,
+    reason: &str,
+) -> Option<ActiveCall> {
+    let in_conference = active.conference_call_ids().iter().any(|id| id == call_id);
+    let call = active.remove(call_id)?;
+    call_history::record_call(app_handle, call.history.clone(), reason);
+    if in_conference {
+        let remaining = active.conference_call_ids();
+        let state = if remaining.is_empty() {
+            "ended"
         } else {
-            Err("No WebRTC session".to_string())
-        }
-    } else {
-        Err("No active call".to_string())
+            "updated"
+        };
+        emit_conference_state(app_handle, state, remaining);
     }
+    Some(call)
 }
 
-/// Set microphone noise reduction for the active call (if any)
-pub async fn handle_set_noise_reduce(handle: &ClientHandle, enabled: bool) {
-    let active = handle.active_call.lock().await;
-    if let Some(ref call) = *active {
-        if let Some(ref session) = call.webrtc_session {
-            session.set_noise_reduce(enabled);
-        }
+/// Tear down an established call that was already removed from `active_calls`.
+async fn hang_up_call(
+    active_call_tokens: &DashMap<String, CancellationToken>,
+    mut call: ActiveCall,
+) -> rsipstack::Result<()> {
+    info!(call_id = %call.call_id, "Hanging up call");
+
+    // Cancel the call token first to trigger cleanup
+    call.cancel_token.cancel();
+
+    // Stop audio
+    if let Some(ref mut session) = call.webrtc_session {
+        session.close().await;
     }
-}
 
-/// Set speaker noise reduction for the active call (if any)
-pub async fn handle_set_speaker_noise_reduce(handle: &ClientHandle, enabled: bool) {
-    let active = handle.active_call.lock().await;
-    if let Some(ref call) = *active {
-        if let Some(ref session) = call.webrtc_session {
-            session.set_speaker_noise_reduce(enabled);
+    // Remove from active_call_tokens
+    active_call_tokens.remove(&call.dialog_id());
+
+    match call.dialog {
+        // CANCEL while the INVITE has no final response yet, BYE once confirmed
+        rsipstack::dialog::dialog::Dialog::ClientInvite(d) => {
+            d.hangup().await.map_err(|e| {
+                error!(call_id = %call.call_id, error = ?e, "Failed to hang up");
+                rsipstack::Error::Error(format!("Failed to hang up: {:?}", e))
+            })?;
+        }
+        rsipstack::dialog::dialog::Dialog::ServerInvite(d) => {
+            d.bye().await.map_err(|e| {
+                error!(call_id = %call.call_id, error = ?e, "Failed to send BYE");
+                rsipstack::Error::Error(format!("Failed to send BYE: {:?}", e))
+            })?;
+        }
+        _ => {
+            debug!(call_id = %call.call_id, "Other dialog type, skipping BYE");
         }
     }
+    info!(call_id = %call.call_id, "Call hung up");
+    Ok(())
 }
 
-/// Toggle microphone noise reduction for the active call
-pub async fn handle_toggle_noise_reduce(handle: &ClientHandle) -> Result<bool, String> {
-    let active = handle.active_call.lock().await;
-    if let Some(ref call) = *active {
-        if let Some(ref session) = call.webrtc_session {
-            Ok(session.toggle_noise_reduce())
-        } else {
-            Err("No WebRTC session".to_string())
+/// Send a re-INVITE carrying `sdp` on an established INVITE dialog.
+async fn send_reinvite(
+    dialog: &rsipstack::dialog::dialog::Dialog,
+    sdp: String,
+) -> rsipstack::Result<Option<rsip::Response>> {
+    let headers = vec![rsip::typed::ContentType(rsip::typed::MediaType::Sdp(vec![])).into()];
+    match dialog {
+        rsipstack::dialog::dialog::Dialog::ClientInvite(d) => {
+            d.reinvite(Some(headers), Some(sdp.into_bytes())).await
         }
-    } else {
-        Err("No active call".to_string())
+        rsipstack::dialog::dialog::Dialog::ServerInvite(d) => {
+            d.reinvite(Some(headers), Some(sdp.into_bytes())).await
+        }
+        _ => Err(rsipstack::Error::Error(
+            "Invalid dialog type for re-INVITE".to_string(),
+        )),
     }
 }
 
-/// Toggle speaker mute for the active call
-pub async fn handle_toggle_speaker_mute(handle: &ClientHandle) -> Result<bool, String> {
-    let active = handle.active_call.lock().await;
-    if let Some(ref call) = *active {
-        if let Some(ref session) = call.webrtc_session {
-            Ok(session.toggle_speaker_mute())
-        } else {
-            Err("No WebRTC session".to_string())
-        }
-    } else {
-        Err("No active call".to_string())
-    }
+/// Emit a `sip://media-warning` when the hold music cannot be played; the
+/// hold then stays silent.
+fn emit_hold_music_warning(app_handle: &AppHandle, call_id: &str, error: &str) {
+    warn!(call_id = %call_id, error = %error, "Hold music unavailable, holding in silence");
+    let _ = app_handle.emit(
+        "sip://media-warning",
+        state::MediaWarningPayload {
+            call_id: Some(call_id.to_string()),
+            warning: "hold-music-unavailable".to_string(),
+            message: format!("Hold music unavailable, holding in silence: {}", error),
+        },
+    );
 }
 
-/// Answer an incoming call
-pub async fn handle_answer_call(
-    handle: &ClientHandle,
-    call_id: String,
-    input_device: Option<String>,
-    output_device: Option<String>,
-    global_cancel_token: CancellationToken,
-    noise_reduce: bool,
-    speaker_noise_reduce: bool,
-) -> rsipstack::Result<()> {
-    info!(call_id = %call_id, "Answering incoming call");
+/// Place `call_id` on hold (`hold = true`) or resume it, via re-INVITE.
+///
+/// The active-calls lock is only held while building the offer and while
+/// committing the result, never across the re-INVITE transaction itself.
+async fn hold_call(handle: &ClientHandle, call_id: &str, hold: bool) -> Result<(), AppError> {
+    let (dialog, offer) = {
+        let active = handle.active_calls.lock().await;
+        let call = active
+            .get(call_id)
+            .ok_or_else(|| AppError::CallNotFound(call_id.to_string()))?;
+        let session = call
+            .webrtc_session
+            .as_ref()
+            .ok_or_else(|| AppError::Other("No WebRTC session".to_string()))?;
+
+        if session.is_local_hold() == hold {
+            debug!(call_id = %call_id, hold, "Call already in requested hold state");
+            return Ok(());
+        }
+        (call.dialog.clone(), session.create_hold_offer(hold))
+    };
 
-    // Retrieve pending call
+    info!(call_id = %call_id, hold, "Sending hold re-INVITE");
+    let resp = send_reinvite(&dialog, offer.clone()).await?;
+
+    match resp {
+        Some(resp) if resp.status_code == rsip::StatusCode::OK => {
+            let mut active = handle.active_calls.lock().await;
+            let session = active
+                .get_mut(call_id)
+                .and_then(|call| call.webrtc_session.as_mut())
+                .ok_or_else(|| AppError::CallNotFound(call_id.to_string()))?;
+            session.set_local_hold(hold, offer);
+            if hold {
+                if let Err(e) = session.start_hold_music() {
+                    emit_hold_music_warning(&handle.app_handle, call_id, &e);
+                }
+            }
+        }
+        Some(resp) => {
+            warn!(call_id = %call_id, status = %resp.status_code, "Hold re-INVITE rejected");
+            return Err(AppError::Other(format!(
+                "re-INVITE rejected: {}",
+                resp.status_code
+            )));
+        }
+        None => {
+            return Err(AppError::Other(
+                "Dialog not confirmed, re-INVITE not sent".to_string(),
+            ));
+        }
+    }
+
+    let _ = handle.app_handle.emit(
+        "sip://call-state",
+        state::CallStatePayload {
+            state: if hold { "held" } else { "connected" }.to_string(),
+            call_id: Some(call_id.to_string()),
+            reason: None,
+        },
+    );
+
+    info!(call_id = %call_id, hold, "Call hold state changed");
+    Ok(())
+}
+
+/// Place the active call on hold (`hold = true`) or resume it.
+async fn set_call_hold(handle: &ClientHandle, hold: bool) -> Result<(), AppError> {
+    let call_id = {
+        let active = handle.active_calls.lock().await;
+        let call = active.current().ok_or(AppError::NoActiveCall)?;
+        call.call_id.clone()
+    };
+    hold_call(handle, &call_id, hold).await
+}
+
+/// Put the active call on hold (re-INVITE with a=sendonly)
+pub async fn handle_hold(handle: &ClientHandle) -> Result<(), AppError> {
+    set_call_hold(handle, true).await
+}
+
+/// Resume the held active call (re-INVITE with a=sendrecv)
+pub async fn handle_resume(handle: &ClientHandle) -> Result<(), AppError> {
+    set_call_hold(handle, false).await
+}
+
+/// Make `call_id` the current call: hold every other call, then resume it.
+pub async fn handle_switch_call(handle: &ClientHandle, call_id: String) -> Result<(), AppError> {
+    let other_ids = {
+        let active = handle.active_calls.lock().await;
+        if !active.contains(&call_id) {
+            return Err(AppError::CallNotFound(call_id));
+        }
+        active.call_ids()
+    };
+
+    info!(call_id = %call_id, "Switching to call");
+    for other_id in other_ids.iter().filter(|id| **id != call_id) {
+        match hold_call(handle, other_id, true).await {
+            // A call that ended meanwhile needs no hold.
+            Ok(()) | Err(AppError::CallNotFound(_)) => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    hold_call(handle, &call_id, false).await?;
+    if !handle.active_calls.lock().await.set_current(&call_id) {
+        return Err(AppError::CallNotFound(call_id));
+    }
+    Ok(())
+}
+
+fn emit_conference_state(app_handle: &AppHandle, state: &str, call_ids: Vec<String>) {
+    let _ = app_handle.emit(
+        "sip://conference-state",
+        state::ConferenceStatePayload {
+            state: state.to_string(),
+            call_ids,
+        },
+    );
+}
+
+/// Merge established calls into a local conference.
+///
+/// All participants are taken off hold; each remote party then hears us and
+/// the other parties, and we hear everyone.
+pub async fn handle_start_conference(
+    handle: &ClientHandle,
+    call_ids: Vec<String>,
+) -> Result<(), AppError> {
+    let mut call_ids = call_ids;
+    call_ids.sort();
+    call_ids.dedup();
+    if call_ids.len() < 2 {
+        return Err("A conference needs at least two calls".into());
+    }
+
+    {
+        let active = handle.active_calls.lock().await;
+        if !active.conference_call_ids().is_empty() {
+            return Err("A conference is already active".into());
+        }
+        if let Some(missing) = call_ids.iter().find(|id| !active.contains(id)) {
+            return Err(AppError::CallNotFound(missing.clone()));
+        }
+    }
+
+    info!(call_ids = ?call_ids, "Starting conference");
+    for id in &call_ids {
+        hold_call(handle, id, false).await?;
+    }
+
+    let mut active = handle.active_calls.lock().await;
+    if !active.conference_call_ids().is_empty() {
+        return Err("A conference is already active".into());
+    }
+    if let Some(missing) = call_ids.iter().find(|id| !active.contains(id)) {
+        return Err(AppError::CallNotFound(missing.clone()));
+    }
+    active.start_conference(&call_ids);
+    drop(active);
+
+    emit_conference_state(&handle.app_handle, "started", call_ids);
+    Ok(())
+}
+
+/// Split the conference back into separate calls.
+///
+/// The current call stays connected; the other participants are put on hold.
+pub async fn handle_end_conference(handle: &ClientHandle) -> Result<(), AppError> {
+    let (call_ids, current) = {
+        let mut active = handle.active_calls.lock().await;
+        let call_ids = active.end_conference();
+        (call_ids, active.current().map(|c| c.call_id.clone()))
+    };
+    if call_ids.is_empty() {
+        return Err("No active conference".into());
+    }
+    info!(call_ids = ?call_ids, "Ending conference");
+
+    for id in &call_ids {
+        if current.as_deref() == Some(id.as_str()) {
+            continue;
+        }
+        if let Err(e) = hold_call(handle, id, true).await {
+            warn!(call_id = %id, error = ?e, "Failed to hold call after conference");
+        }
+    }
+
+    emit_conference_state(&handle.app_handle, "ended", call_ids);
+    Ok(())
+}
+
+/// Call waiting: hold the current call before another one is answered.
+///
+/// A failed hold is logged but does not block answering the new call.
+async fn hold_current_for_waiting_call(handle: &ClientHandle, call_id: &str) {
+    let current = {
+        let active = handle.active_calls.lock().await;
+        active.current().map(|c| c.call_id.clone())
+    };
+    if let Some(current) = current {
+        info!(call_id = %call_id, held_call_id = %current, "Holding current call to answer waiting call");
+        if let Err(e) = hold_call(handle, &current, true).await {
+            warn!(call_id = %current, error = ?e, "Failed to hold current call");
+        }
+    }
+}
+
+/// Blind-transfer the active call to `target` using REFER.
+///
+/// `target` may be a full SIP URI (`sip:1002@pbx.example.com`) or just an
+/// extension, which is resolved against the registrar domain. Progress is
+/// reported through `sip://transfer-state` as NOTIFY/sipfrag messages arrive
+/// (see `transfer::process_notify`).
+pub async fn handle_transfer(handle: &ClientHandle, target: String) -> Result<(), AppError> {
+    let refer_to = if target.starts_with("sip:") || target.starts_with("sips:") {
+        Uri::try_from(target.as_str())
+            .map_err(|e| AppError::Other(format!("Invalid transfer target: {:?}", e)))?
+    } else {
+        Uri {
+            scheme: Some(rsip::Scheme::Sip),
+            auth: Some(rsip::Auth {
+                user: target.clone(),
+                password: None,
+            }),
+            host_with_port: handle.server.host_with_port.clone(),
+            ..Default::default()
+        }
+    };
+
+    let (call_id, dialog) = {
+        let active = handle.active_calls.lock().await;
+        let call = active.current().ok_or(AppError::NoActiveCall)?;
+        (call.call_id.clone(), call.dialog.clone())
+    };
+
+    info!(call_id = %call_id, refer_to = %refer_to, "Sending REFER for blind transfer");
+
+    let resp = match &dialog {
+        rsipstack::dialog::dialog::Dialog::ClientInvite(d) => d.refer(refer_to, None, None).await,
+        rsipstack::dialog::dialog::Dialog::ServerInvite(d) => d.refer(refer_to, None, None).await,
+        _ => Err(rsipstack::Error::Error(
+            "Invalid dialog type for transfer".to_string(),
+        )),
+    };
+
+    let call_id = Some(call_id);
+    match resp {
+        Ok(Some(resp)) if resp.status_code.kind() == rsip::StatusCodeKind::Successful => {
+            info!(call_id = ?call_id, status = %resp.status_code, "REFER accepted");
+            transfer::emit_transfer_state(&handle.app_handle, "trying", call_id, None);
+            Ok(())
+        }
+        Ok(Some(resp)) => {
+            warn!(call_id = ?call_id, status = %resp.status_code, "REFER rejected");
+            let reason = resp.status_code.to_string();
+            transfer::emit_transfer_state(&handle.app_handle, "failed", call_id, Some(reason.clone()));
+            Err(AppError::Other(format!("Transfer rejected: {}", reason)))
+        }
+        Ok(None) => {
+            transfer::emit_transfer_state(&handle.app_handle, "failed", call_id, None);
+            Err("Dialog not confirmed, REFER not sent".into())
+        }
+        Err(e) => {
+            transfer::emit_transfer_state(&handle.app_handle, "failed", call_id, Some(e.to_string()));
+            Err(e.into())
+        }
+    }
+}
+
+/// Attended transfer: connect the remote party of call `a_call_id` to the
+/// remote party of the consultation call `b_call_id`.
+///
+/// Sends REFER on A with `Refer-To: <B-remote?Replaces=B-dialog>`. A's remote
+/// then INVITEs B's remote replacing our B leg. When the NOTIFY on A reports
+/// success both of our legs are released (see `transfer::process_notify`).
+pub async fn handle_attended_transfer(
+    handle: &ClientHandle,
+    a_call_id: String,
+    b_call_id: String,
+) -> Result<(), AppError> {
+    if a_call_id == b_call_id {
+        return Err("Attended transfer needs two different calls".into());
+    }
+
+    let mut active = handle.active_calls.lock().await;
+
+    let (consult_id, consult_target) = {
+        let b = active
+            .get(&b_call_id)
+            .ok_or_else(|| AppError::CallNotFound(b_call_id.clone()))?;
+        match &b.dialog {
+            rsipstack::dialog::dialog::Dialog::ClientInvite(d) => (d.id(), d.snapshot().remote_uri),
+            rsipstack::dialog::dialog::Dialog::ServerInvite(d) => (d.id(), d.snapshot().remote_uri),
+            _ => return Err("Invalid dialog type for transfer".into()),
+        }
+    };
+
+    let a = active
+        .get_mut(&a_call_id)
+        .ok_or_else(|| AppError::CallNotFound(a_call_id.clone()))?;
+    // Release B together with A once the transfer succeeds. Set before the REFER
+    // goes out, since its NOTIFY may arrive before the response is handled here.
+    a.transfer_peer = Some(b_call_id.clone());
+    let dialog = a.dialog.clone();
+    drop(active);
+
+    let refer_to = transfer::replaces_refer_to(&consult_target, &consult_id);
+    info!(a_call_id = %a_call_id, b_call_id = %b_call_id, refer_to = %refer_to, "Sending REFER for attended transfer");
+
+    let headers = vec![rsip::Header::Other("Refer-To".into(), refer_to)];
+    let resp = match &dialog {
+        rsipstack::dialog::dialog::Dialog::ClientInvite(d) => {
+            d.request(rsip::Method::Refer, Some(headers), None).await
+        }
+        rsipstack::dialog::dialog::Dialog::ServerInvite(d) => {
+            d.request(rsip::Method::Refer, Some(headers), None).await
+        }
+        _ => Err(rsipstack::Error::Error(
+            "Invalid dialog type for transfer".to_string(),
+        )),
+    };
+
+    let call_id = Some(a_call_id.clone());
+    let result = match resp {
+        Ok(Some(resp)) if resp.status_code.kind() == rsip::StatusCodeKind::Successful => {
+            info!(call_id = %a_call_id, status = %resp.status_code, "REFER accepted");
+            transfer::emit_transfer_state(&handle.app_handle, "trying", call_id, None);
+            Ok(())
+        }
+        Ok(Some(resp)) => {
+            warn!(call_id = %a_call_id, status = %resp.status_code, "REFER rejected");
+            let reason = resp.status_code.to_string();
+            transfer::emit_transfer_state(&handle.app_handle, "failed", call_id, Some(reason.clone()));
+            Err(AppError::Other(format!("Transfer rejected: {}", reason)))
+        }
+        Ok(None) => {
+            transfer::emit_transfer_state(&handle.app_handle, "failed", call_id, None);
+            Err("Dialog not confirmed, REFER not sent".into())
+        }
+        Err(e) => {
+            transfer::emit_transfer_state(&handle.app_handle, "failed", call_id, Some(e.to_string()));
+            Err(e.into())
+        }
+    };
+
+    if result.is_err() {
+        if let Some(a) = handle.active_calls.lock().await.get_mut(&a_call_id) {
+            a.transfer_peer = None;
+        }
+    }
+    result
+}
+
+/// Toggle mic mute for the active call
+pub async fn handle_toggle_mic_mute(handle: &ClientHandle) -> Result<bool, AppError> {
+    let active = handle.active_calls.lock().await;
+    if let Some(call) = active.current() {
+        if let Some(ref session) = call.webrtc_session {
+            let muted = session.toggle_mic_mute();
+            emit_mute_state(&handle.app_handle, call, session);
+            Ok(muted)
+        } else {
+            Err("No WebRTC session".into())
+        }
+    } else {
+        Err(AppError::NoActiveCall)
+    }
+}
+
+/// RTP/RTCP statistics for the active call
+pub async fn handle_get_call_stats(handle: &ClientHandle) -> Result<CallStats, AppError> {
+    let active = handle.active_calls.lock().await;
+    if let Some(call) = active.current() {
+        if let Some(ref session) = call.webrtc_session {
+            Ok(session.get_stats().await)
+        } else {
+            Ok(CallStats::default())
+        }
+    } else {
+        Err(AppError::NoActiveCall)
+    }
+}
+
+/// Set microphone noise reduction for the active call (if any)
+pub async fn handle_set_noise_reduce(handle: &ClientHandle, enabled: bool) {
+    let active = handle.active_calls.lock().await;
+    if let Some(call) = active.current() {
+        if let Some(ref session) = call.webrtc_session {
+            session.set_noise_reduce(enabled);
+        }
+    }
+}
+
+/// Set speaker noise reduction for the active call (if any)
+pub async fn handle_set_speaker_noise_reduce(handle: &ClientHandle, enabled: bool) {
+    let active = handle.active_calls.lock().await;
+    if let Some(call) = active.current() {
+        if let Some(ref session) = call.webrtc_session {
+            session.set_speaker_noise_reduce(enabled);
+        }
+    }
+}
+
+/// Set the noise reduction strength for the active call (if any)
+pub async fn handle_set_noise_reduce_strength(handle: &ClientHandle, strength: f32) {
+    let active = handle.active_calls.lock().await;
+    if let Some(call) = active.current() {
+        if let Some(ref session) = call.webrtc_session {
+            session.set_noise_reduce_strength(strength);
+        }
+    }
+}
+
+/// Set the playback jitter buffer depth for all calls
+pub async fn handle_set_jitter_buffer_ms(handle: &ClientHandle, ms: u32) {
+    let active = handle.active_calls.lock().await;
+    for call_id in active.call_ids() {
+        if let Some(session) = active.get(&call_id).and_then(|c| c.webrtc_session.as_ref()) {
+            session.set_jitter_buffer_ms(ms);
+        }
+    }
+}
+
+/// Set the audio ring buffer size for all calls (applied when their audio is reopened)
+pub async fn handle_set_audio_buffer_ms(handle: &ClientHandle, ms: u32) {
+    let active = handle.active_calls.lock().await;
+    for call_id in active.call_ids() {
+        if let Some(session) = active.get(&call_id).and_then(|c| c.webrtc_session.as_ref()) {
+            session.set_audio_buffer_ms(ms);
+        }
+    }
+}
+
+/// Set the software speaker volume for all calls
+pub async fn handle_set_speaker_volume(handle: &ClientHandle, volume: f32) {
+    let active = handle.active_calls.lock().await;
+    for call_id in active.call_ids() {
+        if let Some(session) = active.get(&call_id).and_then(|c| c.webrtc_session.as_ref()) {
+            session.set_speaker_volume(volume);
+        }
+    }
+}
+
+/// Set the software microphone gain for all calls
+pub async fn handle_set_mic_gain(handle: &ClientHandle, gain: f32) {
+    let active = handle.active_calls.lock().await;
+    for call_id in active.call_ids() {
+        if let Some(session) = active.get(&call_id).and_then(|c| c.webrtc_session.as_ref()) {
+            session.set_mic_gain(gain);
+        }
+    }
+}
+
+/// Set the music on hold file for all calls; calls already on hold switch at once
+pub async fn handle_set_hold_music_path(handle: &ClientHandle, path: Option<String>) {
+    let mut active = handle.active_calls.lock().await;
+    for call_id in active.call_ids() {
+        let Some(session) = active
+            .get_mut(&call_id)
+            .and_then(|c| c.webrtc_session.as_mut())
+        else {
+            continue;
+        };
+        if let Err(e) = session.set_hold_music_path(path.clone()) {
+            emit_hold_music_warning(&handle.app_handle, &call_id, &e);
+        }
+    }
+}
+
+/// Enable or disable packet loss concealment for all calls
+pub async fn handle_set_plc(handle: &ClientHandle, enabled: bool) {
+    let active = handle.active_calls.lock().await;
+    for call_id in active.call_ids() {
+        if let Some(session) = active.get(&call_id).and_then(|c| c.webrtc_session.as_ref()) {
+            session.set_plc(enabled);
+        }
+    }
+}
+
+/// Enable or disable comfort noise for all calls
+pub async fn handle_set_comfort_noise(handle: &ClientHandle, enabled: bool) {
+    let active = handle.active_calls.lock().await;
+    for call_id in active.call_ids() {
+        if let Some(session) = active.get(&call_id).and_then(|c| c.webrtc_session.as_ref()) {
+            session.set_comfort_noise(enabled);
+        }
+    }
+}
+
+/// Apply Opus encoder settings to every active call
+pub async fn handle_set_opus_config(handle: &ClientHandle, config: &OpusConfig) {
+    let active = handle.active_calls.lock().await;
+    for call_id in active.call_ids() {
+        if let Some(session) = active.get(&call_id).and_then(|c| c.webrtc_session.as_ref()) {
+            session.set_opus_config(config.clone());
+        }
+    }
+}
+
+/// Apply voice activity detection settings to every active call
+pub async fn handle_set_vad(handle: &ClientHandle, config: VadConfig) {
+    let active = handle.active_calls.lock().await;
+    for call_id in active.call_ids() {
+        if let Some(session) = active.get(&call_id).and_then(|c| c.webrtc_session.as_ref()) {
+            session.set_vad(config);
+        }
+    }
+}
+
+/// Move the audio capture of every active call to the input device `device_id`
+pub async fn handle_set_input_device(
+    handle: &ClientHandle,
+    device_id: &str,
+) -> Result<(), AppError> {
+    let mut active = handle.active_calls.lock().await;
+    for call_id in active.call_ids() {
+        if let Some(session) = active
+            .get_mut(&call_id)
+            .and_then(|c| c.webrtc_session.as_mut())
+        {
+            session.switch_input_device(Some(device_id))?;
+        }
+    }
+    Ok(())
+}
+
+/// Move the audio playback of every active call to the output device `device_id`
+pub async fn handle_set_output_device(
+    handle: &ClientHandle,
+    device_id: &str,
+) -> Result<(), AppError> {
+    let mut active = handle.active_calls.lock().await;
+    for call_id in active.call_ids() {
+        if let Some(session) = active
+            .get_mut(&call_id)
+            .and_then(|c| c.webrtc_session.as_mut())
+        {
+            session.switch_output_device(Some(device_id))?;
+        }
+    }
+    Ok(())
+}
+
+/// Move the audio of every active call in `direction` to the system default
+/// device, returning the calls moved
+pub async fn handle_switch_to_default_device(
+    handle: &ClientHandle,
+    direction: AudioDirection,
+) -> Result<Vec<String>, AppError> {
+    let mut active = handle.active_calls.lock().await;
+    let mut moved = Vec::new();
+    for call_id in active.call_ids() {
+        if let Some(session) = active
+            .get_mut(&call_id)
+            .and_then(|c| c.webrtc_session.as_mut())
+        {
+            match direction {
+                AudioDirection::Mic => session.switch_input_device(None)?,
+                AudioDirection::Speaker => session.switch_output_device(None)?,
+            }
+            moved.push(call_id);
+        }
+    }
+    Ok(moved)
+}
+
+/// Set acoustic echo cancellation for the active call (if any)
+pub async fn handle_set_echo_cancel(handle: &ClientHandle, enabled: bool) {
+    let active = handle.active_calls.lock().await;
+    if let Some(call) = active.current() {
+        if let Some(ref session) = call.webrtc_session {
+            session.set_echo_cancel(enabled);
+        }
+    }
+}
+
+/// Toggle microphone noise reduction for the active call
+pub async fn handle_toggle_noise_reduce(handle: &ClientHandle) -> Result<bool, AppError> {
+    let active = handle.active_calls.lock().await;
+    if let Some(call) = active.current() {
+        if let Some(ref session) = call.webrtc_session {
+            Ok(session.toggle_noise_reduce())
+        } else {
+            Err("No WebRTC session".into())
+        }
+    } else {
+        Err(AppError::NoActiveCall)
+    }
+}
+
+/// Mute the active call's microphone, or unmute it
+pub async fn handle_set_mic_mute(handle: &ClientHandle, muted: bool) -> Result<(), AppError> {
+    let active = handle.active_calls.lock().await;
+    if let Some(call) = active.current() {
+        if let Some(ref session) = call.webrtc_session {
+            session.set_mic_mute(muted);
+            emit_mute_state(&handle.app_handle, call, session);
+            Ok(())
+        } else {
+            Err("No WebRTC session".into())
+        }
+    } else {
+        Err(AppError::NoActiveCall)
+    }
+}
+
+/// Mute the active call's speaker, or unmute it
+pub async fn handle_set_speaker_mute(handle: &ClientHandle, muted: bool) -> Result<(), AppError> {
+    let active = handle.active_calls.lock().await;
+    if let Some(call) = active.current() {
+        if let Some(ref session) = call.webrtc_session {
+            session.set_speaker_mute(muted);
+            emit_mute_state(&handle.app_handle, call, session);
+            Ok(())
+        } else {
+            Err("No WebRTC session".into())
+        }
+    } else {
+        Err(AppError::NoActiveCall)
+    }
+}
+
+fn mute_state(call: &ActiveCall, session: &WebRtcSession) -> state::MuteState {
+    let (mic_muted, speaker_muted) = session.mute_state();
+    state::MuteState {
+        call_id: call.call_id.clone(),
+        mic_muted,
+        speaker_muted,
+    }
+}
+
+/// Tell every window the call's mute state after it changed
+fn emit_mute_state(app_handle: &AppHandle, call: &ActiveCall, session: &WebRtcSession) {
+    let _ = app_handle.emit("sip://mute-state", mute_state(call, session));
+}
+
+/// Mic and speaker mute state of the active call
+pub async fn handle_get_mute_state(handle: &ClientHandle) -> Result<state::MuteState, AppError> {
+    let active = handle.active_calls.lock().await;
+    if let Some(call) = active.current() {
+        if let Some(ref session) = call.webrtc_session {
+            Ok(mute_state(call, session))
+        } else {
+            Err("No WebRTC session".into())
+        }
+    } else {
+        Err(AppError::NoActiveCall)
+    }
+}
+
+/// Toggle speaker mute for the active call
+pub async fn handle_toggle_speaker_mute(handle: &ClientHandle) -> Result<bool, AppError> {
+    let active = handle.active_calls.lock().await;
+    if let Some(call) = active.current() {
+        if let Some(ref session) = call.webrtc_session {
+            let muted = session.toggle_speaker_mute();
+            emit_mute_state(&handle.app_handle, call, session);
+            Ok(muted)
+        } else {
+            Err("No WebRTC session".into())
+        }
+    } else {
+        Err(AppError::NoActiveCall)
+    }
+}
+
+/// Stop the incoming-call ringtone, if it is playing
+pub(crate) fn stop_ringtone(app_handle: &AppHandle) {
+    app_handle.state::<SipAppState>().ringtone.stop();
+}
+
+/// Stop the ringtone once an unanswered incoming call has gone, unless another
+/// incoming call is still ringing on any account.
+pub(crate) async fn stop_ringtone_if_idle(app_handle: &AppHandle) {
+    let state = app_handle.state::<SipAppState>();
+    let handles: Vec<_> = state.handles.lock().await.values().cloned().collect();
+    for handle in handles {
+        if !handle.pending_incoming.lock().await.is_empty() {
+            return;
+        }
+    }
+    state.ringtone.stop();
+}
+
+/// Report an incoming call that stopped ringing before it was answered, with
+/// `sip://missed-call` and a call history entry.
+pub(crate) fn report_missed_call(app_handle: &AppHandle, pending: PendingCall, reason: &str) {
+    info!(call_id = %pending.history.call_id, caller = %pending.history.remote_party, reason = %reason, "Missed incoming call");
+    let _ = app_handle.emit(
+        "sip://missed-call",
+        state::MissedCallPayload {
+            account_id: pending.history.account_id.clone(),
+            call_id: pending.history.call_id.clone(),
+            caller: pending.history.remote_party.clone(),
+            reason: reason.to_string(),
+            timestamp: chrono::Utc::now().timestamp_millis(),
+        },
+    );
+    call_history::record_call(app_handle, pending.history, reason);
+}
+
+/// Answer an incoming call
+///
+/// `preferred_codec` is answered with when the offer contains it, whatever its
+/// place in the offer; otherwise the first supported codec of the offer is used.
+pub async fn handle_answer_call(
+    handle: &ClientHandle,
+    call_id: String,
+    global_cancel_token: CancellationToken,
+    media: MediaConfig,
+    preferred_codec: Option<CodecType>,
+    listen_only: bool,
+) -> rsipstack::Result<()> {
+    info!(account = %handle.account_id, call_id = %call_id, listen_only, "Answering incoming call");
+    let media = MediaConfig {
+        listen_only,
+        ..media
+    };
+
+    // Retrieve pending call
     let pending_call = {
         let mut pending = handle.pending_incoming.lock().await;
         pending.remove(&call_id)
@@ -627,25 +2182,49 @@ pub async fn handle_answer_call(
         rsipstack::Error::Error(format!("No pending call found for call_id: {}", call_id))
     })?;
 
+    stop_ringtone(&handle.app_handle);
+
+    hold_current_for_waiting_call(handle, &call_id).await;
+
+    // Move the preferred codec to the front of the offer and answer with it alone
+    let preferred = preferred_codec.and_then(|codec| {
+        let offer = prefer_offered_codec(&pending_call.sdp_offer, codec);
+        if offer.is_none() {
+            info!(call_id = %call_id, codec = ?codec, "Preferred codec not offered, using the offer's order");
+        }
+        offer.map(|offer| (codec, offer))
+    });
+    let (sdp_offer, media) = match preferred {
+        Some((codec, offer)) => (
+            offer,
+            MediaConfig {
+                force_codec: Some(codec),
+                ..media
+            },
+        ),
+        None => (pending_call.sdp_offer.clone(), media),
+    };
+
     // Create inbound WebRTC session with RTP+ICE (automatic STUN)
-    let (mut webrtc_session, sdp_answer) = WebRtcSession::new_inbound(
-        &pending_call.sdp_offer,
-        input_device.as_deref(),
-        output_device.as_deref(),
-    )
-    .await
-    .map_err(|e| rsipstack::Error::Error(format!("Failed to create WebRTC session: {}", e)))?;
+    let (mut webrtc_session, sdp_answer) =
+        WebRtcSession::new_inbound(&sdp_offer, &media)
+            .await
+            .map_err(|e| rsipstack::Error::Error(format!("Failed to create WebRTC session: {}", e)))?;
 
     info!(call_id = %call_id, "WebRTC session created, starting audio capture before 200 OK");
-
-    // Apply noise reduction settings before capture starts
-    webrtc_session.set_noise_reduce(noise_reduce);
-    webrtc_session.set_speaker_noise_reduce(speaker_noise_reduce);
+    webrtc_session.forward_audio_levels(handle.app_handle.clone(), call_id.clone());
+    webrtc_session.forward_received_dtmf(handle.app_handle.clone(), call_id.clone());
+    webrtc_session.forward_audio_errors(handle.app_handle.clone(), call_id.clone());
+    webrtc_session.forward_audio_fallbacks(handle.app_handle.clone(), call_id.clone());
+    webrtc_session.forward_playback_finished(handle.app_handle.clone(), call_id.clone());
+    webrtc_session.watch_one_way_audio(handle.app_handle.clone(), call_id.clone());
+    webrtc_session.watch_rtp_latching(handle.app_handle.clone(), call_id.clone());
+    watch_audio_devices(handle, &call_id, &mut webrtc_session);
 
     // Start audio capture BEFORE sending 200 OK to ensure we send RTP first
     // This allows NAT to create a mapping before PBX starts sending
     webrtc_session
-        .start_inbound_media_early(&pending_call.sdp_offer)
+        .start_inbound_media_early(&sdp_offer)
         .await
         .map_err(|e| rsipstack::Error::Error(format!("Failed to start audio capture: {}", e)))?;
 
@@ -655,6 +2234,7 @@ pub async fn handle_answer_call(
     let PendingCall {
         dialog,
         sdp_offer: _,
+        history,
     } = pending_call;
 
     // Accept the dialog with SDP answer
@@ -684,26 +2264,35 @@ pub async fn handle_answer_call(
 
             // Store active call
             {
-                let mut active = handle.active_call.lock().await;
-                *active = Some(ActiveCall {
+                let mut active = handle.active_calls.lock().await;
+                active.insert(ActiveCall {
                     call_id: call_id.clone(),
                     dialog: rsipstack::dialog::dialog::Dialog::ServerInvite(d),
                     webrtc_session: None, // Will be set after playback starts
-                    cancel_token: call_cancel_token,
+                    cancel_token: call_cancel_token.clone(),
+                    transfer_peer: None,
+                    connected_at: std::time::Instant::now(), // Reset once playback starts
+                    history,
                 });
             }
 
             // Start playback (audio capture already started before 200 OK)
             webrtc_session
-                .start_inbound_playback(&pending_call.sdp_offer, output_device.as_deref())
+                .start_inbound_playback(&sdp_offer, media.output_device.as_deref())
                 .await
                 .map_err(|e| rsipstack::Error::Error(format!("Failed to start playback: {}", e)))?;
 
+            warn_if_relayed(handle, &call_id, &webrtc_session).await;
+            webrtc_session.emit_media_info(&handle.app_handle, &call_id);
+
             // Update active call with WebRTC session
+            let connected_at = std::time::Instant::now();
             {
-                let mut active = handle.active_call.lock().await;
-                if let Some(ref mut call) = *active {
+                let mut active = handle.active_calls.lock().await;
+                if let Some(call) = active.get_mut(&call_id) {
+                    call.history.answered(webrtc_session.codec_name());
                     call.webrtc_session = Some(webrtc_session);
+                    call.connected_at = connected_at;
                 }
             }
 
@@ -716,6 +2305,26 @@ pub async fn handle_answer_call(
                     reason: None,
                 },
             );
+            spawn_call_duration_timer(
+                handle.app_handle.clone(),
+                call_id.clone(),
+                connected_at,
+                call_cancel_token.clone(),
+            );
+            spawn_media_watchdog(
+                handle,
+                call_id.clone(),
+                media.media_timeout_secs,
+                call_cancel_token.clone(),
+            );
+            spawn_call_stats_emitter(handle, call_id.clone(), call_cancel_token.clone());
+            spawn_adaptive_bitrate(handle, call_id.clone(), call_cancel_token.clone());
+            spawn_audio_buffer_monitor(
+                handle,
+                call_id.clone(),
+                media.audio_buffer_ms,
+                call_cancel_token,
+            );
 
             info!(call_id = %call_id, "Incoming call answered successfully");
             Ok(())
@@ -744,12 +2353,16 @@ pub async fn handle_reject_call(
         rsipstack::Error::Error(format!("No pending call found for call_id: {}", call_id))
     })?;
 
+    stop_ringtone_if_idle(&handle.app_handle).await;
+
     // Determine rejection status code
     let status = match reason_code {
         Some(code) => rsip::StatusCode::try_from(code).unwrap_or(rsip::StatusCode::BusyHere),
         None => rsip::StatusCode::BusyHere,
     };
 
+    call_history::record_call(&handle.app_handle, pending_call.history, "rejected");
+
     // Reject the dialog
     match pending_call.dialog {
         rsipstack::dialog::dialog::Dialog::ServerInvite(d) => {
@@ -778,24 +2391,188 @@ pub async fn handle_reject_call(
     }
 }
 
-/// Send DTMF digit during active call
-pub async fn handle_send_dtmf(handle: &ClientHandle, digit: String) -> Result<(), String> {
+/// Send DTMF digit during active call as an RFC 4733 telephone-event, as a
+/// SIP INFO request, or as audible tones in the call audio, depending on `mode`.
+pub async fn handle_send_dtmf(
+    handle: &ClientHandle,
+    digit: String,
+    mode: state::DtmfMode,
+    params: state::DtmfParams,
+) -> Result<(), AppError> {
     let digit_char = digit
         .chars()
         .next()
         .ok_or("DTMF digit must be a single character")?;
 
     // Check if there's an active call
-    let active = handle.active_call.lock().await;
-    if let Some(call) = active.as_ref() {
-        if let Some(session) = call.webrtc_session.as_ref() {
-            info!(digit = %digit_char, call_id = %call.call_id, "Sending DTMF digit");
-            session.send_dtmf(digit_char).await
-        } else {
-            Err("No active WebRTC session".to_string())
+    let call = {
+        let active = handle.active_calls.lock().await;
+        CallDtmf::new(active.current().ok_or(AppError::NoActiveCall)?)
+    };
+    send_call_dtmf(&call, digit_char, mode, params).await
+}
+
+/// Silence between digits of a DTMF string unless the caller asks otherwise
+pub const DEFAULT_DTMF_GAP_MS: u64 = 80;
+
+/// Send a string of DTMF digits (e.g. a conference PIN) on the current call,
+/// `gap_ms` apart. Characters that are not DTMF digits are skipped. Stops with
+/// an error if the call ends mid-sequence.
+pub async fn handle_send_dtmf_string(
+    handle: &ClientHandle,
+    digits: String,
+    mode: state::DtmfMode,
+    params: state::DtmfParams,
+    gap_ms: u64,
+) -> Result<(), AppError> {
+    let (call_id, cancel_token) = {
+        let active = handle.active_calls.lock().await;
+        let call = active.current().ok_or(AppError::NoActiveCall)?;
+        (call.call_id.clone(), call.cancel_token.clone())
+    };
+    info!(call_id = %call_id, count = digits.chars().count(), "Sending DTMF string");
+
+    let mut first = true;
+    for digit in digits.chars() {
+        if !matches!(digit, '0'..='9' | '*' | '#' | 'A'..='D' | 'a'..='d') {
+            warn!(digit = ?digit, "Skipping invalid DTMF character");
+            continue;
         }
-    } else {
-        Err("No active call".to_string())
+        if !first {
+            tokio::select! {
+                _ = tokio::time::sleep(std::time::Duration::from_millis(gap_ms)) => {}
+                _ = cancel_token.cancelled() => {}
+            }
+        }
+        first = false;
+        if cancel_token.is_cancelled() {
+            info!(call_id = %call_id, "Call ended, DTMF string aborted");
+            return Err(AppError::CallNotFound(call_id));
+        }
+        // The lock is only taken to look the call up again, so hangup and hold
+        // are not blocked while a digit is being sent
+        let call = {
+            let active = handle.active_calls.lock().await;
+            let call = active
+                .get(&call_id)
+                .ok_or_else(|| AppError::CallNotFound(call_id.clone()))?;
+            CallDtmf::new(call)
+        };
+        send_call_dtmf(&call, digit, mode, params).await?;
+    }
+    Ok(())
+}
+
+/// The DTMF mode actually used on a call when `mode` is configured.
+fn effective_dtmf_mode(
+    mode: state::DtmfMode,
+    supports_telephone_event: bool,
+    listen_only: bool,
+) -> state::DtmfMode {
+    match mode {
+        // Without telephone-event in the remote SDP, RFC 4733 packets would go to a
+        // payload type the remote does not expect
+        state::DtmfMode::Rfc2833 if !supports_telephone_event => state::DtmfMode::Info,
+        // A listen-only call sends no RTP, so digits go out of band
+        state::DtmfMode::Rfc2833 | state::DtmfMode::Inband if listen_only => state::DtmfMode::Info,
+        mode => mode,
+    }
+}
+
+/// What sending DTMF needs from an active call, taken under the active-calls
+/// lock so the lock is released before the digit goes out.
+struct CallDtmf {
+    call_id: String,
+    dialog: rsipstack::dialog::dialog::Dialog,
+    sender: Option<crate::webrtc::DtmfSender>,
+}
+
+impl CallDtmf {
+    fn new(call: &ActiveCall) -> Self {
+        Self {
+            call_id: call.call_id.clone(),
+            dialog: call.dialog.clone(),
+            sender: call.webrtc_session.as_ref().map(|s| s.dtmf_sender()),
+        }
+    }
+}
+
+/// Send one DTMF digit on `call` using `mode`
+async fn send_call_dtmf(
+    call: &CallDtmf,
+    digit_char: char,
+    mode: state::DtmfMode,
+    params: state::DtmfParams,
+) -> Result<(), AppError> {
+    let mode = match call.sender.as_ref() {
+        Some(session) => {
+            let effective = effective_dtmf_mode(
+                mode,
+                session.supports_telephone_event(),
+                session.is_listen_only(),
+            );
+            if effective != mode {
+                info!(call_id = %call.call_id, requested = mode.as_str(), "Falling back to SIP INFO for DTMF");
+            }
+            effective
+        }
+        None => mode,
+    };
+
+    match mode {
+        state::DtmfMode::Rfc2833 => {
+            if let Some(session) = call.sender.as_ref() {
+                info!(digit = %digit_char, call_id = %call.call_id, "Sending DTMF digit");
+                Ok(session
+                    .send_dtmf(digit_char, params.duration_ms, params.volume_dbm0)
+                    .await?)
+            } else {
+                Err("No active WebRTC session".into())
+            }
+        }
+        state::DtmfMode::Info => {
+            if !matches!(digit_char, '0'..='9' | '*' | '#' | 'A'..='D' | 'a'..='d') {
+                return Err(format!("Invalid DTMF digit: {}", digit_char).into());
+            }
+            info!(digit = %digit_char, call_id = %call.call_id, "Sending DTMF digit via SIP INFO");
+            Ok(send_dtmf_info(
+                &call.dialog,
+                digit_char.to_ascii_uppercase(),
+                params.duration_ms,
+            )
+            .await?)
+        }
+        state::DtmfMode::Inband => {
+            let session = call
+                .sender
+                .as_ref()
+                .ok_or_else(|| "No active WebRTC session".to_string())?;
+            info!(digit = %digit_char, call_id = %call.call_id, "Sending DTMF digit in-band");
+            Ok(session.send_inband_dtmf(digit_char).await?)
+        }
+    }
+}
+
+/// Send one DTMF digit as a SIP INFO request with an `application/dtmf-relay` body.
+async fn send_dtmf_info(
+    dialog: &rsipstack::dialog::dialog::Dialog,
+    digit: char,
+    duration_ms: u32,
+) -> Result<(), String> {
+    let body = format!("Signal={}\r\nDuration={}\r\n", digit, duration_ms).into_bytes();
+    let headers = vec![rsip::Header::ContentType("application/dtmf-relay".into())];
+
+    let resp = match dialog {
+        rsipstack::dialog::dialog::Dialog::ClientInvite(d) => d.info(Some(headers), Some(body)).await,
+        rsipstack::dialog::dialog::Dialog::ServerInvite(d) => d.info(Some(headers), Some(body)).await,
+        _ => return Err("Invalid dialog type for DTMF INFO".to_string()),
+    }
+    .map_err(|e| format!("Failed to send DTMF INFO: {}", e))?;
+
+    match resp {
+        Some(resp) if resp.status_code.kind() == rsip::StatusCodeKind::Successful => Ok(()),
+        Some(resp) => Err(format!("DTMF INFO rejected: {}", resp.status_code)),
+        None => Err("Dialog not confirmed, DTMF INFO not sent".to_string()),
     }
 }
 
@@ -828,6 +2605,28 @@ pub fn handle_is_sip_flow_enabled(handle: &ClientHandle) -> Result<bool, String>
     }
 }
 
+/// Restrict SIP flow logging to one Call-ID, or lift the restriction
+pub fn handle_set_sip_flow_filter(
+    handle: &ClientHandle,
+    call_id: Option<String>,
+) -> Result<(), String> {
+    if let Some(ref sip_flow) = handle.sip_flow {
+        sip_flow.set_sip_flow_filter(call_id);
+        Ok(())
+    } else {
+        Err("SIP flow not available".to_string())
+    }
+}
+
+/// Get the Call-ID SIP flow logging is restricted to
+pub fn handle_get_sip_flow_filter(handle: &ClientHandle) -> Result<Option<String>, String> {
+    if let Some(ref sip_flow) = handle.sip_flow {
+        Ok(sip_flow.sip_flow_filter())
+    } else {
+        Err("SIP flow not available".to_string())
+    }
+}
+
 /// Set SIP flow log directory
 pub fn handle_set_sip_flow_dir(handle: &ClientHandle, dir: String) -> Result<(), String> {
     if let Some(ref sip_flow) = handle.sip_flow {
@@ -837,6 +2636,17 @@ pub fn handle_set_sip_flow_dir(handle: &ClientHandle, dir: String) -> Result<(),
     }
 }
 
+/// SIP messages captured by the flow logger, for the PCAP export
+pub fn handle_sip_flow_captures(
+    handle: &ClientHandle,
+) -> Result<Vec<pcap::CapturedMessage>, String> {
+    if let Some(ref sip_flow) = handle.sip_flow {
+        Ok(sip_flow.captured_messages())
+    } else {
+        Err("SIP flow not available".to_string())
+    }
+}
+
 /// Get SIP flow log directory
 pub fn handle_get_sip_flow_dir(handle: &ClientHandle) -> Result<String, String> {
     if let Some(ref sip_flow) = handle.sip_flow {
@@ -845,3 +2655,34 @@ pub fn handle_get_sip_flow_dir(handle: &ClientHandle) -> Result<String, String>
         Err("SIP flow not available".to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use state::DtmfMode;
+
+    #[test]
+    fn test_dtmf_falls_back_to_info() {
+        use DtmfMode::*;
+        // (configured, telephone-event negotiated, listen-only, used)
+        let cases = [
+            (Rfc2833, false, false, Info),
+            (Inband, false, false, Inband),
+            // Listen-only calls send no RTP
+            (Rfc2833, true, true, Info),
+            (Inband, true, true, Info),
+            (Rfc2833, true, false, Rfc2833),
+            (Info, true, false, Info),
+        ];
+        for (mode, telephone_event, listen_only, expected) in cases {
+            assert_eq!(
+                effective_dtmf_mode(mode, telephone_event, listen_only),
+                expected,
+                "{:?} with telephone-event={} listen-only={}",
+                mode,
+                telephone_event,
+                listen_only
+            );
+        }
+    }
+}
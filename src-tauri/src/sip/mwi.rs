@@ -0,0 +1,124 @@
+use rsip::prelude::HasHeaders;
+use rsipstack::transaction::transaction::Transaction;
+use rsipstack::Result;
+use tauri::Emitter;
+use tracing::{debug, info};
+
+use crate::sip::state::MwiPayload;
+
+/// Event package and NOTIFY body type of voicemail subscriptions (RFC 3842)
+pub const EVENT: &str = "message-summary";
+pub const ACCEPT: &str = "application/simple-message-summary";
+
+/// Voicemail counts from an `application/simple-message-summary` body (RFC 3842).
+#[derive(Debug, Default, PartialEq)]
+pub struct MessageSummary {
+    pub messages_waiting: bool,
+    pub new_messages: u32,
+    pub old_messages: u32,
+}
+
+/// Parse a message-summary body, e.g.
+///
+/// ```text
+/// Messages-Waiting: yes
+/// Voice-Message: 2/8 (0/2)
+/// ```
+///
+/// Returns `None` when the mandatory `Messages-Waiting` line is missing.
+pub fn parse_message_summary(body: &str) -> Option<MessageSummary> {
+    let mut summary = MessageSummary::default();
+    let mut has_status = false;
+    for line in body.lines() {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        if name.trim().eq_ignore_ascii_case("messages-waiting") {
+            summary.messages_waiting = value.eq_ignore_ascii_case("yes");
+            has_status = true;
+        } else if name.trim().eq_ignore_ascii_case("voice-message") {
+            // "new/old" optionally followed by "(urgent_new/urgent_old)"
+            let counts = value.split_whitespace().next().unwrap_or_default();
+            if let Some((new, old)) = counts.split_once('/') {
+                summary.new_messages = new.trim().parse().unwrap_or(0);
+                summary.old_messages = old.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+    has_status.then_some(summary)
+}
+
+/// Check whether a request is a NOTIFY for the `message-summary` event package.
+pub fn is_mwi_notify(req: &rsip::Request) -> bool {
+    req.method == rsip::Method::Notify
+        && req.headers().iter().any(|h| match h {
+            rsip::Header::Event(e) => e.to_string().to_lowercase().contains("message-summary"),
+            rsip::Header::Other(name, value) => {
+                name.eq_ignore_ascii_case("event")
+                    && value.to_lowercase().contains("message-summary")
+            }
+            _ => false,
+        })
+}
+
+/// Answer a message-summary NOTIFY and emit its counts as `sip://mwi`.
+///
+/// Servers send these both for our subscription and unsolicited, so they are
+/// accepted whether or not they match a dialog.
+pub async fn process_notify(
+    tx: &mut Transaction,
+    account_id: &str,
+    app_handle: &tauri::AppHandle,
+) -> Result<()> {
+    tx.reply(rsip::StatusCode::OK).await?;
+
+    let body = String::from_utf8_lossy(&tx.original.body);
+    let Some(summary) = parse_message_summary(&body) else {
+        debug!(account = %account_id, "MWI NOTIFY without message summary");
+        return Ok(());
+    };
+    info!(
+        account = %account_id,
+        waiting = summary.messages_waiting,
+        new = summary.new_messages,
+        old = summary.old_messages,
+        "Voicemail status"
+    );
+    let _ = app_handle.emit(
+        "sip://mwi",
+        MwiPayload {
+            account_id: account_id.to_string(),
+            messages_waiting: summary.messages_waiting,
+            new_messages: summary.new_messages,
+            old_messages: summary.old_messages,
+        },
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_message_summary() {
+        let body = "Messages-Waiting: yes\r\nMessage-Account: sip:1000@pbx\r\nVoice-Message: 2/8 (0/2)\r\n";
+        assert_eq!(
+            parse_message_summary(body),
+            Some(MessageSummary {
+                messages_waiting: true,
+                new_messages: 2,
+                old_messages: 8,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_message_summary_without_counts() {
+        let summary = parse_message_summary("Messages-Waiting: no\r\n").unwrap();
+        assert!(!summary.messages_waiting);
+        assert_eq!(summary.new_messages, 0);
+        assert!(parse_message_summary("Voice-Message: 1/0\r\n").is_none());
+    }
+}
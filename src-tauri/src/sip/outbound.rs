@@ -0,0 +1,144 @@
+//! SIP outbound (RFC 5626) for registrations.
+//!
+//! Every REGISTER carries `Supported: outbound, path` and a Contact with the
+//! device's `+sip.instance` and a `reg-id`, so the registrar can tell a
+//! re-registration over a new flow from a second device. The instance-id is a
+//! UUID URN kept in the app data dir, the same across restarts and accounts.
+//!
+//! A first hop that cannot do outbound answers 439; the registration then
+//! turns it off for the account and registers again without it.
+
+use rsip::{headers::UntypedHeader, Header, SipMessage};
+use rsipstack::{transaction::endpoint::MessageInspector, transport::SipAddr};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tauri::{AppHandle, Manager};
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+/// Instance-id file inside the app data dir
+const INSTANCE_ID_FILE: &str = "instance_id";
+
+/// Registration flow identifier; the app keeps a single flow per account
+const REG_ID: u32 = 1;
+
+/// The device's `+sip.instance` URN, created and saved on first use. If it
+/// cannot be saved, a fresh one is used for this run.
+pub fn load_instance_id(app_handle: &AppHandle) -> String {
+    let path = match app_handle.path().app_data_dir() {
+        Ok(dir) => dir.join(INSTANCE_ID_FILE),
+        Err(e) => {
+            warn!(error = %e, "Failed to resolve data dir, using a temporary instance-id");
+            return new_instance_id();
+        }
+    };
+    if let Ok(saved) = std::fs::read_to_string(&path) {
+        let saved = saved.trim();
+        if saved.starts_with("urn:uuid:") {
+            return saved.to_string();
+        }
+    }
+
+    let instance_id = new_instance_id();
+    let saved = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|()| std::fs::write(&path, &instance_id));
+    match saved {
+        Ok(()) => debug!(instance_id = %instance_id, "Created SIP instance-id"),
+        Err(e) => warn!(path = %path.display(), error = %e, "Failed to save SIP instance-id"),
+    }
+    instance_id
+}
+
+fn new_instance_id() -> String {
+    format!("urn:uuid:{}", Uuid::new_v4())
+}
+
+/// Adds the outbound headers to outgoing REGISTER requests, then passes every
+/// message on to `inner`.
+pub struct OutboundInspector<I> {
+    inner: I,
+    instance_id: String,
+    /// Cleared once the first hop rejected outbound
+    enabled: Arc<AtomicBool>,
+}
+
+impl<I> OutboundInspector<I> {
+    pub fn new(inner: I, instance_id: String, enabled: Arc<AtomicBool>) -> Self {
+        Self {
+            inner,
+            instance_id,
+            enabled,
+        }
+    }
+}
+
+impl<I: MessageInspector> MessageInspector for OutboundInspector<I> {
+    fn before_send(&self, msg: SipMessage, dest: Option<&SipAddr>) -> SipMessage {
+        let msg = match msg {
+            SipMessage::Request(mut req)
+                if req.method == rsip::Method::Register && self.enabled.load(Ordering::Relaxed) =>
+            {
+                add_outbound_headers(&mut req, &self.instance_id);
+                SipMessage::Request(req)
+            }
+            msg => msg,
+        };
+        self.inner.before_send(msg, dest)
+    }
+
+    fn after_received(&self, msg: SipMessage, from: &SipAddr) -> SipMessage {
+        self.inner.after_received(msg, from)
+    }
+}
+
+/// Advertise outbound and tag the Contact with the instance-id and reg-id.
+/// Retransmissions and a Contact echoed back by the registrar already carry
+/// them and are left alone.
+fn add_outbound_headers(req: &mut rsip::Request, instance_id: &str) {
+    let mut has_supported = false;
+    for header in req.headers.iter_mut() {
+        match header {
+            Header::Contact(contact) if !contact.value().contains("+sip.instance") => {
+                *contact = rsip::headers::Contact::new(format!(
+                    "{};+sip.instance=\"<{}>\";reg-id={}",
+                    contact.value(),
+                    instance_id,
+                    REG_ID
+                ));
+            }
+            Header::Supported(_) => has_supported = true,
+            _ => {}
+        }
+    }
+    if !has_supported {
+        req.headers
+            .push(rsip::headers::Supported::new("outbound, path").into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_outbound_headers_once() {
+        let mut req = rsip::Request {
+            method: rsip::Method::Register,
+            uri: rsip::Uri::try_from("sip:pbx.example.com").unwrap(),
+            version: rsip::Version::V2,
+            headers: vec![rsip::headers::Contact::new("<sip:alice@192.0.2.10:5060>").into()].into(),
+            body: vec![],
+        };
+        let instance_id = "urn:uuid:00000000-0000-0000-0000-000000000001";
+        add_outbound_headers(&mut req, instance_id);
+        add_outbound_headers(&mut req, instance_id);
+
+        let text = req.to_string();
+        assert!(text.contains(
+            "Contact: <sip:alice@192.0.2.10:5060>;+sip.instance=\"<urn:uuid:00000000-0000-0000-0000-000000000001>\";reg-id=1\r\n"
+        ));
+        assert_eq!(text.matches("Supported: outbound, path").count(), 1);
+    }
+}
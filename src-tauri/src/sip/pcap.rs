@@ -0,0 +1,203 @@
+//! PCAP export of the captured SIP flow, for opening in Wireshark.
+//!
+//! Every message becomes one UDP datagram with synthetic Ethernet/IP/UDP
+//! headers between the recorded local and remote addresses, whatever the
+//! transport it really used, so Wireshark's SIP dissector and call flow
+//! views work on it directly.
+
+use std::io::Write;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Libpcap link type for Ethernet frames
+const LINKTYPE_ETHERNET: u32 = 1;
+/// Largest captured frame
+const SNAPLEN: u32 = 65535;
+
+const ETHERNET_HEADER_LEN: usize = 14;
+const IPV4_HEADER_LEN: usize = 20;
+const IPV6_HEADER_LEN: usize = 40;
+const UDP_HEADER_LEN: usize = 8;
+const UDP_PROTOCOL: u8 = 17;
+
+/// Locally administered MAC addresses for the two ends
+const LOCAL_MAC: [u8; 6] = [0x02, 0, 0, 0, 0, 0x01];
+const REMOTE_MAC: [u8; 6] = [0x02, 0, 0, 0, 0, 0x02];
+
+/// A SIP message seen by the flow inspector.
+#[derive(Clone, Debug)]
+pub struct CapturedMessage {
+    pub timestamp: SystemTime,
+    pub outgoing: bool,
+    pub local: SocketAddr,
+    pub remote: SocketAddr,
+    pub data: Vec<u8>,
+}
+
+/// Write `messages` as a pcap capture file.
+pub fn write_pcap<W: Write>(out: &mut W, messages: &[CapturedMessage]) -> std::io::Result<()> {
+    out.write_all(&0xa1b2_c3d4u32.to_le_bytes())?;
+    out.write_all(&2u16.to_le_bytes())?;
+    out.write_all(&4u16.to_le_bytes())?;
+    out.write_all(&0i32.to_le_bytes())?; // GMT
+    out.write_all(&0u32.to_le_bytes())?; // timestamp accuracy
+    out.write_all(&SNAPLEN.to_le_bytes())?;
+    out.write_all(&LINKTYPE_ETHERNET.to_le_bytes())?;
+
+    for message in messages {
+        let frame = frame(message);
+        let since_epoch = message
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        out.write_all(&(since_epoch.as_secs() as u32).to_le_bytes())?;
+        out.write_all(&since_epoch.subsec_micros().to_le_bytes())?;
+        out.write_all(&(frame.len() as u32).to_le_bytes())?;
+        out.write_all(&(frame.len() as u32).to_le_bytes())?;
+        out.write_all(&frame)?;
+    }
+    Ok(())
+}
+
+/// Ethernet frame carrying one message as a UDP datagram.
+fn frame(message: &CapturedMessage) -> Vec<u8> {
+    let (src, dst, src_mac, dst_mac) = if message.outgoing {
+        (message.local, message.remote, LOCAL_MAC, REMOTE_MAC)
+    } else {
+        (message.remote, message.local, REMOTE_MAC, LOCAL_MAC)
+    };
+    // IPv4 and IPv6 ends cannot share a header: map the IPv4 one into IPv6
+    let (src_ip, dst_ip) = match (src.ip(), dst.ip()) {
+        (IpAddr::V4(s), IpAddr::V4(d)) => (IpAddr::V4(s), IpAddr::V4(d)),
+        (s, d) => (IpAddr::V6(to_ipv6(s)), IpAddr::V6(to_ipv6(d))),
+    };
+    let ip_header_len = if src_ip.is_ipv4() {
+        IPV4_HEADER_LEN
+    } else {
+        IPV6_HEADER_LEN
+    };
+    let max_payload = SNAPLEN as usize - ETHERNET_HEADER_LEN - ip_header_len - UDP_HEADER_LEN;
+    let payload = &message.data[..message.data.len().min(max_payload)];
+    let udp_len = (UDP_HEADER_LEN + payload.len()) as u16;
+
+    let mut udp = Vec::with_capacity(udp_len as usize);
+    udp.extend_from_slice(&src.port().to_be_bytes());
+    udp.extend_from_slice(&dst.port().to_be_bytes());
+    udp.extend_from_slice(&udp_len.to_be_bytes());
+    udp.extend_from_slice(&[0, 0]);
+    udp.extend_from_slice(payload);
+    let mut pseudo = ip_octets(&src_ip);
+    pseudo.extend(ip_octets(&dst_ip));
+    pseudo.extend_from_slice(&[0, UDP_PROTOCOL]);
+    pseudo.extend_from_slice(&udp_len.to_be_bytes());
+    let udp_checksum = match checksum(&[&pseudo, &udp]) {
+        0 => 0xffff,
+        sum => sum,
+    };
+    udp[6..8].copy_from_slice(&udp_checksum.to_be_bytes());
+
+    let mut frame = Vec::with_capacity(ETHERNET_HEADER_LEN + ip_header_len + udp.len());
+    frame.extend_from_slice(&dst_mac);
+    frame.extend_from_slice(&src_mac);
+    match (src_ip, dst_ip) {
+        (IpAddr::V4(s), IpAddr::V4(d)) => {
+            frame.extend_from_slice(&0x0800u16.to_be_bytes());
+            let mut ip = Vec::with_capacity(IPV4_HEADER_LEN);
+            ip.extend_from_slice(&[0x45, 0]);
+            ip.extend_from_slice(&((IPV4_HEADER_LEN + udp.len()) as u16).to_be_bytes());
+            ip.extend_from_slice(&[0, 0, 0x40, 0]); // ID, don't fragment
+            ip.extend_from_slice(&[64, UDP_PROTOCOL, 0, 0]);
+            ip.extend_from_slice(&s.octets());
+            ip.extend_from_slice(&d.octets());
+            let ip_checksum = checksum(&[&ip]);
+            ip[10..12].copy_from_slice(&ip_checksum.to_be_bytes());
+            frame.extend_from_slice(&ip);
+        }
+        (s, d) => {
+            frame.extend_from_slice(&0x86ddu16.to_be_bytes());
+            frame.extend_from_slice(&[0x60, 0, 0, 0]);
+            frame.extend_from_slice(&udp_len.to_be_bytes());
+            frame.extend_from_slice(&[UDP_PROTOCOL, 64]);
+            frame.extend(ip_octets(&s));
+            frame.extend(ip_octets(&d));
+        }
+    }
+    frame.extend_from_slice(&udp);
+    frame
+}
+
+fn to_ipv6(ip: IpAddr) -> Ipv6Addr {
+    match ip {
+        IpAddr::V4(v4) => v4.to_ipv6_mapped(),
+        IpAddr::V6(v6) => v6,
+    }
+}
+
+fn ip_octets(ip: &IpAddr) -> Vec<u8> {
+    match ip {
+        IpAddr::V4(v4) => v4.octets().to_vec(),
+        IpAddr::V6(v6) => v6.octets().to_vec(),
+    }
+}
+
+/// Internet checksum (RFC 1071) over the concatenation of `parts`
+fn checksum(parts: &[&[u8]]) -> u16 {
+    let bytes = parts.iter().flat_map(|p| p.iter().copied());
+    let mut sum = 0u32;
+    let mut high = None;
+    for byte in bytes {
+        match high.take() {
+            None => high = Some(byte),
+            Some(h) => sum += u16::from_be_bytes([h, byte]) as u32,
+        }
+    }
+    if let Some(h) = high {
+        sum += u16::from_be_bytes([h, 0]) as u32;
+    }
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_pcap_udp_ipv4_frame() {
+        let message = CapturedMessage {
+            timestamp: UNIX_EPOCH + Duration::from_millis(1_700_000_000_250),
+            outgoing: true,
+            local: "192.168.1.10:5060".parse().unwrap(),
+            remote: "10.0.0.1:5080".parse().unwrap(),
+            data: b"OPTIONS sip:pbx SIP/2.0\r\n\r\n".to_vec(),
+        };
+        let mut out = Vec::new();
+        write_pcap(&mut out, std::slice::from_ref(&message)).unwrap();
+
+        assert_eq!(&out[..4], &[0xd4, 0xc3, 0xb2, 0xa1]);
+        let record = &out[24..];
+        let field = |at: usize| u32::from_le_bytes(record[at..at + 4].try_into().unwrap());
+        assert_eq!(field(0), 1_700_000_000);
+        assert_eq!(field(4), 250_000);
+        let frame = &record[16..];
+        assert_eq!(frame.len(), 14 + 20 + 8 + message.data.len());
+        assert_eq!(&frame[12..14], &[0x08, 0x00]);
+
+        let ip = &frame[14..34];
+        assert_eq!(checksum(&[ip]), 0, "IPv4 header checksum");
+        assert_eq!(&ip[12..16], &[192, 168, 1, 10]);
+        assert_eq!(&ip[16..20], &[10, 0, 0, 1]);
+        let udp = &frame[34..];
+        assert_eq!(u16::from_be_bytes([udp[0], udp[1]]), 5060);
+        assert_eq!(u16::from_be_bytes([udp[2], udp[3]]), 5080);
+        assert_eq!(&udp[8..], &message.data[..]);
+
+        let mut pseudo = ip[12..20].to_vec();
+        pseudo.extend_from_slice(&[0, UDP_PROTOCOL]);
+        pseudo.extend_from_slice(&udp[4..6]);
+        assert_eq!(checksum(&[&pseudo, udp]), 0, "UDP checksum");
+    }
+}
@@ -1,14 +1,35 @@
 use rsipstack::dialog::authenticate::Credential;
 use rsipstack::dialog::registration::Registration;
 use rsipstack::transaction::endpoint::EndpointInnerRef;
-use rsipstack::Result;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
+use tauri::{AppHandle, Emitter};
 use tokio::select;
+use tokio::sync::Notify;
 use tokio::time::{interval, MissedTickBehavior};
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
+use crate::error::AppError;
+use crate::sip::state::{RegistrationInfo, RegistrationStatusPayload};
+
+type Result<T> = std::result::Result<T, AppError>;
+
+/// First retry delay after a failed REGISTER; doubled on each further failure
+const RETRY_INITIAL_DELAY: Duration = Duration::from_secs(2);
+/// Longest delay between two registration attempts
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// Delay before retry number `attempt` (1-based): 2s, 4s, 8s, ... capped at 60s.
+pub fn retry_delay(attempt: u32) -> Duration {
+    let factor = 1u32 << attempt.saturating_sub(1).min(16);
+    RETRY_INITIAL_DELAY
+        .saturating_mul(factor)
+        .min(RETRY_MAX_DELAY)
+}
+
 /// SIP registration manager.
 ///
 /// Wraps rsipstack's `Registration` and owns all state needed for the full
@@ -18,56 +39,181 @@ use uuid::Uuid;
 /// Create once via `SipRegistration::new()`; the UUID-based Call-ID is
 /// generated at construction time and reused for every subsequent request,
 /// as required by RFC 3261.
+///
+/// Failed registrations are retried with exponential backoff. The lifecycle
+/// is reported to the frontend as `sip://registration-status` events for
+/// `account_id`: `"registering"` → `"registered"` (or `"failed"`), then
+/// `"refreshing"` → `"refreshed"` on each refresh, `"retrying"` between
+/// failed attempts, and `"unregistered"` once the expires=0 REGISTER is sent.
 pub struct Registrant {
     inner: Registration,
     sip_server: rsip::Uri,
+    app_handle: AppHandle,
+    account_id: String,
+    /// Attempts per registration before giving up (0 = retry until cancelled)
+    max_attempts: u32,
+    /// REGISTERs carry the RFC 5626 outbound headers; cleared on a 439
+    outbound: Arc<AtomicBool>,
+    /// Shared with the account's `ClientHandle`
+    info: Arc<tokio::sync::Mutex<RegistrationInfo>>,
 }
 
 impl Registrant {
     /// Create a new registration manager.
     ///
     /// Initialises the underlying `Registration` with a fresh UUID Call-ID.
-    pub fn new(endpoint: EndpointInnerRef, credential: Credential, sip_server: rsip::Uri) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        endpoint: EndpointInnerRef,
+        credential: Credential,
+        sip_server: rsip::Uri,
+        app_handle: AppHandle,
+        account_id: String,
+        max_attempts: u32,
+        outbound: Arc<AtomicBool>,
+        info: Arc<tokio::sync::Mutex<RegistrationInfo>>,
+    ) -> Self {
         let mut inner = Registration::new(endpoint, Some(credential));
         inner.call_id = rsip::headers::CallId::from(Uuid::new_v4().to_string());
-        Self { inner, sip_server }
+        Self {
+            inner,
+            sip_server,
+            app_handle,
+            account_id,
+            max_attempts,
+            outbound,
+            info,
+        }
+    }
+
+    fn emit_status(&self, status: &str, error: Option<&AppError>, attempt: Option<u32>) {
+        let _ = self.app_handle.emit(
+            "sip://registration-status",
+            RegistrationStatusPayload {
+                account_id: self.account_id.clone(),
+                status: status.to_string(),
+                message: error.map(|e| e.to_string()),
+                code: error.and_then(AppError::status_code),
+                reason: error.map(|e| e.code().to_string()),
+                attempt,
+            },
+        );
     }
 
     /// Send a single REGISTER request and return the negotiated expires value.
+    ///
+    /// A 439 (First Hop Lacks Outbound Support) turns outbound off and the
+    /// REGISTER is sent again without it.
     pub async fn register_once(&mut self) -> Result<u64> {
-        let resp = self.inner.register(self.sip_server.clone(), None).await?;
+        let mut resp = self.inner.register(self.sip_server.clone(), None).await?;
+        if resp.status_code == rsip::StatusCode::FirstHopLacksOutboundSupport
+            && self.outbound.swap(false, Ordering::Relaxed)
+        {
+            warn!(server = %self.sip_server, "First hop lacks outbound support, registering without it");
+            self.inner.contact = None;
+            resp = self.inner.register(self.sip_server.clone(), None).await?;
+        }
 
         if resp.status_code != rsip::StatusCode::OK {
             error!(server = %self.sip_server, status_code = ?resp.status_code, "Registration failed");
-            return Err(rsipstack::Error::Error("Failed to register".to_string()));
+            return Err(AppError::from_register_status(resp.status_code));
         }
 
         let expires = self.inner.expires().max(60) as u64;
         info!(server = %self.sip_server, expires = expires, "Registered successfully");
         debug!(server = %self.sip_server, "Registration response OK");
 
+        let mut info = self.info.lock().await;
+        info.contact = self.inner.contact.as_ref().map(|c| c.uri.to_string());
+        info.expires_secs = expires;
+
         Ok(expires)
     }
 
+    /// Initial registration: `register_with_retry` announced as
+    /// `"registering"`, then `"registered"` or `"failed"`. Returns the expires
+    /// value.
+    pub async fn register(&mut self, cancel_token: &CancellationToken) -> Result<u64> {
+        self.emit_status("registering", None, None);
+        match self.register_with_retry(cancel_token).await {
+            Ok((expires, _)) => {
+                self.emit_status("registered", None, None);
+                Ok(expires)
+            }
+            Err(e) => {
+                if !cancel_token.is_cancelled() {
+                    self.emit_status("failed", Some(&e), None);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Register, retrying failures with exponential backoff.
+    ///
+    /// Each retry is announced as a `"retrying"` status carrying the number of
+    /// the failed attempt. Returns the expires value and the number of attempts
+    /// it took, the last error once `max_attempts` is reached, or an error as
+    /// soon as `cancel_token` is cancelled.
+    pub async fn register_with_retry(
+        &mut self,
+        cancel_token: &CancellationToken,
+    ) -> Result<(u64, u32)> {
+        let mut attempt = 0u32;
+        loop {
+            attempt += 1;
+            let err = match self.register_once().await {
+                Ok(expires) => return Ok((expires, attempt)),
+                Err(e) => e,
+            };
+            if self.max_attempts != 0 && attempt >= self.max_attempts {
+                error!(server = %self.sip_server, attempts = attempt, error = ?err, "Registration failed, giving up");
+                return Err(err);
+            }
+
+            let delay = retry_delay(attempt);
+            warn!(server = %self.sip_server, attempt, retry_in = ?delay, error = ?err, "Registration failed, retrying");
+            self.emit_status("retrying", Some(&err), Some(attempt));
+            select! {
+                _ = cancel_token.cancelled() => {
+                    return Err(AppError::Other("Registration cancelled".to_string()));
+                }
+                _ = tokio::time::sleep(delay) => {}
+            }
+        }
+    }
+
+    /// Record when the next refresh REGISTER is due
+    async fn set_refresh_in(&self, secs: u64) {
+        self.info.lock().await.refresh_at =
+            Some(std::time::Instant::now() + Duration::from_secs(secs));
+    }
+
     /// Send REGISTER with expires=0 to unregister.
     async fn unregister(&mut self) {
+        *self.info.lock().await = RegistrationInfo::default();
         info!(server = %self.sip_server, "Sending unregister (expires=0)");
         if let Err(e) = self.inner.register(self.sip_server.clone(), Some(0)).await {
             error!(server = %self.sip_server, error = ?e, "Unregister failed");
         } else {
             info!(server = %self.sip_server, "Unregistered successfully");
         }
+        self.emit_status("unregistered", None, None);
     }
 
     /// Run the periodic refresh loop.
     ///
     /// Refreshes at 75% of the current expires interval, capped at
     /// `max_keepalive_secs` when set.  For connection-oriented transports
-    /// (TCP/TLS/WS/WSS) pass a value such as `Some(25)`: rsipstack does not
-    /// auto-remove dead connections from its send map, so the only reliable
-    /// way to prevent a stale-connection send error is to keep the TCP
-    /// session alive with periodic REGISTER traffic before the server idles
-    /// it out.
+    /// (TCP/TLS/WS/WSS) pass a value such as `Some(25)` so periodic REGISTER
+    /// traffic keeps the server from idling the connection out.
+    ///
+    /// A notification on `reregister` (sent after the transport supervisor
+    /// rebuilt the connection) refreshes the registration immediately.
+    ///
+    /// A failed refresh is retried with backoff (`register_with_retry`); the
+    /// loop only ends once the retries are exhausted, emitting a `"failed"`
+    /// status, or on cancellation.
     ///
     /// Sends an unregister on cancellation before returning.
     pub async fn run_refresh_loop(
@@ -75,6 +221,7 @@ impl Registrant {
         initial_expires: u64,
         cancel_token: CancellationToken,
         max_keepalive_secs: Option<u64>,
+        reregister: Arc<Notify>,
     ) -> Result<()> {
         let cap = |t: u64| match max_keepalive_secs {
             Some(max) => t.min(max),
@@ -89,6 +236,7 @@ impl Registrant {
             "Starting registration refresh loop"
         );
 
+        self.set_refresh_in(refresh_time).await;
         let mut ticker = interval(Duration::from_secs(refresh_time));
         ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
         ticker.tick().await; // first tick fires immediately, skip it
@@ -101,15 +249,28 @@ impl Registrant {
             }
             result = async {
                 loop {
-                    ticker.tick().await;
-                    match self.register_once().await {
-                        Ok(expires) => {
+                    select! {
+                        _ = ticker.tick() => {}
+                        _ = reregister.notified() => {
+                            info!(server = %self.sip_server, "Re-registering over the new connection");
+                        }
+                    }
+                    self.emit_status("refreshing", None, None);
+                    match self.register_with_retry(&cancel_token).await {
+                        Ok((expires, attempts)) => {
                             let new_refresh = cap(expires * 3 / 4);
                             ticker.reset_after(Duration::from_secs(new_refresh));
+                            self.set_refresh_in(new_refresh).await;
                             debug!(server = %self.sip_server, refresh_in = new_refresh, "Registration refreshed");
+                            if attempts > 1 {
+                                info!(server = %self.sip_server, attempts, "Registration recovered");
+                            }
+                            self.emit_status("refreshed", None, None);
                         }
                         Err(e) => {
                             error!(server = %self.sip_server, error = ?e, "Registration refresh failed");
+                            self.emit_status("failed", Some(&e), None);
+                            *self.info.lock().await = RegistrationInfo::default();
                             return Err(e);
                         }
                     }
@@ -122,3 +283,17 @@ impl Registrant {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_delay_doubles_up_to_cap() {
+        assert_eq!(retry_delay(1), Duration::from_secs(2));
+        assert_eq!(retry_delay(2), Duration::from_secs(4));
+        assert_eq!(retry_delay(3), Duration::from_secs(8));
+        assert_eq!(retry_delay(6), Duration::from_secs(60));
+        assert_eq!(retry_delay(u32::MAX), Duration::from_secs(60));
+    }
+}
@@ -4,18 +4,29 @@ use rsipstack::dialog::authenticate::Credential;
 use rsipstack::dialog::dialog::{Dialog, DialogStateSender};
 use rsipstack::dialog::dialog_layer::DialogLayer;
 use serde::Serialize;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
 use std::sync::Arc;
 use tokio_util::sync::CancellationToken;
 
+use crate::call_history::CallRecord;
+use crate::headset::HeadsetControl;
 use crate::sip::message_inspector::SipFlow;
-use crate::webrtc::WebRtcSession;
+use crate::sip::tls::TlsVerification;
+use crate::webrtc::audio_test::AudioTest;
+use crate::webrtc::conference::ConferenceMixer;
+use crate::webrtc::encoder::OpusConfig;
+use crate::webrtc::ringtone::RingtonePlayer;
+use crate::webrtc::vad::VadConfig;
+use crate::webrtc::{CallStats, IceConfig, IceMode, WebRtcSession};
 
 /// SIP flow log configuration
 #[derive(Clone, Serialize)]
 pub struct FlowConfig {
     pub enabled: bool,
     pub log_dir: String,
+    /// Only messages of this Call-ID are logged (`None` = all)
+    pub filter: Option<String>,
 }
 
 impl Default for FlowConfig {
@@ -35,31 +46,200 @@ impl Default for FlowConfig {
         Self {
             enabled: false,
             log_dir,
+            filter: None,
+        }
+    }
+}
+
+/// How DTMF digits are sent during a call
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DtmfMode {
+    /// RFC 4733 (formerly RFC 2833) telephone-event RTP packets
+    #[default]
+    Rfc2833,
+    /// Out-of-band SIP INFO with `application/dtmf-relay`
+    Info,
+    /// Audible dual tones in the call audio, for gateways that support neither of the above
+    Inband,
+}
+
+impl DtmfMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DtmfMode::Rfc2833 => "rfc2833",
+            DtmfMode::Info => "info",
+            DtmfMode::Inband => "inband",
+        }
+    }
+
+    pub fn parse(mode: &str) -> Result<Self, String> {
+        match mode.to_ascii_lowercase().as_str() {
+            "rfc2833" | "rfc4733" => Ok(DtmfMode::Rfc2833),
+            "info" => Ok(DtmfMode::Info),
+            "inband" => Ok(DtmfMode::Inband),
+            other => Err(format!("Invalid DTMF mode: {}", other)),
+        }
+    }
+}
+
+/// Length and level of sent DTMF tones
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct DtmfParams {
+    /// Tone length, 40–1000 ms
+    pub duration_ms: u32,
+    /// RFC 4733 volume as -dBm0, 0 (loudest) to 36
+    pub volume_dbm0: u8,
+}
+
+impl Default for DtmfParams {
+    fn default() -> Self {
+        Self {
+            duration_ms: 160,
+            volume_dbm0: 10,
+        }
+    }
+}
+
+/// Do Not Disturb: incoming calls are rejected without ringing
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct DndConfig {
+    pub enabled: bool,
+    /// Final response sent to rejected callers, 486 Busy Here by default
+    pub reject_code: u16,
+}
+
+impl Default for DndConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            reject_code: 486,
+        }
+    }
+}
+
+/// Automatic answering of incoming calls (e.g. for an unattended kiosk)
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct AutoAnswerConfig {
+    pub enabled: bool,
+    /// How long the call rings before it is answered
+    pub delay_ms: u64,
+}
+
+/// Opus bitrate adapted to the loss the remote reports in RTCP receiver reports
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct AdaptiveBitrateConfig {
+    pub enabled: bool,
+    /// Bounds in bits per second
+    pub min_bitrate: u32,
+    pub max_bitrate: u32,
+}
+
+impl Default for AdaptiveBitrateConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_bitrate: 12_000,
+            max_bitrate: 64_000,
         }
     }
 }
 
 pub struct SipAppState {
-    pub handle: tokio::sync::Mutex<Option<Arc<ClientHandle>>>,
-    pub cancel_token: tokio::sync::Mutex<Option<CancellationToken>>,
+    /// Registered accounts, keyed by account ID
+    pub handles: tokio::sync::Mutex<BTreeMap<String, Arc<ClientHandle>>>,
     pub input_device: tokio::sync::Mutex<Option<String>>,
     pub output_device: tokio::sync::Mutex<Option<String>>,
+    /// Use the system default devices, moving calls when they change,
+    /// instead of the pinned `input_device` / `output_device`
+    pub follow_system_default: tokio::sync::Mutex<bool>,
     pub sip_flow_config: tokio::sync::Mutex<FlowConfig>,
     pub prefer_srtp: tokio::sync::Mutex<bool>,
+    /// Offer reliable provisional responses (`Supported: 100rel`) on outbound INVITEs
+    pub prack_enabled: tokio::sync::Mutex<bool>,
+    /// REGISTER attempts before giving up on an unreachable server (0 = unlimited)
+    pub registration_max_attempts: tokio::sync::Mutex<u32>,
+    /// OPTIONS keepalive interval in seconds (0 = disabled)
+    pub keepalive_interval_secs: tokio::sync::Mutex<u64>,
+    /// CRLF keepalive interval in seconds on TCP/TLS/WS/WSS (0 = disabled)
+    pub crlf_keepalive_secs: tokio::sync::Mutex<u64>,
+    /// Server certificate check for TLS and WSS connections
+    pub tls_verification: tokio::sync::Mutex<TlsVerification>,
+    pub dnd: tokio::sync::Mutex<DndConfig>,
+    pub auto_answer: tokio::sync::Mutex<AutoAnswerConfig>,
     pub noise_reduce: tokio::sync::Mutex<bool>,
     pub speaker_noise_reduce: tokio::sync::Mutex<bool>,
+    /// Wet/dry mix of the noise reducers (0.0 = bypass, 1.0 = full denoise)
+    pub noise_reduce_strength: tokio::sync::Mutex<f32>,
+    pub echo_cancel: tokio::sync::Mutex<bool>,
+    /// Software speaker volume and mic gain (1.0 = unchanged)
+    pub speaker_volume: tokio::sync::Mutex<f32>,
+    pub mic_gain: tokio::sync::Mutex<f32>,
+    /// WAV file looped to the remote while a call is on hold
+    pub hold_music_path: tokio::sync::Mutex<Option<String>>,
+    pub jitter_buffer_ms: tokio::sync::Mutex<u32>,
+    /// Capacity of the audio ring buffers next to the devices
+    pub audio_buffer_ms: tokio::sync::Mutex<u32>,
+    pub plc: tokio::sync::Mutex<bool>,
+    /// Comfort noise in receive gaps, and CN offered in SDP
+    pub comfort_noise: tokio::sync::Mutex<bool>,
+    /// Offer uncompressed L16 (LAN intercom) ahead of the other codecs
+    pub l16: tokio::sync::Mutex<bool>,
+    /// Keep rtcp-mux in answers to non-ICE peers
+    pub rtcp_mux: tokio::sync::Mutex<bool>,
+    /// Fixed public IP advertised in SDP instead of STUN discovery
+    pub external_ip: tokio::sync::Mutex<Option<IpAddr>>,
+    /// DSCP of outgoing RTP (0 = unmarked)
+    pub dscp: tokio::sync::Mutex<u8>,
+    /// Seconds without received RTP before a call is hung up (0 = never)
+    pub media_timeout_secs: tokio::sync::Mutex<u64>,
+    pub opus_config: tokio::sync::Mutex<OpusConfig>,
+    pub adaptive_bitrate: tokio::sync::Mutex<AdaptiveBitrateConfig>,
+    pub vad: tokio::sync::Mutex<VadConfig>,
+    pub dtmf_mode: tokio::sync::Mutex<DtmfMode>,
+    pub dtmf_params: tokio::sync::Mutex<DtmfParams>,
+    pub ice_config: tokio::sync::Mutex<IceConfig>,
+    pub ice_mode: tokio::sync::Mutex<IceMode>,
+    pub ringtone: RingtonePlayer,
+    pub headset: HeadsetControl,
+    /// Running mic → speaker loopback test, if any
+    pub audio_test: tokio::sync::Mutex<Option<AudioTest>>,
+}
+
+/// Arguments an account was registered with, kept to register it again
+#[derive(Clone)]
+pub struct RegisterArgs {
+    pub server: String,
+    pub username: String,
+    pub password: String,
+    pub outbound_proxy: Option<String>,
 }
 
 pub struct ClientHandle {
+    pub account_id: String,
+    pub register_args: RegisterArgs,
+    /// Local IP the transport was built on, for Contact and Via
+    pub local_ip: IpAddr,
+    /// Host the egress IP is probed against ("host:port")
+    pub route_target: String,
     pub app_handle: tauri::AppHandle,
+    /// Cancels this account's transport, registration refresh and calls
+    pub cancel_token: CancellationToken,
     pub dialog_layer: Arc<DialogLayer>,
     pub state_sender: DialogStateSender,
     pub contact: Uri,
     pub credential: Credential,
     pub server: Uri,
-    pub active_call: Arc<tokio::sync::Mutex<Option<ActiveCall>>>,
+    /// Contact and timers of the current registration
+    pub registration: Arc<tokio::sync::Mutex<RegistrationInfo>>,
+    pub active_calls: Arc<tokio::sync::Mutex<ActiveCalls>>,
     pub pending_incoming: Arc<tokio::sync::Mutex<HashMap<String, PendingCall>>>,
     pub active_call_tokens: Arc<DashMap<String, CancellationToken>>,
+    /// Monitored extensions (BLF), each with the token that ends its subscription
+    pub blf_subscriptions: Arc<DashMap<String, CancellationToken>>,
+    /// OPTIONS keepalive interval in seconds (0 = disabled)
+    pub keepalive_interval: tokio::sync::watch::Sender<u64>,
+    /// CRLF keepalive interval in seconds on TCP/TLS/WS/WSS (0 = disabled)
+    pub crlf_keepalive_interval: tokio::sync::watch::Sender<u64>,
     pub sip_flow: Option<Arc<SipFlow>>,
     pub _tasks: Vec<tokio::task::JoinHandle<()>>,
 }
@@ -69,15 +249,183 @@ pub struct ActiveCall {
     pub dialog: Dialog,
     pub webrtc_session: Option<WebRtcSession>,
     pub cancel_token: CancellationToken,
+    /// Consultation leg to release together with this call once an attended transfer succeeds
+    pub transfer_peer: Option<String>,
+    /// When the call was reported as "connected"
+    pub connected_at: std::time::Instant,
+    /// Call log entry, written when the call ends
+    pub history: CallRecord,
+}
+
+impl ActiveCall {
+    /// Dialog ID used as the key in `active_call_tokens`.
+    pub fn dialog_id(&self) -> String {
+        match &self.dialog {
+            Dialog::ClientInvite(d) => d.id().to_string(),
+            Dialog::ServerInvite(d) => d.id().to_string(),
+            _ => self.call_id.clone(),
+        }
+    }
+}
+
+/// Established calls, keyed by call ID.
+///
+/// Several calls can coexist (e.g. one on hold while a consultation call is
+/// placed); the most recently connected one is the "current" call that
+/// mute/DTMF/hold commands act on.
+#[derive(Default)]
+pub struct ActiveCalls {
+    calls: HashMap<String, ActiveCall>,
+    current: Option<String>,
+    /// Call IDs in the order they were last made current, most recent last
+    recent: Vec<String>,
+    /// Local conference between some of the calls, if one is running
+    conference: Option<Arc<ConferenceMixer>>,
+}
+
+impl ActiveCalls {
+    /// Store a call and make it the current one.
+    pub fn insert(&mut self, call: ActiveCall) {
+        self.touch(&call.call_id);
+        self.current = Some(call.call_id.clone());
+        self.calls.insert(call.call_id.clone(), call);
+    }
+
+    pub fn get(&self, call_id: &str) -> Option<&ActiveCall> {
+        self.calls.get(call_id)
+    }
+
+    pub fn get_mut(&mut self, call_id: &str) -> Option<&mut ActiveCall> {
+        self.calls.get_mut(call_id)
+    }
+
+    pub fn contains(&self, call_id: &str) -> bool {
+        self.calls.contains_key(call_id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.calls.is_empty()
+    }
+
+    pub fn current(&self) -> Option<&ActiveCall> {
+        self.current.as_deref().and_then(|id| self.calls.get(id))
+    }
+
+    pub fn current_mut(&mut self) -> Option<&mut ActiveCall> {
+        match self.current.as_deref() {
+            Some(id) => self.calls.get_mut(id),
+            None => None,
+        }
+    }
+
+    /// Make `call_id` the current call. Returns false if no such call exists.
+    pub fn set_current(&mut self, call_id: &str) -> bool {
+        if !self.calls.contains_key(call_id) {
+            return false;
+        }
+        self.touch(call_id);
+        self.current = Some(call_id.to_string());
+        true
+    }
+
+    /// Move `call_id` to the most recent end of `recent`.
+    fn touch(&mut self, call_id: &str) {
+        self.recent.retain(|id| id != call_id);
+        self.recent.push(call_id.to_string());
+    }
+
+    /// Call IDs, least recently current first.
+    pub fn call_ids(&self) -> Vec<String> {
+        self.recent.clone()
+    }
+
+    /// Remove a call. If it was the current one, the most recently current of
+    /// the remaining calls takes its place.
+    ///
+    /// A conference participant also leaves the conference, which ends once
+    /// fewer than two calls remain in it.
+    pub fn remove(&mut self, call_id: &str) -> Option<ActiveCall> {
+        self.leave_conference(call_id);
+        let call = self.calls.remove(call_id)?;
+        self.recent.retain(|id| id != call_id);
+        if self.current.as_deref() == Some(call_id) {
+            self.current = self.recent.last().cloned();
+        }
+        Some(call)
+    }
+
+    /// Call IDs taking part in the conference (empty when there is none).
+    pub fn conference_call_ids(&self) -> Vec<String> {
+        self.conference
+            .as_ref()
+            .map(|mixer| mixer.leg_ids())
+            .unwrap_or_default()
+    }
+
+    /// Connect the given calls' audio through a new conference mixer.
+    pub fn start_conference(&mut self, call_ids: &[String]) {
+        let mixer = ConferenceMixer::new(call_ids);
+        for id in call_ids {
+            if let Some(session) = self.calls.get(id).and_then(|c| c.webrtc_session.as_ref()) {
+                session.join_conference(mixer.clone(), id);
+            }
+        }
+        self.conference = Some(mixer);
+    }
+
+    /// Disconnect all conference legs. Returns the call IDs that were in it.
+    pub fn end_conference(&mut self) -> Vec<String> {
+        let Some(mixer) = self.conference.take() else {
+            return Vec::new();
+        };
+        let call_ids = mixer.leg_ids();
+        for id in &call_ids {
+            if let Some(session) = self.calls.get(id).and_then(|c| c.webrtc_session.as_ref()) {
+                session.leave_conference();
+            }
+        }
+        call_ids
+    }
+
+    fn leave_conference(&mut self, call_id: &str) {
+        let Some(mixer) = self.conference.as_ref() else {
+            return;
+        };
+        if !mixer.remove_leg(call_id) {
+            return;
+        }
+        if let Some(session) = self
+            .calls
+            .get(call_id)
+            .and_then(|c| c.webrtc_session.as_ref())
+        {
+            session.leave_conference();
+        }
+        if mixer.leg_ids().len() < 2 {
+            self.end_conference();
+        }
+    }
+
+    /// Find the call ID whose dialog matches `dialog_id`.
+    pub fn find_by_dialog_id(&self, dialog_id: &str) -> Option<String> {
+        self.calls
+            .values()
+            .find(|c| c.dialog_id() == dialog_id)
+            .map(|c| c.call_id.clone())
+    }
 }
 
 pub struct PendingCall {
     pub dialog: Dialog,
     pub sdp_offer: String,
+    /// Call log entry, written as a missed call unless the call is answered
+    pub history: CallRecord,
 }
 
 #[derive(Clone, Serialize)]
 pub struct IncomingCallPayload {
+    /// Account that received the call
+    pub account_id: String,
     pub call_id: String,
     pub caller: String,
     pub callee: Option<String>,
@@ -90,8 +438,228 @@ pub struct CallStatePayload {
     pub reason: Option<String>,
 }
 
+/// Mute state of a call, returned by `get_mute_state` and emitted as
+/// `sip://mute-state` whenever it changes
+#[derive(Clone, Serialize)]
+pub struct MuteState {
+    pub call_id: String,
+    pub mic_muted: bool,
+    pub speaker_muted: bool,
+}
+
+/// Current audio settings, for restoring the settings UI
+#[derive(Clone, Serialize)]
+pub struct AudioSettings {
+    pub input_device: Option<String>,
+    pub output_device: Option<String>,
+    pub follow_system_default: bool,
+    pub speaker_volume: f32,
+    pub mic_gain: f32,
+    pub noise_reduce: bool,
+    pub speaker_noise_reduce: bool,
+    pub noise_reduce_strength: f32,
+    pub echo_cancel: bool,
+    pub hold_music_path: Option<String>,
+}
+
+/// Payload of `sip://early-media`: the far end's audio (ringback, IVR) is
+/// playing before the call is answered.
+#[derive(Clone, Serialize)]
+pub struct EarlyMediaPayload {
+    pub call_id: String,
+}
+
+/// Payload of `sip://call-redirected`: a 3xx response moved the outbound
+/// call to `target`, which is called with the Call-ID `new_call_id`.
+#[derive(Clone, Serialize)]
+pub struct CallRedirectedPayload {
+    pub call_id: String,
+    pub new_call_id: String,
+    pub target: String,
+}
+
+/// Payload of `sip://call-retry`: media of the outbound call could not be
+/// connected, and it is being placed once more with the Call-ID `new_call_id`.
+#[derive(Clone, Serialize)]
+pub struct CallRetryPayload {
+    pub call_id: String,
+    pub new_call_id: String,
+    pub reason: String,
+}
+
+/// Payload of `sip://remote-hold`: the far end held (`on_hold = true`) or resumed the call.
+#[derive(Clone, Serialize)]
+pub struct RemoteHoldPayload {
+    pub call_id: String,
+    pub on_hold: bool,
+}
+
+/// Payload of `sip://missed-call`: an incoming call that ended before it was
+/// answered (caller CANCEL, or the INVITE transaction ending on its own).
+#[derive(Clone, Serialize)]
+pub struct MissedCallPayload {
+    /// Account that received the call
+    pub account_id: String,
+    pub call_id: String,
+    pub caller: String,
+    /// "canceled", or why the call otherwise stopped ringing
+    pub reason: String,
+    /// Milliseconds since the Unix epoch
+    pub timestamp: i64,
+}
+
+/// Payload of `sip://call-rejected-dnd`: a call turned away by Do Not Disturb.
+#[derive(Clone, Serialize)]
+pub struct CallRejectedDndPayload {
+    /// Account that received the call
+    pub account_id: String,
+    pub call_id: String,
+    pub caller: String,
+    /// Milliseconds since the Unix epoch
+    pub timestamp: i64,
+}
+
+#[derive(Clone, Serialize)]
+pub struct CallDurationPayload {
+    pub call_id: String,
+    pub seconds: u64,
+}
+
+/// Payload of `sip://call-stats`, emitted every second while a call is up.
+#[derive(Clone, Serialize)]
+pub struct CallStatsPayload {
+    pub call_id: String,
+    /// SDP name of the audio codec in use (`None` before media starts)
+    pub codec: Option<String>,
+    /// Bitrates over the last interval, in kbit/s
+    pub send_bitrate_kbps: f64,
+    pub receive_bitrate_kbps: f64,
+    #[serde(flatten)]
+    pub stats: CallStats,
+}
+
+/// What the registrar granted in the last successful REGISTER, kept up to
+/// date by the registration refresh loop
+#[derive(Clone, Debug, Default)]
+pub struct RegistrationInfo {
+    /// Contact bound by the registrar, as returned in its 200 OK
+    pub contact: Option<String>,
+    /// Registration lifetime in seconds
+    pub expires_secs: u64,
+    /// When the next refresh REGISTER is due
+    pub refresh_at: Option<std::time::Instant>,
+}
+
+/// Registration of one account, as returned by `sip_get_registration_info`
+#[derive(Clone, Debug, Serialize)]
+pub struct RegistrationDetails {
+    pub account_id: String,
+    pub contact: Option<String>,
+    pub expires_secs: u64,
+    /// Seconds until the next refresh REGISTER
+    pub refresh_in_secs: Option<u64>,
+}
+
+/// Payload of `sip://registration-status`: `status` is `"registering"`,
+/// `"registered"`, `"refreshing"`, `"refreshed"`, `"retrying"`, `"failed"` or
+/// `"unregistered"`.
 #[derive(Clone, Serialize)]
 pub struct RegistrationStatusPayload {
+    pub account_id: String,
     pub status: String,
     pub message: Option<String>,
+    /// SIP status of the failed REGISTER (e.g. 403, or 408 on timeout)
+    pub code: Option<u16>,
+    /// `AppError` code of the failure: "auth_failed", "timeout", "transport", ...
+    pub reason: Option<String>,
+    /// Number of the failed attempt, for `"retrying"`
+    pub attempt: Option<u32>,
+}
+
+/// Payload of `sip://transport-state`: the connection to the server on
+/// TCP, TLS, WS or WSS is `"connected"`, `"reconnecting"` or `"failed"`.
+#[derive(Clone, Serialize)]
+pub struct TransportStatePayload {
+    pub account_id: String,
+    pub state: String,
+    pub message: Option<String>,
+    /// Number of the failed attempt, for `"reconnecting"` and `"failed"`
+    pub attempt: Option<u32>,
+}
+
+/// Payload of `sip://network-changed`: the account's egress IP changed and
+/// its transport and registration are being rebuilt on `new_ip`.
+#[derive(Clone, Serialize)]
+pub struct NetworkChangedPayload {
+    pub account_id: String,
+    pub old_ip: String,
+    pub new_ip: String,
+}
+
+#[derive(Clone, Serialize)]
+pub struct ServerReachablePayload {
+    pub account_id: String,
+    pub reachable: bool,
+}
+
+#[derive(Clone, Serialize)]
+pub struct ConferenceStatePayload {
+    pub state: String,
+    pub call_ids: Vec<String>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct TransferStatePayload {
+    pub state: String,
+    pub call_id: Option<String>,
+    pub reason: Option<String>,
+}
+
+/// Payload of `sip://being-transferred`: the far end transferred the call
+/// `call_id` to `target`. `state` is "trying", "success" or "failed" for a
+/// REFER we act on, or "replaced" when `target` called in to take the call's
+/// place (attended transfer). `new_call_id` is the call that took over.
+#[derive(Clone, Serialize)]
+pub struct BeingTransferredPayload {
+    pub call_id: String,
+    pub target: String,
+    pub state: String,
+    pub new_call_id: Option<String>,
+    pub reason: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct MediaWarningPayload {
+    pub call_id: Option<String>,
+    pub warning: String,
+    pub message: String,
+}
+
+#[derive(Clone, Serialize)]
+pub struct IncomingMessagePayload {
+    /// Account that received the message
+    pub account_id: String,
+    /// Sender's From URI
+    pub from: String,
+    pub body: String,
+    /// Receive time, milliseconds since the Unix epoch
+    pub timestamp: i64,
+}
+
+#[derive(Clone, Serialize)]
+pub struct MwiPayload {
+    /// Account whose mailbox changed
+    pub account_id: String,
+    pub messages_waiting: bool,
+    pub new_messages: u32,
+    pub old_messages: u32,
+}
+
+#[derive(Clone, Serialize)]
+pub struct BlfStatePayload {
+    /// Account holding the subscription
+    pub account_id: String,
+    pub extension: String,
+    /// "idle", "ringing" or "confirmed"
+    pub state: String,
 }
@@ -0,0 +1,190 @@
+use rsip::prelude::HeadersExt;
+use rsipstack::dialog::authenticate::{handle_client_authenticate, Credential};
+use rsipstack::transaction::endpoint::EndpointInnerRef;
+use rsipstack::transaction::key::{TransactionKey, TransactionRole};
+use rsipstack::transaction::make_tag;
+use rsipstack::transaction::transaction::Transaction;
+use rsipstack::Result;
+use std::time::Duration;
+use tokio::select;
+use tokio::time::{interval, MissedTickBehavior};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info};
+use uuid::Uuid;
+
+/// Subscription lifetime requested from the server (seconds)
+pub const SUBSCRIBE_EXPIRES: u32 = 3600;
+
+/// Shortest refresh interval, whatever lifetime the server grants (seconds)
+const MIN_REFRESH_SECS: u64 = 30;
+
+/// SIP event subscription (RFC 6665) for one event package and target,
+/// e.g. `message-summary` on our own mailbox or `dialog` on a monitored extension.
+///
+/// Like `Registrant`, the Call-ID and From tag are generated once and reused
+/// for every refresh; the server's To tag is learned from the first 2xx so
+/// refreshes stay within the subscription dialog. NOTIFYs are not routed
+/// through the dialog layer; `coming_request` recognises them by event package.
+pub struct Subscription {
+    endpoint: EndpointInnerRef,
+    credential: Credential,
+    contact: rsip::Uri,
+    target: rsip::Uri,
+    event: &'static str,
+    accept: &'static str,
+    call_id: rsip::headers::CallId,
+    from_tag: rsip::param::Tag,
+    to_tag: Option<rsip::param::Tag>,
+    seq: u32,
+}
+
+impl Subscription {
+    /// `event` is the event package, `accept` the body type expected in its NOTIFYs.
+    pub fn new(
+        endpoint: EndpointInnerRef,
+        credential: Credential,
+        contact: rsip::Uri,
+        target: rsip::Uri,
+        event: &'static str,
+        accept: &'static str,
+    ) -> Self {
+        Self {
+            endpoint,
+            credential,
+            contact,
+            target,
+            event,
+            accept,
+            call_id: rsip::headers::CallId::from(Uuid::new_v4().to_string()),
+            from_tag: make_tag(),
+            to_tag: None,
+            seq: 0,
+        }
+    }
+
+    /// Send SUBSCRIBE (`expires` = 0 ends the subscription) and return the
+    /// lifetime granted by the server.
+    pub async fn subscribe(&mut self, expires: u32) -> Result<u64> {
+        self.seq += 1;
+        let from = rsip::typed::From {
+            display_name: None,
+            uri: self.contact.clone(),
+            params: vec![],
+        }
+        .with_tag(self.from_tag.clone());
+        let mut to = rsip::typed::To {
+            display_name: None,
+            uri: self.target.clone(),
+            params: vec![],
+        };
+        if let Some(ref tag) = self.to_tag {
+            to = to.with_tag(tag.clone());
+        }
+        let via = self.endpoint.get_via(None, None)?;
+        let mut request = self.endpoint.make_request(
+            rsip::Method::Subscribe,
+            self.target.clone(),
+            via,
+            from,
+            to,
+            self.seq,
+            Some(self.call_id.clone()),
+        );
+        request.headers.push(rsip::Header::Event(self.event.into()));
+        request
+            .headers
+            .push(rsip::Header::Accept(self.accept.into()));
+        request
+            .headers
+            .push(rsip::headers::Expires::from(expires).into());
+        request
+            .headers
+            .push(rsip::typed::Contact::from(self.contact.clone()).into());
+        request
+            .headers
+            .push(rsip::headers::ContentLength::from(0u32).into());
+
+        let key = TransactionKey::from_request(&request, TransactionRole::Client)?;
+        let mut tx = Transaction::new_client(key, request, self.endpoint.clone(), None);
+        tx.send().await?;
+
+        let mut auth_sent = false;
+        while let Some(msg) = tx.receive().await {
+            let rsip::SipMessage::Response(resp) = msg else {
+                continue;
+            };
+            match resp.status_code {
+                rsip::StatusCode::Unauthorized | rsip::StatusCode::ProxyAuthenticationRequired
+                    if !auth_sent =>
+                {
+                    self.seq += 1;
+                    tx = handle_client_authenticate(self.seq, &tx, resp, &self.credential).await?;
+                    tx.send().await?;
+                    auth_sent = true;
+                }
+                ref status if status.kind() == rsip::StatusCodeKind::Provisional => {}
+                ref status if status.kind() == rsip::StatusCodeKind::Successful => {
+                    if let Ok(Some(tag)) = resp.to_header().and_then(|h| h.tag()) {
+                        self.to_tag = Some(tag);
+                    }
+                    let granted = resp
+                        .expires_header()
+                        .and_then(|h| h.seconds().ok())
+                        .unwrap_or(expires);
+                    return Ok(granted as u64);
+                }
+                status => {
+                    // The subscription is gone (e.g. 481 after a server restart): start over
+                    self.to_tag = None;
+                    return Err(rsipstack::Error::Error(format!(
+                        "{} subscription rejected: {}",
+                        self.event, status
+                    )));
+                }
+            }
+        }
+        Err(rsipstack::Error::Error(
+            "No final response to SUBSCRIBE".to_string(),
+        ))
+    }
+
+    /// Keep the subscription alive until cancelled, then unsubscribe.
+    ///
+    /// Refreshes at 75% of the granted lifetime, like the registration refresh
+    /// loop. A failed refresh is retried at the next interval rather than
+    /// ending the loop, since presence and voicemail status are not critical
+    /// to calling.
+    pub async fn run_refresh_loop(mut self, initial_expires: u64, cancel_token: CancellationToken) {
+        let refresh_after =
+            |expires: u64| Duration::from_secs((expires * 3 / 4).max(MIN_REFRESH_SECS));
+        let mut ticker = interval(refresh_after(initial_expires));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        ticker.tick().await; // first tick fires immediately, skip it
+
+        loop {
+            select! {
+                biased;
+                _ = cancel_token.cancelled() => {
+                    if self.to_tag.is_some() {
+                        if let Err(e) = self.subscribe(0).await {
+                            debug!(target = %self.target, event = self.event, error = ?e, "Unsubscribe failed");
+                        }
+                    }
+                    info!(target = %self.target, event = self.event, "Subscription stopped");
+                    return;
+                }
+                _ = ticker.tick() => {
+                    match self.subscribe(SUBSCRIBE_EXPIRES).await {
+                        Ok(expires) => {
+                            ticker.reset_after(refresh_after(expires));
+                            debug!(target = %self.target, event = self.event, expires, "Subscription refreshed");
+                        }
+                        Err(e) => {
+                            error!(target = %self.target, event = self.event, error = ?e, "Subscription refresh failed");
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
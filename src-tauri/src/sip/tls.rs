@@ -0,0 +1,353 @@
+//! Server certificate verification for TLS and WSS transports.
+//!
+//! `strict` validates the chain and hostname against the system CA
+//! certificates, `skip` accepts any certificate (self-signed lab PBXes), and
+//! `pinned` accepts only the certificate with a configured SHA-256 fingerprint.
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::crypto::{ring::default_provider, verify_tls12_signature, verify_tls13_signature};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{
+    CertificateError, ClientConfig, DigitallySignedStruct, OtherError, RootCertStore,
+    SignatureScheme,
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+/// How the server certificate is checked on TLS and WSS connections
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum TlsVerification {
+    /// Chain and hostname validated against the system CA certificates
+    #[default]
+    Strict,
+    /// Any certificate is accepted
+    Skip,
+    /// Only the certificate with this SHA-256 fingerprint is accepted
+    Pinned([u8; 32]),
+}
+
+/// `TlsVerification` as reported to the frontend
+#[derive(Clone, Serialize)]
+pub struct TlsVerificationConfig {
+    pub mode: String,
+    /// `AB:CD:...` fingerprint in pinned mode
+    pub fingerprint: Option<String>,
+}
+
+impl TlsVerification {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TlsVerification::Strict => "strict",
+            TlsVerification::Skip => "skip",
+            TlsVerification::Pinned(_) => "pinned",
+        }
+    }
+
+    /// Parse a mode name; `pinned` requires the certificate's SHA-256 fingerprint.
+    pub fn parse(mode: &str, fingerprint: Option<&str>) -> Result<Self, String> {
+        match mode.to_ascii_lowercase().as_str() {
+            "strict" => Ok(TlsVerification::Strict),
+            "skip" => Ok(TlsVerification::Skip),
+            "pinned" => {
+                let fingerprint =
+                    fingerprint.ok_or("Pinned mode requires a SHA-256 fingerprint")?;
+                Ok(TlsVerification::Pinned(parse_fingerprint(fingerprint)?))
+            }
+            other => Err(format!("Invalid TLS verification mode: {}", other)),
+        }
+    }
+
+    pub fn config(&self) -> TlsVerificationConfig {
+        TlsVerificationConfig {
+            mode: self.as_str().to_string(),
+            fingerprint: match self {
+                TlsVerification::Pinned(fp) => Some(format_fingerprint(fp)),
+                _ => None,
+            },
+        }
+    }
+
+    /// Certificate verifier implementing this mode.
+    pub fn verifier(&self) -> Result<Arc<dyn ServerCertVerifier>, String> {
+        Ok(match self {
+            TlsVerification::Strict => WebPkiServerVerifier::builder_with_provider(
+                Arc::new(system_roots()),
+                Arc::new(default_provider()),
+            )
+            .build()
+            .map_err(|e| format!("Failed to build certificate verifier: {}", e))?,
+            TlsVerification::Skip => Arc::new(SkipCertVerifier),
+            TlsVerification::Pinned(fingerprint) => Arc::new(PinnedCertVerifier {
+                fingerprint: *fingerprint,
+            }),
+        })
+    }
+
+    /// rustls client configuration for TLS and WSS connections.
+    pub fn client_config(&self) -> Result<ClientConfig, String> {
+        Ok(ClientConfig::builder()
+            .dangerous()
+            .with_custom_certificate_verifier(self.verifier()?)
+            .with_no_client_auth())
+    }
+}
+
+/// Parse a SHA-256 fingerprint written as hex, with or without `:` separators.
+fn parse_fingerprint(s: &str) -> Result<[u8; 32], String> {
+    let hex: String = s
+        .chars()
+        .filter(|c| *c != ':' && !c.is_whitespace())
+        .collect();
+    let invalid = || format!("Invalid SHA-256 fingerprint: {}", s);
+    if hex.len() != 64 || !hex.is_ascii() {
+        return Err(invalid());
+    }
+    let mut fingerprint = [0u8; 32];
+    for (i, byte) in fingerprint.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).map_err(|_| invalid())?;
+    }
+    Ok(fingerprint)
+}
+
+fn format_fingerprint(fingerprint: &[u8; 32]) -> String {
+    fingerprint
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// CA certificates from the platform store (`SSL_CERT_FILE` overrides it), or
+/// the bundled Mozilla roots when the store yields none.
+fn system_roots() -> RootCertStore {
+    let native = rustls_native_certs::load_native_certs();
+    for error in &native.errors {
+        debug!(error = %error, "Failed to load system CA certificates");
+    }
+
+    let mut roots = RootCertStore::empty();
+    let (added, ignored) = roots.add_parsable_certificates(native.certs);
+    if added > 0 {
+        debug!(added, ignored, "Loaded system CA certificates");
+        return roots;
+    }
+
+    warn!("No system CA certificates found, using the bundled Mozilla roots");
+    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    roots
+}
+
+/// Handshake signatures are checked in every mode, so the peer must hold the
+/// private key of the certificate it presents.
+fn verify_signature_tls12(
+    message: &[u8],
+    cert: &CertificateDer<'_>,
+    dss: &DigitallySignedStruct,
+) -> Result<HandshakeSignatureValid, rustls::Error> {
+    verify_tls12_signature(
+        message,
+        cert,
+        dss,
+        &default_provider().signature_verification_algorithms,
+    )
+}
+
+fn verify_signature_tls13(
+    message: &[u8],
+    cert: &CertificateDer<'_>,
+    dss: &DigitallySignedStruct,
+) -> Result<HandshakeSignatureValid, rustls::Error> {
+    verify_tls13_signature(
+        message,
+        cert,
+        dss,
+        &default_provider().signature_verification_algorithms,
+    )
+}
+
+fn supported_schemes() -> Vec<SignatureScheme> {
+    default_provider()
+        .signature_verification_algorithms
+        .supported_schemes()
+}
+
+/// TLS verifier that skips certificate chain validation (accepts self-signed certs).
+/// Signature verification is still performed to prevent MITM attacks.
+#[derive(Debug)]
+struct SkipCertVerifier;
+
+impl ServerCertVerifier for SkipCertVerifier {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_signature_tls12(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_signature_tls13(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        supported_schemes()
+    }
+}
+
+/// The server presented a certificate other than the pinned one
+struct FingerprintMismatch {
+    expected: [u8; 32],
+    actual: [u8; 32],
+}
+
+impl std::fmt::Display for FingerprintMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "server certificate SHA-256 fingerprint is {}, but {} is pinned \
+             (update the pin if the server certificate was renewed)",
+            format_fingerprint(&self.actual),
+            format_fingerprint(&self.expected)
+        )
+    }
+}
+
+// rustls formats `CertificateError::Other` with `{:?}`, so keep it readable
+impl std::fmt::Debug for FingerprintMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(self, f)
+    }
+}
+
+impl std::error::Error for FingerprintMismatch {}
+
+/// TLS verifier accepting only the server certificate with a known SHA-256
+/// fingerprint, whatever its issuer, hostname or validity period.
+#[derive(Debug)]
+struct PinnedCertVerifier {
+    fingerprint: [u8; 32],
+}
+
+impl ServerCertVerifier for PinnedCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let actual: [u8; 32] = Sha256::digest(end_entity.as_ref()).into();
+        if actual == self.fingerprint {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            let mismatch = FingerprintMismatch {
+                expected: self.fingerprint,
+                actual,
+            };
+            warn!(error = %mismatch, "Rejected server certificate");
+            Err(rustls::Error::InvalidCertificate(CertificateError::Other(
+                OtherError(Arc::new(mismatch)),
+            )))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_signature_tls12(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_signature_tls13(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FINGERPRINT: &str =
+        "AB:CD:EF:01:23:45:67:89:AB:CD:EF:01:23:45:67:89:AB:CD:EF:01:23:45:67:89:AB:CD:EF:01:23:45:67:89";
+
+    #[test]
+    fn test_parse_modes() {
+        assert_eq!(
+            TlsVerification::parse("Strict", None),
+            Ok(TlsVerification::Strict)
+        );
+        assert_eq!(
+            TlsVerification::parse("skip", None),
+            Ok(TlsVerification::Skip)
+        );
+        assert!(TlsVerification::parse("pinned", None).is_err());
+        assert!(TlsVerification::parse("none", None).is_err());
+
+        let pinned = TlsVerification::parse("pinned", Some(FINGERPRINT)).unwrap();
+        assert_eq!(pinned.config().fingerprint.as_deref(), Some(FINGERPRINT));
+        // Separators and case are optional
+        let bare = FINGERPRINT.replace(':', "").to_lowercase();
+        assert_eq!(TlsVerification::parse("pinned", Some(&bare)), Ok(pinned));
+    }
+
+    #[test]
+    fn test_invalid_fingerprint() {
+        assert!(parse_fingerprint("AB:CD").is_err());
+        assert!(parse_fingerprint(&"G".repeat(64)).is_err());
+        assert!(parse_fingerprint(&"é".repeat(32)).is_err());
+    }
+
+    #[test]
+    fn test_pinned_verifier_checks_fingerprint() {
+        let cert = CertificateDer::from(vec![1u8, 2, 3]);
+        let fingerprint: [u8; 32] = Sha256::digest([1u8, 2, 3]).into();
+        let name = ServerName::try_from("pbx.example.com").unwrap();
+        let verify = |fingerprint| {
+            PinnedCertVerifier { fingerprint }
+                .verify_server_cert(&cert, &[], &name, &[], UnixTime::now())
+                .is_ok()
+        };
+        assert!(verify(fingerprint));
+        assert!(!verify([0u8; 32]));
+
+        let err = PinnedCertVerifier {
+            fingerprint: [0u8; 32],
+        }
+        .verify_server_cert(&cert, &[], &name, &[], UnixTime::now())
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains(&format_fingerprint(&fingerprint)), "{}", err);
+    }
+}
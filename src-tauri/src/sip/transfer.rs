@@ -0,0 +1,472 @@
+use dashmap::DashMap;
+use rsip::prelude::HasHeaders;
+use rsipstack::dialog::dialog::{Dialog, TransactionHandle};
+use rsipstack::dialog::DialogId;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use crate::error::AppError;
+use crate::sip::state::{ActiveCalls, BeingTransferredPayload, SipAppState, TransferStatePayload};
+
+/// Transfer target named by the `Refer-To` of a REFER we received.
+#[derive(Debug, PartialEq)]
+struct ReferTarget {
+    uri: String,
+    /// `Replaces` header embedded in the URI (attended transfer), unescaped
+    replaces: Option<String>,
+}
+
+/// Check whether an in-dialog NOTIFY belongs to a REFER subscription (`Event: refer`).
+///
+/// rsip parses `Event` into a typed header, so both the typed and the raw
+/// `Header::Other` forms are checked.
+fn is_refer_notify(req: &rsip::Request) -> bool {
+    req.headers().iter().any(|h| match h {
+        rsip::Header::Event(e) => e.to_string().to_lowercase().contains("refer"),
+        rsip::Header::Other(name, value) => {
+            name.eq_ignore_ascii_case("event") && value.to_lowercase().contains("refer")
+        }
+        _ => false,
+    })
+}
+
+/// Extract the status code from a `message/sipfrag` body, e.g. `SIP/2.0 180 Ringing`.
+fn parse_sipfrag_status(body: &[u8]) -> Option<u16> {
+    let body = std::str::from_utf8(body).ok()?;
+    let status_line = body.lines().next()?.trim();
+    let mut parts = status_line.split_whitespace();
+    if !parts.next()?.starts_with("SIP/") {
+        return None;
+    }
+    parts.next()?.parse().ok()
+}
+
+/// Percent-encode a value for use inside a URI header parameter (RFC 3261 §19.1.1).
+fn escape_uri_header_value(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for b in value.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'!' | b'~' | b'*'
+            | b'\'' | b'(' | b')' => out.push(b as char),
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+/// Decode the percent-escapes of a URI header parameter value.
+fn unescape_uri_header_value(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = value.get(i + 1..i + 3).filter(|_| bytes[i] == b'%');
+        match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+            Some(b) => {
+                out.push(b);
+                i += 3;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Value of the first header named `name` (or its compact form `compact`).
+fn header_value(req: &rsip::Request, name: &str, compact: Option<&str>) -> Option<String> {
+    req.headers().iter().find_map(|h| match h {
+        rsip::Header::Other(n, value)
+            if n.eq_ignore_ascii_case(name)
+                || compact.is_some_and(|c| n.eq_ignore_ascii_case(c)) =>
+        {
+            Some(value.trim().to_string())
+        }
+        _ => None,
+    })
+}
+
+/// Parse a `Refer-To` value such as `<sip:1002@pbx;transport=tcp?Replaces=...>`.
+fn parse_refer_to(value: &str) -> Option<ReferTarget> {
+    let value = value.trim();
+    let addr = match value.strip_prefix('<') {
+        Some(rest) => rest.split('>').next()?,
+        None => value.split(';').next()?,
+    };
+    let (uri, headers) = match addr.split_once('?') {
+        Some((uri, headers)) => (uri, Some(headers)),
+        None => (addr, None),
+    };
+    if !uri.starts_with("sip:") && !uri.starts_with("sips:") {
+        return None;
+    }
+    let replaces = headers.and_then(|headers| {
+        headers.split('&').find_map(|header| {
+            let (name, value) = header.split_once('=')?;
+            name.eq_ignore_ascii_case("replaces")
+                .then(|| unescape_uri_header_value(value))
+        })
+    });
+    Some(ReferTarget {
+        uri: uri.to_string(),
+        replaces,
+    })
+}
+
+/// `Replaces` header of an incoming INVITE (RFC 3891), if any.
+pub fn replaces_header(req: &rsip::Request) -> Option<String> {
+    header_value(req, "Replaces", None)
+}
+
+/// The active call a `Replaces` value names: `call-id;to-tag=..;from-tag=..`,
+/// the to-tag being our tag. Either tag order is accepted, as not every
+/// transferor gets it right.
+pub fn find_replaced_call(active: &ActiveCalls, replaces: &str) -> Option<String> {
+    let mut parts = replaces.split(';').map(str::trim);
+    let call_id = parts.next().filter(|c| !c.is_empty())?;
+    let (mut to_tag, mut from_tag) = (None, None);
+    for param in parts {
+        match param.split_once('=') {
+            Some((name, tag)) if name.eq_ignore_ascii_case("to-tag") => to_tag = Some(tag),
+            Some((name, tag)) if name.eq_ignore_ascii_case("from-tag") => from_tag = Some(tag),
+            _ => {}
+        }
+    }
+    let (to_tag, from_tag) = (to_tag?, from_tag?);
+    active
+        .find_by_dialog_id(&format!("{}-{}-{}", call_id, to_tag, from_tag))
+        .or_else(|| active.find_by_dialog_id(&format!("{}-{}-{}", call_id, from_tag, to_tag)))
+}
+
+/// Final sipfrag status for a transfer call that failed with `error`: the
+/// target's rejection if it sent one, else 503.
+fn failure_status(error: &AppError) -> rsip::StatusCode {
+    match error {
+        AppError::CallRejected(status) => status.clone(),
+        _ => rsip::StatusCode::ServiceUnavailable,
+    }
+}
+
+/// Build the `Refer-To` value for an attended transfer (RFC 3891 / RFC 5589).
+///
+/// The target is the remote party of the consultation dialog, with an embedded
+/// `Replaces` header naming that dialog from the target's point of view: its
+/// to-tag is our remote tag and its from-tag is our local tag.
+pub fn replaces_refer_to(target: &rsip::Uri, consult: &DialogId) -> String {
+    let replaces = format!(
+        "{};to-tag={};from-tag={}",
+        consult.call_id, consult.remote_tag, consult.local_tag
+    );
+    format!("<{}?Replaces={}>", target, escape_uri_header_value(&replaces))
+}
+
+/// Emit a `sip://transfer-state` event.
+pub fn emit_transfer_state(
+    app_handle: &AppHandle,
+    state: &str,
+    call_id: Option<String>,
+    reason: Option<String>,
+) {
+    let _ = app_handle.emit(
+        "sip://transfer-state",
+        TransferStatePayload {
+            state: state.to_string(),
+            call_id,
+            reason,
+        },
+    );
+}
+
+/// Emit a `sip://being-transferred` event.
+pub fn emit_being_transferred(
+    app_handle: &AppHandle,
+    call_id: &str,
+    target: &str,
+    state: &str,
+    new_call_id: Option<String>,
+    reason: Option<String>,
+) {
+    let _ = app_handle.emit(
+        "sip://being-transferred",
+        BeingTransferredPayload {
+            call_id: call_id.to_string(),
+            target: target.to_string(),
+            state: state.to_string(),
+            new_call_id,
+            reason,
+        },
+    );
+}
+
+/// Report the progress of the call to a REFER target to the transferor with a
+/// NOTIFY carrying a `message/sipfrag` status line (RFC 3515 §2.4.5). A final
+/// status also ends the implicit subscription.
+async fn notify_refer_progress(dialog: &Dialog, status: rsip::StatusCode) {
+    let subscription_state = if u16::from(status.clone()) >= 200 {
+        "terminated;reason=noresource"
+    } else {
+        "active;expires=60"
+    };
+    let headers = vec![
+        rsip::Header::Other("Event".into(), "refer".into()),
+        rsip::Header::Other("Subscription-State".into(), subscription_state.into()),
+        rsip::Header::ContentType("message/sipfrag;version=2.0".into()),
+    ];
+    let body = format!("SIP/2.0 {}", status).into_bytes();
+    let result = match dialog {
+        Dialog::ClientInvite(d) => d.notify(Some(headers), Some(body)).await,
+        Dialog::ServerInvite(d) => d.notify(Some(headers), Some(body)).await,
+        _ => Ok(None),
+    };
+    if let Err(e) = result {
+        warn!(status = %status, error = ?e, "Failed to send REFER NOTIFY");
+    }
+}
+
+/// Act on a REFER received on a call: we are being transferred.
+///
+/// The REFER is accepted with 202 and the `Refer-To` target called like any
+/// outbound call, with the embedded `Replaces` header for an attended transfer.
+/// The transferor learns the outcome through NOTIFYs; once the new call is
+/// connected our leg of the original call is released with BYE, otherwise it
+/// is left up. Progress is emitted as `sip://being-transferred`.
+pub async fn process_refer(
+    id: DialogId,
+    req: rsip::Request,
+    tx_handle: TransactionHandle,
+    app_handle: AppHandle,
+    account_id: String,
+    active_calls: Arc<tokio::sync::Mutex<ActiveCalls>>,
+    active_call_tokens: Arc<DashMap<String, CancellationToken>>,
+) {
+    let call = {
+        let active = active_calls.lock().await;
+        active
+            .find_by_dialog_id(&id.to_string())
+            .and_then(|call_id| active.get(&call_id))
+            .map(|call| (call.call_id.clone(), call.dialog.clone()))
+    };
+    let Some((call_id, dialog)) = call else {
+        warn!(dialog_id = %id, "REFER for a call that is no longer active, rejecting");
+        let _ = tx_handle
+            .reply(rsip::StatusCode::CallTransactionDoesNotExist)
+            .await;
+        return;
+    };
+    let Some(target) = header_value(&req, "Refer-To", Some("r")).and_then(|v| parse_refer_to(&v))
+    else {
+        warn!(call_id = %call_id, "REFER without a usable Refer-To, rejecting");
+        let _ = tx_handle.reply(rsip::StatusCode::BadRequest).await;
+        return;
+    };
+    let state = app_handle.state::<SipAppState>();
+    let Some(handle) = state.handles.lock().await.get(&account_id).cloned() else {
+        let _ = tx_handle
+            .reply(rsip::StatusCode::TemporarilyUnavailable)
+            .await;
+        return;
+    };
+
+    let accepted = rsip::StatusCode::Other(202, "Accepted".into());
+    if let Err(e) = tx_handle.reply(accepted).await {
+        warn!(call_id = %call_id, error = ?e, "Failed to accept REFER");
+        return;
+    }
+    info!(call_id = %call_id, target = %target.uri, attended = target.replaces.is_some(), "Being transferred");
+    emit_being_transferred(&app_handle, &call_id, &target.uri, "trying", None, None);
+    notify_refer_progress(&dialog, rsip::StatusCode::Trying).await;
+
+    let media = crate::media_config(&state).await;
+    let support_100rel = *state.prack_enabled.lock().await;
+    let headers = target
+        .replaces
+        .clone()
+        .map(|replaces| HashMap::from([("Replaces".to_string(), replaces)]));
+    let result = super::handle_make_call(
+        &handle,
+        target.uri.clone(),
+        handle.cancel_token.clone(),
+        media,
+        support_100rel,
+        headers,
+        None,
+        false,
+    )
+    .await;
+
+    match result {
+        Ok(new_call_id) => {
+            info!(call_id = %call_id, new_call_id = %new_call_id, "Transfer target answered");
+            notify_refer_progress(&dialog, rsip::StatusCode::OK).await;
+            emit_being_transferred(
+                &app_handle,
+                &call_id,
+                &target.uri,
+                "success",
+                Some(new_call_id),
+                None,
+            );
+            end_transferred_call(&app_handle, &id, &active_calls, &active_call_tokens).await;
+        }
+        Err(e) => {
+            let reason = e.to_string();
+            warn!(call_id = %call_id, error = %reason, "Call to transfer target failed");
+            notify_refer_progress(&dialog, failure_status(&e)).await;
+            emit_being_transferred(
+                &app_handle,
+                &call_id,
+                &target.uri,
+                "failed",
+                None,
+                Some(reason),
+            );
+        }
+    }
+}
+
+/// Handle a NOTIFY received on an INVITE dialog.
+///
+/// Always answers 200 OK. For REFER progress notifications the sipfrag status
+/// is mapped onto `sip://transfer-state`: 1xx → "trying", 2xx → "success",
+/// anything else → "failed". On success our leg of the call is torn down with
+/// BYE since the remote party is now talking to the transfer target; on
+/// failure the call is left up so the user can resume it.
+pub async fn process_notify(
+    id: DialogId,
+    req: rsip::Request,
+    tx_handle: TransactionHandle,
+    app_handle: AppHandle,
+    active_calls: Arc<tokio::sync::Mutex<ActiveCalls>>,
+    active_call_tokens: Arc<DashMap<String, CancellationToken>>,
+) {
+    if let Err(e) = tx_handle.reply(rsip::StatusCode::OK).await {
+        warn!(dialog_id = %id, error = ?e, "Failed to reply to NOTIFY");
+    }
+
+    if !is_refer_notify(&req) {
+        debug!(dialog_id = %id, "Ignoring non-refer NOTIFY");
+        return;
+    }
+
+    let Some(code) = parse_sipfrag_status(&req.body) else {
+        warn!(dialog_id = %id, "REFER NOTIFY without a valid sipfrag body");
+        return;
+    };
+
+    let call_id = id.call_id.clone();
+    let reason = Some(
+        String::from_utf8_lossy(&req.body)
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .to_string(),
+    );
+    info!(dialog_id = %id, status = code, "Transfer progress");
+
+    match code {
+        100..=199 => emit_transfer_state(&app_handle, "trying", Some(call_id), reason),
+        200..=299 => {
+            emit_transfer_state(&app_handle, "success", Some(call_id.clone()), reason);
+            end_transferred_call(&app_handle, &id, &active_calls, &active_call_tokens).await;
+        }
+        _ => emit_transfer_state(&app_handle, "failed", Some(call_id), reason),
+    }
+}
+
+/// Tear down our leg of a successfully transferred call, plus the consultation
+/// leg for attended transfers (normally the target already released it with BYE).
+async fn end_transferred_call(
+    app_handle: &AppHandle,
+    id: &DialogId,
+    active_calls: &Arc<tokio::sync::Mutex<ActiveCalls>>,
+    active_call_tokens: &Arc<DashMap<String, CancellationToken>>,
+) {
+    let dialog_id = id.to_string();
+    let calls = {
+        let mut active = active_calls.lock().await;
+        let call = active.find_by_dialog_id(&dialog_id).and_then(|call_id| {
+            super::remove_active_call(app_handle, &mut active, &call_id, "transferred")
+        });
+        let peer = call
+            .as_ref()
+            .and_then(|c| c.transfer_peer.clone())
+            .and_then(|peer_id| {
+                super::remove_active_call(app_handle, &mut active, &peer_id, "transferred")
+            });
+        call.into_iter().chain(peer).collect::<Vec<_>>()
+    };
+
+    if calls.is_empty() {
+        debug!(dialog_id = %dialog_id, "Transferred call is no longer active");
+        return;
+    }
+
+    for mut call in calls {
+        call.cancel_token.cancel();
+        if let Some(ref mut session) = call.webrtc_session {
+            session.close().await;
+        }
+        let call_dialog_id = call.dialog_id();
+        active_call_tokens.remove(&call_dialog_id);
+
+        let result = match call.dialog {
+            Dialog::ClientInvite(d) => d.bye().await,
+            Dialog::ServerInvite(d) => d.bye().await,
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            warn!(dialog_id = %call_dialog_id, error = ?e, "Failed to send BYE after transfer");
+        } else {
+            info!(dialog_id = %call_dialog_id, call_id = %call.call_id, "Call transferred, local leg released");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_refer_to_with_replaces() {
+        let consult = DialogId {
+            call_id: "abc@10.0.0.1".to_string(),
+            local_tag: "111".to_string(),
+            remote_tag: "222".to_string(),
+        };
+        let target = rsip::Uri::try_from("sip:1002@pbx.example.com").unwrap();
+        let refer_to = replaces_refer_to(&target, &consult);
+
+        assert_eq!(
+            parse_refer_to(&refer_to),
+            Some(ReferTarget {
+                uri: "sip:1002@pbx.example.com".to_string(),
+                replaces: Some("abc@10.0.0.1;to-tag=222;from-tag=111".to_string()),
+            })
+        );
+        assert_eq!(
+            parse_refer_to("sip:1003@pbx.example.com;method=INVITE"),
+            Some(ReferTarget {
+                uri: "sip:1003@pbx.example.com".to_string(),
+                replaces: None,
+            })
+        );
+        assert_eq!(parse_refer_to("<tel:+15551234>"), None);
+    }
+
+    #[test]
+    fn test_failure_status_from_rejection() {
+        assert_eq!(
+            failure_status(&AppError::CallRejected(rsip::StatusCode::BusyHere)),
+            rsip::StatusCode::BusyHere
+        );
+        assert_eq!(
+            failure_status(&AppError::MediaFailed("Connection timed out".to_string())),
+            rsip::StatusCode::ServiceUnavailable
+        );
+    }
+}
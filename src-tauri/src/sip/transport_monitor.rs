@@ -0,0 +1,219 @@
+//! Supervision of the connection to the server on TCP, TLS, WS and WSS.
+//!
+//! When the server or a NAT drops the socket, rsipstack stops reading from it
+//! but keeps it in its send map, so every later request fails. The
+//! `ClosedConnectionInspector` reports the closure to a `TransportSupervisor`,
+//! which opens a new connection, puts it in place of the dead one and asks
+//! the registration refresh loop to register again at once.
+//!
+//! The supervisor also sends the RFC 5626 double-CRLF keepalive on the
+//! connection, which keeps NAT bindings open and lets a dead connection fail
+//! (and be rebuilt) without waiting for the next REGISTER. The server's CRLF
+//! pong is consumed by rsipstack's stream reader and never reaches the app.
+
+use async_trait::async_trait;
+use rsipstack::transaction::endpoint::{EndpointInnerRef, TransportEventInspector};
+use rsipstack::transport::connection::KEEPALIVE_REQUEST;
+use rsipstack::transport::stream::StreamConnection;
+use rsipstack::transport::{SipAddr, SipConnection, TransportEvent};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+use tokio::select;
+use tokio::sync::{mpsc, watch, Notify};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
+
+use crate::sip::helpers::create_transport_connection;
+use crate::sip::registration::retry_delay;
+use crate::sip::state::TransportStatePayload;
+use crate::sip::tls::TlsVerification;
+
+/// Forwards the address of every closed connection; all events pass through unchanged.
+pub struct ClosedConnectionInspector {
+    closed: mpsc::UnboundedSender<SipAddr>,
+}
+
+impl ClosedConnectionInspector {
+    pub fn new() -> (Self, mpsc::UnboundedReceiver<SipAddr>) {
+        let (closed, rx) = mpsc::unbounded_channel();
+        (Self { closed }, rx)
+    }
+}
+
+#[async_trait]
+impl TransportEventInspector for ClosedConnectionInspector {
+    async fn handle(&self, event: TransportEvent) -> Option<TransportEvent> {
+        if let TransportEvent::Closed(connection) = &event {
+            let _ = self.closed.send(connection.get_addr().clone());
+        }
+        Some(event)
+    }
+}
+
+/// Rebuilds the account's connection to the server when it closes.
+pub struct TransportSupervisor {
+    endpoint: EndpointInnerRef,
+    local_addr: SocketAddr,
+    target: SipAddr,
+    ws_path: Option<String>,
+    tls: TlsVerification,
+    /// The supervised connection; its address is the key in the transport
+    /// layer's send map
+    connection: SipConnection,
+    reregister: Arc<Notify>,
+    app_handle: AppHandle,
+    account_id: String,
+    /// Connection attempts before giving up (0 = unlimited)
+    max_attempts: u32,
+}
+
+impl TransportSupervisor {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        endpoint: EndpointInnerRef,
+        local_addr: SocketAddr,
+        target: SipAddr,
+        ws_path: Option<String>,
+        tls: TlsVerification,
+        connection: SipConnection,
+        reregister: Arc<Notify>,
+        app_handle: AppHandle,
+        account_id: String,
+        max_attempts: u32,
+    ) -> Self {
+        Self {
+            endpoint,
+            local_addr,
+            target,
+            ws_path,
+            tls,
+            connection,
+            reregister,
+            app_handle,
+            account_id,
+            max_attempts,
+        }
+    }
+
+    fn emit_state(&self, state: &str, message: Option<String>, attempt: Option<u32>) {
+        let _ = self.app_handle.emit(
+            "sip://transport-state",
+            TransportStatePayload {
+                account_id: self.account_id.clone(),
+                state: state.to_string(),
+                message,
+                attempt,
+            },
+        );
+    }
+
+    /// Send one double-CRLF ping on the supervised connection.
+    async fn send_crlf_ping(&self) -> rsipstack::Result<()> {
+        match &self.connection {
+            SipConnection::Tcp(c) => c.send_raw(KEEPALIVE_REQUEST).await,
+            SipConnection::Tls(c) => c.send_raw(KEEPALIVE_REQUEST).await,
+            SipConnection::WebSocket(c) => c.send_raw(KEEPALIVE_REQUEST).await,
+            _ => Ok(()),
+        }
+    }
+
+    /// Wait for the supervised connection to close and reconnect, until
+    /// cancellation or until the connection cannot be rebuilt.
+    ///
+    /// Meanwhile a CRLF ping is sent every `crlf_interval` seconds (`0` =
+    /// disabled); a new interval takes effect immediately.
+    pub async fn run(
+        mut self,
+        mut closed: mpsc::UnboundedReceiver<SipAddr>,
+        mut crlf_interval: watch::Receiver<u64>,
+        cancel_token: CancellationToken,
+    ) {
+        loop {
+            let secs = *crlf_interval.borrow_and_update();
+            let ping_due = async {
+                match secs {
+                    0 => std::future::pending().await,
+                    secs => tokio::time::sleep(Duration::from_secs(secs)).await,
+                }
+            };
+            let addr = select! {
+                _ = cancel_token.cancelled() => return,
+                changed = crlf_interval.changed() => {
+                    if changed.is_err() {
+                        return;
+                    }
+                    continue;
+                }
+                _ = ping_due => {
+                    // A write to a dead connection fails, or makes the read
+                    // side fail soon after, which reports it as closed
+                    match self.send_crlf_ping().await {
+                        Ok(()) => debug!(account = %self.account_id, "CRLF keepalive sent"),
+                        Err(e) => warn!(account = %self.account_id, error = %e, "CRLF keepalive failed"),
+                    }
+                    continue;
+                }
+                addr = closed.recv() => match addr {
+                    Some(addr) => addr,
+                    None => return,
+                },
+            };
+            // Unregistering closes the connection too
+            if cancel_token.is_cancelled() {
+                return;
+            }
+            if &addr != self.connection.get_addr() {
+                continue;
+            }
+
+            warn!(account = %self.account_id, remote = %addr, "Connection to server closed, reconnecting");
+            self.endpoint.transport_layer.del_connection(&addr);
+            self.emit_state("reconnecting", None, None);
+            if !self.reconnect(&cancel_token).await {
+                return;
+            }
+        }
+    }
+
+    /// Open a new connection with backoff; `false` when giving up or cancelled.
+    async fn reconnect(&mut self, cancel_token: &CancellationToken) -> bool {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            match create_transport_connection(
+                self.local_addr,
+                self.target.clone(),
+                cancel_token.clone(),
+                self.ws_path.clone(),
+                &self.tls,
+            )
+            .await
+            {
+                Ok(connection) => {
+                    self.connection = connection.clone();
+                    self.endpoint.transport_layer.add_connection(connection);
+                    info!(account = %self.account_id, remote = %self.connection.get_addr(), attempt, "Reconnected to server");
+                    self.emit_state("connected", None, None);
+                    self.reregister.notify_one();
+                    return true;
+                }
+                Err(e) => {
+                    if self.max_attempts != 0 && attempt >= self.max_attempts {
+                        error!(account = %self.account_id, attempt, error = %e, "Reconnecting to server failed, giving up");
+                        self.emit_state("failed", Some(e.to_string()), Some(attempt));
+                        return false;
+                    }
+                    let delay = retry_delay(attempt);
+                    warn!(account = %self.account_id, attempt, error = %e, retry_in = ?delay, "Reconnecting to server failed");
+                    self.emit_state("reconnecting", Some(e.to_string()), Some(attempt));
+                    select! {
+                        _ = cancel_token.cancelled() => return false,
+                        _ = tokio::time::sleep(delay) => {}
+                    }
+                }
+            }
+        }
+    }
+}
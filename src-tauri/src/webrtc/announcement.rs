@@ -0,0 +1,74 @@
+//! Announcement: a WAV file played once to the far end in place of the mic.
+//!
+//! Like music on hold, the file is decoded up front and stepped through at the
+//! codec rate with linear interpolation; unlike it, it does not loop.
+
+use super::ringtone::{load_wav, LoopSamples};
+
+pub struct Announcement {
+    audio: LoopSamples,
+    /// Source samples per codec sample
+    step: f64,
+    /// Read position in the source, in samples
+    pos: f64,
+}
+
+impl Announcement {
+    /// Decode `path` for playback at `sample_rate` (the negotiated codec rate).
+    pub fn load(path: &str, sample_rate: u32) -> Result<Self, String> {
+        Ok(Self::new(load_wav(path)?, sample_rate))
+    }
+
+    fn new(audio: LoopSamples, sample_rate: u32) -> Self {
+        Self {
+            step: audio.sample_rate as f64 / sample_rate.max(1) as f64,
+            audio,
+            pos: 0.0,
+        }
+    }
+
+    /// Fill `pcm` with the next samples, silence past the end of the file.
+    pub fn fill(&mut self, pcm: &mut [f32]) {
+        let samples = &self.audio.samples;
+        let last = samples.len().saturating_sub(1);
+        for out in pcm.iter_mut() {
+            let index = self.pos as usize;
+            *out = if index < last {
+                let frac = (self.pos - index as f64) as f32;
+                samples[index] + (samples[index + 1] - samples[index]) * frac
+            } else if index == last {
+                samples[index]
+            } else {
+                0.0
+            };
+            self.pos += self.step;
+        }
+    }
+
+    /// Whether the whole file has been played
+    pub fn is_finished(&self) -> bool {
+        self.pos as usize >= self.audio.samples.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fill_plays_once_then_silence() {
+        let audio = LoopSamples {
+            samples: vec![0.0, 0.5, 1.0],
+            sample_rate: 8000,
+        };
+        let mut announcement = Announcement::new(audio, 16000);
+        let mut pcm = [9.0f32; 4];
+        announcement.fill(&mut pcm);
+        assert_eq!(pcm, [0.0, 0.25, 0.5, 0.75]);
+        assert!(!announcement.is_finished());
+
+        announcement.fill(&mut pcm);
+        assert_eq!(pcm, [1.0, 1.0, 0.0, 0.0]);
+        assert!(announcement.is_finished());
+    }
+}
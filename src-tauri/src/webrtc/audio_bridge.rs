@@ -1,19 +1,285 @@
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::{HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
 use std::sync::Arc;
 
 use bytes::Bytes;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::{DeviceId, SampleFormat, StreamConfig};
 use ringbuf::traits::{Consumer, Observer, Producer, Split};
-use ringbuf::HeapRb;
+use ringbuf::{HeapCons, HeapProd, HeapRb};
 use rustrtc::media::frame::{AudioFrame, MediaSample};
 use rustrtc::media::track::{sample_track, SampleStreamSource, SampleStreamTrack};
 use rustrtc::media::MediaStreamTrack;
-use tokio::sync::Notify;
+use serde::Serialize;
+use tokio::sync::{mpsc, Notify};
 use tracing::{debug, error, info, warn};
 
-use super::codec::{CodecTypeExt, NegotiatedCodec};
+use crate::error::AppError;
+
+use super::announcement::Announcement;
+use super::codec::{AudioCodec, NegotiatedCodec};
+use super::comfort_noise::{self, ComfortNoise, CN_PAYLOAD_TYPE};
+use super::conference::ConferenceLink;
 use super::denoiser::NoiseReducer;
+use super::echo_canceller::EchoCanceller;
+use super::encoder::{FrameEncoder, OpusConfig};
+use super::hold_music::HoldMusic;
+use super::inband_dtmf;
+use super::jitter_buffer::{JitterBuffer, JitterStats, Playout};
+use super::plc::FrameDecoder;
+use super::recorder::{CallRecorder, RecordingMode};
+use super::telephone_event::{DtmfDetector, ReceivedDtmf};
+use super::vad::{VadConfig, VoiceActivityDetector};
+
+/// Minimum interval between two level reports for the same direction (~20 Hz)
+const LEVEL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(50);
+
+/// Default jitter buffer target depth
+pub const DEFAULT_JITTER_BUFFER_MS: u32 = 60;
+
+/// Default capacity of the ring buffers between the audio devices and the
+/// codec tasks. Smaller buffers cap the latency a slow device or a busy task
+/// can build up, at the cost of dropped samples (audible glitches) sooner.
+pub const DEFAULT_AUDIO_BUFFER_MS: u32 = 200;
+
+/// Ring buffer capacity of the low-latency preset
+pub const LOW_LATENCY_AUDIO_BUFFER_MS: u32 = 60;
+
+/// Upper bound on audio held by the jitter buffer
+const JITTER_BUFFER_MAX_MS: u32 = 1000;
+
+/// Echo reference buffer size: 500 ms at the highest codec rate (48 kHz)
+const ECHO_REFERENCE_CAPACITY: usize = 24_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioDirection {
+    Mic,
+    Speaker,
+}
+
+/// An error reported by a cpal stream (XRUN, driver error, lost device).
+#[derive(Clone, Debug)]
+pub struct AudioStreamError {
+    pub direction: AudioDirection,
+    pub device: String,
+    pub message: String,
+}
+
+/// The configured device was not found and the system default is used instead.
+#[derive(Clone, Debug)]
+pub struct AudioFallback {
+    pub direction: AudioDirection,
+    /// Id of the configured device
+    pub requested: String,
+    /// Name of the default device used instead
+    pub chosen: String,
+}
+
+/// RMS and peak level (0.0 - 1.0) over the last `LEVEL_INTERVAL`.
+#[derive(Clone, Copy, Debug)]
+pub struct AudioLevel {
+    pub direction: AudioDirection,
+    pub rms: f32,
+    pub peak: f32,
+}
+
+/// Accumulates the level of processed frames and reports it at most every
+/// `LEVEL_INTERVAL`. Runs in the capture/playback tokio tasks, never in the
+/// cpal callbacks; reports are dropped if the receiver falls behind.
+struct LevelMeter {
+    direction: AudioDirection,
+    tx: mpsc::Sender<AudioLevel>,
+    sum_squares: f64,
+    samples: usize,
+    peak: f32,
+    last_report: std::time::Instant,
+}
+
+impl LevelMeter {
+    fn new(direction: AudioDirection, tx: mpsc::Sender<AudioLevel>) -> Self {
+        Self {
+            direction,
+            tx,
+            sum_squares: 0.0,
+            samples: 0,
+            peak: 0.0,
+            last_report: std::time::Instant::now(),
+        }
+    }
+
+    fn process(&mut self, pcm: &[f32]) {
+        for &s in pcm {
+            self.sum_squares += (s * s) as f64;
+            self.peak = self.peak.max(s.abs());
+        }
+        self.samples += pcm.len();
+
+        if self.last_report.elapsed() < LEVEL_INTERVAL || self.samples == 0 {
+            return;
+        }
+        let level = AudioLevel {
+            direction: self.direction,
+            rms: (self.sum_squares / self.samples as f64).sqrt() as f32,
+            peak: self.peak.min(1.0),
+        };
+        let _ = self.tx.try_send(level);
+
+        self.sum_squares = 0.0;
+        self.samples = 0;
+        self.peak = 0.0;
+        self.last_report = std::time::Instant::now();
+    }
+}
+
+/// Highest software gain for the speaker volume and mic gain (1.0 = unchanged)
+pub const MAX_GAIN: f32 = 1.5;
+
+/// Linear gain shared with an audio task, stored as `f32` bits.
+#[derive(Clone)]
+struct Gain(Arc<AtomicU32>);
+
+impl Gain {
+    fn new(gain: f32) -> Self {
+        Self(Arc::new(AtomicU32::new(gain.to_bits())))
+    }
+
+    fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    fn set(&self, gain: f32) {
+        let gain = gain.clamp(0.0, MAX_GAIN);
+        self.0.store(gain.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Scale samples in place, clamping to full scale so boosted peaks clip
+    /// cleanly instead of wrapping when converted to integers.
+    fn apply(&self, samples: &mut [f32]) {
+        let gain = self.get();
+        if gain == 1.0 {
+            return;
+        }
+        for s in samples.iter_mut() {
+            *s = (*s * gain).clamp(-1.0, 1.0);
+        }
+    }
+}
+
+/// RTP timestamp of the outgoing stream, shared by the capture task and
+/// RFC 4733 telephone-events so both use the same timeline.
+///
+/// The next frame's timestamp (low 32 bits) and the event flag (`EVENT_BIT`)
+/// share one atomic, so an audio frame and an event never take the same timestamp.
+#[derive(Clone, Default)]
+struct RtpClock {
+    state: Arc<AtomicU64>,
+}
+
+/// Set while a telephone-event is sent; audio frames are held back meanwhile
+const EVENT_BIT: u64 = 1 << 32;
+
+impl RtpClock {
+    /// Timestamp of the next audio frame
+    fn next(&self) -> u32 {
+        self.state.load(Ordering::Acquire) as u32
+    }
+
+    /// Called by the capture task once per frame: move the clock past the frame
+    /// at `timestamp` and return whether it may be sent, i.e. no event holds
+    /// back audio.
+    fn claim_frame(&self, timestamp: u32, ticks: u32) -> bool {
+        let next = timestamp.wrapping_add(ticks) as u64;
+        let previous = self
+            .state
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |state| {
+                Some(state & EVENT_BIT | next)
+            })
+            .unwrap_or_else(|state| state);
+        previous & EVENT_BIT == 0
+    }
+
+    /// Hold back audio and return the event's base timestamp: that of the
+    /// first audio frame not yet claimed, which the event replaces.
+    fn begin_event(&self) -> u32 {
+        self.state.fetch_or(EVENT_BIT, Ordering::AcqRel) as u32
+    }
+
+    fn end_event(&self) {
+        self.state.fetch_and(!EVENT_BIT, Ordering::AcqRel);
+    }
+}
+
+/// Sends DTMF on a call's outgoing stream, taken from `AudioBridge::dtmf_channel`
+/// so digits can be sent without holding on to the bridge.
+#[derive(Clone)]
+pub struct DtmfChannel {
+    audio_source: SampleStreamSource,
+    rtp_clock: RtpClock,
+    inband_dtmf: Arc<std::sync::Mutex<VecDeque<f32>>>,
+    /// Codec sample rate once the call media has started
+    sample_rate: Option<u32>,
+}
+
+impl DtmfChannel {
+    /// Queue a DTMF digit's dual tone to replace the outgoing audio (even while
+    /// the mic is muted). Returns how long the tone and its trailing gap take to send.
+    pub fn queue_inband_dtmf(&self, digit: char) -> Result<std::time::Duration, String> {
+        let rate = self
+            .sample_rate
+            .ok_or_else(|| "Call media has not started yet".to_string())?;
+        let tone = inband_dtmf::tone(digit, rate)
+            .ok_or_else(|| format!("Invalid DTMF digit: {}", digit))?;
+        let duration = std::time::Duration::from_secs_f64(tone.len() as f64 / rate as f64);
+        self.inband_dtmf.lock().unwrap().extend(tone);
+        Ok(duration)
+    }
+
+    /// Start an RFC 4733 event: audio packets are held back until
+    /// `end_dtmf_event`. Returns the event's timestamp, taken from the audio
+    /// stream's timeline.
+    pub fn begin_dtmf_event(&self) -> u32 {
+        self.rtp_clock.begin_event()
+    }
+
+    /// Resume sending audio after an RFC 4733 event.
+    pub fn end_dtmf_event(&self) {
+        self.rtp_clock.end_event();
+    }
+
+    /// Send a single RFC 4733 telephone-event RTP packet.
+    /// Called repeatedly by send_dtmf() to transmit one DTMF event.
+    /// `clock_rate` is the audio stream's RTP clock, which `timestamp` and the
+    /// payload's duration are counted in.
+    pub async fn send_dtmf_packet(
+        &self,
+        payload: &[u8],
+        pt: u8,
+        timestamp: u32,
+        clock_rate: u32,
+    ) -> Result<(), String> {
+        let frame = AudioFrame {
+            rtp_timestamp: timestamp,
+            clock_rate,
+            data: Bytes::from(payload.to_vec()),
+            payload_type: Some(pt),
+            ..Default::default()
+        };
+        self.audio_source
+            .send_audio(frame)
+            .await
+            .map_err(|_| "DTMF send channel closed".to_string())
+    }
+}
+
+/// Audio frames exchanged with the remote, for one-way audio detection.
+#[derive(Default)]
+pub struct MediaFlow {
+    /// Frames handed to the RTP sender
+    pub sent: AtomicU64,
+    /// Audio frames received from the remote track
+    pub received: AtomicU64,
+}
 
 /// AudioBridge connects cpal audio I/O to rustrtc media tracks.
 pub struct AudioBridge {
@@ -23,9 +289,114 @@ pub struct AudioBridge {
     speaker_muted: Arc<AtomicBool>,
     noise_reduce: Arc<AtomicBool>,
     speaker_noise_reduce: Arc<AtomicBool>,
-    stop_notify: Arc<Notify>,
+    /// Wet/dry mix of both noise reducers (0.0-1.0)
+    noise_reduce_strength: Gain,
+    echo_cancel: Arc<AtomicBool>,
+    /// Software gains applied to the speaker output and the mic input
+    speaker_volume: Gain,
+    mic_gain: Gain,
+    jitter_buffer_ms: Arc<AtomicU32>,
+    jitter_stats: Arc<JitterStats>,
+    plc: Arc<AtomicBool>,
+    comfort_noise: Arc<AtomicBool>,
+    /// Ring buffer capacity, applied when a capture or playback stream is built
+    audio_buffer_ms: AtomicU32,
+    /// Ring buffer overflows and playback underruns, counted once per device
+    /// callback or frame they affect
+    ring_xruns: Arc<AtomicU64>,
+    /// Opus encoder settings, picked up by the capture task on its next frame
+    opus_config: Arc<std::sync::Mutex<OpusConfig>>,
+    /// Voice activity detection: silence is not sent
+    vad: Arc<std::sync::Mutex<VadConfig>>,
+    on_hold: Arc<AtomicBool>,
+    /// The far end has held us: the output stream is paused
+    playback_paused: bool,
+    /// Far-end audio handed from playback to capture as the echo reference;
+    /// each half is taken by the running task and put back when it stops, so a
+    /// restarted stream picks it up again
+    echo_reference_tx: Arc<std::sync::Mutex<Option<HeapProd<f32>>>>,
+    echo_reference_rx: Arc<std::sync::Mutex<Option<HeapCons<f32>>>>,
+    /// Set while the call is part of a local conference
+    conference: Arc<std::sync::Mutex<Option<ConferenceLink>>>,
+    /// Set while the call is being recorded; fed by both the capture and playback tasks
+    recorder: Arc<std::sync::Mutex<Option<CallRecorder>>>,
+    /// In-band DTMF tone samples (codec rate) still to be sent in place of the mic
+    inband_dtmf: Arc<std::sync::Mutex<VecDeque<f32>>>,
+    /// Music sent in place of the mic while on hold (nothing is sent without it)
+    hold_music: Arc<std::sync::Mutex<Option<HoldMusic>>>,
+    /// WAV file sent once in place of the mic
+    announcement: Arc<std::sync::Mutex<Option<Announcement>>>,
+    /// End of each announcement: `true` when played to the end, `false` when stopped
+    announcement_done_tx: mpsc::UnboundedSender<bool>,
+    announcement_done_rx: Option<mpsc::UnboundedReceiver<bool>>,
+    /// Stop signals for the current capture / playback tasks (replaced on reconnect)
+    capture_stop: Arc<Notify>,
+    playback_stop: Arc<Notify>,
+    closed: bool,
     audio_source: SampleStreamSource,
     input_device_name: Option<String>,
+    output_device_name: Option<String>,
+    /// Codec and remote track of the running streams, kept to restart them on reconnect
+    negotiated: Option<NegotiatedCodec>,
+    remote_track: Option<Arc<SampleStreamTrack>>,
+    /// Next RTP timestamp to send, so a restarted capture task continues the
+    /// sequence and DTMF events line up with the audio
+    rtp_clock: RtpClock,
+    device_loss: DeviceLossSignal,
+    device_loss_rx: Option<mpsc::UnboundedReceiver<AudioDirection>>,
+    stream_error_rx: Option<mpsc::Receiver<AudioStreamError>>,
+    fallback_tx: mpsc::UnboundedSender<AudioFallback>,
+    fallback_rx: Option<mpsc::UnboundedReceiver<AudioFallback>>,
+    /// Configured device ids already reported as replaced by the default
+    reported_fallbacks: HashSet<String>,
+    level_tx: mpsc::Sender<AudioLevel>,
+    level_rx: Option<mpsc::Receiver<AudioLevel>>,
+    /// Digits decoded from received telephone-events
+    dtmf_tx: mpsc::Sender<ReceivedDtmf>,
+    dtmf_rx: Option<mpsc::Receiver<ReceivedDtmf>>,
+    /// When the playback task last received an RTP packet (or the hold state changed)
+    last_received: Arc<std::sync::Mutex<std::time::Instant>>,
+    flow: Arc<MediaFlow>,
+    /// Receive-only call: the microphone is never opened and no RTP is sent
+    listen_only: bool,
+}
+
+/// Raised from the cpal error callbacks when a device disappears. Reported once
+/// per stream generation; `AudioBridge::reconnect` re-arms it. Every stream
+/// error is also passed on through `errors` (dropped if the receiver lags).
+#[derive(Clone)]
+struct DeviceLossSignal {
+    lost: Arc<AtomicBool>,
+    tx: mpsc::UnboundedSender<AudioDirection>,
+    errors: mpsc::Sender<AudioStreamError>,
+}
+
+impl DeviceLossSignal {
+    /// Error callback for a cpal stream on `device`: logs and reports the error,
+    /// and reports a lost device.
+    fn error_callback(
+        &self,
+        direction: AudioDirection,
+        device: &cpal::Device,
+    ) -> impl FnMut(cpal::StreamError) + Send {
+        let signal = self.clone();
+        let device = device_name(device);
+        move |err| {
+            error!(?direction, "Audio stream error: {}", err);
+            let _ = signal.errors.try_send(AudioStreamError {
+                direction,
+                device: device.clone(),
+                message: err.to_string(),
+            });
+            if matches!(
+                err,
+                cpal::StreamError::DeviceNotAvailable | cpal::StreamError::StreamInvalidated
+            ) && !signal.lost.swap(true, Ordering::Relaxed)
+            {
+                let _ = signal.tx.send(direction);
+            }
+        }
+    }
 }
 
 impl AudioBridge {
@@ -40,13 +411,10 @@ impl AudioBridge {
     ) -> Result<(Self, Arc<SampleStreamTrack>), String> {
         let host = cpal::default_host();
 
-        // Validate input device exists
-        let input_device = if let Some(name) = input_device_name {
-            find_device_by_id(&host, name)?
-        } else {
-            host.default_input_device()
-                .ok_or_else(|| "No microphone found. Please connect a microphone and try again.".to_string())?
-        };
+        // Validate input device exists, falling back to the default one
+        let (input_device, input_fallback) =
+            device_or_default(&host, AudioDirection::Mic, input_device_name)
+                .ok_or_else(|| "No microphone found. Please connect a microphone and try again.".to_string())?;
 
         // Validate the input device is actually accessible and can provide a config.
         // This catches missing microphone permission and devices that exist but cannot be opened.
@@ -61,13 +429,26 @@ impl AudioBridge {
             }
         })?;
 
+        let input_desc = device_name(&input_device);
+        info!(input = %input_desc, "Audio input device selected");
+
+        let (mut bridge, track) = Self::new_playback_only(output_device_name)?;
+        bridge.input_device_name = input_device_name.map(|s| s.to_string());
+        bridge.report_fallback(input_fallback);
+        Ok((bridge, track))
+    }
+
+    /// Create an AudioBridge that only plays to the speaker, e.g. a test tone, so
+    /// no microphone is needed. `start_capture()` would use the default one.
+    pub fn new_playback_only(
+        output_device_name: Option<&str>,
+    ) -> Result<(Self, Arc<SampleStreamTrack>), String> {
+        let host = cpal::default_host();
+
         // Validate output device exists and is accessible
-        let output_device = if let Some(name) = output_device_name {
-            find_device_by_id(&host, name)?
-        } else {
-            host.default_output_device()
-                .ok_or_else(|| "No speaker or audio output device found. Please connect one and try again.".to_string())?
-        };
+        let (output_device, output_fallback) =
+            device_or_default(&host, AudioDirection::Speaker, output_device_name)
+                .ok_or_else(|| "No speaker or audio output device found. Please connect one and try again.".to_string())?;
 
         output_device.default_output_config().map_err(|_| {
             #[cfg(target_os = "macos")]
@@ -80,51 +461,148 @@ impl AudioBridge {
             }
         })?;
 
-        let input_desc = input_device
-            .description()
-            .map(|d| d.name().to_string())
-            .unwrap_or_default();
-        info!(input = %input_desc, "Audio input device selected");
-
         // Create sample track for sending captured audio
         let (audio_source, track, _feedback_rx) =
             sample_track(rustrtc::media::frame::MediaKind::Audio, 100);
 
-        let bridge = AudioBridge {
+        let (level_tx, level_rx) = mpsc::channel(32);
+        let (dtmf_tx, dtmf_rx) = mpsc::channel(32);
+        let (echo_reference_tx, echo_reference_rx) =
+            HeapRb::<f32>::new(ECHO_REFERENCE_CAPACITY).split();
+        let (device_loss_tx, device_loss_rx) = mpsc::unbounded_channel();
+        let (stream_error_tx, stream_error_rx) = mpsc::channel(32);
+        let (fallback_tx, fallback_rx) = mpsc::unbounded_channel();
+        let (announcement_done_tx, announcement_done_rx) = mpsc::unbounded_channel();
+
+        let mut bridge = AudioBridge {
             capture_stream: None,
             playback_stream: None,
             mic_muted: Arc::new(AtomicBool::new(false)),
             speaker_muted: Arc::new(AtomicBool::new(false)),
             noise_reduce: Arc::new(AtomicBool::new(false)),
             speaker_noise_reduce: Arc::new(AtomicBool::new(false)),
-            stop_notify: Arc::new(Notify::new()),
+            noise_reduce_strength: Gain::new(1.0),
+            echo_cancel: Arc::new(AtomicBool::new(false)),
+            speaker_volume: Gain::new(1.0),
+            mic_gain: Gain::new(1.0),
+            jitter_buffer_ms: Arc::new(AtomicU32::new(DEFAULT_JITTER_BUFFER_MS)),
+            jitter_stats: Arc::new(JitterStats::default()),
+            plc: Arc::new(AtomicBool::new(true)),
+            comfort_noise: Arc::new(AtomicBool::new(true)),
+            audio_buffer_ms: AtomicU32::new(DEFAULT_AUDIO_BUFFER_MS),
+            ring_xruns: Arc::new(AtomicU64::new(0)),
+            opus_config: Arc::new(std::sync::Mutex::new(OpusConfig::default())),
+            vad: Arc::new(std::sync::Mutex::new(VadConfig::default())),
+            on_hold: Arc::new(AtomicBool::new(false)),
+            playback_paused: false,
+            echo_reference_tx: Arc::new(std::sync::Mutex::new(Some(echo_reference_tx))),
+            echo_reference_rx: Arc::new(std::sync::Mutex::new(Some(echo_reference_rx))),
+            conference: Arc::new(std::sync::Mutex::new(None)),
+            recorder: Arc::new(std::sync::Mutex::new(None)),
+            inband_dtmf: Arc::new(std::sync::Mutex::new(VecDeque::new())),
+            hold_music: Arc::new(std::sync::Mutex::new(None)),
+            announcement: Arc::new(std::sync::Mutex::new(None)),
+            announcement_done_tx,
+            announcement_done_rx: Some(announcement_done_rx),
+            capture_stop: Arc::new(Notify::new()),
+            playback_stop: Arc::new(Notify::new()),
+            closed: false,
             audio_source,
-            input_device_name: input_device_name.map(|s| s.to_string()),
+            input_device_name: None,
+            output_device_name: output_device_name.map(|s| s.to_string()),
+            negotiated: None,
+            remote_track: None,
+            rtp_clock: RtpClock::default(),
+            device_loss: DeviceLossSignal {
+                lost: Arc::new(AtomicBool::new(false)),
+                tx: device_loss_tx,
+                errors: stream_error_tx,
+            },
+            device_loss_rx: Some(device_loss_rx),
+            stream_error_rx: Some(stream_error_rx),
+            fallback_tx,
+            fallback_rx: Some(fallback_rx),
+            reported_fallbacks: HashSet::new(),
+            level_tx,
+            level_rx: Some(level_rx),
+            dtmf_tx,
+            dtmf_rx: Some(dtmf_rx),
+            last_received: Arc::new(std::sync::Mutex::new(std::time::Instant::now())),
+            flow: Arc::new(MediaFlow::default()),
+            listen_only: false,
         };
+        bridge.report_fallback(output_fallback);
 
         Ok((bridge, track))
     }
 
+    /// Create an AudioBridge for a receive-only call: no microphone is needed
+    /// and `start_capture()` leaves it closed, so nothing is sent.
+    pub fn new_listen_only(
+        output_device_name: Option<&str>,
+    ) -> Result<(Self, Arc<SampleStreamTrack>), String> {
+        let (mut bridge, track) = Self::new_playback_only(output_device_name)?;
+        bridge.listen_only = true;
+        Ok((bridge, track))
+    }
+
+    /// Pass a device fallback on to `take_fallback_receiver`, once per configured device.
+    fn report_fallback(&mut self, fallback: Option<AudioFallback>) {
+        if let Some(fallback) = fallback {
+            if self.reported_fallbacks.insert(fallback.requested.clone()) {
+                let _ = self.fallback_tx.send(fallback);
+            }
+        }
+    }
+
     /// Start capturing audio from the microphone using the negotiated codec.
     pub fn start_capture(&mut self, negotiated: &NegotiatedCodec) -> Result<(), String> {
+        if self.listen_only {
+            info!("Listen-only call, microphone not started");
+            return Ok(());
+        }
         let host = cpal::default_host();
-        let input_device = if let Some(ref name) = self.input_device_name {
-            find_device_by_id(&host, name)?
-        } else {
-            host.default_input_device()
-                .ok_or_else(|| "No default input device".to_string())?
+        let (input_device, fallback) = device_or_default(
+            &host,
+            AudioDirection::Mic,
+            self.input_device_name.as_deref(),
+        )
+        .ok_or_else(|| "No default input device".to_string())?;
+        self.report_fallback(fallback);
+
+        let controls = CaptureControls {
+            mic_muted: self.mic_muted.clone(),
+            mic_gain: self.mic_gain.clone(),
+            noise_reduce: self.noise_reduce.clone(),
+            noise_reduce_strength: self.noise_reduce_strength.clone(),
+            echo_cancel: self.echo_cancel.clone(),
+            echo_reference: self.echo_reference_rx.clone(),
+            opus_config: self.opus_config.clone(),
+            vad: self.vad.clone(),
+            on_hold: self.on_hold.clone(),
+            conference: self.conference.clone(),
+            recorder: self.recorder.clone(),
+            inband_dtmf: self.inband_dtmf.clone(),
+            hold_music: self.hold_music.clone(),
+            announcement: self.announcement.clone(),
+            announcement_done: self.announcement_done_tx.clone(),
+            level_tx: self.level_tx.clone(),
+            rtp_clock: self.rtp_clock.clone(),
+            device_loss: self.device_loss.clone(),
+            buffer_ms: self.audio_buffer_ms.load(Ordering::Relaxed),
+            xruns: self.ring_xruns.clone(),
+            flow: self.flow.clone(),
         };
-
         let capture_stream = setup_capture_stream(
             &input_device,
             &self.audio_source,
-            self.mic_muted.clone(),
-            self.noise_reduce.clone(),
-            self.stop_notify.clone(),
+            controls,
+            self.capture_stop.clone(),
             negotiated,
         )?;
 
         self.capture_stream = Some(capture_stream);
+        self.negotiated = Some(negotiated.clone());
         info!(codec = ?negotiated.codec, ptime = negotiated.ptime_ms, "Capture started");
         Ok(())
     }
@@ -137,27 +615,53 @@ impl AudioBridge {
         negotiated: &NegotiatedCodec,
     ) -> Result<(), String> {
         let host = cpal::default_host();
-        let output_device = if let Some(name) = output_device_name {
-            find_device_by_id(&host, name)?
-        } else {
-            host.default_output_device()
-                .ok_or_else(|| "No default output device".to_string())?
+        let (output_device, fallback) =
+            device_or_default(&host, AudioDirection::Speaker, output_device_name)
+                .ok_or_else(|| "No default output device".to_string())?;
+        self.report_fallback(fallback);
+
+        let controls = PlaybackControls {
+            speaker_muted: self.speaker_muted.clone(),
+            speaker_volume: self.speaker_volume.clone(),
+            speaker_noise_reduce: self.speaker_noise_reduce.clone(),
+            noise_reduce_strength: self.noise_reduce_strength.clone(),
+            jitter_buffer_ms: self.jitter_buffer_ms.clone(),
+            jitter_stats: self.jitter_stats.clone(),
+            plc: self.plc.clone(),
+            comfort_noise: self.comfort_noise.clone(),
+            echo_reference: self.echo_reference_tx.clone(),
+            conference: self.conference.clone(),
+            recorder: self.recorder.clone(),
+            level_tx: self.level_tx.clone(),
+            dtmf_tx: self.dtmf_tx.clone(),
+            device_loss: self.device_loss.clone(),
+            last_received: self.last_received.clone(),
+            buffer_ms: self.audio_buffer_ms.load(Ordering::Relaxed),
+            xruns: self.ring_xruns.clone(),
+            flow: self.flow.clone(),
         };
-
         let playback_stream = setup_playback_stream(
             &output_device,
-            remote_track,
-            self.speaker_muted.clone(),
-            self.speaker_noise_reduce.clone(),
-            self.stop_notify.clone(),
+            remote_track.clone(),
+            controls,
+            self.playback_stop.clone(),
             negotiated,
         )?;
 
         self.playback_stream = Some(playback_stream);
+        self.output_device_name = output_device_name.map(|s| s.to_string());
+        self.remote_track = Some(remote_track);
+        self.negotiated = Some(negotiated.clone());
         info!(codec = ?negotiated.codec, ptime = negotiated.ptime_ms, "Playback started");
         Ok(())
     }
 
+    /// Whether received audio is being played (playback may start before capture
+    /// for early media).
+    pub fn is_playing(&self) -> bool {
+        self.playback_stream.is_some()
+    }
+
     pub fn toggle_mic_mute(&self) -> bool {
         let prev = self.mic_muted.fetch_xor(true, Ordering::Relaxed);
         let new_state = !prev;
@@ -172,6 +676,24 @@ impl AudioBridge {
         new_state
     }
 
+    pub fn set_mic_mute(&self, muted: bool) {
+        self.mic_muted.store(muted, Ordering::Relaxed);
+        info!(muted, "Microphone mute set");
+    }
+
+    pub fn set_speaker_mute(&self, muted: bool) {
+        self.speaker_muted.store(muted, Ordering::Relaxed);
+        info!(muted, "Speaker mute set");
+    }
+
+    pub fn is_mic_muted(&self) -> bool {
+        self.mic_muted.load(Ordering::Relaxed)
+    }
+
+    pub fn is_speaker_muted(&self) -> bool {
+        self.speaker_muted.load(Ordering::Relaxed)
+    }
+
     /// Toggle microphone noise reduction. Returns new enabled state.
     pub fn toggle_noise_reduce(&self) -> bool {
         let prev = self.noise_reduce.fetch_xor(true, Ordering::Relaxed);
@@ -192,33 +714,398 @@ impl AudioBridge {
         info!(enabled, "Speaker noise reduction set");
     }
 
-    /// Send a single RFC 4733 telephone-event RTP packet.
-    /// Called repeatedly by send_dtmf() to transmit one DTMF event.
-    pub async fn send_dtmf_packet(
-        &self,
-        payload: &[u8],
-        pt: u8,
-        timestamp: u32,
-    ) -> Result<(), String> {
-        let frame = AudioFrame {
-            rtp_timestamp: timestamp,
-            clock_rate: 8000, // telephone-event clock is always 8000 Hz
-            data: Bytes::from(payload.to_vec()),
-            payload_type: Some(pt),
-            ..Default::default()
+    /// Set how much of the denoised signal both noise reducers output
+    /// (0.0 = bypass, 1.0 = full denoise), picked up on their next frame.
+    pub fn set_noise_reduce_strength(&self, strength: f32) {
+        self.noise_reduce_strength.set(strength.clamp(0.0, 1.0));
+        info!(strength, "Noise reduction strength set");
+    }
+
+    /// Set acoustic echo cancellation to a specific state.
+    pub fn set_echo_cancel(&self, enabled: bool) {
+        self.echo_cancel.store(enabled, Ordering::Relaxed);
+        info!(enabled, "Echo cancellation set");
+    }
+
+    /// Set the speaker volume (0.0 - `MAX_GAIN`).
+    pub fn set_speaker_volume(&self, volume: f32) {
+        self.speaker_volume.set(volume);
+        info!(volume, "Speaker volume set");
+    }
+
+    /// Set the microphone gain (0.0 - `MAX_GAIN`).
+    pub fn set_mic_gain(&self, gain: f32) {
+        self.mic_gain.set(gain);
+        info!(gain, "Microphone gain set");
+    }
+
+    /// Set the playback jitter buffer target depth in milliseconds.
+    pub fn set_jitter_buffer_ms(&self, ms: u32) {
+        self.jitter_buffer_ms.store(ms, Ordering::Relaxed);
+        info!(ms, "Jitter buffer target set");
+    }
+
+    /// Enable or disable packet loss concealment.
+    pub fn set_plc(&self, enabled: bool) {
+        self.plc.store(enabled, Ordering::Relaxed);
+        info!(enabled, "Packet loss concealment set");
+    }
+
+    /// Set the ring buffer capacity in milliseconds. Takes effect when the
+    /// streams are next built (a new call or an audio reconnect).
+    pub fn set_audio_buffer_ms(&self, ms: u32) {
+        self.audio_buffer_ms.store(ms, Ordering::Relaxed);
+        info!(ms, "Audio ring buffer size set");
+    }
+
+    /// Ring buffer overflows and playback underruns so far.
+    pub fn ring_xruns(&self) -> u64 {
+        self.ring_xruns.load(Ordering::Relaxed)
+    }
+
+    /// Counter behind `ring_xruns`, for reading it without the bridge.
+    pub fn ring_xruns_counter(&self) -> Arc<AtomicU64> {
+        self.ring_xruns.clone()
+    }
+
+    /// Enable or disable comfort noise in receive gaps (takes effect immediately).
+    pub fn set_comfort_noise(&self, enabled: bool) {
+        self.comfort_noise.store(enabled, Ordering::Relaxed);
+        info!(enabled, "Comfort noise set");
+    }
+
+    /// Set the Opus encoder settings (applied to the running encoder, if any).
+    pub fn set_opus_config(&self, config: OpusConfig) {
+        info!(?config, "Opus encoder config set");
+        *self.opus_config.lock().unwrap() = config;
+    }
+
+    /// Change only the Opus bitrate, keeping FEC and DTX as configured.
+    pub fn set_opus_bitrate(&self, bitrate: u32) {
+        debug!(bitrate, "Opus bitrate set");
+        self.opus_config.lock().unwrap().bitrate = bitrate;
+    }
+
+    /// Set voice activity detection, picked up by the capture task on its next frame.
+    pub fn set_vad(&self, config: VadConfig) {
+        info!(?config, "VAD config set");
+        *self.vad.lock().unwrap() = config;
+    }
+
+    /// Counters of the audio exchanged with the remote.
+    pub fn media_flow(&self) -> Arc<MediaFlow> {
+        self.flow.clone()
+    }
+
+    /// Codec of the running streams, once media has started.
+    pub fn negotiated_codec(&self) -> Option<AudioCodec> {
+        self.negotiated.as_ref().map(|n| n.codec)
+    }
+
+    /// Jitter buffer counters for this call's playback.
+    pub fn jitter_stats(&self) -> Arc<JitterStats> {
+        self.jitter_stats.clone()
+    }
+
+    /// Put the capture path on hold (or take it off hold).
+    ///
+    /// While on hold the capture task stops sending RTP and the cpal input
+    /// stream is paused, but both stream objects stay alive so resuming does
+    /// not need to renegotiate or reopen the devices.
+    pub fn set_hold(&self, held: bool) {
+        self.on_hold.store(held, Ordering::Relaxed);
+        // The far end may stop sending while held; time silence from here on
+        *self.last_received.lock().unwrap() = std::time::Instant::now();
+        if let Some(ref stream) = self.capture_stream {
+            let result = if held {
+                stream.pause().map_err(|e| e.to_string())
+            } else {
+                stream.play().map_err(|e| e.to_string())
+            };
+            if let Err(e) = result {
+                warn!(error = %e, held, "Failed to change capture stream state");
+            }
+        }
+        info!(held, "Audio hold set");
+    }
+
+    /// Loop the WAV file at `path` to the far end while on hold (`None` stops it).
+    ///
+    /// The file is decoded at the negotiated codec rate, so media must have
+    /// started. On error the previous music is dropped and hold stays silent.
+    pub fn set_hold_music(&self, path: Option<&str>) -> Result<(), String> {
+        self.hold_music.lock().unwrap().take();
+        let Some(path) = path else {
+            return Ok(());
         };
-        let source = self.audio_source.clone();
-        source
-            .send_audio(frame)
-            .await
-            .map_err(|_| "DTMF send channel closed".to_string())
+        let rate = self
+            .negotiated
+            .as_ref()
+            .ok_or_else(|| "Call media has not started yet".to_string())?
+            .sample_rate;
+        *self.hold_music.lock().unwrap() = Some(HoldMusic::load(path, rate)?);
+        info!(path, "Hold music loaded");
+        Ok(())
+    }
+
+    /// Send the WAV file at `path` to the far end once, in place of the mic,
+    /// replacing an announcement already playing.
+    ///
+    /// The file is decoded at the negotiated codec rate, so media must have started.
+    pub fn play_announcement(&self, path: &str) -> Result<(), String> {
+        let rate = self
+            .negotiated
+            .as_ref()
+            .ok_or_else(|| "Call media has not started yet".to_string())?
+            .sample_rate;
+        let announcement = Announcement::load(path, rate)?;
+        let replaced = self.announcement.lock().unwrap().replace(announcement);
+        if replaced.is_some() {
+            let _ = self.announcement_done_tx.send(false);
+        }
+        info!(path, "Announcement started");
+        Ok(())
+    }
+
+    /// Stop the announcement and return to the mic. Returns whether one was playing.
+    pub fn stop_announcement(&self) -> bool {
+        let stopped = self.announcement.lock().unwrap().take().is_some();
+        if stopped {
+            let _ = self.announcement_done_tx.send(false);
+            info!("Announcement stopped");
+        }
+        stopped
+    }
+
+    /// Pause the speaker output while the far end has held us (or resume it).
+    ///
+    /// The playback task keeps receiving and decoding, so the jitter buffer,
+    /// recording and DTMF detection carry on; only the cpal output stream stops.
+    pub fn set_playback_paused(&mut self, paused: bool) {
+        self.playback_paused = paused;
+        if let Some(ref stream) = self.playback_stream {
+            let result = if paused {
+                stream.pause().map_err(|e| e.to_string())
+            } else {
+                stream.play().map_err(|e| e.to_string())
+            };
+            if let Err(e) = result {
+                warn!(error = %e, paused, "Failed to change playback stream state");
+            }
+        }
+        info!(paused, "Audio playback pause set");
+    }
+
+    /// Time since an RTP packet was last received (or the hold state last changed).
+    pub fn media_idle(&self) -> std::time::Duration {
+        self.last_received.lock().unwrap().elapsed()
+    }
+
+    /// Take the receiver of device-loss reports (available once). Each report
+    /// names the direction whose device disappeared.
+    pub fn take_device_loss_receiver(&mut self) -> Option<mpsc::UnboundedReceiver<AudioDirection>> {
+        self.device_loss_rx.take()
+    }
+
+    /// Switch the microphone (`None` = system default). During a call the
+    /// capture stream is rebuilt on the new device with the negotiated codec;
+    /// mute, hold and noise-reduce state carry over.
+    pub fn switch_input_device(&mut self, device_id: Option<&str>) -> Result<(), AppError> {
+        if let Some(id) = device_id {
+            find_device_by_id(&cpal::default_host(), id)?;
+        }
+        self.input_device_name = device_id.map(|s| s.to_string());
+        if self.closed || self.capture_stream.is_none() {
+            return Ok(());
+        }
+        info!(device = ?device_id, "Switching input device");
+        Ok(self.restart_capture()?)
+    }
+
+    /// Switch the speaker (`None` = system default). During a call the
+    /// playback stream is rebuilt on the new device with the negotiated codec.
+    pub fn switch_output_device(&mut self, device_id: Option<&str>) -> Result<(), AppError> {
+        if let Some(id) = device_id {
+            find_device_by_id(&cpal::default_host(), id)?;
+        }
+        self.output_device_name = device_id.map(|s| s.to_string());
+        if self.closed || self.playback_stream.is_none() {
+            return Ok(());
+        }
+        info!(device = ?device_id, "Switching output device");
+        Ok(self.restart_playback()?)
+    }
+
+    /// Re-open the audio devices and restart capture and playback with the
+    /// negotiated codec, e.g. after a headset was unplugged mid-call.
+    ///
+    /// The configured devices are used if still present, otherwise the system
+    /// defaults. The bridge stays open throughout: only the streams and their
+    /// tasks are replaced.
+    pub fn reconnect(&mut self) -> Result<(), String> {
+        if self.closed {
+            return Err("Audio bridge is closed".to_string());
+        }
+        if self.negotiated.is_none() {
+            return Err("Audio has not started yet".to_string());
+        }
+        info!("Reconnecting audio devices");
+
+        let host = cpal::default_host();
+        if let Some(ref name) = self.input_device_name {
+            if find_device_by_id(&host, name).is_err() {
+                warn!(device = %name, "Configured input device gone, using default");
+                self.input_device_name = None;
+            }
+        }
+        if let Some(ref name) = self.output_device_name {
+            if find_device_by_id(&host, name).is_err() {
+                warn!(device = %name, "Configured output device gone, using default");
+                self.output_device_name = None;
+            }
+        }
+
+        self.restart_capture()?;
+        if self.remote_track.is_some() {
+            self.restart_playback()?;
+        }
+        info!("Audio devices reconnected");
+        Ok(())
+    }
+
+    /// Stop the capture stream and task, then start them again on the current input device.
+    fn restart_capture(&mut self) -> Result<(), String> {
+        let negotiated = self
+            .negotiated
+            .clone()
+            .ok_or_else(|| "Audio has not started yet".to_string())?;
+        self.capture_stop.notify_one();
+        self.capture_stream.take();
+        self.capture_stop = Arc::new(Notify::new());
+        self.device_loss.lost.store(false, Ordering::Relaxed);
+
+        self.start_capture(&negotiated)?;
+        if self.on_hold.load(Ordering::Relaxed) {
+            self.set_hold(true);
+        }
+        Ok(())
+    }
+
+    /// Stop the playback stream and task, then start them again on the current output device.
+    fn restart_playback(&mut self) -> Result<(), String> {
+        let (Some(negotiated), Some(remote_track)) =
+            (self.negotiated.clone(), self.remote_track.clone())
+        else {
+            return Err("Playback has not started yet".to_string());
+        };
+        self.playback_stop.notify_one();
+        self.playback_stream.take();
+        self.playback_stop = Arc::new(Notify::new());
+        self.device_loss.lost.store(false, Ordering::Relaxed);
+
+        let output_device = self.output_device_name.clone();
+        self.start_playback(output_device.as_deref(), remote_track, &negotiated)?;
+        if self.playback_paused {
+            self.set_playback_paused(true);
+        }
+        Ok(())
+    }
+
+    /// Take the receiver of mic/speaker level reports (available once).
+    pub fn take_level_receiver(&mut self) -> Option<mpsc::Receiver<AudioLevel>> {
+        self.level_rx.take()
+    }
+
+    /// Take the receiver of announcement ends (available once).
+    pub fn take_announcement_done_receiver(&mut self) -> Option<mpsc::UnboundedReceiver<bool>> {
+        self.announcement_done_rx.take()
+    }
+
+    /// Take the receiver of reports of configured devices replaced by the
+    /// system default (available once).
+    pub fn take_fallback_receiver(&mut self) -> Option<mpsc::UnboundedReceiver<AudioFallback>> {
+        self.fallback_rx.take()
+    }
+
+    /// Take the receiver of audio stream errors (available once).
+    pub fn take_stream_error_receiver(&mut self) -> Option<mpsc::Receiver<AudioStreamError>> {
+        self.stream_error_rx.take()
+    }
+
+    /// Take the receiver of DTMF digits sent by the far end (available once).
+    pub fn take_dtmf_receiver(&mut self) -> Option<mpsc::Receiver<ReceivedDtmf>> {
+        self.dtmf_rx.take()
+    }
+
+    /// Join (`Some`) or leave (`None`) a local conference.
+    ///
+    /// While joined, decoded remote audio is handed to the conference mixer
+    /// (even with the speaker muted) and the other legs' audio is mixed into
+    /// every frame we send.
+    pub fn set_conference(&self, link: Option<ConferenceLink>) {
+        let joined = link.is_some();
+        *self.conference.lock().unwrap() = link;
+        info!(joined, "Audio conference link set");
+    }
+
+    /// Start recording the call to a WAV file at `path`.
+    ///
+    /// `Stereo` puts the local mic on the left channel and the far end on the
+    /// right; `Mixed` writes both into a single channel. Needs the negotiated
+    /// codec, so it is only available once media has started.
+    pub fn start_recording(&self, path: &str, mode: RecordingMode) -> Result<(), String> {
+        let negotiated = self
+            .negotiated
+            .as_ref()
+            .ok_or_else(|| "Call media has not started yet".to_string())?;
+        let mut recorder = self.recorder.lock().unwrap();
+        if let Some(ref current) = *recorder {
+            return Err(format!("Already recording to '{}'", current.path()));
+        }
+        *recorder = Some(CallRecorder::create(path, mode, negotiated.sample_rate)?);
+        info!(path, ?mode, "Call recording started");
+        Ok(())
+    }
+
+    /// Stop recording and return the path of the finished file.
+    pub fn stop_recording(&self) -> Result<String, String> {
+        let recorder = self
+            .recorder
+            .lock()
+            .unwrap()
+            .take()
+            .ok_or_else(|| "Call is not being recorded".to_string())?;
+        let path = recorder.finish()?;
+        info!(path = %path, "Call recording stopped");
+        Ok(path)
+    }
+
+    /// Handle for sending DTMF on the outgoing stream.
+    pub fn dtmf_channel(&self) -> DtmfChannel {
+        DtmfChannel {
+            audio_source: self.audio_source.clone(),
+            rtp_clock: self.rtp_clock.clone(),
+            inband_dtmf: self.inband_dtmf.clone(),
+            sample_rate: self.negotiated.as_ref().map(|n| n.sample_rate),
+        }
     }
 
     pub fn close(&mut self) {
+        // Runs from both `WebRtcSession::close()` and `Drop`; only the first call counts
+        if self.closed {
+            return;
+        }
+        self.closed = true;
         info!("Closing audio bridge");
-        self.stop_notify.notify_waiters();
+        self.capture_stop.notify_one();
+        self.playback_stop.notify_one();
         self.capture_stream.take();
         self.playback_stream.take();
+        if let Some(recorder) = self.recorder.lock().unwrap().take() {
+            if let Err(e) = recorder.finish() {
+                warn!("{}", e);
+            }
+        }
     }
 }
 
@@ -229,23 +1116,163 @@ impl Drop for AudioBridge {
 }
 
 /// Find a cpal device by its ID string (format: "host:device_id").
-fn find_device_by_id(host: &cpal::Host, id_str: &str) -> Result<cpal::Device, String> {
+pub(crate) fn find_device_by_id(host: &cpal::Host, id_str: &str) -> Result<cpal::Device, AppError> {
     let device_id: DeviceId = id_str
         .parse()
-        .map_err(|e| format!("Invalid device ID '{}': {}", id_str, e))?;
+        .map_err(|e| AppError::Other(format!("Invalid device ID '{}': {}", id_str, e)))?;
     host.device_by_id(&device_id)
-        .ok_or_else(|| format!("Audio device not found: {}", id_str))
+        .ok_or_else(|| AppError::DeviceNotFound(id_str.to_string()))
+}
+
+/// ID and name of the system default device for `direction`, if any
+pub(crate) fn default_device(direction: AudioDirection) -> Option<(String, String)> {
+    let host = cpal::default_host();
+    let device = match direction {
+        AudioDirection::Mic => host.default_input_device(),
+        AudioDirection::Speaker => host.default_output_device(),
+    }?;
+    let id = device.id().ok()?.to_string();
+    Some((id, device_name(&device)))
+}
+
+/// Display name of a device
+fn device_name(device: &cpal::Device) -> String {
+    device
+        .description()
+        .map(|d| d.name().to_string())
+        .unwrap_or_default()
+}
+
+/// The device with id `id` if it still exists, otherwise the system default for
+/// `direction` along with a description of the fallback (none when `id` is
+/// `None`). `None` when there is no default device either.
+fn device_or_default(
+    host: &cpal::Host,
+    direction: AudioDirection,
+    id: Option<&str>,
+) -> Option<(cpal::Device, Option<AudioFallback>)> {
+    if let Some(id) = id {
+        match find_device_by_id(host, id) {
+            Ok(device) => return Some((device, None)),
+            Err(e) => {
+                warn!(?direction, device = %id, error = %e, "Configured audio device not found, using the default");
+            }
+        }
+    }
+    let device = match direction {
+        AudioDirection::Mic => host.default_input_device(),
+        AudioDirection::Speaker => host.default_output_device(),
+    }?;
+    let fallback = id.map(|requested| AudioFallback {
+        direction,
+        requested: requested.to_string(),
+        chosen: device_name(&device),
+    });
+    Some((device, fallback))
+}
+
+/// Shared state the capture task reads on every frame.
+struct CaptureControls {
+    mic_muted: Arc<AtomicBool>,
+    mic_gain: Gain,
+    noise_reduce: Arc<AtomicBool>,
+    noise_reduce_strength: Gain,
+    echo_cancel: Arc<AtomicBool>,
+    /// Far-end audio at codec rate, as pushed by the playback task
+    echo_reference: Arc<std::sync::Mutex<Option<HeapCons<f32>>>>,
+    opus_config: Arc<std::sync::Mutex<OpusConfig>>,
+    vad: Arc<std::sync::Mutex<VadConfig>>,
+    on_hold: Arc<AtomicBool>,
+    conference: Arc<std::sync::Mutex<Option<ConferenceLink>>>,
+    recorder: Arc<std::sync::Mutex<Option<CallRecorder>>>,
+    inband_dtmf: Arc<std::sync::Mutex<VecDeque<f32>>>,
+    hold_music: Arc<std::sync::Mutex<Option<HoldMusic>>>,
+    announcement: Arc<std::sync::Mutex<Option<Announcement>>>,
+    announcement_done: mpsc::UnboundedSender<bool>,
+    level_tx: mpsc::Sender<AudioLevel>,
+    rtp_clock: RtpClock,
+    device_loss: DeviceLossSignal,
+    /// Ring buffer capacity in milliseconds
+    buffer_ms: u32,
+    xruns: Arc<AtomicU64>,
+    flow: Arc<MediaFlow>,
+}
+
+/// Hand one frame to the call recorder, if any. A failing recorder is dropped
+/// so a full disk does not interrupt the call.
+fn record_frame(
+    recorder: &std::sync::Mutex<Option<CallRecorder>>,
+    write: impl FnOnce(&mut CallRecorder) -> Result<(), String>,
+) {
+    let mut recorder = recorder.lock().unwrap();
+    if let Some(ref mut rec) = *recorder {
+        if let Err(e) = write(rec) {
+            warn!("{}, recording stopped", e);
+            recorder.take();
+        }
+    }
+}
+
+/// Convert f32 samples to i16, clamping to [-1.0, 1.0].
+fn pcm_f32_to_i16(samples: &[f32]) -> Vec<i16> {
+    samples
+        .iter()
+        .map(|&s| {
+            let clamped = s.clamp(-1.0, 1.0);
+            (clamped * 32767.0) as i16
+        })
+        .collect()
+}
+
+/// Run `samples` through `reducer` when noise reduction is `enabled` with a
+/// non-zero `strength`; otherwise pass them through untouched. The reducer is
+/// fed only while active, so it resumes from its own state when re-enabled.
+fn apply_noise_reduction(
+    reducer: &mut NoiseReducer,
+    enabled: bool,
+    strength: f32,
+    samples: Vec<f32>,
+) -> Vec<f32> {
+    if !enabled || strength <= 0.0 {
+        return samples;
+    }
+    reducer.set_strength(strength);
+    let denoised = reducer.process(&pcm_f32_to_i16(&samples), samples.len());
+    denoised.iter().map(|&s| s as f32 / 32768.0).collect()
 }
 
 /// Set up the capture stream: mic → ringbuf → tokio task → encode → send to rustrtc
 fn setup_capture_stream(
     device: &cpal::Device,
     audio_source: &SampleStreamSource,
-    mic_muted: Arc<AtomicBool>,
-    noise_reduce: Arc<AtomicBool>,
+    controls: CaptureControls,
     stop_notify: Arc<Notify>,
     negotiated: &NegotiatedCodec,
 ) -> Result<cpal::Stream, String> {
+    let CaptureControls {
+        mic_muted,
+        mic_gain,
+        noise_reduce,
+        noise_reduce_strength,
+        echo_cancel,
+        echo_reference: echo_reference_slot,
+        opus_config,
+        vad,
+        on_hold,
+        conference,
+        recorder,
+        inband_dtmf,
+        hold_music,
+        announcement,
+        announcement_done,
+        level_tx,
+        rtp_clock,
+        device_loss,
+        buffer_ms,
+        xruns,
+        flow,
+    } = controls;
+
     let supported_config = device
         .default_input_config()
         .map_err(|e| format!("No input config: {}", e))?;
@@ -264,33 +1291,41 @@ fn setup_capture_stream(
     };
 
     // Codec parameters from SDP negotiation
-    let codec_sample_rate = negotiated.clock_rate;
+    let codec_sample_rate = negotiated.sample_rate;
+    let rtp_clock_rate = negotiated.clock_rate;
+    let rtp_frame_ticks = negotiated.rtp_frame_ticks();
     let frame_samples = negotiated.frame_samples();
     let frame_duration_ms = negotiated.ptime_ms;
     let codec_type = negotiated.codec;
 
-    // Ring buffer: ~200ms of audio at device sample rate
-    let rb_capacity = (device_sample_rate as usize / 1000) * 200;
+    // Ring buffer: `buffer_ms` of audio at device sample rate
+    let rb_capacity = ring_capacity(device_sample_rate, buffer_ms);
     let rb = HeapRb::<f32>::new(rb_capacity);
     let (mut producer, mut consumer) = rb.split();
 
-    // cpal capture callback → write raw f32 samples to ring buffer
+    // cpal capture callback → write raw f32 samples to ring buffer; a full
+    // buffer drops the samples and counts one overflow per callback
+    let overflows = xruns.clone();
     let stream = match supported_config.sample_format() {
         SampleFormat::F32 => device.build_input_stream(
             &stream_config,
             move |data: &[f32], _: &cpal::InputCallbackInfo| {
+                let mut overflow = false;
                 if channels > 1 {
                     for chunk in data.chunks(channels) {
                         let mono: f32 = chunk.iter().sum::<f32>() / channels as f32;
-                        let _ = producer.try_push(mono);
+                        overflow |= producer.try_push(mono).is_err();
                     }
                 } else {
                     for &s in data {
-                        let _ = producer.try_push(s);
+                        overflow |= producer.try_push(s).is_err();
                     }
                 }
+                if overflow {
+                    overflows.fetch_add(1, Ordering::Relaxed);
+                }
             },
-            |err| error!("Capture stream error: {}", err),
+            device_loss.error_callback(AudioDirection::Mic, device),
             None,
         ),
         SampleFormat::I16 => device.build_input_stream(
@@ -300,19 +1335,23 @@ fn setup_capture_stream(
                 buffer_size: cpal::BufferSize::Default,
             },
             move |data: &[i16], _: &cpal::InputCallbackInfo| {
+                let mut overflow = false;
                 if channels > 1 {
                     for chunk in data.chunks(channels) {
                         let mono: f32 = chunk.iter().map(|&s| s as f32 / 32768.0).sum::<f32>()
                             / channels as f32;
-                        let _ = producer.try_push(mono);
+                        overflow |= producer.try_push(mono).is_err();
                     }
                 } else {
                     for &s in data {
-                        let _ = producer.try_push(s as f32 / 32768.0);
+                        overflow |= producer.try_push(s as f32 / 32768.0).is_err();
                     }
                 }
+                if overflow {
+                    xruns.fetch_add(1, Ordering::Relaxed);
+                }
             },
-            |err| error!("Capture stream error: {}", err),
+            device_loss.error_callback(AudioDirection::Mic, device),
             None,
         ),
         fmt => return Err(format!("Unsupported sample format: {:?}", fmt)),
@@ -354,8 +1393,48 @@ fn setup_capture_stream(
         // When device_sample_rate == 48000, NoiseReducer needs zero internal resampling.
         let mut noise_reducer = NoiseReducer::new(device_sample_rate);
 
+        // Echo canceller: mic at device rate, far-end reference at codec rate
+        let mut echo_canceller = EchoCanceller::new(device_sample_rate, codec_sample_rate);
+        // Reference older than this is dropped so it stays close to what is playing now
+        let max_reference_lag = frame_samples * 3;
+
+        // Mix the other conference legs' audio into a frame about to be sent
+        let mix_conference = |pcm: &mut [f32]| {
+            if let Some(link) = conference.lock().unwrap().as_ref() {
+                link.mixer.mix_into(&link.leg_id, pcm, codec_sample_rate);
+            }
+        };
+
+        // Replace a frame about to be sent with queued in-band DTMF tone, if any;
+        // true when a tone was played
+        let play_inband_dtmf = |pcm: &mut [f32]| {
+            let mut tone = inband_dtmf.lock().unwrap();
+            if tone.is_empty() {
+                return false;
+            }
+            for s in pcm.iter_mut() {
+                *s = tone.pop_front().unwrap_or(0.0);
+            }
+            true
+        };
+
+        // The local side of the recording, without the conference mix
+        let record_local = |pcm: &[i16]| record_frame(&recorder, |rec| rec.write_local(pcm));
+        let silent_pcm = vec![0i16; frame_samples];
+
+        let mut mic_meter = LevelMeter::new(AudioDirection::Mic, level_tx);
+        let silent_frame = vec![0.0f32; frame_samples];
+
         let mut device_buf = vec![0.0f32; device_frame_samples];
-        let mut rtp_timestamp: u32 = 0;
+        // One encoder for the whole stream: Opus and G.722 keep state between frames
+        let mut applied_opus_config = opus_config.lock().unwrap().clone();
+        let mut encoder = FrameEncoder::new(codec_type, codec_sample_rate, &applied_opus_config);
+
+        let mut rtp_timestamp: u32 = rtp_clock.next();
+        // DTMF tones always go out, even when the VAD would suppress the frame
+        let mut vad_detector = VoiceActivityDetector::new(frame_duration_ms);
+        // Taken from the shared slot on first use (a previous task may still hold it)
+        let mut echo_reference = None;
         let frame_interval = tokio::time::Duration::from_millis(frame_duration_ms as u64);
         let mut interval = tokio::time::interval(frame_interval);
         interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
@@ -365,24 +1444,143 @@ fn setup_capture_stream(
                 _ = interval.tick() => {},
                 _ = stop_notify.notified() => {
                     debug!("Capture task stopping");
+                    if let Some(rx) = echo_reference.take() {
+                        *echo_reference_slot.lock().unwrap() = Some(rx);
+                    }
                     break;
                 }
             }
 
-            // If mic is muted, send encoded silence (proper per-codec representation)
+            let current_opus_config = opus_config.lock().unwrap().clone();
+            if current_opus_config != applied_opus_config {
+                encoder.set_opus_config(&current_opus_config);
+                applied_opus_config = current_opus_config;
+            }
+
+            // Far-end audio played during this frame (silence if none arrived).
+            // Consumed on every tick so it stays aligned with the mic.
+            let mut reference = vec![0i16; frame_samples];
+            if echo_reference.is_none() {
+                echo_reference = echo_reference_slot.lock().unwrap().take();
+            }
+            if let Some(ref mut rx) = echo_reference {
+                rx.skip(rx.occupied_len().saturating_sub(max_reference_lag));
+                for (out, s) in reference.iter_mut().zip(rx.pop_iter()) {
+                    *out = (s.clamp(-1.0, 1.0) * 32767.0) as i16;
+                }
+            }
+
+            // An RFC 4733 event is being sent in place of the audio:
+            // send nothing, keeping the clock running so audio resumes after the event
+            if !rtp_clock.claim_frame(rtp_timestamp, rtp_frame_ticks) {
+                consumer.clear();
+                mic_meter.process(&silent_frame);
+                record_local(&silent_pcm);
+                rtp_timestamp = rtp_timestamp.wrapping_add(rtp_frame_ticks);
+                continue;
+            }
+
+            // While on hold, drop whatever the mic captured and send the hold music,
+            // or nothing without it; the RTP clock keeps running so the timestamp
+            // stays continuous after resume
+            if on_hold.load(Ordering::Relaxed) {
+                consumer.clear();
+                inband_dtmf.lock().unwrap().clear();
+                mic_meter.process(&silent_frame);
+                record_local(&silent_pcm);
+                let mut music = silent_frame.clone();
+                let playing = match hold_music.lock().unwrap().as_mut() {
+                    Some(hold_music) => {
+                        hold_music.fill(&mut music);
+                        true
+                    }
+                    None => false,
+                };
+                if playing {
+                    let encoded = encoder.encode(&pcm_f32_to_i16(&music));
+                    if !encoded.is_empty() {
+                        let frame = AudioFrame {
+                            rtp_timestamp,
+                            clock_rate: rtp_clock_rate,
+                            data: Bytes::from(encoded),
+                            ..Default::default()
+                        };
+                        if audio_source_clone.send_audio(frame).await.is_err() {
+                            break;
+                        }
+                        flow.sent.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+                rtp_timestamp = rtp_timestamp.wrapping_add(rtp_frame_ticks);
+                continue;
+            }
+
+            // A playing announcement replaces the mic, muted or not; the VAD is
+            // bypassed so the recording goes out unchanged
+            let announced = {
+                let mut slot = announcement.lock().unwrap();
+                let pcm = slot.as_mut().map(|playing| {
+                    let mut pcm = silent_frame.clone();
+                    playing.fill(&mut pcm);
+                    pcm
+                });
+                if slot.as_ref().is_some_and(Announcement::is_finished) {
+                    slot.take();
+                    let _ = announcement_done.send(true);
+                }
+                pcm
+            };
+            if let Some(mut pcm) = announced {
+                consumer.clear();
+                mic_meter.process(&pcm);
+                record_local(&pcm_f32_to_i16(&pcm));
+                mix_conference(&mut pcm);
+                let encoded = encoder.encode(&pcm_f32_to_i16(&pcm));
+                if !encoded.is_empty() {
+                    let frame = AudioFrame {
+                        rtp_timestamp,
+                        clock_rate: rtp_clock_rate,
+                        data: Bytes::from(encoded),
+                        ..Default::default()
+                    };
+                    if audio_source_clone.send_audio(frame).await.is_err() {
+                        break;
+                    }
+                    flow.sent.fetch_add(1, Ordering::Relaxed);
+                }
+                rtp_timestamp = rtp_timestamp.wrapping_add(rtp_frame_ticks);
+                continue;
+            }
+
+            // If mic is muted, send encoded silence (proper per-codec representation);
+            // in a conference the other legs' audio is still passed through
             if mic_muted.load(Ordering::Relaxed) {
-                let silence_pcm = vec![0i16; frame_samples];
-                let encoded = codec_type.encode(&silence_pcm);
-                let frame = AudioFrame {
-                    rtp_timestamp,
-                    clock_rate: codec_sample_rate,
-                    data: Bytes::from(encoded),
-                    ..Default::default()
+                mic_meter.process(&silent_frame);
+                record_local(&silent_pcm);
+                let mut silence = silent_frame.clone();
+                mix_conference(&mut silence);
+                let dtmf = play_inband_dtmf(&mut silence);
+                let vad_config = *vad.lock().unwrap();
+                let Some(marker) = vad_detector.gate(&silence, &vad_config, dtmf) else {
+                    rtp_timestamp = rtp_timestamp.wrapping_add(rtp_frame_ticks);
+                    continue;
                 };
-                if audio_source_clone.send_audio(frame).await.is_err() {
-                    break;
+                let encoded = encoder.encode(&pcm_f32_to_i16(&silence));
+                // Empty with Opus DTX: nothing to send, the RTP clock still advances
+                if !encoded.is_empty() {
+                    let frame = AudioFrame {
+                        rtp_timestamp,
+                        clock_rate: rtp_clock_rate,
+                        data: Bytes::from(encoded),
+                        marker,
+                        ..Default::default()
+                    };
+                    if audio_source_clone.send_audio(frame).await.is_err() {
+                        break;
+                    }
+                    flow.sent.fetch_add(1, Ordering::Relaxed);
                 }
-                rtp_timestamp = rtp_timestamp.wrapping_add(frame_samples as u32);
+                rtp_timestamp = rtp_timestamp.wrapping_add(rtp_frame_ticks);
                 continue;
             }
 
@@ -390,18 +1588,32 @@ fn setup_capture_stream(
             let available = consumer.occupied_len();
             let needed = device_frame_samples;
             if available < needed {
-                let silence_pcm = vec![0i16; frame_samples];
-                let encoded = codec_type.encode(&silence_pcm);
-                let frame = AudioFrame {
-                    rtp_timestamp,
-                    clock_rate: codec_sample_rate,
-                    data: Bytes::from(encoded),
-                    ..Default::default()
+                mic_meter.process(&silent_frame);
+                record_local(&silent_pcm);
+                let mut silence = silent_frame.clone();
+                mix_conference(&mut silence);
+                let dtmf = play_inband_dtmf(&mut silence);
+                let vad_config = *vad.lock().unwrap();
+                let Some(marker) = vad_detector.gate(&silence, &vad_config, dtmf) else {
+                    rtp_timestamp = rtp_timestamp.wrapping_add(rtp_frame_ticks);
+                    continue;
                 };
-                if audio_source_clone.send_audio(frame).await.is_err() {
-                    break;
+                let encoded = encoder.encode(&pcm_f32_to_i16(&silence));
+                // Empty with Opus DTX: nothing to send, the RTP clock still advances
+                if !encoded.is_empty() {
+                    let frame = AudioFrame {
+                        rtp_timestamp,
+                        clock_rate: rtp_clock_rate,
+                        data: Bytes::from(encoded),
+                        marker,
+                        ..Default::default()
+                    };
+                    if audio_source_clone.send_audio(frame).await.is_err() {
+                        break;
+                    }
+                    flow.sent.fetch_add(1, Ordering::Relaxed);
                 }
-                rtp_timestamp = rtp_timestamp.wrapping_add(frame_samples as u32);
+                rtp_timestamp = rtp_timestamp.wrapping_add(rtp_frame_ticks);
                 continue;
             }
 
@@ -409,22 +1621,30 @@ fn setup_capture_stream(
                 device_buf[i] = consumer.try_pop().unwrap_or(0.0);
             }
 
+            // Cancel speaker echo before noise reduction: the denoiser's non-linear
+            // gain would otherwise hide the echo path from the adaptive filter
+            if echo_cancel.load(Ordering::Relaxed) {
+                let mic_i16 = pcm_f32_to_i16(&device_buf[..needed]);
+                let cleaned = echo_canceller.process(&mic_i16, &reference, needed);
+                for (out, &s) in device_buf.iter_mut().zip(&cleaned) {
+                    *out = s as f32 / 32768.0;
+                }
+            }
+
             // Apply noise reduction at device rate BEFORE downsampling to codec rate.
             // This avoids the double-resampling penalty (device→48k→device) that occurs
             // when NoiseReducer runs at codec rate (e.g. 8 kHz → 48 kHz → 8 kHz internally).
-            let device_f32: Vec<f32> = if noise_reduce.load(Ordering::Relaxed) {
-                let device_i16: Vec<i16> = device_buf[..needed]
-                    .iter()
-                    .map(|&s| (s.clamp(-1.0, 1.0) * 32767.0) as i16)
-                    .collect();
-                let denoised = noise_reducer.process(&device_i16, needed);
-                denoised.iter().map(|&s| s as f32 / 32768.0).collect()
-            } else {
-                device_buf[..needed].to_vec()
-            };
+            let mut device_f32 = apply_noise_reduction(
+                &mut noise_reducer,
+                noise_reduce.load(Ordering::Relaxed),
+                noise_reduce_strength.get(),
+                device_buf[..needed].to_vec(),
+            );
+            mic_gain.apply(&mut device_f32);
+            mic_meter.process(&device_f32);
 
             // Resample if needed (device rate → codec rate)
-            let pcm_f32 = if let Some(ref mut resampler) = resampler {
+            let mut pcm_f32 = if let Some(ref mut resampler) = resampler {
                 use audioadapter_buffers::owned::InterleavedOwned;
                 use rubato::Resampler;
 
@@ -446,45 +1666,108 @@ fn setup_capture_stream(
                 device_f32[..frame_samples].to_vec()
             };
 
-            // Convert f32 → i16 at codec rate
-            let pcm_i16: Vec<i16> = pcm_f32
-                .iter()
-                .map(|&s| {
-                    let clamped = s.clamp(-1.0, 1.0);
-                    (clamped * 32767.0) as i16
-                })
-                .collect();
-
-            let encoded = codec_type.encode(&pcm_i16);
-
-            let frame = AudioFrame {
-                rtp_timestamp,
-                clock_rate: codec_sample_rate,
-                data: Bytes::from(encoded),
-                ..Default::default()
+            record_local(&pcm_f32_to_i16(&pcm_f32));
+            mix_conference(&mut pcm_f32);
+            let dtmf = play_inband_dtmf(&mut pcm_f32);
+            let vad_config = *vad.lock().unwrap();
+            let Some(marker) = vad_detector.gate(&pcm_f32, &vad_config, dtmf) else {
+                rtp_timestamp = rtp_timestamp.wrapping_add(rtp_frame_ticks);
+                continue;
             };
 
-            if audio_source_clone.send_audio(frame).await.is_err() {
-                debug!("Audio source closed, stopping capture");
-                break;
+            // Convert f32 → i16 at codec rate
+            let pcm_i16 = pcm_f32_to_i16(&pcm_f32);
+
+            let encoded = encoder.encode(&pcm_i16);
+
+            if !encoded.is_empty() {
+                let frame = AudioFrame {
+                    rtp_timestamp,
+                    clock_rate: rtp_clock_rate,
+                    data: Bytes::from(encoded),
+                    marker,
+                    ..Default::default()
+                };
+
+                if audio_source_clone.send_audio(frame).await.is_err() {
+                    debug!("Audio source closed, stopping capture");
+                    break;
+                }
+                flow.sent.fetch_add(1, Ordering::Relaxed);
             }
 
-            rtp_timestamp = rtp_timestamp.wrapping_add(frame_samples as u32);
+            rtp_timestamp = rtp_timestamp.wrapping_add(rtp_frame_ticks);
         }
     });
 
     Ok(stream)
 }
 
-/// Set up the playback stream: remote track → decode → resample → ringbuf → speaker
+/// Shared state the playback task reads on every frame.
+struct PlaybackControls {
+    speaker_muted: Arc<AtomicBool>,
+    speaker_volume: Gain,
+    speaker_noise_reduce: Arc<AtomicBool>,
+    noise_reduce_strength: Gain,
+    jitter_buffer_ms: Arc<AtomicU32>,
+    jitter_stats: Arc<JitterStats>,
+    /// Conceal lost frames instead of playing silence
+    plc: Arc<AtomicBool>,
+    /// Fill receive gaps with comfort noise instead of silence
+    comfort_noise: Arc<AtomicBool>,
+    /// Receives the decoded far-end audio the speaker plays, for echo cancellation
+    echo_reference: Arc<std::sync::Mutex<Option<HeapProd<f32>>>>,
+    conference: Arc<std::sync::Mutex<Option<ConferenceLink>>>,
+    recorder: Arc<std::sync::Mutex<Option<CallRecorder>>>,
+    level_tx: mpsc::Sender<AudioLevel>,
+    dtmf_tx: mpsc::Sender<ReceivedDtmf>,
+    device_loss: DeviceLossSignal,
+    last_received: Arc<std::sync::Mutex<std::time::Instant>>,
+    /// Ring buffer capacity in milliseconds
+    buffer_ms: u32,
+    xruns: Arc<AtomicU64>,
+    flow: Arc<MediaFlow>,
+}
+
+/// Ring buffer capacity in samples (one channel) for `buffer_ms` at `sample_rate`.
+fn ring_capacity(sample_rate: u32, buffer_ms: u32) -> usize {
+    (sample_rate as usize / 1000) * buffer_ms as usize
+}
+
+/// Jitter buffer target in frames for a target depth in milliseconds (rounded up).
+fn jitter_target_frames(target_ms: u32, frame_duration_ms: u32) -> usize {
+    target_ms.div_ceil(frame_duration_ms.max(1)) as usize
+}
+
+/// Set up the playback stream: remote track → jitter buffer → decode → resample → ringbuf → speaker
 fn setup_playback_stream(
     device: &cpal::Device,
     remote_track: Arc<SampleStreamTrack>,
-    speaker_muted: Arc<AtomicBool>,
-    speaker_noise_reduce: Arc<AtomicBool>,
+    controls: PlaybackControls,
     stop_notify: Arc<Notify>,
     negotiated: &NegotiatedCodec,
 ) -> Result<cpal::Stream, String> {
+    let PlaybackControls {
+        speaker_muted,
+        speaker_volume,
+        speaker_noise_reduce,
+        noise_reduce_strength,
+        jitter_buffer_ms,
+        jitter_stats,
+        plc,
+        comfort_noise,
+        echo_reference: echo_reference_slot,
+        conference,
+        recorder,
+        level_tx,
+        dtmf_tx,
+        device_loss,
+        last_received,
+        buffer_ms,
+        xruns,
+        flow,
+    } = controls;
+
     let supported_config = device
         .default_output_config()
         .map_err(|e| format!("No output config: {}", e))?;
@@ -503,17 +1786,21 @@ fn setup_playback_stream(
     };
 
     // Codec parameters from SDP negotiation
-    let codec_sample_rate = negotiated.clock_rate;
+    let codec_sample_rate = negotiated.sample_rate;
     let frame_samples = negotiated.frame_samples();
+    let frame_duration_ms = negotiated.ptime_ms;
     let codec_type = negotiated.codec;
+    let telephone_event_pt = negotiated.telephone_event_pt;
 
-    // Ring buffer: ~200ms of audio at device sample rate, per channel
-    let rb_capacity = (device_sample_rate as usize / 1000) * 200 * channels;
+    // Ring buffer: `buffer_ms` of audio at device sample rate, per channel
+    let rb_capacity = ring_capacity(device_sample_rate, buffer_ms) * channels;
     let rb = HeapRb::<f32>::new(rb_capacity);
     let (mut producer, mut consumer) = rb.split();
 
-    // Tokio task: receive from remote track → decode → resample → write to ring buffer
+    // Tokio task: receive from remote track → jitter buffer; every ptime pop a frame
+    // → decode → resample → write to ring buffer
     let stop = stop_notify.clone();
+    let overflows = xruns.clone();
     let muted = speaker_muted.clone();
     tokio::spawn(async move {
         let needs_resample = device_sample_rate != codec_sample_rate;
@@ -541,94 +1828,62 @@ fn setup_playback_stream(
         // When device_sample_rate == 48000, NoiseReducer needs zero internal resampling.
         let mut speaker_noise_reducer = NoiseReducer::new(device_sample_rate);
 
+        let mut speaker_meter = LevelMeter::new(AudioDirection::Speaker, level_tx);
+        let silent_frame = vec![0.0f32; frame_samples];
+
+        let mut jitter_buffer = JitterBuffer::new(
+            jitter_target_frames(jitter_buffer_ms.load(Ordering::Relaxed), frame_duration_ms),
+            (JITTER_BUFFER_MAX_MS / frame_duration_ms.max(1)) as usize,
+            jitter_stats.clone(),
+        );
+        let mut decoder = FrameDecoder::new(codec_type, codec_sample_rate, frame_samples);
+        let mut noise = ComfortNoise::default();
+        // The far end sent a CN packet and nothing since: a gap is expected silence
+        let mut far_end_silent = false;
+        let mut dtmf_detector = DtmfDetector::default();
+        // Taken from the shared slot on first use (a previous task may still hold it)
+        let mut echo_reference = None;
+        let mut playout =
+            tokio::time::interval(tokio::time::Duration::from_millis(frame_duration_ms as u64));
+        playout.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
         loop {
             tokio::select! {
                 result = remote_track.recv() => {
                     match result {
                         Ok(MediaSample::Audio(frame)) => {
                             frame_count += 1;
+                            flow.received.fetch_add(1, Ordering::Relaxed);
+                            *last_received.lock().unwrap() = std::time::Instant::now();
                             if frame_count == 1 {
                                 info!(bytes = frame.data.len(), timestamp = frame.rtp_timestamp, "Started receiving audio frames from remote");
                             }
-                            if muted.load(Ordering::Relaxed) {
-                                continue;
-                            }
 
-                            // Skip frames that are too small (likely STUN packets misidentified as RTP)
-                            if frame.data.len() < 10 {
-                                debug!(bytes = frame.data.len(), "Skipping small frame (possibly STUN packet)");
+                            // RFC 4733 events are not audio: decode the keypress instead
+                            if telephone_event_pt.is_some() && frame.payload_type == telephone_event_pt {
+                                if let Some(dtmf) = dtmf_detector.process(frame.rtp_timestamp, &frame.data) {
+                                    let _ = dtmf_tx.try_send(dtmf);
+                                }
                                 continue;
                             }
 
-                            // Decode with negotiated codec → i16
-                            let pcm_i16 = codec_type.decode(&frame.data);
-
-                            // Skip if decoded data is too small
-                            if pcm_i16.len() < frame_samples {
-                                skipped_frames += 1;
-                                debug!(actual = pcm_i16.len(), expected = frame_samples, "Decoded frame too small, skipping");
-
-                                // Report statistics every 5 seconds
-                                if last_report_time.elapsed().as_secs() >= 5 && skipped_frames > 0 {
-                                    warn!(
-                                        skipped = skipped_frames,
-                                        total = frame_count,
-                                        rate = format!("{:.1}%", (skipped_frames as f64 / frame_count as f64) * 100.0),
-                                        "Audio frame quality report: some frames were too small and skipped"
-                                    );
-                                    last_report_time = std::time::Instant::now();
+                            // RFC 3389 comfort noise: an empty frame keeps its place in the
+                            // sequence and plays as noise at the announced level
+                            if frame.payload_type == Some(CN_PAYLOAD_TYPE) {
+                                if let Some(level) = comfort_noise::parse_level(&frame.data) {
+                                    noise.set_level(level);
                                 }
+                                jitter_buffer.push(frame.sequence_number, Bytes::new());
                                 continue;
                             }
 
-                            let pcm_f32: Vec<f32> = pcm_i16
-                                .iter()
-                                .map(|&s| s as f32 / 32768.0)
-                                .collect();
-
-                            // Resample if needed (codec rate → device rate)
-                            let output_samples = if let Some(ref mut resampler) = resampler {
-                                use rubato::Resampler;
-                                use audioadapter_buffers::owned::InterleavedOwned;
-
-                                let input = InterleavedOwned::new_from(
-                                    pcm_f32,
-                                    1, // single channel
-                                    frame_samples,
-                                ).expect("Failed to create input buffer");
-
-                                match resampler.process(&input, 0, None) {
-                                    Ok(output) => output.take_data(),
-                                    Err(e) => {
-                                        warn!("Playback resample error: {}", e);
-                                        continue;
-                                    }
-                                }
-                            } else {
-                                pcm_f32
-                            };
-
-                            // Apply speaker noise reduction at device rate AFTER upsampling.
-                            // Denoiser runs at device rate (usually 48 kHz) with zero internal
-                            // resampling, avoiding the codec_rate→48k→codec_rate round-trip.
-                            let output_samples = if speaker_noise_reduce.load(Ordering::Relaxed) {
-                                let out_len = output_samples.len();
-                                let device_i16: Vec<i16> = output_samples
-                                    .iter()
-                                    .map(|&s| (s.clamp(-1.0, 1.0) * 32767.0) as i16)
-                                    .collect();
-                                let denoised = speaker_noise_reducer.process(&device_i16, out_len);
-                                denoised.iter().map(|&s| s as f32 / 32768.0).collect()
-                            } else {
-                                output_samples
-                            };
-
-                            // Write to ring buffer, duplicating to all channels
-                            for &s in &output_samples {
-                                for _ in 0..channels {
-                                    let _ = producer.try_push(s);
-                                }
+                            // Skip frames that are too small (likely STUN packets misidentified as RTP)
+                            if frame.data.len() < 10 {
+                                debug!(bytes = frame.data.len(), "Skipping small frame (possibly STUN packet)");
+                                continue;
                             }
+
+                            jitter_buffer.push(frame.sequence_number, frame.data);
                         }
                         Ok(_) => {}
                         Err(_) => {
@@ -641,12 +1896,162 @@ fn setup_playback_stream(
                         }
                     }
                 }
+                _ = playout.tick() => {
+                    jitter_buffer.set_target(jitter_target_frames(
+                        jitter_buffer_ms.load(Ordering::Relaxed),
+                        frame_duration_ms,
+                    ));
+                    // After a CN packet the far end sends nothing until speech resumes;
+                    // not popping keeps that gap from counting as an underrun
+                    let due = if far_end_silent && jitter_buffer.is_empty() {
+                        Playout::Idle
+                    } else {
+                        jitter_buffer.pop()
+                    };
+                    let cn_enabled = comfort_noise.load(Ordering::Relaxed);
+                    // Decode with negotiated codec → i16 (always, so the decoder state
+                    // stays continuous while the speaker is muted)
+                    let pcm_i16 = match due {
+                        Playout::Frame(data) if data.is_empty() => {
+                            far_end_silent = true;
+                            if !cn_enabled {
+                                speaker_meter.process(&silent_frame);
+                                continue;
+                            }
+                            noise.generate(frame_samples)
+                        }
+                        Playout::Frame(data) => {
+                            far_end_silent = false;
+                            decoder.decode(&data)
+                        }
+                        // Synthesize the missing frame so the gap does not click; once
+                        // the concealment has faded out, noise takes over
+                        Playout::Lost { next } if plc.load(Ordering::Relaxed) => {
+                            // A CN placeholder carries nothing to recover from
+                            let (pcm, recovered) =
+                                decoder.conceal(next.as_deref().filter(|n| !n.is_empty()));
+                            if recovered {
+                                jitter_stats.fec_recovered.fetch_add(1, Ordering::Relaxed);
+                            }
+                            if cn_enabled && pcm.iter().all(|&s| s == 0) {
+                                noise.generate(frame_samples)
+                            } else {
+                                pcm
+                            }
+                        }
+                        // A gap in the middle of the call (DTX, underrun, loss with PLC off)
+                        _ if cn_enabled && frame_count > 0 => noise.generate(frame_samples),
+                        // Nothing due (buffering, or lost with PLC off): the speaker plays silence
+                        _ => {
+                            speaker_meter.process(&silent_frame);
+                            continue;
+                        }
+                    };
+                    // Recorded whether or not the speaker is muted
+                    record_frame(&recorder, |rec| {
+                        rec.push_remote(&pcm_i16);
+                        Ok(())
+                    });
+
+                    // A muted speaker still has to feed the conference mixer
+                    let speaker_muted = muted.load(Ordering::Relaxed);
+                    if speaker_muted && conference.lock().unwrap().is_none() {
+                        speaker_meter.process(&silent_frame);
+                        continue;
+                    }
+
+                    // Skip if decoded data is too small
+                    if pcm_i16.len() < frame_samples {
+                        skipped_frames += 1;
+                        debug!(actual = pcm_i16.len(), expected = frame_samples, "Decoded frame too small, skipping");
+
+                        // Report statistics every 5 seconds
+                        if last_report_time.elapsed().as_secs() >= 5 && skipped_frames > 0 {
+                            warn!(
+                                skipped = skipped_frames,
+                                total = frame_count,
+                                rate = format!("{:.1}%", (skipped_frames as f64 / frame_count as f64) * 100.0),
+                                "Audio frame quality report: some frames were too small and skipped"
+                            );
+                            last_report_time = std::time::Instant::now();
+                        }
+                        continue;
+                    }
+
+                    let pcm_f32: Vec<f32> = pcm_i16
+                        .iter()
+                        .map(|&s| s as f32 / 32768.0)
+                        .collect();
+
+                    if let Some(link) = conference.lock().unwrap().as_ref() {
+                        link.mixer.push_remote(&link.leg_id, &pcm_f32, codec_sample_rate);
+                    }
+                    if speaker_muted {
+                        speaker_meter.process(&silent_frame);
+                        continue;
+                    }
+                    if echo_reference.is_none() {
+                        echo_reference = echo_reference_slot.lock().unwrap().take();
+                    }
+                    if let Some(ref mut tx) = echo_reference {
+                        tx.push_slice(&pcm_f32);
+                    }
+
+                    // Resample if needed (codec rate → device rate)
+                    let output_samples = if let Some(ref mut resampler) = resampler {
+                        use rubato::Resampler;
+                        use audioadapter_buffers::owned::InterleavedOwned;
+
+                        let input = InterleavedOwned::new_from(
+                            pcm_f32,
+                            1, // single channel
+                            frame_samples,
+                        ).expect("Failed to create input buffer");
+
+                        match resampler.process(&input, 0, None) {
+                            Ok(output) => output.take_data(),
+                            Err(e) => {
+                                warn!("Playback resample error: {}", e);
+                                continue;
+                            }
+                        }
+                    } else {
+                        pcm_f32
+                    };
+
+                    // Apply speaker noise reduction at device rate AFTER upsampling.
+                    // Denoiser runs at device rate (usually 48 kHz) with zero internal
+                    // resampling, avoiding the codec_rate→48k→codec_rate round-trip.
+                    let mut output_samples = apply_noise_reduction(
+                        &mut speaker_noise_reducer,
+                        speaker_noise_reduce.load(Ordering::Relaxed),
+                        noise_reduce_strength.get(),
+                        output_samples,
+                    );
+                    speaker_volume.apply(&mut output_samples);
+
+                    speaker_meter.process(&output_samples);
+
+                    // Write to ring buffer, duplicating to all channels
+                    let mut overflow = false;
+                    for &s in &output_samples {
+                        for _ in 0..channels {
+                            overflow |= producer.try_push(s).is_err();
+                        }
+                    }
+                    if overflow {
+                        overflows.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
                 _ = stop.notified() => {
                     info!(
                         total_frames = frame_count,
                         skipped_frames,
                         "Playback task stopping, audio session complete"
                     );
+                    if let Some(tx) = echo_reference.take() {
+                        *echo_reference_slot.lock().unwrap() = Some(tx);
+                    }
                     break;
                 }
             }
@@ -658,11 +2063,15 @@ fn setup_playback_stream(
         .build_output_stream(
             &stream_config,
             move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
-                for sample in data.iter_mut() {
-                    *sample = consumer.try_pop().unwrap_or(0.0);
+                let played = consumer.pop_slice(data);
+                data[played..].fill(0.0);
+                // Running out part-way through a callback cuts audio off; an
+                // empty buffer is just nothing to play
+                if played > 0 && played < data.len() {
+                    xruns.fetch_add(1, Ordering::Relaxed);
                 }
             },
-            |err| error!("Playback stream error: {}", err),
+            device_loss.error_callback(AudioDirection::Speaker, device),
             None,
         )
         .map_err(|e| format!("Failed to build output stream: {}", e))?;
@@ -673,3 +2082,73 @@ fn setup_playback_stream(
 
     Ok(stream)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 20 ms of deterministic pseudo-random noise at 48 kHz
+    fn noise_frame(seed: &mut u32) -> Vec<f32> {
+        (0..960)
+            .map(|_| {
+                *seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                ((*seed >> 16) as f32 / 32_768.0 - 1.0) * 0.05
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_dtmf_event_follows_audio_timeline() {
+        let clock = RtpClock::default();
+        clock.claim_frame(48_000u32.wrapping_sub(160), 160);
+        // One capture tick as the capture task runs it: the timestamp of the
+        // frame sent, if any, then the clock advances
+        let mut audio_ts = clock.next();
+        let mut tick = || {
+            let sent = clock.claim_frame(audio_ts, 160).then_some(audio_ts);
+            audio_ts = audio_ts.wrapping_add(160);
+            sent
+        };
+
+        let before: Vec<_> = (0..3).filter_map(|_| tick()).collect();
+        assert_eq!(before, [48_000, 48_160, 48_320]);
+
+        // The last frame was already claimed: the event starts after it
+        let base_ts = clock.begin_event();
+        assert_eq!(base_ts, 48_480);
+
+        // No audio goes out during the event (8 packets of 20 ms)
+        assert!((0..8).all(|_| tick().is_none()));
+        clock.end_event();
+
+        // Audio resumes right after the event's duration
+        assert_eq!(tick(), Some(base_ts + 8 * 160));
+    }
+
+    #[test]
+    fn test_noise_reduction_follows_flag() {
+        let mut reducer = NoiseReducer::new(48_000);
+        let mut seed = 1;
+
+        let frame = noise_frame(&mut seed);
+        let passed = apply_noise_reduction(&mut reducer, false, 1.0, frame.clone());
+        assert_eq!(passed, frame);
+        let bypassed = apply_noise_reduction(&mut reducer, true, 0.0, frame.clone());
+        assert_eq!(bypassed, frame);
+
+        let energy = |pcm: &[f32]| pcm.iter().map(|s| s * s).sum::<f32>();
+        let mut input = 0.0;
+        let mut output = 0.0;
+        for _ in 0..50 {
+            let frame = noise_frame(&mut seed);
+            input += energy(&frame);
+            let denoised = apply_noise_reduction(&mut reducer, true, 1.0, frame);
+            assert_eq!(denoised.len(), 960);
+            output += energy(&denoised);
+        }
+        assert!(
+            output < input / 2.0,
+            "noise not reduced: {output} vs {input}"
+        );
+    }
+}
@@ -0,0 +1,148 @@
+//! Local self-tests of the audio devices, without any SIP or RTP.
+//!
+//! The loopback test opens an `AudioBridge` on the configured devices with its
+//! send track fed straight back into its own playback, so the mic is heard on
+//! the speaker after passing through resampling and the PCMU codec, as in a
+//! call. The test tone plays a sine through the same playback path, so it
+//! also works at the output device's native rate.
+
+use bytes::Bytes;
+use rustrtc::media::frame::{AudioFrame, MediaKind};
+use rustrtc::media::track::sample_track;
+use std::f32::consts::PI;
+use std::time::Duration;
+use tracing::info;
+
+use super::audio_bridge::{AudioBridge, DEFAULT_AUDIO_BUFFER_MS, DEFAULT_JITTER_BUFFER_MS};
+use super::codec::NegotiatedCodec;
+use super::encoder::{FrameEncoder, OpusConfig};
+use super::{spawn_level_forwarder, MediaConfig};
+
+/// `call_id` of the `sip://audio-level` events emitted during the test
+pub const AUDIO_TEST_ID: &str = "audio-test";
+
+/// How far the speaker lags behind the mic, so the user hears themselves as an echo
+const LOOPBACK_DELAY_MS: u32 = 300;
+
+/// Test tone frequency range; PCMU at 8 kHz carries up to about 3.4 kHz
+const MIN_TONE_HZ: u32 = 50;
+const MAX_TONE_HZ: u32 = 3400;
+/// Longest test tone
+const MAX_TONE_MS: u32 = 10_000;
+/// Peak amplitude of the test tone (-6 dBFS)
+const TONE_AMPLITUDE: f32 = 0.5;
+/// Fade in and out so the tone starts and stops without a click
+const TONE_FADE_MS: u32 = 10;
+
+pub struct AudioTest {
+    bridge: AudioBridge,
+}
+
+impl AudioTest {
+    /// Open the devices of `media` and start the loopback.
+    pub fn start(media: &MediaConfig, app_handle: tauri::AppHandle) -> Result<Self, String> {
+        let (mut bridge, track) = AudioBridge::new(
+            media.input_device.as_deref(),
+            media.output_device.as_deref(),
+        )?;
+        bridge.set_noise_reduce(media.noise_reduce);
+        bridge.set_speaker_noise_reduce(media.speaker_noise_reduce);
+        bridge.set_speaker_volume(media.speaker_volume);
+        bridge.set_mic_gain(media.mic_gain);
+        // The playout delay is the jitter buffer depth
+        bridge.set_jitter_buffer_ms(LOOPBACK_DELAY_MS);
+
+        let negotiated = NegotiatedCodec::default();
+        bridge.start_capture(&negotiated)?;
+        bridge.start_playback(media.output_device.as_deref(), track, &negotiated)?;
+        if let Some(level_rx) = bridge.take_level_receiver() {
+            spawn_level_forwarder(level_rx, app_handle, AUDIO_TEST_ID.to_string());
+        }
+
+        info!(input = ?media.input_device, output = ?media.output_device, "Audio test started");
+        Ok(Self { bridge })
+    }
+
+    /// Stop the streams and release the devices.
+    pub fn stop(mut self) {
+        self.bridge.close();
+        info!("Audio test stopped");
+    }
+}
+
+pub fn validate_test_tone(freq_hz: u32, ms: u32) -> Result<(), String> {
+    if !(MIN_TONE_HZ..=MAX_TONE_HZ).contains(&freq_hz) {
+        return Err(format!(
+            "Test tone frequency must be {}-{} Hz, got {}",
+            MIN_TONE_HZ, MAX_TONE_HZ, freq_hz
+        ));
+    }
+    if ms == 0 || ms > MAX_TONE_MS {
+        return Err(format!(
+            "Test tone length must be 1-{} ms, got {}",
+            MAX_TONE_MS, ms
+        ));
+    }
+    Ok(())
+}
+
+/// Play a `freq_hz` sine for `ms` on the output device of `media`, at its
+/// speaker volume, and release the device once it has played out.
+pub async fn play_test_tone(media: &MediaConfig, freq_hz: u32, ms: u32) -> Result<(), String> {
+    validate_test_tone(freq_hz, ms)?;
+    let output_device = media.output_device.as_deref();
+    let (mut bridge, _) = AudioBridge::new_playback_only(output_device)?;
+    bridge.set_speaker_volume(media.speaker_volume);
+    bridge.set_comfort_noise(false);
+
+    let negotiated = NegotiatedCodec::default();
+    let (source, track, _feedback_rx) = sample_track(MediaKind::Audio, 100);
+    bridge.start_playback(output_device, track, &negotiated)?;
+    info!(output = ?media.output_device, freq_hz, ms, "Test tone started");
+
+    let frame_samples = (negotiated.sample_rate * negotiated.ptime_ms / 1000) as usize;
+    let mut encoder = FrameEncoder::new(
+        negotiated.codec,
+        negotiated.clock_rate,
+        &OpusConfig::default(),
+    );
+    let mut ticker = tokio::time::interval(Duration::from_millis(negotiated.ptime_ms as u64));
+    let mut rtp_timestamp = 0u32;
+    for chunk in tone(freq_hz, ms, negotiated.sample_rate).chunks(frame_samples) {
+        ticker.tick().await;
+        let mut pcm = chunk.to_vec();
+        pcm.resize(frame_samples, 0);
+        let frame = AudioFrame {
+            rtp_timestamp,
+            clock_rate: negotiated.clock_rate,
+            data: Bytes::from(encoder.encode(&pcm)),
+            ..Default::default()
+        };
+        if source.send_audio(frame).await.is_err() {
+            break;
+        }
+        rtp_timestamp = rtp_timestamp.wrapping_add(frame_samples as u32);
+    }
+
+    // Let the jitter buffer and the output buffer play out before closing
+    let drain_ms = DEFAULT_JITTER_BUFFER_MS + DEFAULT_AUDIO_BUFFER_MS + negotiated.ptime_ms;
+    tokio::time::sleep(Duration::from_millis(drain_ms as u64)).await;
+    bridge.close();
+    info!("Test tone finished");
+    Ok(())
+}
+
+/// `ms` of a `freq_hz` sine at `sample_rate`, faded in and out
+fn tone(freq_hz: u32, ms: u32, sample_rate: u32) -> Vec<i16> {
+    let samples = (sample_rate as u64 * ms as u64 / 1000) as usize;
+    let fade = (sample_rate * TONE_FADE_MS / 1000) as usize;
+    let fade = fade.min(samples / 2).max(1);
+    let rate = sample_rate as f32;
+    (0..samples)
+        .map(|i| {
+            let envelope = (i.min(samples - 1 - i) as f32 / fade as f32).min(1.0);
+            let s = TONE_AMPLITUDE * envelope * (2.0 * PI * freq_hz as f32 * i as f32 / rate).sin();
+            (s * 32767.0) as i16
+        })
+        .collect()
+}
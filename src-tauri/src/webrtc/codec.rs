@@ -1,9 +1,14 @@
 /// Codec support using audio-codec crate.
 ///
 /// Directly uses audio-codec's CodecType to support all available codecs:
-/// PCMU, PCMA, G722, G729, Opus, etc.
+/// PCMU, PCMA, G722, G729, Opus, etc. audio-codec 0.3 has no Speex or iLBC
+/// implementation, so those are never offered and a far end listing them is
+/// negotiated down to the first codec we do support (see
+/// `parse_negotiated_codec`). L16, a plain byte-order conversion, is handled
+/// here instead (`AudioCodec::L16`). Encoding and decoding go through the
+/// per-call `encoder::FrameEncoder` / `plc::FrameDecoder`, never a codec
+/// instance created per frame (that would reset Opus/G.722/G.729 state).
 pub use audio_codec::CodecType;
-use audio_codec::{create_decoder, create_encoder};
 
 /// Extension trait for CodecType to add helper methods
 pub trait CodecTypeExt {
@@ -16,12 +21,6 @@ pub trait CodecTypeExt {
 
     /// Get default clock rate for this codec
     fn default_clock_rate(&self) -> u32;
-
-    /// Encode PCM samples
-    fn encode(&self, pcm: &[i16]) -> Vec<u8>;
-
-    /// Decode encoded data to PCM samples
-    fn decode(&self, data: &[u8]) -> Vec<i16>;
 }
 
 impl CodecTypeExt for CodecType {
@@ -56,42 +55,130 @@ impl CodecTypeExt for CodecType {
             CodecType::TelephoneEvent => 8000,
         }
     }
+}
+
+/// Audio codec for an SDP encoding name (`PCMU`, `opus`, ...), case-insensitive.
+/// telephone-event is not an audio codec and yields `None`.
+pub fn codec_from_name(name: &str) -> Option<CodecType> {
+    match name.to_uppercase().as_str() {
+        "PCMU" => Some(CodecType::PCMU),
+        "PCMA" => Some(CodecType::PCMA),
+        "G722" => Some(CodecType::G722),
+        "G729" => Some(CodecType::G729),
+        "OPUS" => Some(CodecType::Opus),
+        _ => None,
+    }
+}
+
+/// G.729 codes 10 ms frames (80 samples, 10 bytes); a packet carries several.
+pub const G729_FRAME_MS: u32 = 10;
+
+/// RTP timestamp clock of a codec. RFC 3551 fixes G722's at 8000 Hz although
+/// it samples at 16 kHz, so `a=rtpmap:9 G722/8000` is the normal declaration.
+fn rtp_clock_rate(codec: CodecType) -> u32 {
+    match codec {
+        CodecType::G722 => 8000,
+        codec => codec.default_clock_rate(),
+    }
+}
+
+/// SDP encoding name of a codec (the inverse of `codec_from_name`).
+pub fn codec_name(codec: CodecType) -> &'static str {
+    match codec {
+        CodecType::PCMU => "PCMU",
+        CodecType::PCMA => "PCMA",
+        CodecType::G722 => "G722",
+        CodecType::G729 => "G729",
+        CodecType::Opus => "opus",
+        CodecType::TelephoneEvent => "telephone-event",
+    }
+}
+
+/// Dynamic payload type L16 is offered with, as 16 kHz mono
+pub const L16_PAYLOAD_TYPE: u8 = 118;
+pub const L16_CLOCK_RATE: u32 = 16000;
+/// Static payload type of L16/44100 mono (RFC 3551); 10 is stereo, which we skip
+const L16_MONO_STATIC_PT: u8 = 11;
+/// Largest L16 payload per packet, keeping packets within a 1500-byte MTU
+const L16_MAX_PAYLOAD: usize = 1400;
+
+/// Codec of a call's audio: one of audio-codec's, or L16 linear PCM (RFC 3551
+/// §4.5.11), which it lacks. Compares equal to the `CodecType` it wraps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioCodec {
+    Native(CodecType),
+    /// 16-bit PCM in network byte order, at the rtpmap clock rate
+    L16,
+}
+
+impl From<CodecType> for AudioCodec {
+    fn from(codec: CodecType) -> Self {
+        AudioCodec::Native(codec)
+    }
+}
 
-    fn encode(&self, pcm: &[i16]) -> Vec<u8> {
-        let mut encoder = create_encoder(*self);
-        encoder.encode(pcm)
+impl PartialEq<CodecType> for AudioCodec {
+    fn eq(&self, other: &CodecType) -> bool {
+        *self == AudioCodec::Native(*other)
     }
+}
 
-    fn decode(&self, data: &[u8]) -> Vec<i16> {
-        let mut decoder = create_decoder(*self);
-        decoder.decode(data)
+impl AudioCodec {
+    /// SDP encoding name
+    pub fn name(&self) -> &'static str {
+        match self {
+            AudioCodec::Native(codec) => codec_name(*codec),
+            AudioCodec::L16 => "L16",
+        }
     }
 }
 
+/// Encode mono PCM as L16: big-endian (network byte order) samples.
+pub fn l16_encode(pcm: &[i16]) -> Vec<u8> {
+    pcm.iter().flat_map(|s| s.to_be_bytes()).collect()
+}
+
+/// Decode an L16 payload; a trailing odd byte is ignored.
+pub fn l16_decode(data: &[u8]) -> Vec<i16> {
+    data.chunks_exact(2)
+        .map(|b| i16::from_be_bytes([b[0], b[1]]))
+        .collect()
+}
+
 /// Parameters negotiated from SDP answer
 #[derive(Debug, Clone)]
 pub struct NegotiatedCodec {
-    pub codec: CodecType,
+    pub codec: AudioCodec,
     pub payload_type: u8,
+    /// RTP timestamp clock, as declared in `a=rtpmap`
     pub clock_rate: u32,
+    /// Rate of the audio the codec encodes and decodes (16000 for G722,
+    /// otherwise the same as `clock_rate`)
+    pub sample_rate: u32,
     pub ptime_ms: u32,
     /// RFC 4733 telephone-event payload type (dynamic, typically 101)
     pub telephone_event_pt: Option<u8>,
 }
 
 impl NegotiatedCodec {
-    /// Samples per frame = clock_rate * ptime_ms / 1000
+    /// Samples per frame = sample_rate * ptime_ms / 1000
     pub fn frame_samples(&self) -> usize {
-        (self.clock_rate * self.ptime_ms / 1000) as usize
+        (self.sample_rate * self.ptime_ms / 1000) as usize
+    }
+
+    /// RTP timestamp increment per frame = clock_rate * ptime_ms / 1000
+    pub fn rtp_frame_ticks(&self) -> u32 {
+        self.clock_rate * self.ptime_ms / 1000
     }
 }
 
 impl Default for NegotiatedCodec {
     fn default() -> Self {
         Self {
-            codec: CodecType::PCMU,
+            codec: AudioCodec::Native(CodecType::PCMU),
             payload_type: 0,
             clock_rate: 8000,
+            sample_rate: 8000,
             ptime_ms: 20,
             telephone_event_pt: None,
         }
@@ -99,11 +186,27 @@ impl Default for NegotiatedCodec {
 }
 
 /// Parse negotiated codec from SDP answer text.
-/// Extracts the first supported audio codec and ptime.
+/// Extracts the first supported audio codec and ptime. Formats audio-codec
+/// cannot handle (Speex, iLBC, GSM, ...) are skipped in favour of the next one.
 pub fn parse_negotiated_codec(sdp: &str) -> NegotiatedCodec {
+    parse_sdp_codec(sdp, true)
+}
+
+/// Codec to use for a remote offer. An offer may list codecs we do not
+/// advertise, so L16 is only taken when it is enabled (`l16`).
+pub fn parse_offered_codec(sdp: &str, l16: bool) -> NegotiatedCodec {
+    parse_sdp_codec(sdp, l16)
+}
+
+fn parse_sdp_codec(sdp: &str, accept_l16: bool) -> NegotiatedCodec {
     let mut result = NegotiatedCodec::default();
     let mut in_audio_section = false;
-    let mut media_pt: Option<u8> = None;
+    // Formats of the m=audio line, in preference order
+    let mut media_pts: Vec<u8> = Vec::new();
+    // Payload types declared by rtpmap: codec (when supported) and clock rate
+    let mut rtpmaps: Vec<(u8, Option<AudioCodec>, Option<u32>)> = Vec::new();
+    // telephone-event payload types and their clock rates
+    let mut telephone_events: Vec<(u8, Option<u32>)> = Vec::new();
 
     for line in sdp.lines() {
         let line = line.trim();
@@ -112,12 +215,11 @@ pub fn parse_negotiated_codec(sdp: &str) -> NegotiatedCodec {
         if line.starts_with("m=audio") {
             in_audio_section = true;
             // First format in m= line is the preferred codec
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() >= 4 {
-                if let Ok(pt) = parts[3].parse::<u8>() {
-                    media_pt = Some(pt);
-                }
-            }
+            media_pts = line
+                .split_whitespace()
+                .skip(3)
+                .filter_map(|pt| pt.parse::<u8>().ok())
+                .collect();
         } else if line.starts_with("m=") {
             in_audio_section = false;
         }
@@ -134,31 +236,20 @@ pub fn parse_negotiated_codec(sdp: &str) -> NegotiatedCodec {
                     if let Ok(pt) = parts[0].parse::<u8>() {
                         let codec_parts: Vec<&str> = parts[1].split('/').collect();
                         if let Some(&codec_name) = codec_parts.first() {
+                            let rate = codec_parts.get(1).and_then(|r| r.parse::<u32>().ok());
                             // Check for telephone-event on every rtpmap line
                             if codec_name.to_uppercase() == "TELEPHONE-EVENT" {
-                                result.telephone_event_pt = Some(pt);
+                                telephone_events.push((pt, rate));
                             }
 
-                            let codec = match codec_name.to_uppercase().as_str() {
-                                "PCMU" => Some(CodecType::PCMU),
-                                "PCMA" => Some(CodecType::PCMA),
-                                "G722" => Some(CodecType::G722),
-                                "G729" => Some(CodecType::G729),
-                                "OPUS" => Some(CodecType::Opus),
-                                _ => None,
+                            // Only mono L16 is played; "L16/16000/2" is skipped
+                            let mono = codec_parts.get(2).is_none_or(|c| *c == "1");
+                            let codec = if codec_name.eq_ignore_ascii_case("L16") {
+                                (accept_l16 && mono).then_some(AudioCodec::L16)
+                            } else {
+                                codec_from_name(codec_name).map(AudioCodec::Native)
                             };
-                            // Only use this if it matches the preferred PT from m= line
-                            if let (Some(c), Some(mpt)) = (codec, media_pt) {
-                                if pt == mpt {
-                                    result.codec = c;
-                                    result.payload_type = pt;
-                                    if let Some(rate_str) = codec_parts.get(1) {
-                                        if let Ok(rate) = rate_str.parse::<u32>() {
-                                            result.clock_rate = rate;
-                                        }
-                                    }
-                                }
-                            }
+                            rtpmaps.push((pt, codec, rate));
                         }
                     }
                 }
@@ -177,29 +268,130 @@ pub fn parse_negotiated_codec(sdp: &str) -> NegotiatedCodec {
         }
     }
 
-    // If no rtpmap matched, determine from PT alone
-    if media_pt.is_some() && result.payload_type != media_pt.unwrap() {
-        if let Some(pt) = media_pt {
-            if let Some(c) = <CodecType as CodecTypeExt>::from_payload_type(pt) {
-                result.codec = c;
-                result.payload_type = pt;
-                result.clock_rate = c.default_clock_rate();
-            }
+    // First format we support, by its rtpmap or, without one (legacy gateways
+    // omit them for static payload types), by its number alone
+    let supported = |pt: u8| match rtpmaps.iter().find(|(mapped, _, _)| *mapped == pt) {
+        Some(&(_, codec, rate)) => codec.map(|c| (pt, c, rate)),
+        None if pt == L16_MONO_STATIC_PT && accept_l16 => Some((pt, AudioCodec::L16, Some(44100))),
+        None => <CodecType as CodecTypeExt>::from_payload_type(pt)
+            .map(|c| (pt, AudioCodec::Native(c), None)),
+    };
+    let chosen = media_pts.iter().find_map(|&pt| supported(pt));
+    match chosen {
+        Some((pt, AudioCodec::Native(c), rate)) => {
+            result.codec = AudioCodec::Native(c);
+            result.payload_type = pt;
+            result.clock_rate = rate.unwrap_or_else(|| rtp_clock_rate(c));
+            result.sample_rate = c.default_clock_rate();
         }
+        // L16 samples at its RTP clock, whatever rate the rtpmap declares
+        Some((pt, AudioCodec::L16, rate)) => {
+            result.codec = AudioCodec::L16;
+            result.payload_type = pt;
+            result.clock_rate = rate.unwrap_or(L16_CLOCK_RATE);
+            result.sample_rate = result.clock_rate;
+        }
+        None => {}
+    }
+
+    // DTMF events share the audio RTP timeline, so prefer the telephone-event
+    // clocked like the chosen codec (e.g. telephone-event/48000 with Opus)
+    result.telephone_event_pt = telephone_events
+        .iter()
+        .find(|(_, rate)| *rate == Some(result.clock_rate))
+        .or(telephone_events.first())
+        .map(|&(pt, _)| pt);
+
+    // A G.729 packet holds whole 10 ms frames; any remainder would be dropped
+    if result.codec == CodecType::G729 && result.ptime_ms % G729_FRAME_MS != 0 {
+        result.ptime_ms = result.ptime_ms.div_ceil(G729_FRAME_MS) * G729_FRAME_MS;
+    }
+
+    // Uncompressed audio fills packets fast (20 ms at 44.1 kHz is 1764 bytes):
+    // fall back to 10 ms packets when a frame would not fit the MTU
+    if result.codec == AudioCodec::L16 && result.frame_samples() * 2 > L16_MAX_PAYLOAD {
+        result.ptime_ms = 10;
     }
 
     result
 }
 
+/// `sdp` with the first payload type of `codec` moved to the front of the
+/// m=audio format list, so it is the codec negotiated from the offer. `None`
+/// when the offer does not contain `codec`.
+pub fn prefer_offered_codec(sdp: &str, codec: CodecType) -> Option<String> {
+    let mut in_audio_section = false;
+    let mut media_pts: Vec<u8> = Vec::new();
+    let mut rtpmaps: Vec<(u8, Option<CodecType>)> = Vec::new();
+    for line in sdp.lines().map(str::trim) {
+        if line.starts_with("m=audio") {
+            in_audio_section = true;
+            media_pts = line
+                .split_whitespace()
+                .skip(3)
+                .filter_map(|pt| pt.parse::<u8>().ok())
+                .collect();
+        } else if line.starts_with("m=") {
+            in_audio_section = false;
+        } else if in_audio_section {
+            let Some((pt, encoding)) = line
+                .strip_prefix("a=rtpmap:")
+                .and_then(|rest| rest.split_once(' '))
+            else {
+                continue;
+            };
+            if let Ok(pt) = pt.parse::<u8>() {
+                let name = encoding.split('/').next().unwrap_or_default();
+                rtpmaps.push((pt, codec_from_name(name)));
+            }
+        }
+    }
+
+    // By rtpmap, or by the static payload type when there is none
+    let is_codec = |pt: u8| match rtpmaps.iter().find(|(mapped, _)| *mapped == pt) {
+        Some(&(_, mapped)) => mapped == Some(codec),
+        None => <CodecType as CodecTypeExt>::from_payload_type(pt) == Some(codec),
+    };
+    let preferred = *media_pts.iter().find(|&&pt| is_codec(pt))?;
+
+    let lines: Vec<String> = sdp
+        .lines()
+        .map(|line| {
+            if !line.trim().starts_with("m=audio") {
+                return line.to_string();
+            }
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let formats = parts
+                .iter()
+                .skip(3)
+                .filter(|pt| pt.parse() != Ok(preferred));
+            let mut reordered = parts[..3.min(parts.len())].to_vec();
+            let preferred = preferred.to_string();
+            reordered.push(&preferred);
+            reordered.extend(formats);
+            reordered.join(" ")
+        })
+        .collect();
+    Some(lines.join("\r\n") + "\r\n")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::webrtc::encoder::{FrameEncoder, OpusConfig};
+    use crate::webrtc::plc::FrameDecoder;
+
+    fn roundtrip(codec: CodecType, pcm: &[i16]) -> Vec<i16> {
+        let clock_rate = codec.default_clock_rate();
+        let mut encoder = FrameEncoder::new(codec, clock_rate, &OpusConfig::default());
+        let mut decoder = FrameDecoder::new(codec, clock_rate, pcm.len());
+        decoder.decode(&encoder.encode(pcm))
+    }
 
     #[test]
     fn roundtrip_pcmu_silence() {
         let pcm = vec![0i16; 160];
-        let encoded = CodecType::PCMU.encode(&pcm);
-        let decoded = CodecType::PCMU.decode(&encoded);
+        let decoded = roundtrip(CodecType::PCMU, &pcm);
         for s in &decoded {
             assert!(s.abs() < 10, "expected near-zero, got {}", s);
         }
@@ -208,8 +400,7 @@ mod tests {
     #[test]
     fn roundtrip_pcma_silence() {
         let pcm = vec![0i16; 160];
-        let encoded = CodecType::PCMA.encode(&pcm);
-        let decoded = CodecType::PCMA.decode(&encoded);
+        let decoded = roundtrip(CodecType::PCMA, &pcm);
         for s in &decoded {
             assert!(s.abs() < 16, "expected near-zero, got {}", s);
         }
@@ -225,6 +416,91 @@ mod tests {
         assert_eq!(codec.ptime_ms, 20); // default
     }
 
+    #[test]
+    fn parse_sdp_static_payload_types_only() {
+        let sdp = "v=0\r\nc=IN IP4 10.0.0.1\r\nm=audio 5004 RTP/AVP 0 8\r\n";
+        let codec = parse_negotiated_codec(sdp);
+        assert_eq!(codec.codec, CodecType::PCMU);
+        assert_eq!(codec.payload_type, 0);
+        assert_eq!(codec.clock_rate, 8000);
+        assert_eq!(codec.ptime_ms, 20);
+
+        let codec = parse_negotiated_codec("v=0\r\nm=audio 5004 RTP/AVP 8 0\r\n");
+        assert_eq!(codec.codec, CodecType::PCMA);
+        assert_eq!(codec.payload_type, 8);
+
+        // An unsupported preferred format falls through to the next static one
+        let sdp = "v=0\r\nm=audio 5004 RTP/AVP 3 8\r\na=rtpmap:3 GSM/8000\r\n";
+        assert_eq!(parse_negotiated_codec(sdp).codec, CodecType::PCMA);
+    }
+
+    #[test]
+    fn parse_sdp_skips_speex_and_ilbc() {
+        let sdp = "v=0\r\nm=audio 5004 RTP/AVP 97 98 8 101\r\n\
+                   a=rtpmap:97 iLBC/8000\r\na=fmtp:97 mode=30\r\n\
+                   a=rtpmap:98 speex/16000\r\na=rtpmap:8 PCMA/8000\r\n\
+                   a=rtpmap:101 telephone-event/8000\r\n";
+        let codec = parse_negotiated_codec(sdp);
+        assert_eq!(codec.codec, CodecType::PCMA);
+        assert_eq!(codec.payload_type, 8);
+        assert_eq!(codec.telephone_event_pt, Some(101));
+
+        // A dynamic PT that clashes with our static fallback (111 = Opus) goes by its rtpmap
+        let sdp = "v=0\r\nm=audio 5004 RTP/AVP 111 0\r\na=rtpmap:111 speex/8000\r\n";
+        let codec = parse_negotiated_codec(sdp);
+        assert_eq!(codec.codec, CodecType::PCMU);
+        assert_eq!(codec.payload_type, 0);
+    }
+
+    #[test]
+    fn prefer_offered_codec_reorders_m_line() {
+        let sdp = "v=0\r\nm=audio 5004 RTP/AVP 111 0 8 101\r\n\
+                   a=rtpmap:111 opus/48000/2\r\na=rtpmap:0 PCMU/8000\r\n\
+                   a=rtpmap:101 telephone-event/8000\r\n";
+        let preferred = prefer_offered_codec(sdp, CodecType::PCMA).unwrap();
+        assert!(preferred.contains("m=audio 5004 RTP/AVP 8 111 0 101\r\n"));
+        let codec = parse_negotiated_codec(&preferred);
+        assert_eq!(codec.codec, CodecType::PCMA);
+        assert_eq!(codec.payload_type, 8);
+
+        assert!(prefer_offered_codec(sdp, CodecType::G722).is_none());
+    }
+
+    #[test]
+    fn roundtrip_l16_network_byte_order() {
+        let pcm: Vec<i16> = vec![0x0102, -2, i16::MAX, i16::MIN, 0];
+        let mut encoder = FrameEncoder::new(AudioCodec::L16, 16000, &OpusConfig::default());
+        let encoded = encoder.encode(&pcm);
+        assert_eq!(&encoded[..4], &[0x01, 0x02, 0xff, 0xfe]);
+        assert_eq!(encoded.len(), pcm.len() * 2);
+
+        let mut decoder = FrameDecoder::new(AudioCodec::L16, 16000, pcm.len());
+        assert_eq!(decoder.decode(&encoded), pcm);
+    }
+
+    #[test]
+    fn parse_sdp_l16() {
+        let sdp = "v=0\r\nm=audio 5004 RTP/AVP 118 0\r\na=rtpmap:118 L16/16000\r\n";
+        let codec = parse_negotiated_codec(sdp);
+        assert_eq!(codec.codec, AudioCodec::L16);
+        assert_eq!(codec.payload_type, 118);
+        assert_eq!(codec.clock_rate, 16000);
+        assert_eq!(codec.sample_rate, 16000);
+        assert_eq!(codec.frame_samples(), 320);
+
+        // Not taken from an offer unless enabled, nor in stereo
+        assert_eq!(parse_offered_codec(sdp, false).codec, CodecType::PCMU);
+        let stereo = "v=0\r\nm=audio 5004 RTP/AVP 118 8\r\na=rtpmap:118 L16/16000/2\r\n";
+        assert_eq!(parse_negotiated_codec(stereo).codec, CodecType::PCMA);
+
+        // Static PT 11 is 44.1 kHz mono; 20 ms would overflow the MTU
+        let codec = parse_negotiated_codec("v=0\r\nm=audio 5004 RTP/AVP 11\r\n");
+        assert_eq!(codec.codec, AudioCodec::L16);
+        assert_eq!(codec.clock_rate, 44100);
+        assert_eq!(codec.ptime_ms, 10);
+        assert_eq!(codec.frame_samples(), 441);
+    }
+
     #[test]
     fn parse_sdp_pcma_with_ptime() {
         let sdp = "v=0\r\nm=audio 5004 RTP/AVP 8\r\na=rtpmap:8 PCMA/8000\r\na=ptime:30\r\n";
@@ -246,6 +522,27 @@ mod tests {
         assert_eq!(codec.frame_samples(), 960); // 48000 * 20 / 1000
     }
 
+    #[test]
+    fn roundtrip_g729_frame_sizing() {
+        let config = OpusConfig::default();
+        // 20 ms and 30 ms packets: two and three 10-byte sub-frames
+        for (samples, bytes) in [(160, 20), (240, 30)] {
+            let pcm = vec![0i16; samples];
+            let mut encoder = FrameEncoder::new(CodecType::G729, 8000, &config);
+            let encoded = encoder.encode(&pcm);
+            assert_eq!(encoded.len(), bytes);
+            let mut decoder = FrameDecoder::new(CodecType::G729, 8000, samples);
+            assert_eq!(decoder.decode(&encoded).len(), samples);
+        }
+
+        // ptime is rounded up to whole 10 ms frames
+        let sdp = "v=0\r\nm=audio 5004 RTP/AVP 18\r\na=rtpmap:18 G729/8000\r\na=ptime:25\r\n";
+        let codec = parse_negotiated_codec(sdp);
+        assert_eq!(codec.codec, CodecType::G729);
+        assert_eq!(codec.ptime_ms, 30);
+        assert_eq!(codec.frame_samples(), 240); // three 80-sample frames
+    }
+
     #[test]
     fn parse_sdp_g722() {
         let sdp = "v=0\r\nm=audio 5004 RTP/AVP 9\r\na=rtpmap:9 G722/16000\r\na=ptime:20\r\n";
@@ -257,6 +554,48 @@ mod tests {
         assert_eq!(codec.frame_samples(), 320); // 16000 * 20 / 1000
     }
 
+    #[test]
+    fn parse_sdp_g722_rtp_clock_quirk() {
+        let sdp = "v=0\r\nm=audio 5004 RTP/AVP 9\r\na=rtpmap:9 G722/8000\r\na=ptime:20\r\n";
+        let codec = parse_negotiated_codec(sdp);
+        assert_eq!(codec.codec, CodecType::G722);
+        assert_eq!(codec.clock_rate, 8000);
+        assert_eq!(codec.sample_rate, 16000);
+        assert_eq!(codec.frame_samples(), 320); // 16000 * 20 / 1000
+        assert_eq!(codec.rtp_frame_ticks(), 160); // 8000 * 20 / 1000
+
+        // Same without an rtpmap line (static payload type)
+        let codec = parse_negotiated_codec("v=0\r\nm=audio 5004 RTP/AVP 9\r\n");
+        assert_eq!(codec.clock_rate, 8000);
+        assert_eq!(codec.sample_rate, 16000);
+    }
+
+    #[test]
+    fn parse_sdp_telephone_event_pt() {
+        let sdp = "v=0\r\nm=audio 5004 RTP/AVP 0 96\r\na=rtpmap:0 PCMU/8000\r\na=rtpmap:96 telephone-event/8000\r\na=fmtp:96 0-16\r\n";
+        assert_eq!(parse_negotiated_codec(sdp).telephone_event_pt, Some(96));
+
+        // With several rates offered, the one matching the audio clock wins
+        let sdp = "v=0\r\nm=audio 5004 RTP/AVP 111 101 96\r\na=rtpmap:111 opus/48000/2\r\na=rtpmap:101 telephone-event/8000\r\na=rtpmap:96 telephone-event/48000\r\n";
+        assert_eq!(parse_negotiated_codec(sdp).telephone_event_pt, Some(96));
+
+        // An answer without telephone-event leaves DTMF to SIP INFO
+        let sdp = "v=0\r\nm=audio 5004 RTP/AVP 0\r\na=rtpmap:0 PCMU/8000\r\n";
+        assert_eq!(parse_negotiated_codec(sdp).telephone_event_pt, None);
+    }
+
+    #[test]
+    fn test_codec_from_name() {
+        assert_eq!(codec_from_name("pcmu"), Some(CodecType::PCMU));
+        assert_eq!(codec_from_name("OPUS"), Some(CodecType::Opus));
+        assert_eq!(codec_from_name("telephone-event"), None);
+        assert_eq!(codec_from_name("speex"), None);
+        assert_eq!(
+            codec_from_name(codec_name(CodecType::G729)),
+            Some(CodecType::G729)
+        );
+    }
+
     #[test]
     fn test_codec_extensions() {
         // Test from_payload_type
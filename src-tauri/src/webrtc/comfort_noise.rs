@@ -0,0 +1,99 @@
+//! Comfort noise (RFC 3389) for the playback path.
+//!
+//! A far end using VAD stops sending during silence, optionally announcing the
+//! background level with a CN packet. Playing dead silence meanwhile sounds
+//! like a dropped call, so the gap is filled with low-level noise at the
+//! announced level (or `DEFAULT_LEVEL_DBOV` without a CN packet). The noise is
+//! low-pass filtered white noise; CN spectral coefficients are ignored.
+
+/// Static RTP payload type of comfort noise (RFC 3551)
+pub const CN_PAYLOAD_TYPE: u8 = 13;
+
+/// Noise level used for gaps not announced by a CN packet, in -dBov
+const DEFAULT_LEVEL_DBOV: u8 = 70;
+
+/// Smoothing of the one-pole low-pass filter shaping the noise (0 - 1)
+const LOWPASS_ALPHA: f32 = 0.4;
+
+/// Noise level of a CN payload, in -dBov (0 = loudest, 127 = quietest).
+pub fn parse_level(payload: &[u8]) -> Option<u8> {
+    payload.first().map(|b| b & 0x7f)
+}
+
+pub struct ComfortNoise {
+    level_dbov: u8,
+    /// xorshift32 state
+    seed: u32,
+    /// Low-pass filter memory
+    last: f32,
+}
+
+impl Default for ComfortNoise {
+    fn default() -> Self {
+        Self {
+            level_dbov: DEFAULT_LEVEL_DBOV,
+            seed: 0x9e37_79b9,
+            last: 0.0,
+        }
+    }
+}
+
+impl ComfortNoise {
+    /// Use the level announced by a CN packet from now on.
+    pub fn set_level(&mut self, level_dbov: u8) {
+        self.level_dbov = level_dbov.min(127);
+    }
+
+    /// One frame of noise at the current level.
+    pub fn generate(&mut self, frame_samples: usize) -> Vec<i16> {
+        let rms = 32767.0 * 10f32.powf(-(self.level_dbov as f32) / 20.0);
+        // Uniform noise in [-1, 1) has an RMS of 1/sqrt(3); the filter scales
+        // it by sqrt(alpha / (2 - alpha))
+        let gain = rms * 3f32.sqrt() * ((2.0 - LOWPASS_ALPHA) / LOWPASS_ALPHA).sqrt();
+        (0..frame_samples)
+            .map(|_| {
+                let white = self.next_random() * gain;
+                self.last += LOWPASS_ALPHA * (white - self.last);
+                self.last.clamp(i16::MIN as f32, i16::MAX as f32) as i16
+            })
+            .collect()
+    }
+
+    /// Uniform random value in [-1, 1)
+    fn next_random(&mut self) -> f32 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        (self.seed as f32 / u32::MAX as f32) * 2.0 - 1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rms_dbov(pcm: &[i16]) -> f32 {
+        let mean_square = pcm.iter().map(|&s| (s as f32).powi(2)).sum::<f32>() / pcm.len() as f32;
+        20.0 * (mean_square.sqrt() / 32767.0).log10()
+    }
+
+    #[test]
+    fn test_parse_level() {
+        assert_eq!(parse_level(&[60]), Some(60));
+        assert_eq!(parse_level(&[0xff, 1, 2]), Some(127));
+        assert_eq!(parse_level(&[]), None);
+    }
+
+    #[test]
+    fn test_generate_matches_level() {
+        let mut cn = ComfortNoise::default();
+        cn.set_level(40);
+        let pcm = cn.generate(8000);
+        let level = rms_dbov(&pcm);
+        assert!((level + 40.0).abs() < 2.0, "level {}", level);
+
+        cn.set_level(70);
+        let level = rms_dbov(&cn.generate(8000));
+        assert!((level + 70.0).abs() < 3.0, "level {}", level);
+    }
+}
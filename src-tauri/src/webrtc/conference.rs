@@ -0,0 +1,197 @@
+//! Local conference mixing.
+//!
+//! Every call in a conference keeps its own `AudioBridge`, so the local user
+//! already hears all remote parties (one playback stream per call) and is heard
+//! by all of them (one capture stream per call). The mixer adds the missing
+//! path between the remote parties: each call's decoded far-end audio is queued
+//! for every other call and mixed into that call's outgoing frames before
+//! encoding.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+/// Sample rate of the queued conference audio
+const MIX_RATE: u32 = 16000;
+
+/// Maximum audio queued per path (200 ms); older samples are dropped
+const MAX_QUEUED_SAMPLES: usize = MIX_RATE as usize / 5;
+
+/// Exchanges far-end audio between the legs (calls) of a local conference.
+pub struct ConferenceMixer {
+    inner: Mutex<MixerInner>,
+}
+
+#[derive(Default)]
+struct MixerInner {
+    legs: Vec<String>,
+    /// Queued audio at `MIX_RATE`, keyed by `(source leg, destination leg)`
+    paths: HashMap<(String, String), VecDeque<f32>>,
+}
+
+impl ConferenceMixer {
+    pub fn new(leg_ids: &[String]) -> Arc<Self> {
+        Arc::new(Self {
+            inner: Mutex::new(MixerInner {
+                legs: leg_ids.to_vec(),
+                paths: HashMap::new(),
+            }),
+        })
+    }
+
+    pub fn leg_ids(&self) -> Vec<String> {
+        self.inner.lock().unwrap().legs.clone()
+    }
+
+    /// Drop a leg and any audio queued from or for it. Returns false if it was not a leg.
+    pub fn remove_leg(&self, leg_id: &str) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        let before = inner.legs.len();
+        inner.legs.retain(|l| l != leg_id);
+        inner
+            .paths
+            .retain(|(from, to), _| from != leg_id && to != leg_id);
+        inner.legs.len() != before
+    }
+
+    /// Queue decoded far-end audio received on leg `from` for all other legs.
+    pub fn push_remote(&self, from: &str, pcm: &[f32], rate: u32) {
+        if pcm.is_empty() || rate == 0 {
+            return;
+        }
+        let resampled = resample_to_len(pcm, pcm.len() * MIX_RATE as usize / rate as usize);
+
+        let mut inner = self.inner.lock().unwrap();
+        let MixerInner { legs, paths } = &mut *inner;
+        for to in legs.iter().filter(|l| l.as_str() != from) {
+            let queue = paths.entry((from.to_string(), to.clone())).or_default();
+            queue.extend(resampled.iter().copied());
+            let overflow = queue.len().saturating_sub(MAX_QUEUED_SAMPLES);
+            queue.drain(..overflow);
+        }
+    }
+
+    /// Mix the audio queued for leg `to` into `pcm` (one outgoing frame at `rate`).
+    ///
+    /// Missing audio (a leg that has not delivered enough yet) is treated as silence.
+    pub fn mix_into(&self, to: &str, pcm: &mut [f32], rate: u32) {
+        if pcm.is_empty() || rate == 0 {
+            return;
+        }
+        let frame_len = pcm.len();
+        let needed = frame_len * MIX_RATE as usize / rate as usize;
+
+        let mut inner = self.inner.lock().unwrap();
+        let MixerInner { legs, paths } = &mut *inner;
+        for from in legs.iter().filter(|l| l.as_str() != to) {
+            let Some(queue) = paths.get_mut(&(from.clone(), to.to_string())) else {
+                continue;
+            };
+            let take = needed.min(queue.len());
+            let mut chunk: Vec<f32> = queue.drain(..take).collect();
+            chunk.resize(needed, 0.0);
+            for (out, s) in pcm.iter_mut().zip(resample_to_len(&chunk, frame_len)) {
+                *out += s;
+            }
+        }
+    }
+}
+
+/// Handle held by an `AudioBridge` whose call takes part in a conference.
+#[derive(Clone)]
+pub struct ConferenceLink {
+    pub mixer: Arc<ConferenceMixer>,
+    pub leg_id: String,
+}
+
+/// Linear-interpolation resample of `input` to exactly `out_len` samples.
+fn resample_to_len(input: &[f32], out_len: usize) -> Vec<f32> {
+    if input.is_empty() {
+        return vec![0.0; out_len];
+    }
+    if input.len() == out_len {
+        return input.to_vec();
+    }
+    let step = input.len() as f64 / out_len as f64;
+    (0..out_len)
+        .map(|i| {
+            let pos = i as f64 * step;
+            let idx = pos as usize;
+            let frac = (pos - idx as f64) as f32;
+            let a = input[idx.min(input.len() - 1)];
+            let b = input[(idx + 1).min(input.len() - 1)];
+            a + (b - a) * frac
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn legs(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_resample_to_len() {
+        assert_eq!(resample_to_len(&[0.5, 0.5], 2), vec![0.5, 0.5]);
+        assert_eq!(resample_to_len(&[], 3), vec![0.0; 3]);
+        let up = resample_to_len(&[0.0, 1.0], 4);
+        assert_eq!(up, vec![0.0, 0.5, 1.0, 1.0]);
+        assert_eq!(resample_to_len(&[0.0, 0.25, 0.5, 0.75], 2), vec![0.0, 0.5]);
+    }
+
+    #[test]
+    fn test_audio_routed_to_other_legs_only() {
+        let mixer = ConferenceMixer::new(&legs(&["a", "b"]));
+        mixer.push_remote("a", &[0.25; 160], 8000);
+
+        let mut to_b = vec![0.0f32; 160];
+        mixer.mix_into("b", &mut to_b, 8000);
+        assert!(to_b.iter().all(|&s| (s - 0.25).abs() < 1e-6));
+
+        let mut to_a = vec![0.0f32; 160];
+        mixer.mix_into("a", &mut to_a, 8000);
+        assert!(to_a.iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn test_mix_adds_to_existing_audio_across_rates() {
+        let mixer = ConferenceMixer::new(&legs(&["a", "b"]));
+        // 20 ms at 8 kHz in, 20 ms at 48 kHz out
+        mixer.push_remote("a", &[0.5; 160], 8000);
+        let mut frame = vec![0.25f32; 960];
+        mixer.mix_into("b", &mut frame, 48000);
+        assert!(frame.iter().all(|&s| (s - 0.75).abs() < 1e-6));
+    }
+
+    #[test]
+    fn test_underrun_is_silence_and_queue_is_bounded() {
+        let mixer = ConferenceMixer::new(&legs(&["a", "b"]));
+        mixer.push_remote("a", &[0.5; 80], 8000);
+        let mut frame = vec![0.0f32; 160];
+        mixer.mix_into("b", &mut frame, 8000);
+        assert!(frame[..70].iter().all(|&s| (s - 0.5).abs() < 1e-6));
+        assert!(frame[90..].iter().all(|&s| s == 0.0));
+
+        for _ in 0..100 {
+            mixer.push_remote("a", &[0.5; 160], 8000);
+        }
+        let inner = mixer.inner.lock().unwrap();
+        let queued = inner.paths[&("a".to_string(), "b".to_string())].len();
+        assert_eq!(queued, MAX_QUEUED_SAMPLES);
+    }
+
+    #[test]
+    fn test_remove_leg() {
+        let mixer = ConferenceMixer::new(&legs(&["a", "b", "c"]));
+        mixer.push_remote("a", &[0.5; 160], 8000);
+        assert!(mixer.remove_leg("a"));
+        assert!(!mixer.remove_leg("a"));
+        assert_eq!(mixer.leg_ids(), legs(&["b", "c"]));
+
+        let mut frame = vec![0.0f32; 160];
+        mixer.mix_into("b", &mut frame, 8000);
+        assert!(frame.iter().all(|&s| s == 0.0));
+    }
+}
@@ -1,5 +1,6 @@
 use audio_codec::Resampler;
 use nnnoiseless::DenoiseState;
+use std::collections::VecDeque;
 
 /// Real-time microphone noise reducer using RNNoise (nnnoiseless).
 ///
@@ -8,6 +9,7 @@ use nnnoiseless::DenoiseState;
 ///     → resample to 48 kHz  (nnnoiseless requires 48 kHz)
 ///     → f32 i16-scale        (range −32768..32767, not normalised)
 ///     → DenoiseState::process_frame() in 480-sample chunks (10 ms)
+///     → blend with the input by `strength` (wet/dry mix)
 ///     → f32 → i16
 ///     → resample back to codec_rate
 ///     → resize to exact expected_len
@@ -20,6 +22,10 @@ pub struct NoiseReducer {
     up_resampler: Option<Resampler>,
     /// 48 000 Hz → codec_rate (None when codec_rate already is 48 000)
     down_resampler: Option<Resampler>,
+    /// Share of denoised signal in the output (0.0 = input only, 1.0 = full denoise)
+    strength: f32,
+    /// Input at 48 kHz delayed by one RNNoise frame, to line up with its output
+    dry: VecDeque<f32>,
 }
 
 // DenoiseState contains raw pointers, but we only touch it from a single task.
@@ -40,9 +46,16 @@ impl NoiseReducer {
             denoiser: DenoiseState::new(),
             up_resampler: up,
             down_resampler: down,
+            strength: 1.0,
+            dry: VecDeque::from(vec![0.0; DenoiseState::FRAME_SIZE]),
         }
     }
 
+    /// Set the wet/dry mix, clamped to 0.0 (bypass) - 1.0 (full denoise).
+    pub fn set_strength(&mut self, strength: f32) {
+        self.strength = strength.clamp(0.0, 1.0);
+    }
+
     /// Denoise one PCM frame.
     ///
     /// * `pcm`          – input samples at codec_rate
@@ -89,6 +102,13 @@ impl NoiseReducer {
             offset += chunk_len;
         }
 
+        // Mix in the input, which RNNoise's output lags by one frame
+        for (out, &input) in output_f32.iter_mut().zip(&input_f32) {
+            self.dry.push_back(input);
+            let dry = self.dry.pop_front().unwrap_or(0.0);
+            *out = dry + (*out - dry) * self.strength;
+        }
+
         // 4. f32 → i16 (clamp to avoid overflow)
         let denoised: Vec<i16> = output_f32
             .iter()
@@ -0,0 +1,237 @@
+use audio_codec::Resampler;
+
+/// Rate the adaptive filter runs at (wideband, like WebRTC's AEC core band)
+const AEC_SAMPLE_RATE: u32 = 16_000;
+
+/// Echo path length covered by the filter: speaker buffering + room + mic buffering
+const ECHO_TAIL_MS: u32 = 200;
+
+/// NLMS step size
+const STEP_SIZE: f32 = 0.25;
+
+/// Regularisation added to the reference energy (avoids huge steps on near-silence)
+const REGULARIZATION: f32 = 1e-3;
+
+/// Geigel double-talk threshold: near-end louder than this fraction of the
+/// recent far-end peak means the local user is talking
+const DOUBLE_TALK_THRESHOLD: f32 = 0.6;
+
+/// Frames to keep adaptation frozen after double talk was detected
+const DOUBLE_TALK_HANGOVER: u32 = 5;
+
+/// Far-end peak below which there is nothing to cancel or learn from
+const FAR_END_SILENCE: f32 = 1e-3;
+
+/// Acoustic echo canceller for the microphone path.
+///
+/// Processing chain per frame:
+///   mic i16 PCM @ mic_rate, far-end reference i16 PCM @ reference_rate
+///     → resample both to 16 kHz
+///     → NLMS adaptive filter estimates the echo of the reference in the mic
+///       signal and subtracts it (adaptation frozen during double talk)
+///     → resample back to mic_rate
+///     → resize to exact expected_len
+///
+/// The filter converges over a few seconds of far-end speech, so create one
+/// per call and feed it every frame, not once per packet.
+pub struct EchoCanceller {
+    filter: NlmsFilter,
+    /// mic_rate → 16 kHz (None when mic_rate already is 16 kHz)
+    mic_down: Option<Resampler>,
+    /// reference_rate → 16 kHz (None when reference_rate already is 16 kHz)
+    reference_down: Option<Resampler>,
+    /// 16 kHz → mic_rate (None when mic_rate already is 16 kHz)
+    mic_up: Option<Resampler>,
+}
+
+impl EchoCanceller {
+    /// `mic_sample_rate` / `reference_sample_rate` must match the PCM passed to `process()`.
+    pub fn new(mic_sample_rate: u32, reference_sample_rate: u32) -> Self {
+        let resampler =
+            |from: u32, to: u32| (from != to).then(|| Resampler::new(from as usize, to as usize));
+        Self {
+            filter: NlmsFilter::new((AEC_SAMPLE_RATE * ECHO_TAIL_MS / 1000) as usize),
+            mic_down: resampler(mic_sample_rate, AEC_SAMPLE_RATE),
+            reference_down: resampler(reference_sample_rate, AEC_SAMPLE_RATE),
+            mic_up: resampler(AEC_SAMPLE_RATE, mic_sample_rate),
+        }
+    }
+
+    /// Remove the echo of `reference` (what the speaker played for this frame)
+    /// from one mic frame.
+    ///
+    /// * `mic`          – captured samples at mic_rate
+    /// * `reference`    – far-end samples at reference_rate covering the same frame
+    /// * `expected_len` – target output length; the result is zero-padded or
+    ///   truncated to this size.
+    pub fn process(&mut self, mic: &[i16], reference: &[i16], expected_len: usize) -> Vec<i16> {
+        let mic_16k = match self.mic_down {
+            Some(ref mut r) => r.resample(mic),
+            None => mic.to_vec(),
+        };
+        let reference_16k = match self.reference_down {
+            Some(ref mut r) => r.resample(reference),
+            None => reference.to_vec(),
+        };
+
+        let near: Vec<f32> = mic_16k.iter().map(|&s| s as f32 / 32768.0).collect();
+        // The two resamplers may drift by ±1 sample; pad the reference with silence
+        let mut far: Vec<f32> = reference_16k.iter().map(|&s| s as f32 / 32768.0).collect();
+        far.resize(near.len(), 0.0);
+
+        let cleaned: Vec<i16> = self
+            .filter
+            .process(&near, &far)
+            .iter()
+            .map(|&s| (s.clamp(-1.0, 1.0) * 32767.0) as i16)
+            .collect();
+
+        let mut result = match self.mic_up {
+            Some(ref mut r) => r.resample(&cleaned),
+            None => cleaned,
+        };
+        result.resize(expected_len, 0);
+        result
+    }
+}
+
+/// Time-domain NLMS filter with a Geigel double-talk detector.
+struct NlmsFilter {
+    weights: Vec<f32>,
+    /// Reference history, stored twice so the newest `taps` samples are always
+    /// the contiguous slice `history[pos..pos + taps]` (newest first)
+    history: Vec<f32>,
+    pos: usize,
+    /// Energy of the reference samples currently in the filter window
+    energy: f32,
+    hangover: u32,
+}
+
+impl NlmsFilter {
+    fn new(taps: usize) -> Self {
+        Self {
+            weights: vec![0.0; taps],
+            history: vec![0.0; taps * 2],
+            pos: 0,
+            energy: 0.0,
+            hangover: 0,
+        }
+    }
+
+    fn push_reference(&mut self, sample: f32) {
+        let taps = self.weights.len();
+        self.pos = if self.pos == 0 {
+            taps - 1
+        } else {
+            self.pos - 1
+        };
+        let oldest = self.history[self.pos];
+        self.energy = (self.energy + sample * sample - oldest * oldest).max(0.0);
+        self.history[self.pos] = sample;
+        self.history[self.pos + taps] = sample;
+    }
+
+    /// Cancel echo in one frame of `near` (mic) given the aligned `far` (reference).
+    fn process(&mut self, near: &[f32], far: &[f32]) -> Vec<f32> {
+        let taps = self.weights.len();
+        // Recompute once per frame so the running sum cannot drift
+        self.energy = self.history[self.pos..self.pos + taps]
+            .iter()
+            .map(|x| x * x)
+            .sum();
+
+        let near_peak = near.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+        let far_peak = self.history[..taps]
+            .iter()
+            .chain(far)
+            .fold(0.0f32, |m, s| m.max(s.abs()));
+
+        if far_peak < FAR_END_SILENCE {
+            // Nothing played recently: nothing to cancel, keep the learned echo path
+            for &x in far {
+                self.push_reference(x);
+            }
+            return near.to_vec();
+        }
+
+        if near_peak > DOUBLE_TALK_THRESHOLD * far_peak {
+            self.hangover = DOUBLE_TALK_HANGOVER;
+        } else {
+            self.hangover = self.hangover.saturating_sub(1);
+        }
+        let adapt = self.hangover == 0;
+
+        near.iter()
+            .zip(far)
+            .map(|(&d, &x)| {
+                self.push_reference(x);
+                let window = &self.history[self.pos..self.pos + taps];
+                let echo: f32 = self.weights.iter().zip(window).map(|(w, x)| w * x).sum();
+                let error = d - echo;
+
+                if adapt {
+                    let step = STEP_SIZE * error / (self.energy + REGULARIZATION);
+                    for (w, x) in self.weights.iter_mut().zip(window) {
+                        *w += step * x;
+                    }
+                }
+                error
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic noise-like far-end signal
+    fn far_end(len: usize) -> Vec<f32> {
+        let mut seed = 12345u32;
+        (0..len)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                ((seed >> 16) as f32 / 32768.0 - 1.0) * 0.3
+            })
+            .collect()
+    }
+
+    fn energy(pcm: &[f32]) -> f32 {
+        pcm.iter().map(|s| s * s).sum()
+    }
+
+    #[test]
+    fn test_filter_cancels_delayed_echo() {
+        let far = far_end(16_000 * 3);
+        // Echo: reference delayed by 40 samples, attenuated
+        let mut near = vec![0.0f32; far.len()];
+        for i in 40..far.len() {
+            near[i] = 0.5 * far[i - 40];
+        }
+
+        let mut filter = NlmsFilter::new(128);
+        let mut out = Vec::new();
+        for (n, f) in near.chunks(320).zip(far.chunks(320)) {
+            out.extend(filter.process(n, f));
+        }
+
+        // After convergence (last second) the echo is attenuated by well over 20 dB
+        let tail = far.len() - 16_000;
+        assert!(energy(&out[tail..]) < energy(&near[tail..]) * 0.01);
+    }
+
+    #[test]
+    fn test_near_end_passes_through_without_far_end() {
+        let near = far_end(320);
+        let mut filter = NlmsFilter::new(128);
+        assert_eq!(filter.process(&near, &[0.0; 320]), near);
+    }
+
+    #[test]
+    fn test_process_output_length() {
+        let mut aec = EchoCanceller::new(48_000, 8_000);
+        let out = aec.process(&[0i16; 960], &[0i16; 160], 960);
+        assert_eq!(out.len(), 960);
+        assert!(out.iter().all(|&s| s == 0));
+    }
+}
@@ -0,0 +1,303 @@
+//! Encoding for the capture path.
+//!
+//! One `FrameEncoder` lives for the whole call: codecs such as Opus and G.722
+//! carry state between frames, so recreating the encoder per frame degrades
+//! quality and defeats Opus FEC and DTX. Opus is driven through its own
+//! encoder so bitrate, in-band FEC and DTX can be tuned (`OpusConfig`).
+
+use std::os::raw::c_int;
+use std::ptr::NonNull;
+
+use audio_codec::{create_encoder, CodecType, Encoder};
+use opusic_sys::{
+    opus_encode, opus_encoder_create, opus_encoder_ctl, opus_encoder_destroy,
+    OPUS_APPLICATION_VOIP, OPUS_OK, OPUS_SET_BITRATE_REQUEST, OPUS_SET_DTX_REQUEST,
+    OPUS_SET_INBAND_FEC_REQUEST, OPUS_SET_PACKET_LOSS_PERC_REQUEST,
+};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use super::codec::{l16_encode, AudioCodec};
+
+/// Opus bitrate limits (bits per second) accepted by `set_opus_config`
+pub const OPUS_MIN_BITRATE: u32 = 6_000;
+pub const OPUS_MAX_BITRATE: u32 = 510_000;
+
+/// Remote loss (RTCP fraction lost) above which adaptive bitrate steps down
+const ADAPTIVE_HIGH_LOSS: f64 = 0.10;
+/// Remote loss below which adaptive bitrate steps back up
+const ADAPTIVE_LOW_LOSS: f64 = 0.02;
+/// Bitrate added per step while the network is clean
+const ADAPTIVE_STEP_UP: u32 = 4_000;
+
+/// Expected packet loss the encoder plans FEC for; Opus only adds FEC data when this is non-zero
+const OPUS_FEC_LOSS_PERC: c_int = 10;
+
+/// Largest encoded packet accepted from `opus_encode`
+const OPUS_MAX_PACKET: usize = 1500;
+
+/// Opus encoder settings for outgoing audio.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OpusConfig {
+    /// Target bitrate in bits per second
+    pub bitrate: u32,
+    /// In-band forward error correction (lets the far end recover single lost packets)
+    pub fec: bool,
+    /// Discontinuous transmission: stop sending during silence
+    pub dtx: bool,
+}
+
+impl Default for OpusConfig {
+    fn default() -> Self {
+        Self {
+            bitrate: 32_000,
+            fec: true,
+            dtx: false,
+        }
+    }
+}
+
+impl OpusConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(OPUS_MIN_BITRATE..=OPUS_MAX_BITRATE).contains(&self.bitrate) {
+            return Err(format!(
+                "Opus bitrate must be between {} and {} bps",
+                OPUS_MIN_BITRATE, OPUS_MAX_BITRATE
+            ));
+        }
+        Ok(())
+    }
+}
+
+pub struct FrameEncoder {
+    encoder: EncoderKind,
+}
+
+enum EncoderKind {
+    Opus(OpusConfiguredEncoder),
+    L16,
+    Other(Box<dyn Encoder>),
+}
+
+impl FrameEncoder {
+    pub fn new(codec: impl Into<AudioCodec>, clock_rate: u32, opus_config: &OpusConfig) -> Self {
+        let encoder = match codec.into() {
+            AudioCodec::L16 => EncoderKind::L16,
+            AudioCodec::Native(CodecType::Opus) => {
+                match OpusConfiguredEncoder::new(clock_rate, opus_config) {
+                    Ok(opus) => EncoderKind::Opus(opus),
+                    Err(e) => {
+                        warn!(error = %e, "Configurable Opus encoder unavailable, using defaults");
+                        EncoderKind::Other(create_encoder(CodecType::Opus))
+                    }
+                }
+            }
+            AudioCodec::Native(codec) => EncoderKind::Other(create_encoder(codec)),
+        };
+        Self { encoder }
+    }
+
+    /// Encode one frame of mono PCM. An empty result means nothing should be
+    /// sent for this frame (Opus DTX during silence).
+    pub fn encode(&mut self, pcm: &[i16]) -> Vec<u8> {
+        match self.encoder {
+            EncoderKind::Opus(ref mut opus) => opus.encode(pcm),
+            EncoderKind::L16 => l16_encode(pcm),
+            EncoderKind::Other(ref mut encoder) => encoder.encode(pcm),
+        }
+    }
+
+    /// Apply new Opus settings to the running encoder (no-op for other codecs).
+    pub fn set_opus_config(&mut self, config: &OpusConfig) {
+        if let EncoderKind::Opus(ref mut opus) = self.encoder {
+            opus.configure(config);
+        }
+    }
+}
+
+/// Next Opus bitrate for adaptive bitrate, given the fraction of our packets
+/// the remote reported lost: a quarter less under heavy loss, a small step up
+/// once loss is low again, unchanged in between. Always within `min..=max`.
+pub fn adapt_opus_bitrate(current: u32, fraction_lost: f64, min: u32, max: u32) -> u32 {
+    let next = if fraction_lost > ADAPTIVE_HIGH_LOSS {
+        current - current / 4
+    } else if fraction_lost < ADAPTIVE_LOW_LOSS {
+        current.saturating_add(ADAPTIVE_STEP_UP)
+    } else {
+        current
+    };
+    next.clamp(min, max)
+}
+
+/// Mono Opus encoder with bitrate, FEC and DTX control, which the
+/// `audio_codec` encoder does not expose.
+struct OpusConfiguredEncoder {
+    encoder: NonNull<opusic_sys::OpusEncoder>,
+    dtx: bool,
+}
+
+// The encoder state is only touched from the capture task.
+unsafe impl Send for OpusConfiguredEncoder {}
+
+impl OpusConfiguredEncoder {
+    fn new(sample_rate: u32, config: &OpusConfig) -> Result<Self, String> {
+        let mut error: c_int = 0;
+        let ptr = unsafe {
+            opus_encoder_create(sample_rate as i32, 1, OPUS_APPLICATION_VOIP, &mut error)
+        };
+        if error != OPUS_OK {
+            if !ptr.is_null() {
+                unsafe { opus_encoder_destroy(ptr) };
+            }
+            return Err(format!("opus_encoder_create failed ({})", error));
+        }
+        let encoder =
+            NonNull::new(ptr).ok_or_else(|| "opus_encoder_create returned null".to_string())?;
+        let mut opus = Self {
+            encoder,
+            dtx: config.dtx,
+        };
+        opus.configure(config);
+        Ok(opus)
+    }
+
+    fn configure(&mut self, config: &OpusConfig) {
+        let loss_perc = if config.fec { OPUS_FEC_LOSS_PERC } else { 0 };
+        let settings = [
+            (OPUS_SET_BITRATE_REQUEST, config.bitrate as c_int),
+            (OPUS_SET_INBAND_FEC_REQUEST, config.fec as c_int),
+            (OPUS_SET_PACKET_LOSS_PERC_REQUEST, loss_perc),
+            (OPUS_SET_DTX_REQUEST, config.dtx as c_int),
+        ];
+        for (request, value) in settings {
+            let result = unsafe { opus_encoder_ctl(self.encoder.as_ptr(), request, value) };
+            if result != OPUS_OK {
+                warn!(request, value, result, "opus_encoder_ctl failed");
+            }
+        }
+        self.dtx = config.dtx;
+    }
+
+    fn encode(&mut self, pcm: &[i16]) -> Vec<u8> {
+        let mut output = vec![0u8; OPUS_MAX_PACKET];
+        let len = unsafe {
+            opus_encode(
+                self.encoder.as_ptr(),
+                pcm.as_ptr(),
+                pcm.len() as c_int,
+                output.as_mut_ptr(),
+                output.len() as i32,
+            )
+        };
+        // With DTX, packets of 2 bytes or less carry no audio and need not be sent
+        if len < 0 || (self.dtx && len <= 2) {
+            return Vec::new();
+        }
+        output.truncate(len as usize);
+        output
+    }
+}
+
+impl Drop for OpusConfiguredEncoder {
+    fn drop(&mut self) {
+        unsafe { opus_encoder_destroy(self.encoder.as_ptr()) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opus_config_validate_bitrate() {
+        assert!(OpusConfig::default().validate().is_ok());
+        let low = OpusConfig {
+            bitrate: 1_000,
+            ..Default::default()
+        };
+        assert!(low.validate().is_err());
+    }
+
+    #[test]
+    fn test_adapt_opus_bitrate() {
+        assert_eq!(adapt_opus_bitrate(32_000, 0.2, 12_000, 64_000), 24_000);
+        assert_eq!(adapt_opus_bitrate(14_000, 0.2, 12_000, 64_000), 12_000);
+        assert_eq!(adapt_opus_bitrate(24_000, 0.05, 12_000, 64_000), 24_000);
+        assert_eq!(adapt_opus_bitrate(24_000, 0.0, 12_000, 64_000), 28_000);
+        assert_eq!(adapt_opus_bitrate(62_000, 0.0, 12_000, 64_000), 64_000);
+        // A bitrate configured outside the range is pulled into it
+        assert_eq!(adapt_opus_bitrate(96_000, 0.05, 12_000, 64_000), 64_000);
+    }
+
+    /// 440 Hz tone split into 20 ms frames at 48 kHz
+    fn tone_frames(count: usize) -> Vec<Vec<i16>> {
+        (0..count)
+            .map(|f| {
+                (0..960)
+                    .map(|i| {
+                        let t = (f * 960 + i) as f32 / 48_000.0;
+                        ((2.0 * std::f32::consts::PI * 440.0 * t).sin() * 8_000.0) as i16
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_persistent_opus_encoder_differs_from_per_frame_recreation() {
+        let config = OpusConfig::default();
+        let frames = tone_frames(10);
+
+        let mut persistent = FrameEncoder::new(CodecType::Opus, 48_000, &config);
+        let continuous: Vec<Vec<u8>> = frames.iter().map(|f| persistent.encode(f)).collect();
+        let recreated: Vec<Vec<u8>> = frames
+            .iter()
+            .map(|f| FrameEncoder::new(CodecType::Opus, 48_000, &config).encode(f))
+            .collect();
+
+        // The first frame starts from the same fresh state; after that the
+        // persistent encoder predicts from its history and produces other packets
+        assert_eq!(continuous[0], recreated[0]);
+        assert_ne!(continuous[1..], recreated[1..]);
+    }
+
+    /// Muted and starved capture frames are encoded silence, so they must come
+    /// out as real frames of the codec rather than raw zero bytes
+    #[test]
+    fn test_silence_encodes_to_full_codec_frame() {
+        let config = OpusConfig::default();
+        // 20 ms at 8 kHz
+        let tone: Vec<i16> = tone_frames(1)[0].iter().step_by(6).copied().collect();
+        let silence = vec![0i16; 160];
+        for codec in [CodecType::PCMU, CodecType::PCMA, CodecType::G729] {
+            let mut encoder = FrameEncoder::new(codec, 8_000, &config);
+            let speech_len = encoder.encode(&tone).len();
+            assert_eq!(encoder.encode(&silence).len(), speech_len, "{:?}", codec);
+        }
+        // G.729: 10 bytes per 10 ms, not one byte per sample
+        let mut g729 = FrameEncoder::new(CodecType::G729, 8_000, &config);
+        assert_eq!(g729.encode(&silence).len(), 20);
+
+        // Opus is variable-rate: silence is a short packet that decodes to a full frame
+        let mut opus = FrameEncoder::new(CodecType::Opus, 48_000, &config);
+        let packet = opus.encode(&vec![0i16; 960]);
+        assert!(!packet.is_empty() && packet.len() < 960);
+        let mut decoder = crate::webrtc::plc::FrameDecoder::new(CodecType::Opus, 48_000, 960);
+        assert_eq!(decoder.decode(&packet).len(), 960);
+    }
+
+    #[test]
+    fn test_opus_dtx_suppresses_silence() {
+        let config = OpusConfig {
+            dtx: true,
+            ..Default::default()
+        };
+        let mut encoder = FrameEncoder::new(CodecType::Opus, 48_000, &config);
+        let silence = vec![0i16; 960];
+        // DTX kicks in after a few frames of silence
+        let sent = (0..50)
+            .filter(|_| !encoder.encode(&silence).is_empty())
+            .count();
+        assert!(sent < 50);
+    }
+}
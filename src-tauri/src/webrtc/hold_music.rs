@@ -0,0 +1,78 @@
+//! Music on hold: a WAV file looped in place of the mic while we hold the call.
+//!
+//! The file is decoded once when the hold starts and stepped through at the
+//! codec rate with linear interpolation, so the capture task only has to
+//! encode the frames it is handed.
+
+use super::ringtone::{load_wav, LoopSamples};
+
+pub struct HoldMusic {
+    music: LoopSamples,
+    /// Source samples per codec sample
+    step: f64,
+    /// Read position in the source, in samples
+    pos: f64,
+}
+
+impl HoldMusic {
+    /// Decode `path` for playback at `sample_rate` (the negotiated codec rate).
+    pub fn load(path: &str, sample_rate: u32) -> Result<Self, String> {
+        Ok(Self::new(load_wav(path)?, sample_rate))
+    }
+
+    fn new(music: LoopSamples, sample_rate: u32) -> Self {
+        Self {
+            step: music.sample_rate as f64 / sample_rate.max(1) as f64,
+            music,
+            pos: 0.0,
+        }
+    }
+
+    /// Fill `pcm` with the next samples, wrapping around at the end of the file.
+    pub fn fill(&mut self, pcm: &mut [f32]) {
+        let samples = &self.music.samples;
+        let len = samples.len();
+        for out in pcm.iter_mut() {
+            let index = self.pos as usize;
+            let frac = (self.pos - index as f64) as f32;
+            let a = samples[index % len];
+            let b = samples[(index + 1) % len];
+            *out = a + (b - a) * frac;
+            self.pos = (self.pos + self.step) % len as f64;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn music(samples: Vec<f32>, sample_rate: u32) -> LoopSamples {
+        LoopSamples {
+            samples,
+            sample_rate,
+        }
+    }
+
+    #[test]
+    fn test_fill_loops_at_source_rate() {
+        let mut hold = HoldMusic::new(music(vec![0.1, 0.2, 0.3], 8000), 8000);
+        let mut pcm = [0.0f32; 7];
+        hold.fill(&mut pcm);
+        assert_eq!(pcm, [0.1, 0.2, 0.3, 0.1, 0.2, 0.3, 0.1]);
+    }
+
+    #[test]
+    fn test_fill_interpolates_when_upsampling() {
+        // 8 kHz file played at 16 kHz: every other sample is the midpoint
+        let mut hold = HoldMusic::new(music(vec![0.0, 0.5, 1.0, 0.5], 8000), 16000);
+        let mut pcm = [0.0f32; 6];
+        hold.fill(&mut pcm);
+        assert_eq!(pcm, [0.0, 0.25, 0.5, 0.75, 1.0, 0.75]);
+    }
+
+    #[test]
+    fn test_missing_file() {
+        assert!(HoldMusic::load("/nonexistent/hold-music.wav", 8000).is_err());
+    }
+}
@@ -0,0 +1,89 @@
+//! In-band DTMF: the digit's dual-tone sine pair mixed into the outgoing
+//! audio, for gateways that understand neither RFC 4733 nor SIP INFO.
+
+use std::f32::consts::PI;
+
+/// Tone length per digit
+pub const TONE_MS: u32 = 160;
+/// Silence after each tone so repeated digits are detected separately
+pub const GAP_MS: u32 = 40;
+
+/// Peak amplitude of each of the two sines (about -12 dBFS each)
+const TONE_AMPLITUDE: f32 = 0.25;
+
+/// Low (row) and high (column) frequencies of a DTMF digit.
+pub fn frequencies(digit: char) -> Option<(f32, f32)> {
+    let (row, col) = match digit.to_ascii_uppercase() {
+        '1' => (0, 0),
+        '2' => (0, 1),
+        '3' => (0, 2),
+        'A' => (0, 3),
+        '4' => (1, 0),
+        '5' => (1, 1),
+        '6' => (1, 2),
+        'B' => (1, 3),
+        '7' => (2, 0),
+        '8' => (2, 1),
+        '9' => (2, 2),
+        'C' => (2, 3),
+        '*' => (3, 0),
+        '0' => (3, 1),
+        '#' => (3, 2),
+        'D' => (3, 3),
+        _ => return None,
+    };
+    const LOW: [f32; 4] = [697.0, 770.0, 852.0, 941.0];
+    const HIGH: [f32; 4] = [1209.0, 1336.0, 1477.0, 1633.0];
+    Some((LOW[row], HIGH[col]))
+}
+
+/// PCM for one digit at `sample_rate`: `TONE_MS` of tone followed by `GAP_MS` of silence.
+pub fn tone(digit: char, sample_rate: u32) -> Option<Vec<f32>> {
+    let (low, high) = frequencies(digit)?;
+    let tone_samples = (sample_rate * TONE_MS / 1000) as usize;
+    let gap_samples = (sample_rate * GAP_MS / 1000) as usize;
+    let rate = sample_rate as f32;
+    let mut pcm: Vec<f32> = (0..tone_samples)
+        .map(|i| {
+            let t = i as f32 / rate;
+            TONE_AMPLITUDE * ((2.0 * PI * low * t).sin() + (2.0 * PI * high * t).sin())
+        })
+        .collect();
+    pcm.resize(tone_samples + gap_samples, 0.0);
+    Some(pcm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Signal power at `freq` (Goertzel)
+    fn power_at(pcm: &[f32], freq: f32, rate: u32) -> f32 {
+        let coeff = 2.0 * (2.0 * PI * freq / rate as f32).cos();
+        let (mut s1, mut s2) = (0.0f32, 0.0f32);
+        for &x in pcm {
+            let s = x + coeff * s1 - s2;
+            s2 = s1;
+            s1 = s;
+        }
+        s1 * s1 + s2 * s2 - coeff * s1 * s2
+    }
+
+    #[test]
+    fn test_tone_contains_digit_frequencies() {
+        let pcm = tone('5', 8000).unwrap();
+        assert_eq!(pcm.len(), 8000 * (TONE_MS + GAP_MS) as usize / 1000);
+        let tone_part = &pcm[..8000 * TONE_MS as usize / 1000];
+        let row = power_at(tone_part, 770.0, 8000);
+        let col = power_at(tone_part, 1336.0, 8000);
+        let other = power_at(tone_part, 941.0, 8000);
+        assert!(row > other * 100.0 && col > other * 100.0);
+        assert!(pcm[tone_part.len()..].iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn test_invalid_digit() {
+        assert!(tone('x', 8000).is_none());
+        assert_eq!(frequencies('d'), Some((941.0, 1633.0)));
+    }
+}
@@ -0,0 +1,297 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use bytes::Bytes;
+
+/// Playout ticks without an underrun before the depth shrinks by one frame
+const SHRINK_AFTER_TICKS: u32 = 500;
+
+/// Counters shared between the playback task and `WebRtcSession::get_stats`.
+#[derive(Debug, Default)]
+pub struct JitterStats {
+    /// Playout ticks that found the buffer empty (playout paused to rebuffer)
+    pub underruns: AtomicU64,
+    /// Frames dropped because too much audio was buffered
+    pub overruns: AtomicU64,
+    /// Packets discarded because their playout time had already passed
+    pub late_packets: AtomicU64,
+    /// Frames that never arrived in time for playout
+    pub lost_frames: AtomicU64,
+    /// Lost frames rebuilt from the in-band FEC of the next packet (Opus);
+    /// counted by the playback task, which does the decoding
+    pub fec_recovered: AtomicU64,
+}
+
+/// What to play at a playout tick.
+#[derive(Debug, PartialEq)]
+pub enum Playout {
+    /// The frame due now
+    Frame(Bytes),
+    /// The frame due now was lost; `next` is the following frame if it has already arrived
+    Lost { next: Option<Bytes> },
+    /// Nothing to play (buffering at start or after an underrun)
+    Idle,
+}
+
+/// Adaptive jitter buffer for received audio frames, ordered by RTP sequence number.
+///
+/// The playback task pushes every received frame and pops one frame per ptime.
+/// Playout starts (and restarts after an underrun) once `depth` frames are
+/// buffered. Each underrun grows the depth by one frame; a long underrun-free
+/// stretch shrinks it back towards the configured target.
+pub struct JitterBuffer {
+    /// Encoded frames keyed by extended (wrap-free) sequence number
+    frames: BTreeMap<u64, Bytes>,
+    /// Extended sequence number of the next frame to play (None before first playout)
+    next_seq: Option<u64>,
+    /// Highest extended sequence number seen, used to unwrap 16-bit sequence numbers
+    highest_seq: Option<u64>,
+    /// Configured target depth in frames
+    target: usize,
+    /// Current (adapted) depth in frames, never below `target`
+    depth: usize,
+    /// Hard limit in frames; older frames are dropped beyond it
+    max: usize,
+    buffering: bool,
+    ticks_since_underrun: u32,
+    stats: Arc<JitterStats>,
+}
+
+impl JitterBuffer {
+    pub fn new(target_frames: usize, max_frames: usize, stats: Arc<JitterStats>) -> Self {
+        let max = max_frames.max(2);
+        let target = target_frames.clamp(1, max / 2);
+        Self {
+            frames: BTreeMap::new(),
+            next_seq: None,
+            highest_seq: None,
+            target,
+            depth: target,
+            max,
+            buffering: true,
+            ticks_since_underrun: 0,
+            stats,
+        }
+    }
+
+    /// Change the target depth (takes effect immediately).
+    pub fn set_target(&mut self, target_frames: usize) {
+        let target = target_frames.clamp(1, self.max / 2);
+        if target != self.target {
+            self.target = target;
+            self.depth = target;
+        }
+    }
+
+    /// Buffer a received frame. `seq` is the RTP sequence number; frames without
+    /// one are treated as arriving in order.
+    ///
+    /// A sequence jump larger than the buffer (sender restart, SSRC change)
+    /// resynchronises playout on the new sequence instead of discarding every
+    /// frame as late or playing out the gap as lost frames.
+    pub fn push(&mut self, seq: Option<u16>, payload: Bytes) {
+        let mut ext = match seq {
+            Some(seq) => self.extend_seq(seq),
+            None => self.highest_seq.map_or(0, |h| h + 1),
+        };
+        if let (Some(seq), Some(highest)) = (seq, self.highest_seq) {
+            let window = self.max as u64;
+            let reference = self.next_seq.unwrap_or(highest);
+            if ext > highest + window || ext + window < reference {
+                self.resync();
+                ext = self.extend_seq(seq);
+            }
+        }
+        self.highest_seq = Some(self.highest_seq.map_or(ext, |h| h.max(ext)));
+
+        if self.next_seq.is_some_and(|next| ext < next) {
+            self.stats.late_packets.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        self.frames.entry(ext).or_insert(payload);
+
+        while self.frames.len() > self.max {
+            self.drop_oldest();
+        }
+    }
+
+    /// Whether no frame is buffered.
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Take the frame due at this playout tick.
+    pub fn pop(&mut self) -> Playout {
+        if self.buffering {
+            if self.frames.len() < self.depth {
+                return Playout::Idle;
+            }
+            self.buffering = false;
+            self.next_seq = self.frames.keys().next().copied();
+        }
+
+        if self.frames.is_empty() {
+            self.stats.underruns.fetch_add(1, Ordering::Relaxed);
+            self.buffering = true;
+            self.depth = (self.depth + 1).min(self.max / 2);
+            self.ticks_since_underrun = 0;
+            return Playout::Idle;
+        }
+
+        self.ticks_since_underrun += 1;
+        if self.ticks_since_underrun >= SHRINK_AFTER_TICKS && self.depth > self.target {
+            self.depth -= 1;
+            self.ticks_since_underrun = 0;
+        }
+
+        // Sender clock running ahead of ours: drop old frames to bring latency back down
+        while self.frames.len() > self.depth * 2 {
+            self.drop_oldest();
+        }
+
+        let seq = self.next_seq.unwrap_or(0);
+        self.next_seq = Some(seq + 1);
+        match self.frames.remove(&seq) {
+            Some(frame) => Playout::Frame(frame),
+            None => {
+                self.stats.lost_frames.fetch_add(1, Ordering::Relaxed);
+                Playout::Lost {
+                    next: self.frames.get(&(seq + 1)).cloned(),
+                }
+            }
+        }
+    }
+
+    /// Forget the sequence state and buffered frames, and rebuffer from the next frame.
+    fn resync(&mut self) {
+        self.frames.clear();
+        self.next_seq = None;
+        self.highest_seq = None;
+        self.buffering = true;
+    }
+
+    fn drop_oldest(&mut self) {
+        if self.frames.pop_first().is_some() {
+            self.stats.overruns.fetch_add(1, Ordering::Relaxed);
+        }
+        if self.next_seq.is_some() {
+            self.next_seq = self.frames.keys().next().copied().or(self.next_seq);
+        }
+    }
+
+    /// Map a 16-bit sequence number onto the extended sequence closest to the highest seen.
+    fn extend_seq(&self, seq: u16) -> u64 {
+        let Some(highest) = self.highest_seq else {
+            // Start well above zero so a reordered first packet cannot underflow
+            return (1 << 16) + seq as u64;
+        };
+        let cycle = highest & !0xFFFF;
+        [cycle.saturating_sub(1 << 16), cycle, cycle + (1 << 16)]
+            .into_iter()
+            .map(|c| c + seq as u64)
+            .min_by_key(|&ext| ext.abs_diff(highest))
+            .unwrap_or(cycle + seq as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(n: u8) -> Bytes {
+        Bytes::from(vec![n; 10])
+    }
+
+    fn buffer(target: usize) -> JitterBuffer {
+        JitterBuffer::new(target, 50, Arc::new(JitterStats::default()))
+    }
+
+    #[test]
+    fn test_reorders_and_waits_for_target_depth() {
+        let mut jb = buffer(3);
+        jb.push(Some(11), frame(11));
+        jb.push(Some(10), frame(10));
+        assert_eq!(jb.pop(), Playout::Idle); // still buffering
+        jb.push(Some(12), frame(12));
+        assert_eq!(jb.pop(), Playout::Frame(frame(10)));
+        assert_eq!(jb.pop(), Playout::Frame(frame(11)));
+        assert_eq!(jb.pop(), Playout::Frame(frame(12)));
+    }
+
+    #[test]
+    fn test_late_packets_are_discarded_and_counted() {
+        let mut jb = buffer(1);
+        jb.push(Some(5), frame(5));
+        jb.push(Some(7), frame(7));
+        assert_eq!(jb.pop(), Playout::Frame(frame(5)));
+        assert_eq!(
+            jb.pop(),
+            Playout::Lost {
+                next: Some(frame(7))
+            }
+        ); // 6 missing
+        assert_eq!(jb.stats.lost_frames.load(Ordering::Relaxed), 1);
+        jb.push(Some(6), frame(6));
+        assert_eq!(jb.stats.late_packets.load(Ordering::Relaxed), 1);
+        assert_eq!(jb.pop(), Playout::Frame(frame(7)));
+    }
+
+    #[test]
+    fn test_underrun_rebuffers_and_grows_depth() {
+        let mut jb = buffer(1);
+        jb.push(Some(1), frame(1));
+        assert_eq!(jb.pop(), Playout::Frame(frame(1)));
+        assert_eq!(jb.pop(), Playout::Idle);
+        assert_eq!(jb.stats.underruns.load(Ordering::Relaxed), 1);
+        assert_eq!(jb.depth, 2);
+
+        jb.push(Some(2), frame(2));
+        assert_eq!(jb.pop(), Playout::Idle); // waiting for 2 frames
+        jb.push(Some(3), frame(3));
+        assert_eq!(jb.pop(), Playout::Frame(frame(2)));
+    }
+
+    #[test]
+    fn test_overrun_drops_oldest() {
+        let mut jb = JitterBuffer::new(2, 4, Arc::new(JitterStats::default()));
+        for seq in 0..6u16 {
+            jb.push(Some(seq), frame(seq as u8));
+        }
+        assert_eq!(jb.stats.overruns.load(Ordering::Relaxed), 2);
+        assert_eq!(jb.pop(), Playout::Frame(frame(2)));
+    }
+
+    #[test]
+    fn test_sequence_jump_resyncs() {
+        // Backward jump: the frames would otherwise all be discarded as late
+        let mut jb = buffer(1);
+        jb.push(Some(1000), frame(1));
+        assert_eq!(jb.pop(), Playout::Frame(frame(1)));
+        jb.push(Some(10), frame(2));
+        assert_eq!(jb.pop(), Playout::Frame(frame(2)));
+        jb.push(Some(11), frame(3));
+        assert_eq!(jb.pop(), Playout::Frame(frame(3)));
+        assert_eq!(jb.stats.late_packets.load(Ordering::Relaxed), 0);
+
+        // Forward jump: the gap would otherwise be played out as lost frames
+        let mut jb = buffer(1);
+        jb.push(Some(1), frame(1));
+        jb.push(Some(2), frame(2));
+        assert_eq!(jb.pop(), Playout::Frame(frame(1)));
+        jb.push(Some(5000), frame(3));
+        assert_eq!(jb.pop(), Playout::Frame(frame(3)));
+        jb.push(Some(5001), frame(4));
+        assert_eq!(jb.pop(), Playout::Frame(frame(4)));
+        assert_eq!(jb.stats.lost_frames.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_sequence_wraparound() {
+        let mut jb = buffer(2);
+        jb.push(Some(65535), frame(1));
+        jb.push(Some(0), frame(2));
+        assert_eq!(jb.pop(), Playout::Frame(frame(1)));
+        assert_eq!(jb.pop(), Playout::Frame(frame(2)));
+    }
+}
@@ -1,16 +1,37 @@
+pub mod announcement;
 pub mod audio_bridge;
+pub mod audio_test;
 pub mod codec;
+pub mod comfort_noise;
+pub mod conference;
 pub mod denoiser;
+pub mod echo_canceller;
+pub mod encoder;
+pub mod hold_music;
+pub mod inband_dtmf;
+pub mod jitter_buffer;
+pub mod plc;
+pub mod recorder;
+pub mod ringtone;
+pub mod telephone_event;
+pub mod vad;
 
 use rustrtc::config::MediaCapabilities;
+use rustrtc::transports::ice::{IceCandidate, IceCandidateType, IceSocketWrapper};
 use rustrtc::{
     AudioCapability, MediaKind, PeerConnection, RtcConfiguration, RtpCodecParameters, SdpType,
     SessionDescription, TransportMode,
 };
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use tauri::Emitter;
 use tracing::{debug, info, warn};
 
-use audio_bridge::AudioBridge;
+use crate::error::AppError;
+
+use audio_bridge::{AudioBridge, AudioDirection};
 use codec::NegotiatedCodec;
+use conference::{ConferenceLink, ConferenceMixer};
 
 /// Detect whether an SDP string contains SRTP-related attributes (using the rustrtc standard SDP parsing API).
 ///
@@ -75,6 +96,295 @@ fn build_dtmf_payload(event: u8, end: u8, volume: u8, duration: u16) -> Vec<u8>
     payload
 }
 
+/// A single STUN/TURN server entry.
+///
+/// `urls` accepts `stun:`, `stuns:`, `turn:` and `turns:` URLs. TURN servers
+/// use long-term credentials (`username` + `credential`).
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct IceServerConfig {
+    pub urls: Vec<String>,
+    pub username: Option<String>,
+    pub credential: Option<String>,
+}
+
+/// User-configured ICE servers. When empty, the built-in public STUN list is used.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct IceConfig {
+    pub servers: Vec<IceServerConfig>,
+}
+
+impl IceConfig {
+    /// Check URL schemes and that every TURN server carries credentials.
+    pub fn validate(&self) -> Result<(), String> {
+        for server in &self.servers {
+            if server.urls.is_empty() {
+                return Err("ICE server entry has no URLs".to_string());
+            }
+            for url in &server.urls {
+                let scheme = url.split(':').next().unwrap_or_default().to_ascii_lowercase();
+                match scheme.as_str() {
+                    "stun" | "stuns" => {}
+                    "turn" | "turns" => {
+                        if server.username.is_none() || server.credential.is_none() {
+                            return Err(format!("TURN server {} requires username and credential", url));
+                        }
+                    }
+                    _ => return Err(format!("Unsupported ICE server URL: {}", url)),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Build the rustrtc ICE server list, falling back to the defaults when none are configured.
+    fn to_ice_servers(&self) -> Vec<rustrtc::IceServer> {
+        if self.servers.is_empty() {
+            return default_ice_servers();
+        }
+        self.servers
+            .iter()
+            .map(|s| {
+                let server = rustrtc::IceServer::new(s.urls.clone());
+                match (&s.username, &s.credential) {
+                    (Some(username), Some(credential)) => server.with_credential(username, credential),
+                    _ => server,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Candidate gathering mode.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IceMode {
+    /// Host candidates plus STUN/TURN candidates from the ICE servers
+    #[default]
+    Full,
+    /// Host candidates only: no query leaves the LAN
+    HostOnly,
+}
+
+/// Media settings for one call, snapshotted from the app settings when the
+/// call is placed or answered.
+#[derive(Clone, Debug, Default)]
+pub struct MediaConfig {
+    pub input_device: Option<String>,
+    pub output_device: Option<String>,
+    pub prefer_srtp: bool,
+    pub noise_reduce: bool,
+    pub speaker_noise_reduce: bool,
+    /// Wet/dry mix of the noise reducers (0.0 = bypass, 1.0 = full denoise)
+    pub noise_reduce_strength: f32,
+    pub echo_cancel: bool,
+    pub speaker_volume: f32,
+    pub mic_gain: f32,
+    /// Playback jitter buffer target depth
+    pub jitter_buffer_ms: u32,
+    /// Capacity of the ring buffers next to the audio devices
+    pub audio_buffer_ms: u32,
+    /// Packet loss concealment on playback
+    pub plc: bool,
+    /// Comfort noise in receive gaps, and CN offered in SDP
+    pub comfort_noise: bool,
+    /// Offer uncompressed L16 ahead of the other codecs
+    pub l16: bool,
+    /// Keep `a=rtcp-mux` in answers to non-ICE peers, so RTCP shares the RTP port
+    pub rtcp_mux: bool,
+    /// Fixed public IP (1:1 NAT) advertised with the local RTP port instead of
+    /// a STUN-discovered address
+    pub external_ip: Option<IpAddr>,
+    /// DSCP of outgoing RTP (0 = unmarked)
+    pub dscp: u8,
+    pub opus: encoder::OpusConfig,
+    pub ice_config: IceConfig,
+    pub ice_mode: IceMode,
+    /// Offer only this codec (plus telephone-event) instead of the full list
+    pub force_codec: Option<codec::CodecType>,
+    /// WAV file looped to the remote while we hold the call
+    pub hold_music_path: Option<String>,
+    /// Hang up after this many seconds without received RTP (0 = never)
+    pub media_timeout_secs: u64,
+    /// Voice activity detection on outgoing audio
+    pub vad: vad::VadConfig,
+    /// Receive only (`a=recvonly`): no microphone is opened and no audio is sent
+    pub listen_only: bool,
+}
+
+impl MediaConfig {
+    /// Gathering mode for the call: a configured external IP replaces STUN as well.
+    fn effective_ice_mode(&self) -> IceMode {
+        match self.external_ip {
+            Some(_) => IceMode::HostOnly,
+            None => self.ice_mode,
+        }
+    }
+}
+
+/// Payload of the `sip://audio-level` event.
+#[derive(Clone, Debug, Serialize)]
+pub struct AudioLevelPayload {
+    pub call_id: String,
+    pub direction: AudioDirection,
+    pub rms: f32,
+    pub peak: f32,
+}
+
+/// Payload of the `sip://dtmf-received` event: one keypress sent by the far end.
+#[derive(Clone, Debug, Serialize)]
+pub struct DtmfReceivedPayload {
+    pub call_id: String,
+    pub digit: String,
+    pub duration_ms: u32,
+}
+
+/// Payload of the `sip://audio-warning` event.
+#[derive(Clone, Debug, Serialize)]
+pub struct AudioWarningPayload {
+    pub call_id: String,
+    /// Direction without audio: "inbound" (nothing received) or "outbound" (nothing sent)
+    pub direction: String,
+    pub message: String,
+}
+
+/// Payload of the `sip://audio-error` event: an error on a capture or playback stream.
+#[derive(Clone, Debug, Serialize)]
+pub struct AudioErrorPayload {
+    pub call_id: String,
+    pub direction: AudioDirection,
+    pub device: String,
+    pub message: String,
+}
+
+/// Payload of the `sip://rtp-latched` event: the far end's media comes from
+/// another address than the SDP announced, and RTP is now sent there.
+#[derive(Clone, Debug, Serialize)]
+pub struct RtpLatchedPayload {
+    pub call_id: String,
+    /// Address RTP was sent to until now, "ip:port"
+    pub old_address: String,
+    /// Address the far end's packets arrive from, "ip:port"
+    pub new_address: String,
+}
+
+/// Media parameters negotiated for a call.
+#[derive(Clone, Debug, Serialize)]
+pub struct MediaInfo {
+    /// SDP codec name, e.g. "PCMU", "opus"
+    pub codec: String,
+    pub payload_type: u8,
+    pub clock_rate: u32,
+    pub ptime_ms: u32,
+    /// Media is encrypted with SRTP (RTP/SAVP)
+    pub srtp: bool,
+}
+
+impl MediaInfo {
+    fn new(negotiated: &NegotiatedCodec, srtp: bool) -> Self {
+        Self {
+            codec: negotiated.codec.name().to_string(),
+            payload_type: negotiated.payload_type,
+            clock_rate: negotiated.clock_rate,
+            ptime_ms: negotiated.ptime_ms,
+            srtp,
+        }
+    }
+}
+
+/// Payload of the `sip://media-info` event, emitted once media is established.
+#[derive(Clone, Debug, Serialize)]
+pub struct MediaInfoPayload {
+    pub call_id: String,
+    #[serde(flatten)]
+    pub info: MediaInfo,
+}
+
+/// Payload of the `sip://playback-finished` event: an announcement played into
+/// the call ended and the mic is live again.
+#[derive(Clone, Debug, Serialize)]
+pub struct PlaybackFinishedPayload {
+    pub call_id: String,
+    /// `true` when the file played to the end, `false` when it was stopped or replaced
+    pub completed: bool,
+}
+
+/// Payload of the `sip://audio-fallback` event: the configured device was not
+/// found and the system default is used instead.
+#[derive(Clone, Debug, Serialize)]
+pub struct AudioFallbackPayload {
+    pub call_id: String,
+    pub direction: AudioDirection,
+    /// Id of the configured device
+    pub requested: String,
+    /// Name of the default device used instead
+    pub chosen: String,
+}
+
+/// Identical audio stream errors are emitted at most once per this interval
+const AUDIO_ERROR_THROTTLE: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// How long audio has to flow one way only before `sip://audio-warning`
+const ONE_WAY_AUDIO_AFTER: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Payload of the `sip://audio-device-lost` event.
+#[derive(Clone, Debug, Serialize)]
+pub struct AudioDeviceLostPayload {
+    pub call_id: String,
+    pub direction: AudioDirection,
+}
+
+/// Payload of the `sip://device-followed` event: the system default device
+/// changed and calls were moved to it.
+#[derive(Clone, Debug, Serialize)]
+pub struct DeviceFollowedPayload {
+    pub direction: AudioDirection,
+    pub device_id: String,
+    pub device_name: String,
+    /// Calls whose audio was moved
+    pub call_ids: Vec<String>,
+}
+
+/// RTP/RTCP quality statistics for the active call.
+///
+/// Fields stay zero until the corresponding packets or RTCP reports have been seen.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct CallStats {
+    pub packets_sent: u64,
+    pub packets_received: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    /// Cumulative packets lost as reported by the remote (RTCP receiver report)
+    pub packets_lost: i64,
+    /// Fraction of packets lost since the previous receiver report (0.0 - 1.0)
+    pub fraction_lost: f64,
+    /// Interarrival jitter reported by the remote, in milliseconds
+    pub jitter_ms: f64,
+    /// Round-trip time derived from RTCP, in milliseconds
+    pub round_trip_time_ms: f64,
+    /// Playout ticks that found the jitter buffer empty
+    pub jitter_buffer_underruns: u64,
+    /// Frames dropped because the jitter buffer held too much audio
+    pub jitter_buffer_overruns: u64,
+    /// Packets that arrived after their playout time and were discarded
+    pub late_packets_discarded: u64,
+    /// Frames missing at playout time (concealed when PLC is enabled)
+    pub lost_frames: u64,
+    /// Lost frames recovered from Opus in-band FEC instead of concealed
+    pub fec_recovered_frames: u64,
+    /// Audio ring buffer overflows and playback underruns (see `set_audio_buffer_ms`)
+    pub audio_buffer_xruns: u64,
+}
+
+/// Public STUN servers used when the user has not configured any.
+fn default_ice_servers() -> Vec<rustrtc::IceServer> {
+    vec![
+        rustrtc::IceServer::new(vec!["stun:stun.l.google.com:19302".to_string()]),
+        rustrtc::IceServer::new(vec!["stun:stun1.l.google.com:19302".to_string()]),
+        rustrtc::IceServer::new(vec!["stun:restsend.com:3478".to_string()]),
+        rustrtc::IceServer::new(vec!["stun:stun.voip.blackberry.com:3478".to_string()]),
+    ]
+}
+
 /// Create an RTP+ICE configuration compatible with legacy SIP PBXes and supporting NAT traversal.
 ///
 /// `transport_mode` parameter:
@@ -92,26 +402,74 @@ fn build_dtmf_payload(event: u8, end: u8, volume: u8, duration: u16) -> Vec<u8>
 ///    - Protocol: RTP/AVP (plain RTP)
 ///    - ICE attributes: a=ice-ufrag, a=ice-pwd, a=candidate
 ///    - Correct public IP and NAT-mapped port
-fn create_rtp_ice_config(transport_mode: TransportMode) -> RtcConfiguration {
-    info!(transport_mode = ?transport_mode, "Creating RTP+ICE config for NAT traversal");
+///
+/// ICE servers come from `ice_config`, or the built-in STUN list when it is empty.
+/// `force_codec` restricts the audio capabilities to that codec and telephone-event.
+/// `comfort_noise` adds CN (RFC 3389) so a far end using VAD can announce its noise level.
+/// `l16` puts 16 kHz L16 first in the full list, for LAN intercoms with bandwidth to spare.
+/// `IceMode::HostOnly` leaves out every ICE server, so gathering only finds host
+/// candidates and completes at once.
+fn create_rtp_ice_config(
+    transport_mode: TransportMode,
+    ice_config: &IceConfig,
+    force_codec: Option<codec::CodecType>,
+    comfort_noise: bool,
+    l16: bool,
+    ice_mode: IceMode,
+) -> RtcConfiguration {
+    info!(
+        transport_mode = ?transport_mode,
+        custom_ice_servers = ice_config.servers.len(),
+        force_codec = ?force_codec,
+        comfort_noise,
+        l16,
+        ice_mode = ?ice_mode,
+        "Creating RTP+ICE config for NAT traversal"
+    );
+
+    let mut audio = match force_codec {
+        Some(codec) => vec![audio_capability(codec), AudioCapability::telephone_event()],
+        None => vec![
+            AudioCapability::opus(),
+            AudioCapability::pcmu(),
+            AudioCapability::pcma(),
+            AudioCapability::g722(),
+            AudioCapability::g729(),
+            AudioCapability::telephone_event(),
+        ],
+    };
+    if l16 && force_codec.is_none() {
+        audio.insert(
+            0,
+            AudioCapability {
+                payload_type: codec::L16_PAYLOAD_TYPE,
+                codec_name: "L16".to_string(),
+                clock_rate: codec::L16_CLOCK_RATE,
+                channels: 1,
+                fmtp: None,
+                rtcp_fbs: vec![],
+            },
+        );
+    }
+    if comfort_noise {
+        audio.push(AudioCapability {
+            payload_type: comfort_noise::CN_PAYLOAD_TYPE,
+            codec_name: "CN".to_string(),
+            clock_rate: 8000,
+            channels: 1,
+            fmtp: None,
+            rtcp_fbs: vec![],
+        });
+    }
 
     RtcConfiguration {
         transport_mode,
-        ice_servers: vec![
-            rustrtc::IceServer::new(vec!["stun:stun.l.google.com:19302".to_string()]),
-            rustrtc::IceServer::new(vec!["stun:stun1.l.google.com:19302".to_string()]),
-            rustrtc::IceServer::new(vec!["stun:restsend.com:3478".to_string()]),
-            rustrtc::IceServer::new(vec!["stun:stun.voip.blackberry.com:3478".to_string()]),
-        ],
+        ice_servers: match ice_mode {
+            IceMode::Full => ice_config.to_ice_servers(),
+            IceMode::HostOnly => vec![],
+        },
         media_capabilities: Some(MediaCapabilities {
-            audio: vec![
-                AudioCapability::opus(),
-                AudioCapability::pcmu(),
-                AudioCapability::pcma(),
-                AudioCapability::g722(),
-                AudioCapability::g729(),
-                AudioCapability::telephone_event(),
-            ],
+            audio,
             video: vec![],
             application: None,
         }),
@@ -122,24 +480,92 @@ fn create_rtp_ice_config(transport_mode: TransportMode) -> RtcConfiguration {
     }
 }
 
-/// Replace SDP addresses with public IP:port from server-reflexive candidate
-/// and remove ICE attributes (for non-ICE peers)
-fn replace_with_public_address(sdp: &str, public_ip: &str, public_port: u16) -> String {
+fn audio_capability(codec: codec::CodecType) -> AudioCapability {
+    match codec {
+        codec::CodecType::PCMU => AudioCapability::pcmu(),
+        codec::CodecType::PCMA => AudioCapability::pcma(),
+        codec::CodecType::G722 => AudioCapability::g722(),
+        codec::CodecType::G729 => AudioCapability::g729(),
+        codec::CodecType::Opus => AudioCapability::opus(),
+        codec::CodecType::TelephoneEvent => AudioCapability::telephone_event(),
+    }
+}
+
+/// Pick the address to advertise to a non-ICE peer.
+///
+/// Prefers a server-reflexive candidate (direct path through our NAT) and falls
+/// back to a TURN relay candidate, which is the only option behind symmetric NAT.
+fn select_public_candidate(candidates: &[IceCandidate]) -> Option<&IceCandidate> {
+    candidates
+        .iter()
+        .find(|c| c.typ == IceCandidateType::ServerReflexive)
+        .or_else(|| candidates.iter().find(|c| c.typ == IceCandidateType::Relay))
+}
+
+/// The configured external IP with the port of the locally bound RTP socket,
+/// for a 1:1 NAT that maps the public address to ours, port for port.
+fn external_address(external_ip: IpAddr, candidates: &[IceCandidate]) -> Option<(String, u16)> {
+    candidates
+        .iter()
+        .find(|c| c.typ == IceCandidateType::Host)
+        .map(|c| (external_ip.to_string(), c.address.port()))
+}
+
+/// Whether relay candidates are the only non-host candidates gathered, i.e. STUN
+/// failed but TURN succeeded.
+fn is_relay_only(candidates: &[IceCandidate]) -> bool {
+    let srflx = candidates.iter().any(|c| c.typ == IceCandidateType::ServerReflexive);
+    let relay = candidates.iter().any(|c| c.typ == IceCandidateType::Relay);
+    relay && !srflx
+}
+
+/// SDP address type (`IP4` / `IP6`) of an address, and the address itself with any
+/// URI-style brackets removed (SDP writes IPv6 addresses bare, RFC 4566 §5.7).
+fn sdp_address(ip: &str) -> (&'static str, &str) {
+    let bare = ip.trim_start_matches('[').trim_end_matches(']');
+    let addr_type = if bare.contains(':') { "IP6" } else { "IP4" };
+    (addr_type, bare)
+}
+
+/// Strip brackets some peers put around IPv6 addresses in `c=`, `o=` and `a=rtcp:`
+/// lines, which the SDP parser would otherwise reject.
+fn unbracket_sdp_address(line: &str) -> String {
+    if !(line.starts_with("c=") || line.starts_with("o=") || line.starts_with("a=rtcp:")) {
+        return line.to_string();
+    }
+    match line.rsplit_once(' ') {
+        Some((head, addr)) if addr.starts_with('[') => {
+            format!("{} {}", head, sdp_address(addr).1)
+        }
+        _ => line.to_string(),
+    }
+}
+
+/// Replace SDP addresses with public IP:port from server-reflexive (or relay) candidate
+/// and remove ICE attributes (for non-ICE peers). `a=rtcp-mux` is removed too unless
+/// `rtcp_mux` is set, for PBXs that expect RTCP on a separate port.
+fn replace_with_public_address(
+    sdp: &str,
+    public_ip: &str,
+    public_port: u16,
+    rtcp_mux: bool,
+) -> String {
+    let (addr_type, public_ip) = sdp_address(public_ip);
     let lines: Vec<&str> = sdp.lines().collect();
     let mut result = Vec::new();
 
     for line in lines {
         // Replace c= line
-        if line.starts_with("c=IN IP4") {
-            result.push(format!("c=IN IP4 {}", public_ip));
+        if line.starts_with("c=IN IP4") || line.starts_with("c=IN IP6") {
+            result.push(format!("c=IN {} {}", addr_type, public_ip));
         }
-        // Replace o= line IP
+        // Replace o= line address type and IP
         else if line.starts_with("o=") {
             let parts: Vec<&str> = line.split_whitespace().collect();
             if parts.len() >= 6 {
                 result.push(format!(
                     "{} {} {} {} {} {}",
-                    parts[0], parts[1], parts[2], parts[3], parts[4], public_ip
+                    parts[0], parts[1], parts[2], parts[3], addr_type, public_ip
                 ));
             } else {
                 result.push(line.to_string());
@@ -159,11 +585,11 @@ fn replace_with_public_address(sdp: &str, public_ip: &str, public_port: u16) ->
         else if line.starts_with("a=sendonly") {
             result.push("a=sendrecv".to_string());
         }
-        // Remove ICE-related attributes AND rtcp-mux (PBX doesn't support it)
+        // Remove ICE-related attributes AND rtcp-mux unless enabled (many PBXs don't support it)
         else if line.starts_with("a=ice-")
             || line.starts_with("a=candidate:")
             || line.starts_with("a=end-of-candidates")
-            || line.starts_with("a=rtcp-mux")
+            || (!rtcp_mux && line.starts_with("a=rtcp-mux"))
         {
             // Skip ICE and RTCP-mux attributes
             continue;
@@ -175,9 +601,121 @@ fn replace_with_public_address(sdp: &str, public_ip: &str, public_port: u16) ->
     result.join("\r\n") + "\r\n"
 }
 
+/// Mark the media socket with `dscp` whenever ICE selects one; the candidate
+/// sockets are internal to rustrtc until then.
+fn spawn_dscp_marker(pc: &PeerConnection, dscp: u8) {
+    if dscp == 0 {
+        return;
+    }
+    let mut selected = pc.ice_transport().subscribe_selected_socket();
+    tokio::spawn(async move {
+        loop {
+            let socket = selected.borrow_and_update().clone();
+            if let Some(IceSocketWrapper::Udp(socket)) = socket {
+                crate::qos::mark_udp(&socket, dscp);
+            }
+            if selected.changed().await.is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Emit the levels reported by an audio bridge as `sip://audio-level` events for `call_id`,
+/// until the bridge and its tasks are gone.
+fn spawn_level_forwarder(
+    mut level_rx: tokio::sync::mpsc::Receiver<audio_bridge::AudioLevel>,
+    app_handle: tauri::AppHandle,
+    call_id: String,
+) {
+    tokio::spawn(async move {
+        while let Some(level) = level_rx.recv().await {
+            let _ = app_handle.emit(
+                "sip://audio-level",
+                AudioLevelPayload {
+                    call_id: call_id.clone(),
+                    direction: level.direction,
+                    rms: level.rms,
+                    peak: level.peak,
+                },
+            );
+        }
+        debug!(call_id = %call_id, "Audio level forwarding stopped");
+    });
+}
+
+/// Media direction of an SDP (the first direction attribute, `sendrecv` if none).
+fn sdp_direction(sdp: &str) -> &str {
+    sdp.lines()
+        .map(str::trim)
+        .find_map(|line| match line {
+            "a=sendrecv" | "a=sendonly" | "a=recvonly" | "a=inactive" => Some(&line[2..]),
+            _ => None,
+        })
+        .unwrap_or("sendrecv")
+}
+
+/// Our direction in the answer to a re-INVITE offering `remote` (RFC 3264 §6.1),
+/// keeping our own hold: we never receive while we hold, and never send when
+/// the remote does not want to receive.
+fn answer_direction(remote: &str, local_hold: bool) -> &'static str {
+    match (remote, local_hold) {
+        ("sendrecv", false) => "sendrecv",
+        ("sendrecv", true) | ("recvonly", _) => "sendonly",
+        ("sendonly", false) => "recvonly",
+        _ => "inactive",
+    }
+}
+
+/// `direction` without our sending, for listen-only calls.
+fn receive_only(direction: &str) -> &'static str {
+    match direction {
+        "sendrecv" | "recvonly" => "recvonly",
+        _ => "inactive",
+    }
+}
+
+/// Rewrite the media direction attribute of an SDP and bump the `o=` session version.
+///
+/// Any existing `a=sendrecv` / `a=sendonly` / `a=recvonly` / `a=inactive` line is replaced
+/// with `a=<direction>`; if none is present the attribute is appended to the last media
+/// section. Incrementing the session version (RFC 3264 §8) marks this as a modified offer
+/// rather than a retransmission of the previous one.
+fn rewrite_sdp_direction(sdp: &str, direction: &str) -> String {
+    let mut result = Vec::new();
+    let mut replaced = false;
+
+    for line in sdp.lines() {
+        if line == "a=sendrecv" || line == "a=sendonly" || line == "a=recvonly" || line == "a=inactive" {
+            result.push(format!("a={}", direction));
+            replaced = true;
+        } else if line.starts_with("o=") {
+            let mut parts: Vec<String> = line.split_whitespace().map(|p| p.to_string()).collect();
+            if parts.len() >= 6 {
+                if let Ok(version) = parts[2].parse::<u64>() {
+                    parts[2] = (version + 1).to_string();
+                }
+                result.push(parts.join(" "));
+            } else {
+                result.push(line.to_string());
+            }
+        } else if !line.is_empty() {
+            result.push(line.to_string());
+        }
+    }
+
+    if !replaced {
+        result.push(format!("a={}", direction));
+    }
+
+    result.join("\r\n") + "\r\n"
+}
+
 /// Inject fake ICE attributes into SDP offer to trick rustrtc into doing ICE gathering
+///
+/// Bracketed IPv6 addresses are unwrapped on the way so the offer parses.
 fn inject_ice_attributes(sdp: &str) -> String {
-    let mut lines: Vec<String> = sdp.lines().map(|s| s.to_string()).collect();
+    let mut lines: Vec<String> = sdp.lines().map(unbracket_sdp_address).collect();
 
     // Find the m=audio line index
     let audio_idx = lines.iter().position(|l| l.starts_with("m=audio"));
@@ -191,23 +729,91 @@ fn inject_ice_attributes(sdp: &str) -> String {
     lines.join("\r\n") + "\r\n"
 }
 
-/// Wait for the RTP connection to be established, then start audio capture and playback.
-async fn start_audio(
-    pc: &PeerConnection,
-    audio_bridge: &mut AudioBridge,
-    output_device: Option<&str>,
-    negotiated: &NegotiatedCodec,
-) -> Result<(), String> {
+/// Decline every non-audio stream of `offer` in `answer` (RFC 3264 §6).
+///
+/// The answer gets one m-line per offered stream, in offer order: audio keeps
+/// the section rustrtc built, anything else becomes `m=<media> 0 <proto> <fmts>`
+/// without attributes, and its mid leaves the BUNDLE group.
+fn decline_non_audio_streams(offer: &str, answer: &str) -> String {
+    let offered: Vec<&str> = offer
+        .lines()
+        .map(str::trim)
+        .filter(|l| l.starts_with("m="))
+        .collect();
+    if offered.iter().all(|m| m.starts_with("m=audio")) {
+        return answer.to_string();
+    }
+
+    // Session-level lines, then one Vec of lines per media section
+    let mut session: Vec<&str> = Vec::new();
+    let mut sections: Vec<Vec<&str>> = Vec::new();
+    for line in answer.lines().filter(|l| !l.is_empty()) {
+        if line.starts_with("m=") {
+            sections.push(vec![line]);
+        } else if let Some(section) = sections.last_mut() {
+            section.push(line);
+        } else {
+            session.push(line);
+        }
+    }
+    let (audio, other): (Vec<_>, Vec<_>) = sections
+        .into_iter()
+        .partition(|s| s[0].starts_with("m=audio"));
+    let declined_mids: Vec<&str> = other
+        .iter()
+        .flatten()
+        .filter_map(|l| l.strip_prefix("a=mid:"))
+        .collect();
+
+    let mut result: Vec<String> = session
+        .iter()
+        .map(|line| match line.strip_prefix("a=group:BUNDLE") {
+            Some(mids) => {
+                let kept: Vec<&str> = mids
+                    .split_whitespace()
+                    .filter(|mid| !declined_mids.contains(mid))
+                    .collect();
+                format!("a=group:BUNDLE {}", kept.join(" "))
+            }
+            None => line.to_string(),
+        })
+        .collect();
+    let mut audio = audio.into_iter();
+    for m_line in offered {
+        if m_line.starts_with("m=audio") {
+            if let Some(section) = audio.next() {
+                result.extend(section.iter().map(|l| l.to_string()));
+                continue;
+            }
+        }
+        let mut fields: Vec<&str> = m_line.split_whitespace().collect();
+        if fields.len() >= 2 {
+            fields[1] = "0";
+        }
+        result.push(fields.join(" "));
+    }
+
+    result.join("\r\n") + "\r\n"
+}
+
+/// Wait for the RTP connection to be established.
+async fn wait_for_connection(pc: &PeerConnection) -> Result<(), String> {
     info!("Waiting for RTP connection...");
     match tokio::time::timeout(std::time::Duration::from_secs(10), pc.wait_for_connected()).await {
         Ok(Ok(_)) => info!("RTP connection established"),
         Ok(Err(e)) => return Err(format!("Connection failed: {}", e)),
-        Err(_) => return Err("Connection timed out".to_string()),
+        Err(_) => return Err(CONNECTION_TIMED_OUT.to_string()),
     }
+    Ok(())
+}
 
-    info!("Starting audio capture...");
-    audio_bridge.start_capture(negotiated)?;
-
+/// Start playing the remote audio track to the speaker.
+fn start_remote_playback(
+    pc: &PeerConnection,
+    audio_bridge: &mut AudioBridge,
+    output_device: Option<&str>,
+    negotiated: &NegotiatedCodec,
+) -> Result<(), String> {
     let transceivers = pc.get_transceivers();
     info!(transceiver_count = transceivers.len(), "Got transceivers");
     for t in &transceivers {
@@ -228,15 +834,61 @@ async fn start_audio(
     Ok(())
 }
 
+/// Error of `new_outbound` when ICE gathering produced no candidate at all
+const NO_ICE_CANDIDATES: &str = "No ICE candidates gathered";
+
+/// Error of `apply_answer` when the RTP connection does not come up in time
+const CONNECTION_TIMED_OUT: &str = "Connection timed out";
+
+/// Whether media setup failed on our side in a way a fresh attempt may get
+/// past (no ICE candidates, RTP connection timeout), rather than for good.
+pub fn is_transient_media_error(error: &str) -> bool {
+    error == NO_ICE_CANDIDATES || error == CONNECTION_TIMED_OUT
+}
+
+/// Wait for the RTP connection to be established, then start audio capture and playback.
+async fn start_audio(
+    pc: &PeerConnection,
+    audio_bridge: &mut AudioBridge,
+    output_device: Option<&str>,
+    negotiated: &NegotiatedCodec,
+) -> Result<(), String> {
+    wait_for_connection(pc).await?;
+
+    info!("Starting audio capture...");
+    audio_bridge.start_capture(negotiated)?;
+
+    start_remote_playback(pc, audio_bridge, output_device, negotiated)
+}
+
 /// A WebRTC session wrapping a PeerConnection and audio bridge for one call.
 pub struct WebRtcSession {
     pc: PeerConnection,
     audio_bridge: AudioBridge,
     closed: std::sync::Arc<std::sync::atomic::AtomicBool>,
-    /// Negotiated telephone-event payload type (RFC 4733), default 101
-    telephone_event_pt: u8,
-    /// RTP timestamp counter for DTMF events (8 kHz clock)
-    dtmf_timestamp: std::sync::Arc<std::sync::atomic::AtomicU32>,
+    /// Negotiated telephone-event payload type (RFC 4733); `None` when the
+    /// remote SDP has no telephone-event, so DTMF has to go another way
+    telephone_event_pt: Option<u8>,
+    /// Last SDP we sent to the remote (offer or answer), used as the template for re-INVITEs
+    local_sdp: String,
+    /// Whether we placed the call on hold
+    local_hold: bool,
+    /// Whether the remote placed the call on hold
+    remote_hold: bool,
+    /// Only TURN relay candidates (no server-reflexive) were gathered
+    relay_only: bool,
+    /// Negotiated audio clock rate, used to convert RTCP jitter to milliseconds
+    clock_rate: u32,
+    /// SDP of the 183 Session Progress whose early media is playing, until the call is answered
+    early_media_sdp: Option<String>,
+    /// WAV file looped to the remote while we hold the call (silence if `None`)
+    hold_music_path: Option<String>,
+    /// L16 was advertised, so a remote offer's L16 can be used
+    l16: bool,
+    /// Codec and SRTP use, known once the SDP exchange is complete
+    media_info: Option<MediaInfo>,
+    /// Receive-only call: every SDP we send leaves out our sending direction
+    listen_only: bool,
 }
 
 impl WebRtcSession {
@@ -251,11 +903,8 @@ impl WebRtcSession {
     /// 1. Create offer (triggers ICE gathering)
     /// 2. Wait for STUN query to complete
     /// 3. Generate final offer with server-reflexive candidates (public IP:port)
-    pub async fn new_outbound(
-        input_device: Option<&str>,
-        output_device: Option<&str>,
-        prefer_srtp: bool,
-    ) -> Result<(Self, String), String> {
+    pub async fn new_outbound(media: &MediaConfig) -> Result<(Self, String), String> {
+        let prefer_srtp = media.prefer_srtp;
         let transport_mode = if prefer_srtp {
             TransportMode::Srtp
         } else {
@@ -267,10 +916,38 @@ impl WebRtcSession {
             "Creating outbound WebRTC session with ICE"
         );
 
-        let pc = PeerConnection::new(create_rtp_ice_config(transport_mode));
+        let config = create_rtp_ice_config(
+            transport_mode,
+            &media.ice_config,
+            media.force_codec,
+            media.comfort_noise,
+            media.l16,
+            media.effective_ice_mode(),
+        );
+        let pc = PeerConnection::new(config);
+        spawn_dscp_marker(&pc, media.dscp);
 
         // Create audio bridge (validates devices, creates track, but does NOT start capture)
-        let (audio_bridge, send_track) = AudioBridge::new(input_device, output_device)?;
+        let (audio_bridge, send_track) = if media.listen_only {
+            AudioBridge::new_listen_only(media.output_device.as_deref())?
+        } else {
+            AudioBridge::new(
+                media.input_device.as_deref(),
+                media.output_device.as_deref(),
+            )?
+        };
+        audio_bridge.set_noise_reduce(media.noise_reduce);
+        audio_bridge.set_speaker_noise_reduce(media.speaker_noise_reduce);
+        audio_bridge.set_noise_reduce_strength(media.noise_reduce_strength);
+        audio_bridge.set_echo_cancel(media.echo_cancel);
+        audio_bridge.set_speaker_volume(media.speaker_volume);
+        audio_bridge.set_mic_gain(media.mic_gain);
+        audio_bridge.set_jitter_buffer_ms(media.jitter_buffer_ms);
+        audio_bridge.set_audio_buffer_ms(media.audio_buffer_ms);
+        audio_bridge.set_plc(media.plc);
+        audio_bridge.set_comfort_noise(media.comfort_noise);
+        audio_bridge.set_opus_config(media.opus.clone());
+        audio_bridge.set_vad(media.vad);
 
         // Add the capture track to PeerConnection with PCMU codec parameters
         let params = RtpCodecParameters {
@@ -313,28 +990,59 @@ impl WebRtcSession {
         let candidates = pc.ice_transport().local_candidates();
         let srflx_count = candidates
             .iter()
-            .filter(|c| {
-                matches!(
-                    c.typ,
-                    rustrtc::transports::ice::IceCandidateType::ServerReflexive
-                )
-            })
+            .filter(|c| c.typ == IceCandidateType::ServerReflexive)
+            .count();
+        let relay_count = candidates
+            .iter()
+            .filter(|c| c.typ == IceCandidateType::Relay)
             .count();
         info!(
             total_candidates = candidates.len(),
             server_reflexive = srflx_count,
+            relay = relay_count,
             "ICE candidates collected"
         );
+        if candidates.is_empty() {
+            return Err(NO_ICE_CANDIDATES.to_string());
+        }
+        let relay_only = is_relay_only(&candidates);
 
         pc.set_local_description(offer)
             .map_err(|e| format!("Failed to set local description: {}", e))?;
 
+        // With a static external IP, advertise it directly instead of the ICE candidates.
+        // rustrtc always muxes RTCP on offers, so the attribute stays.
+        let sdp_string = match media
+            .external_ip
+            .and_then(|ip| external_address(ip, &candidates))
+        {
+            Some((public_ip, public_port)) => {
+                info!(public_ip = %public_ip, public_port, "Building SDP offer with configured external IP");
+                replace_with_public_address(&sdp_string, &public_ip, public_port, true)
+            }
+            None => sdp_string,
+        };
+        let sdp_string = if media.listen_only {
+            rewrite_sdp_direction(&sdp_string, "recvonly")
+        } else {
+            sdp_string
+        };
+
         let session = WebRtcSession {
             pc,
             audio_bridge,
             closed: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
-            telephone_event_pt: 101,
-            dtmf_timestamp: std::sync::Arc::new(std::sync::atomic::AtomicU32::new(0)),
+            telephone_event_pt: Some(101),
+            local_sdp: sdp_string.clone(),
+            local_hold: false,
+            remote_hold: false,
+            relay_only,
+            clock_rate: 8000,
+            early_media_sdp: None,
+            hold_music_path: media.hold_music_path.clone(),
+            l16: media.l16,
+            media_info: None,
+            listen_only: media.listen_only,
         };
 
         info!("WebRTC outbound session created");
@@ -356,13 +1064,9 @@ impl WebRtcSession {
     /// 5. Build custom SDP answer string with public address (no ICE attributes for non-ICE peers)
     ///
     /// Note: We use standard Answerer mode to ensure proper WebRTC signaling state machine.
-    pub async fn new_inbound(
-        sdp_offer: &str,
-        input_device: Option<&str>,
-        output_device: Option<&str>,
-    ) -> Result<(Self, String), String> {
+    pub async fn new_inbound(sdp_offer: &str, media: &MediaConfig) -> Result<(Self, String), String> {
         // Parse negotiated codec from SDP offer
-        let negotiated = codec::parse_negotiated_codec(sdp_offer);
+        let negotiated = codec::parse_offered_codec(sdp_offer, media.l16);
 
         // Auto-detect SRTP from remote SDP
         let uses_srtp = detect_srtp_from_sdp(sdp_offer);
@@ -388,10 +1092,38 @@ impl WebRtcSession {
             "Checking remote ICE support"
         );
 
-        let pc = PeerConnection::new(create_rtp_ice_config(transport_mode));
+        let config = create_rtp_ice_config(
+            transport_mode,
+            &media.ice_config,
+            media.force_codec,
+            media.comfort_noise,
+            media.l16,
+            media.effective_ice_mode(),
+        );
+        let pc = PeerConnection::new(config);
+        spawn_dscp_marker(&pc, media.dscp);
 
         // Create audio bridge (validates devices, creates track, but does NOT start capture)
-        let (audio_bridge, send_track) = AudioBridge::new(input_device, output_device)?;
+        let (audio_bridge, send_track) = if media.listen_only {
+            AudioBridge::new_listen_only(media.output_device.as_deref())?
+        } else {
+            AudioBridge::new(
+                media.input_device.as_deref(),
+                media.output_device.as_deref(),
+            )?
+        };
+        audio_bridge.set_noise_reduce(media.noise_reduce);
+        audio_bridge.set_speaker_noise_reduce(media.speaker_noise_reduce);
+        audio_bridge.set_noise_reduce_strength(media.noise_reduce_strength);
+        audio_bridge.set_echo_cancel(media.echo_cancel);
+        audio_bridge.set_speaker_volume(media.speaker_volume);
+        audio_bridge.set_mic_gain(media.mic_gain);
+        audio_bridge.set_jitter_buffer_ms(media.jitter_buffer_ms);
+        audio_bridge.set_audio_buffer_ms(media.audio_buffer_ms);
+        audio_bridge.set_plc(media.plc);
+        audio_bridge.set_comfort_noise(media.comfort_noise);
+        audio_bridge.set_opus_config(media.opus.clone());
+        audio_bridge.set_vad(media.vad);
 
         // Add the capture track to PeerConnection with negotiated codec parameters
         let params = RtpCodecParameters {
@@ -449,46 +1181,47 @@ impl WebRtcSession {
 
         let offer_sdp = answer.to_sdp_string();
 
-        // Step 5: Extract server-reflexive candidate (public IP:port)
+        // Step 5: Extract server-reflexive candidate (public IP:port), or a TURN
+        // relay candidate when STUN could not traverse the NAT
         let candidates = pc.ice_transport().local_candidates();
         let srflx_count = candidates
             .iter()
-            .filter(|c| {
-                matches!(
-                    c.typ,
-                    rustrtc::transports::ice::IceCandidateType::ServerReflexive
-                )
-            })
+            .filter(|c| c.typ == IceCandidateType::ServerReflexive)
+            .count();
+        let relay_count = candidates
+            .iter()
+            .filter(|c| c.typ == IceCandidateType::Relay)
             .count();
         info!(
             total_candidates = candidates.len(),
             server_reflexive = srflx_count,
+            relay = relay_count,
             "ICE candidates collected"
         );
+        let relay_only = is_relay_only(&candidates);
 
-        let public_addr = candidates
-            .iter()
-            .find(|c| {
-                matches!(
-                    c.typ,
-                    rustrtc::transports::ice::IceCandidateType::ServerReflexive
-                )
-            })
-            .map(|c| {
+        let public_addr = match media.external_ip {
+            Some(ip) => external_address(ip, &candidates),
+            None => select_public_candidate(&candidates).map(|c| {
                 let ip = c.address.ip().to_string();
                 let port = c.address.port();
-                info!(public_ip = %ip, public_port = port, "Found server-reflexive candidate");
+                info!(public_ip = %ip, public_port = port, candidate_type = ?c.typ, "Selected public candidate");
                 (ip, port)
-            });
+            }),
+        };
 
         // Step 6: Build SDP answer string
         let final_sdp = if !remote_has_ice {
             if let Some((public_ip, public_port)) = public_addr {
                 info!(public_ip = %public_ip, public_port = public_port, "Building SDP answer with public address");
                 // Use the offer SDP as template and replace with public address
-                replace_with_public_address(&offer_sdp, &public_ip, public_port)
+                replace_with_public_address(&offer_sdp, &public_ip, public_port, media.rtcp_mux)
             } else {
-                warn!("No public address found, using offer SDP with internal address");
+                if media.ice_mode == IceMode::HostOnly {
+                    info!("Host-only ICE mode, using offer SDP with internal address");
+                } else {
+                    warn!("No public address found, using offer SDP with internal address");
+                }
                 // Remove ICE attributes even if we don't have public address
                 let lines: Vec<&str> = offer_sdp.lines().collect();
                 let mut result = Vec::new();
@@ -496,7 +1229,7 @@ impl WebRtcSession {
                     if line.starts_with("a=ice-")
                         || line.starts_with("a=candidate:")
                         || line.starts_with("a=end-of-candidates")
-                        || line.starts_with("a=rtcp-mux")
+                        || (!media.rtcp_mux && line.starts_with("a=rtcp-mux"))
                     {
                         continue;
                     }
@@ -513,6 +1246,14 @@ impl WebRtcSession {
             offer_sdp
         };
 
+        let final_sdp = if media.listen_only {
+            let direction = receive_only(answer_direction(sdp_direction(sdp_offer), false));
+            rewrite_sdp_direction(&final_sdp, direction)
+        } else {
+            final_sdp
+        };
+        let final_sdp = decline_non_audio_streams(sdp_offer, &final_sdp);
+
         info!(sdp_len = final_sdp.len(), "SDP answer created");
         debug!(sdp_answer = %final_sdp, "Local SDP answer content");
 
@@ -520,8 +1261,17 @@ impl WebRtcSession {
             pc,
             audio_bridge,
             closed: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
-            telephone_event_pt: 101,
-            dtmf_timestamp: std::sync::Arc::new(std::sync::atomic::AtomicU32::new(0)),
+            telephone_event_pt: Some(101),
+            local_sdp: final_sdp.clone(),
+            local_hold: false,
+            remote_hold: false,
+            relay_only,
+            clock_rate: 8000,
+            early_media_sdp: None,
+            hold_music_path: media.hold_music_path.clone(),
+            l16: media.l16,
+            media_info: Some(MediaInfo::new(&negotiated, uses_srtp)),
+            listen_only: media.listen_only,
         };
 
         info!("WebRTC inbound session created with Answerer mode");
@@ -532,10 +1282,10 @@ impl WebRtcSession {
     /// This allows RTP packets to be sent before PBX starts sending, ensuring NAT works.
     pub async fn start_inbound_media_early(&mut self, sdp_offer: &str) -> Result<(), String> {
         // Parse negotiated codec from SDP offer
-        let negotiated = codec::parse_negotiated_codec(sdp_offer);
+        let negotiated = codec::parse_offered_codec(sdp_offer, self.l16);
 
-        // Store negotiated telephone-event payload type
-        self.telephone_event_pt = negotiated.telephone_event_pt.unwrap_or(101);
+        self.set_telephone_event_pt(&negotiated);
+        self.clock_rate = negotiated.clock_rate;
 
         info!("Starting audio capture early (before 200 OK)...");
 
@@ -554,7 +1304,7 @@ impl WebRtcSession {
         output_device: Option<&str>,
     ) -> Result<(), String> {
         // Parse negotiated codec from SDP offer
-        let negotiated = codec::parse_negotiated_codec(sdp_offer);
+        let negotiated = codec::parse_offered_codec(sdp_offer, self.l16);
 
         info!("Waiting for RTP connection...");
         match tokio::time::timeout(
@@ -596,11 +1346,31 @@ impl WebRtcSession {
         sdp_answer: &str,
         output_device: Option<&str>,
     ) -> Result<(), String> {
+        // The 183 already carried the answer (RFC 6337 §3.1.1: the 200 OK must repeat
+        // it) and playback is running: only the microphone is left to start
+        if let Some(early_sdp) = self.early_media_sdp.take() {
+            if early_sdp != sdp_answer {
+                warn!("SDP in 200 OK differs from the early media SDP; keeping the early one");
+            }
+            let negotiated = codec::parse_negotiated_codec(&early_sdp);
+            self.media_info = Some(MediaInfo::new(
+                &negotiated,
+                detect_srtp_from_sdp(&early_sdp),
+            ));
+            if !self.audio_bridge.is_playing() {
+                // Early media never got going (e.g. the connection timed out)
+                return start_audio(&self.pc, &mut self.audio_bridge, output_device, &negotiated)
+                    .await;
+            }
+            info!("Call answered after early media, starting audio capture...");
+            return self.audio_bridge.start_capture(&negotiated);
+        }
+
         // Parse negotiated codec from SDP answer
         let negotiated = codec::parse_negotiated_codec(sdp_answer);
 
-        // Store negotiated telephone-event payload type
-        self.telephone_event_pt = negotiated.telephone_event_pt.unwrap_or(101);
+        self.set_telephone_event_pt(&negotiated);
+        self.clock_rate = negotiated.clock_rate;
 
         // Check if remote supports SRTP
         let remote_uses_srtp = detect_srtp_from_sdp(sdp_answer);
@@ -626,10 +1396,47 @@ impl WebRtcSession {
             srtp = remote_uses_srtp,
             "Remote SDP answer applied, waiting for connection..."
         );
+        self.media_info = Some(MediaInfo::new(&negotiated, remote_uses_srtp));
 
         start_audio(&self.pc, &mut self.audio_bridge, output_device, &negotiated).await
     }
 
+    /// Apply the SDP of a 183 Session Progress and play the far end's early media
+    /// (ringback tone, IVR announcement) before the call is answered.
+    ///
+    /// Only playback starts; the microphone stays off until `apply_answer` is
+    /// called for the 200 OK, which then continues with the same media.
+    pub async fn start_early_media(
+        &mut self,
+        sdp: &str,
+        output_device: Option<&str>,
+    ) -> Result<(), String> {
+        if self.audio_bridge.is_playing() {
+            return Ok(());
+        }
+        // A repeated 183 only retries playback; the first SDP stays in effect
+        let sdp = match self.early_media_sdp.clone() {
+            Some(early_sdp) => early_sdp,
+            None => {
+                let answer = rustrtc::SessionDescription::parse(rustrtc::SdpType::Answer, sdp)
+                    .map_err(|e| format!("Failed to parse early media SDP: {}", e))?;
+                self.pc
+                    .set_remote_description(answer)
+                    .await
+                    .map_err(|e| format!("Failed to set remote description: {}", e))?;
+                self.early_media_sdp = Some(sdp.to_string());
+                sdp.to_string()
+            }
+        };
+        let negotiated = codec::parse_negotiated_codec(&sdp);
+        self.set_telephone_event_pt(&negotiated);
+        self.clock_rate = negotiated.clock_rate;
+        info!(codec = ?negotiated.codec, pt = negotiated.payload_type, "Applying early media SDP");
+
+        wait_for_connection(&self.pc).await?;
+        start_remote_playback(&self.pc, &mut self.audio_bridge, output_device, &negotiated)
+    }
+
     /// Toggle microphone mute. Returns new mute state.
     pub fn toggle_mic_mute(&self) -> bool {
         self.audio_bridge.toggle_mic_mute()
@@ -640,6 +1447,22 @@ impl WebRtcSession {
         self.audio_bridge.toggle_speaker_mute()
     }
 
+    pub fn set_mic_mute(&self, muted: bool) {
+        self.audio_bridge.set_mic_mute(muted);
+    }
+
+    pub fn set_speaker_mute(&self, muted: bool) {
+        self.audio_bridge.set_speaker_mute(muted);
+    }
+
+    /// Current microphone and speaker mute state.
+    pub fn mute_state(&self) -> (bool, bool) {
+        (
+            self.audio_bridge.is_mic_muted(),
+            self.audio_bridge.is_speaker_muted(),
+        )
+    }
+
     /// Toggle microphone noise reduction. Returns new enabled state.
     pub fn toggle_noise_reduce(&self) -> bool {
         self.audio_bridge.toggle_noise_reduce()
@@ -655,8 +1478,657 @@ impl WebRtcSession {
         self.audio_bridge.set_speaker_noise_reduce(enabled);
     }
 
+    /// Set the wet/dry mix of the noise reducers (0.0 = bypass, 1.0 = full denoise).
+    pub fn set_noise_reduce_strength(&self, strength: f32) {
+        self.audio_bridge.set_noise_reduce_strength(strength);
+    }
+
+    /// Set acoustic echo cancellation to a specific state.
+    pub fn set_echo_cancel(&self, enabled: bool) {
+        self.audio_bridge.set_echo_cancel(enabled);
+    }
+
+    /// Set the software speaker volume (1.0 = unchanged).
+    pub fn set_speaker_volume(&self, volume: f32) {
+        self.audio_bridge.set_speaker_volume(volume);
+    }
+
+    /// Set the software microphone gain (1.0 = unchanged).
+    pub fn set_mic_gain(&self, gain: f32) {
+        self.audio_bridge.set_mic_gain(gain);
+    }
+
+    /// Set the playback jitter buffer target depth in milliseconds.
+    pub fn set_jitter_buffer_ms(&self, ms: u32) {
+        self.audio_bridge.set_jitter_buffer_ms(ms);
+    }
+
+    /// Set the audio ring buffer capacity in milliseconds (used when the
+    /// audio streams are next opened).
+    pub fn set_audio_buffer_ms(&self, ms: u32) {
+        self.audio_bridge.set_audio_buffer_ms(ms);
+    }
+
+    /// Ring buffer overflows and playback underruns so far.
+    pub fn audio_buffer_xruns(&self) -> u64 {
+        self.audio_bridge.ring_xruns()
+    }
+
+    /// Enable or disable packet loss concealment.
+    pub fn set_plc(&self, enabled: bool) {
+        self.audio_bridge.set_plc(enabled);
+    }
+
+    /// Enable or disable comfort noise in receive gaps.
+    pub fn set_comfort_noise(&self, enabled: bool) {
+        self.audio_bridge.set_comfort_noise(enabled);
+    }
+
+    /// Set the Opus encoder settings for this call's outgoing audio.
+    pub fn set_opus_config(&self, config: encoder::OpusConfig) {
+        self.audio_bridge.set_opus_config(config);
+    }
+
+    /// Change the Opus bitrate of this call's outgoing audio (adaptive bitrate).
+    pub fn set_opus_bitrate(&self, bitrate: u32) {
+        self.audio_bridge.set_opus_bitrate(bitrate);
+    }
+
+    pub fn set_vad(&self, config: vad::VadConfig) {
+        self.audio_bridge.set_vad(config);
+    }
+
+    /// Candidate type of the selected ICE pair, logged for diagnostics.
+    ///
+    /// Returns `Some(IceCandidateType::Relay)` when media flows through TURN.
+    pub async fn selected_candidate_type(&self) -> Option<IceCandidateType> {
+        let pair = self.pc.ice_transport().get_selected_pair().await?;
+        info!(
+            local_type = ?pair.local.typ,
+            local_addr = %pair.local.address,
+            remote_type = ?pair.remote.typ,
+            remote_addr = %pair.remote.address,
+            "Selected ICE candidate pair"
+        );
+        Some(pair.local.typ)
+    }
+
+    /// Whether media is (or can only be) relayed through a TURN server.
+    pub async fn is_relayed(&self) -> bool {
+        self.relay_only || self.selected_candidate_type().await == Some(IceCandidateType::Relay)
+    }
+
+    /// SDP name of the audio codec in use, once media has started.
+    pub fn codec_name(&self) -> Option<&'static str> {
+        self.audio_bridge.negotiated_codec().map(|c| c.name())
+    }
+
+    /// Negotiated codec and SRTP use; `None` until the SDP exchange is complete.
+    pub fn media_info(&self) -> Option<&MediaInfo> {
+        self.media_info.as_ref()
+    }
+
+    /// Emit `sip://media-info` with the negotiated media parameters.
+    pub fn emit_media_info(&self, app_handle: &tauri::AppHandle, call_id: &str) {
+        if let Some(info) = self.media_info.clone() {
+            let _ = app_handle.emit(
+                "sip://media-info",
+                MediaInfoPayload {
+                    call_id: call_id.to_string(),
+                    info,
+                },
+            );
+        }
+    }
+
+    /// Collect RTP/RTCP statistics from the PeerConnection.
+    ///
+    /// Returns zeroed stats when the stats report is not available yet.
+    pub async fn get_stats(&self) -> CallStats {
+        self.stats_source().get_stats().await
+    }
+
+    /// Handle for collecting this call's statistics, usable after the
+    /// session's lock is released.
+    pub fn stats_source(&self) -> CallStatsSource {
+        CallStatsSource {
+            pc: self.pc.clone(),
+            jitter: self.audio_bridge.jitter_stats(),
+            ring_xruns: self.audio_bridge.ring_xruns_counter(),
+            clock_rate: self.clock_rate,
+        }
+    }
+
+    /// Build a re-INVITE offer that places the call on hold (`hold = true`) or resumes it.
+    ///
+    /// The offer reuses the last local SDP, so ICE credentials, candidates and the RTP
+    /// port stay the same; only the direction attribute and the session version change.
+    /// Holding gives `a=sendonly`, or `a=inactive` when the remote has also held us.
+    /// Nothing is changed until the offer is accepted, see [`Self::set_local_hold`].
+    pub fn create_hold_offer(&self, hold: bool) -> String {
+        let direction = match (hold, self.remote_hold) {
+            (true, false) => "sendonly",
+            (true, true) => "inactive",
+            (false, false) => "sendrecv",
+            (false, true) => "recvonly",
+        };
+        let direction = if self.listen_only {
+            receive_only(direction)
+        } else {
+            direction
+        };
+        let offer = rewrite_sdp_direction(&self.local_sdp, direction);
+        debug!(direction, sdp = %offer, "Created hold re-INVITE offer");
+        offer
+    }
+
+    /// Commit the accepted hold `offer` as the local SDP and apply the local hold
+    /// state to the audio path once the re-INVITE succeeded.
+    pub fn set_local_hold(&mut self, hold: bool, offer: String) {
+        self.local_sdp = offer;
+        self.local_hold = hold;
+        self.audio_bridge.set_hold(hold || self.remote_hold);
+        if !hold {
+            let _ = self.audio_bridge.set_hold_music(None);
+        }
+    }
+
+    /// Start looping the hold music to the remote once the call is on hold.
+    /// Without a configured file, or when it cannot be read, the hold stays silent.
+    pub fn start_hold_music(&self) -> Result<(), String> {
+        self.audio_bridge
+            .set_hold_music(self.hold_music_path.as_deref())
+    }
+
+    /// Set the hold music file (`None` = silence); a call already on hold switches at once.
+    pub fn set_hold_music_path(&mut self, path: Option<String>) -> Result<(), String> {
+        self.hold_music_path = path;
+        if self.local_hold {
+            self.start_hold_music()
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Build the SDP answer to a re-INVITE received from the remote.
+    ///
+    /// Like `create_hold_offer`, the last local SDP is reused with only the
+    /// direction changed. Returns the answer and whether the offer holds us
+    /// (`a=sendonly` / `a=inactive`); an offerless re-INVITE gets our current
+    /// SDP as the offer and leaves the hold state alone.
+    pub fn create_reinvite_answer(&mut self, offer: &str) -> (String, bool) {
+        if offer.trim().is_empty() {
+            return (self.local_sdp.clone(), self.remote_hold);
+        }
+        let remote = sdp_direction(offer);
+        let mut direction = answer_direction(remote, self.local_hold);
+        if self.listen_only {
+            direction = receive_only(direction);
+        }
+        if sdp_direction(&self.local_sdp) != direction {
+            self.local_sdp = rewrite_sdp_direction(&self.local_sdp, direction);
+        }
+        debug!(remote, direction, sdp = %self.local_sdp, "Created re-INVITE answer");
+        // A listen-only call expects the remote to only send; that is no hold
+        let held = match remote {
+            "sendonly" => !self.listen_only,
+            "inactive" => true,
+            _ => false,
+        };
+        (self.local_sdp.clone(), held)
+    }
+
+    /// Apply the remote hold state: while held we neither send nor play audio.
+    pub fn set_remote_hold(&mut self, hold: bool) {
+        self.remote_hold = hold;
+        self.audio_bridge.set_hold(hold || self.local_hold);
+        self.audio_bridge.set_playback_paused(hold);
+    }
+
+    /// Whether the remote placed the call on hold.
+    pub fn is_remote_hold(&self) -> bool {
+        self.remote_hold
+    }
+
+    /// Whether we placed the call on hold.
+    pub fn is_local_hold(&self) -> bool {
+        self.local_hold
+    }
+
+    /// Time since RTP was last received, or `None` while the call is held
+    /// on either side and the far end may legitimately send nothing.
+    pub fn media_idle(&self) -> Option<std::time::Duration> {
+        if self.local_hold || self.remote_hold {
+            None
+        } else {
+            Some(self.audio_bridge.media_idle())
+        }
+    }
+
+    /// Forward mic/speaker levels of this session as `sip://audio-level` events.
+    ///
+    /// The forwarding task ends when the audio bridge and its tasks are gone.
+    pub fn forward_audio_levels(&mut self, app_handle: tauri::AppHandle, call_id: String) {
+        if let Some(level_rx) = self.audio_bridge.take_level_receiver() {
+            spawn_level_forwarder(level_rx, app_handle, call_id);
+        }
+    }
+
+    /// Emit `sip://audio-warning` when audio flows one way only: RTP has been
+    /// sent for `ONE_WAY_AUDIO_AFTER` without a single frame received, or the
+    /// other way round, which usually points at a NAT or firewall problem.
+    ///
+    /// Each direction is reported once; the task ends when audio has flowed
+    /// both ways or the session is closed.
+    pub fn watch_one_way_audio(&self, app_handle: tauri::AppHandle, call_id: String) {
+        use std::sync::atomic::Ordering;
+
+        let flow = self.audio_bridge.media_flow();
+        let closed = self.closed.clone();
+        let listen_only = self.listen_only;
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(std::time::Duration::from_secs(1));
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            let mut sending_since = None;
+            let mut receiving_since = None;
+            // A direction is done once audio flowed in it or it was reported;
+            // a listen-only call sends nothing on purpose
+            let mut inbound_done = false;
+            let mut outbound_done = listen_only;
+
+            while !closed.load(Ordering::Relaxed) {
+                ticker.tick().await;
+                let sent = flow.sent.load(Ordering::Relaxed);
+                let received = flow.received.load(Ordering::Relaxed);
+                if sent > 0 {
+                    sending_since.get_or_insert_with(std::time::Instant::now);
+                }
+                if received > 0 {
+                    receiving_since.get_or_insert_with(std::time::Instant::now);
+                }
+
+                let stalled = |since: Option<std::time::Instant>| {
+                    since.is_some_and(|t| t.elapsed() >= ONE_WAY_AUDIO_AFTER)
+                };
+                let warning = if !inbound_done && received == 0 && stalled(sending_since) {
+                    inbound_done = true;
+                    Some(("inbound", "No audio is arriving from the other party; a firewall or NAT may be blocking incoming RTP."))
+                } else if !outbound_done && sent == 0 && stalled(receiving_since) {
+                    outbound_done = true;
+                    Some(("outbound", "No audio is being sent to the other party; check the microphone and outgoing RTP."))
+                } else {
+                    inbound_done |= received > 0;
+                    outbound_done |= sent > 0;
+                    None
+                };
+                if let Some((direction, message)) = warning {
+                    warn!(call_id = %call_id, direction, sent, received, "One-way audio");
+                    let _ = app_handle.emit(
+                        "sip://audio-warning",
+                        AudioWarningPayload {
+                            call_id: call_id.clone(),
+                            direction: direction.to_string(),
+                            message: message.to_string(),
+                        },
+                    );
+                }
+                if inbound_done && outbound_done {
+                    break;
+                }
+            }
+            debug!(call_id = %call_id, "One-way audio watch stopped");
+        });
+    }
+
+    /// Emit `sip://rtp-latched` whenever symmetric RTP latching re-points the
+    /// send address from the negotiated one to where the far end's packets come
+    /// from, typically because of a NAT in front of it.
+    ///
+    /// rustrtc latches on the STUN Binding requests of the far end and reports
+    /// it as a new selected candidate pair. The task ends with the session.
+    pub fn watch_rtp_latching(&self, app_handle: tauri::AppHandle, call_id: String) {
+        let mut selected = self.pc.ice_transport().subscribe_selected_pair();
+        tokio::spawn(async move {
+            let mut current = None;
+            loop {
+                let remote = selected
+                    .borrow_and_update()
+                    .as_ref()
+                    .map(|pair| pair.remote.address);
+                if let Some(new) = remote {
+                    match current.replace(new) {
+                        Some(old) if old != new => {
+                            info!(call_id = %call_id, old = %old, new = %new, "RTP latched to a new remote address");
+                            let _ = app_handle.emit(
+                                "sip://rtp-latched",
+                                RtpLatchedPayload {
+                                    call_id: call_id.clone(),
+                                    old_address: old.to_string(),
+                                    new_address: new.to_string(),
+                                },
+                            );
+                        }
+                        _ => {}
+                    }
+                }
+                if selected.changed().await.is_err() {
+                    break;
+                }
+            }
+            debug!(call_id = %call_id, "RTP latching watch stopped");
+        });
+    }
+
+    /// Emit DTMF digits received from the far end (RFC 4733) as `sip://dtmf-received`.
+    ///
+    /// The forwarding task ends when the audio bridge and its tasks are gone.
+    pub fn forward_received_dtmf(&mut self, app_handle: tauri::AppHandle, call_id: String) {
+        let Some(mut dtmf_rx) = self.audio_bridge.take_dtmf_receiver() else {
+            return;
+        };
+        tokio::spawn(async move {
+            while let Some(dtmf) = dtmf_rx.recv().await {
+                info!(call_id = %call_id, digit = %dtmf.digit, duration_ms = dtmf.duration_ms, "DTMF received");
+                let _ = app_handle.emit(
+                    "sip://dtmf-received",
+                    DtmfReceivedPayload {
+                        call_id: call_id.clone(),
+                        digit: dtmf.digit.to_string(),
+                        duration_ms: dtmf.duration_ms,
+                    },
+                );
+            }
+            debug!(call_id = %call_id, "DTMF forwarding stopped");
+        });
+    }
+
+    /// Emit errors of the capture and playback streams (XRUNs, driver errors) as
+    /// `sip://audio-error`, at most once per `AUDIO_ERROR_THROTTLE` for the same error.
+    ///
+    /// The forwarding task ends when the audio bridge and its tasks are gone.
+    pub fn forward_audio_errors(&mut self, app_handle: tauri::AppHandle, call_id: String) {
+        let Some(mut error_rx) = self.audio_bridge.take_stream_error_receiver() else {
+            return;
+        };
+        tokio::spawn(async move {
+            let mut last_emitted: std::collections::HashMap<_, std::time::Instant> =
+                std::collections::HashMap::new();
+            while let Some(err) = error_rx.recv().await {
+                let now = std::time::Instant::now();
+                let key = (err.direction, err.message.clone());
+                if last_emitted
+                    .get(&key)
+                    .is_some_and(|at| now.duration_since(*at) < AUDIO_ERROR_THROTTLE)
+                {
+                    continue;
+                }
+                last_emitted.insert(key, now);
+                let _ = app_handle.emit(
+                    "sip://audio-error",
+                    AudioErrorPayload {
+                        call_id: call_id.clone(),
+                        direction: err.direction,
+                        device: err.device,
+                        message: err.message,
+                    },
+                );
+            }
+            debug!(call_id = %call_id, "Audio error forwarding stopped");
+        });
+    }
+
+    /// Emit `sip://audio-fallback` when a configured device is missing and the
+    /// system default is used instead.
+    ///
+    /// The forwarding task ends when the audio bridge is gone.
+    pub fn forward_audio_fallbacks(&mut self, app_handle: tauri::AppHandle, call_id: String) {
+        let Some(mut fallback_rx) = self.audio_bridge.take_fallback_receiver() else {
+            return;
+        };
+        tokio::spawn(async move {
+            while let Some(fallback) = fallback_rx.recv().await {
+                let _ = app_handle.emit(
+                    "sip://audio-fallback",
+                    AudioFallbackPayload {
+                        call_id: call_id.clone(),
+                        direction: fallback.direction,
+                        requested: fallback.requested,
+                        chosen: fallback.chosen,
+                    },
+                );
+            }
+            debug!(call_id = %call_id, "Audio fallback forwarding stopped");
+        });
+    }
+
+    /// Emit `sip://playback-finished` whenever an announcement played into the
+    /// call ends, whether played through or stopped.
+    ///
+    /// The forwarding task ends when the audio bridge is gone.
+    pub fn forward_playback_finished(&mut self, app_handle: tauri::AppHandle, call_id: String) {
+        let Some(mut done_rx) = self.audio_bridge.take_announcement_done_receiver() else {
+            return;
+        };
+        tokio::spawn(async move {
+            while let Some(completed) = done_rx.recv().await {
+                let _ = app_handle.emit(
+                    "sip://playback-finished",
+                    PlaybackFinishedPayload {
+                        call_id: call_id.clone(),
+                        completed,
+                    },
+                );
+            }
+            debug!(call_id = %call_id, "Playback finished forwarding stopped");
+        });
+    }
+
+    /// Take the receiver of audio device loss reports (available once).
+    pub fn take_device_loss_receiver(
+        &mut self,
+    ) -> Option<tokio::sync::mpsc::UnboundedReceiver<AudioDirection>> {
+        self.audio_bridge.take_device_loss_receiver()
+    }
+
+    /// Move this call's capture to another microphone (`None` = default device).
+    pub fn switch_input_device(&mut self, device_id: Option<&str>) -> Result<(), AppError> {
+        self.audio_bridge.switch_input_device(device_id)
+    }
+
+    /// Move this call's playback to another speaker (`None` = default device).
+    pub fn switch_output_device(&mut self, device_id: Option<&str>) -> Result<(), AppError> {
+        self.audio_bridge.switch_output_device(device_id)
+    }
+
+    /// Re-open the audio devices and restart capture/playback for this call.
+    pub fn reconnect_audio(&mut self) -> Result<(), String> {
+        self.audio_bridge.reconnect()
+    }
+
+    /// Start recording the call to a WAV file
+    pub fn start_recording(&self, path: &str, mode: recorder::RecordingMode) -> Result<(), String> {
+        self.audio_bridge.start_recording(path, mode)
+    }
+
+    /// Stop recording the call; returns the recording's path
+    pub fn stop_recording(&self) -> Result<String, String> {
+        self.audio_bridge.stop_recording()
+    }
+
+    /// Send a WAV file to the far end in place of the mic until it ends
+    pub fn play_announcement(&self, path: &str) -> Result<(), String> {
+        if self.listen_only {
+            return Err("Cannot play audio into a listen-only call".to_string());
+        }
+        self.audio_bridge.play_announcement(path)
+    }
+
+    /// Stop the announcement and return to the mic; whether one was playing
+    pub fn stop_announcement(&self) -> bool {
+        self.audio_bridge.stop_announcement()
+    }
+
+    /// Route this call's audio through a local conference as leg `leg_id`.
+    pub fn join_conference(&self, mixer: std::sync::Arc<ConferenceMixer>, leg_id: &str) {
+        self.audio_bridge.set_conference(Some(ConferenceLink {
+            mixer,
+            leg_id: leg_id.to_string(),
+        }));
+    }
+
+    /// Stop exchanging audio with the other conference legs.
+    pub fn leave_conference(&self) {
+        self.audio_bridge.set_conference(None);
+    }
+
+    /// Store the telephone-event payload type the remote SDP negotiated.
+    fn set_telephone_event_pt(&mut self, negotiated: &NegotiatedCodec) {
+        self.telephone_event_pt = negotiated.telephone_event_pt;
+        if self.telephone_event_pt.is_none() {
+            warn!("Remote SDP has no telephone-event, RFC 4733 DTMF unavailable");
+        }
+    }
+
+    /// Handle for sending DTMF on this call, usable after the session's
+    /// lock is released.
+    pub fn dtmf_sender(&self) -> DtmfSender {
+        DtmfSender {
+            channel: self.audio_bridge.dtmf_channel(),
+            telephone_event_pt: self.telephone_event_pt,
+            ptime_ms: self.media_info.as_ref().map_or(20, |m| m.ptime_ms).max(10),
+            clock_rate: self.clock_rate,
+            listen_only: self.listen_only,
+        }
+    }
+
+    /// Close the session: stop audio, close PeerConnection.
+    pub async fn close(&mut self) {
+        // Check if already closed to prevent double-close
+        if self.closed.swap(true, std::sync::atomic::Ordering::SeqCst) {
+            debug!("WebRTC session already closed, skipping");
+            return;
+        }
+
+        info!("Closing WebRTC session");
+
+        // Step 1: Close audio first (unrelated to ICE)
+        debug!("Closing audio bridge...");
+        self.audio_bridge.close();
+
+        // Step 2: Close PeerConnection (ICE transport will be closed)
+        debug!("Closing PeerConnection (ICE transport will be closed)...");
+        self.pc.close();
+
+        debug!("WebRTC session closed");
+    }
+}
+
+/// Collects one call's statistics, taken from `WebRtcSession::stats_source` so
+/// the periodic stats tasks do not hold the active-calls lock while waiting
+/// for the PeerConnection's report.
+#[derive(Clone)]
+pub struct CallStatsSource {
+    pc: PeerConnection,
+    jitter: std::sync::Arc<jitter_buffer::JitterStats>,
+    ring_xruns: std::sync::Arc<std::sync::atomic::AtomicU64>,
+    /// Negotiated audio clock rate, used to convert RTCP jitter to milliseconds
+    clock_rate: u32,
+}
+
+impl CallStatsSource {
+    /// Collect RTP/RTCP statistics from the PeerConnection.
+    ///
+    /// Returns zeroed stats when the stats report is not available yet.
+    pub async fn get_stats(&self) -> CallStats {
+        use rustrtc::stats::StatsKind;
+        use std::sync::atomic::Ordering;
+
+        let jitter = &self.jitter;
+        let mut stats = CallStats {
+            jitter_buffer_underruns: jitter.underruns.load(Ordering::Relaxed),
+            jitter_buffer_overruns: jitter.overruns.load(Ordering::Relaxed),
+            late_packets_discarded: jitter.late_packets.load(Ordering::Relaxed),
+            lost_frames: jitter.lost_frames.load(Ordering::Relaxed),
+            fec_recovered_frames: jitter.fec_recovered.load(Ordering::Relaxed),
+            audio_buffer_xruns: self.ring_xruns.load(Ordering::Relaxed),
+            ..Default::default()
+        };
+        let report = match self.pc.get_stats().await {
+            Ok(report) => report,
+            Err(e) => {
+                debug!(error = %e, "Call stats not available yet");
+                return stats;
+            }
+        };
+
+        let u64_of = |values: &std::collections::BTreeMap<String, serde_json::Value>, key: &str| {
+            values.get(key).and_then(|v| v.as_u64()).unwrap_or(0)
+        };
+        for entry in &report.entries {
+            match entry.kind {
+                StatsKind::OutboundRtp => {
+                    stats.packets_sent += u64_of(&entry.values, "packetsSent");
+                    stats.bytes_sent += u64_of(&entry.values, "bytesSent");
+                }
+                StatsKind::InboundRtp => {
+                    stats.packets_received += u64_of(&entry.values, "packetsReceived");
+                    stats.bytes_received += u64_of(&entry.values, "bytesReceived");
+                }
+                StatsKind::RemoteInboundRtp => {
+                    let values = &entry.values;
+                    if let Some(lost) = values.get("packetsLost").and_then(|v| v.as_i64()) {
+                        stats.packets_lost += lost;
+                    }
+                    // fractionLost is the 8-bit fixed-point value from the report block
+                    stats.fraction_lost = u64_of(values, "fractionLost") as f64 / 256.0;
+                    // Jitter is reported in RTP timestamp units
+                    let jitter = u64_of(values, "jitter");
+                    if self.clock_rate > 0 {
+                        stats.jitter_ms = jitter as f64 * 1000.0 / self.clock_rate as f64;
+                    }
+                    if let Some(rtt) = values.get("roundTripTime").and_then(|v| v.as_f64()) {
+                        stats.round_trip_time_ms = rtt * 1000.0;
+                    }
+                }
+                _ => {}
+            }
+        }
+        stats
+    }
+}
+
+/// Sends DTMF for one call, taken from `WebRtcSession::dtmf_sender` so a digit
+/// can be sent without holding the active-calls lock.
+#[derive(Clone)]
+pub struct DtmfSender {
+    channel: audio_bridge::DtmfChannel,
+    telephone_event_pt: Option<u8>,
+    ptime_ms: u32,
+    clock_rate: u32,
+    listen_only: bool,
+}
+
+impl DtmfSender {
+    /// Whether the call only receives audio (`a=recvonly`).
+    pub fn is_listen_only(&self) -> bool {
+        self.listen_only
+    }
+
+    /// Whether the remote accepted RFC 4733 telephone-event for DTMF.
+    pub fn supports_telephone_event(&self) -> bool {
+        self.telephone_event_pt.is_some()
+    }
+
     /// Send DTMF digit (0-9, *, #, A-D) via RFC 4733 telephone-event.
-    pub async fn send_dtmf(&self, digit: char) -> Result<(), String> {
+    pub async fn send_dtmf(
+        &self,
+        digit: char,
+        duration_ms: u32,
+        volume_dbm0: u8,
+    ) -> Result<(), String> {
+        let telephone_event_pt = self
+            .telephone_event_pt
+            .ok_or_else(|| "Remote did not negotiate telephone-event".to_string())?;
         // Map digit to event code (RFC 4733)
         let event_code: u8 = match digit {
             '0' => 0,
@@ -681,61 +2153,54 @@ impl WebRtcSession {
         info!(
             digit = %digit,
             event_code = event_code,
-            telephone_event_pt = self.telephone_event_pt,
+            telephone_event_pt,
             "Sending DTMF"
         );
 
-        // RFC 4733: 8 packets × 20ms = 160ms total event duration at 8 kHz clock
-        // All packets for the same event share the same base timestamp (event start).
-        // The duration field increases by 160 per packet (20ms × 8000 Hz / 1000 = 160).
+        // RFC 4733: one packet per ptime for `duration_ms`
+        // (160 ms at 20 ms ptime = 8 packets).
+        // All packets for the same event share the same base timestamp (event start),
+        // the audio stream's current timestamp; audio is held back during the event.
+        // The duration field is in units of the audio RTP clock and increases by one
+        // ptime per packet (20 ms × 8000 Hz / 1000 = 160, or 960 for Opus at 48 kHz).
         // Last 3 packets have the End (E) bit set.
-        const PACKET_DURATION: u16 = 160; // timestamp units per 20ms at 8 kHz
-        const TOTAL_PACKETS: usize = 8;
-        const VOLUME: u8 = 10; // dBm0, 0 = loudest, 63 = silence
-
-        // Reserve a base timestamp for this event (advances counter for next event)
-        let base_ts = self.dtmf_timestamp.fetch_add(
-            PACKET_DURATION as u32 * TOTAL_PACKETS as u32,
-            std::sync::atomic::Ordering::Relaxed,
-        );
+        let ptime_ms = self.ptime_ms;
+        let packet_duration = (ptime_ms * self.clock_rate / 1000) as u16;
+        let total_packets = duration_ms.div_ceil(ptime_ms).max(3) as u16;
 
-        for i in 0..TOTAL_PACKETS {
-            let duration = PACKET_DURATION * (i as u16 + 1);
-            let end_bit: u8 = if i >= TOTAL_PACKETS - 3 { 1 } else { 0 };
+        let base_ts = self.channel.begin_dtmf_event();
+        let mut result = Ok(());
+        for i in 0..total_packets {
+            let duration = packet_duration * (i + 1);
+            let end_bit: u8 = if i >= total_packets - 3 { 1 } else { 0 };
 
             // Build RFC 4733 telephone-event payload (4 bytes)
-            let payload = build_dtmf_payload(event_code, end_bit, VOLUME, duration);
+            let payload = build_dtmf_payload(event_code, end_bit, volume_dbm0, duration);
 
-            self.audio_bridge
-                .send_dtmf_packet(&payload, self.telephone_event_pt, base_ts)
-                .await?;
+            result = self
+                .channel
+                .send_dtmf_packet(&payload, telephone_event_pt, base_ts, self.clock_rate)
+                .await;
+            if result.is_err() {
+                break;
+            }
 
-            tokio::time::sleep(tokio::time::Duration::from_millis(20)).await;
+            tokio::time::sleep(tokio::time::Duration::from_millis(ptime_ms as u64)).await;
         }
+        self.channel.end_dtmf_event();
+        result?;
 
         info!(digit = %digit, "DTMF sent successfully");
         Ok(())
     }
 
-    /// Close the session: stop audio, close PeerConnection.
-    pub async fn close(&mut self) {
-        // Check if already closed to prevent double-close
-        if self.closed.swap(true, std::sync::atomic::Ordering::SeqCst) {
-            debug!("WebRTC session already closed, skipping");
-            return;
-        }
-
-        info!("Closing WebRTC session");
-
-        // Step 1: Close audio first (unrelated to ICE)
-        debug!("Closing audio bridge...");
-        self.audio_bridge.close();
-
-        // Step 2: Close PeerConnection (ICE transport will be closed)
-        debug!("Closing PeerConnection (ICE transport will be closed)...");
-        self.pc.close();
-
-        debug!("WebRTC session closed");
+    /// Send a DTMF digit in-band, as its dual tone in the outgoing audio.
+    /// Returns once the tone (and the gap after it) has been queued and played out.
+    pub async fn send_inband_dtmf(&self, digit: char) -> Result<(), String> {
+        let duration = self.channel.queue_inband_dtmf(digit)?;
+        info!(digit = %digit, "Sending in-band DTMF tone");
+        tokio::time::sleep(duration).await;
+        Ok(())
     }
 }
 
@@ -756,3 +2221,131 @@ impl Drop for WebRtcSession {
         // Always call close().await explicitly before dropping when possible
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// IPv6 offer from a non-ICE PBX, with a bracketed origin address
+    const IPV6_OFFER: &str = "v=0\r\n\
+        o=- 1234 1 IN IP6 [2001:db8::10]\r\n\
+        s=-\r\n\
+        c=IN IP6 2001:db8::10\r\n\
+        t=0 0\r\n\
+        m=audio 40000 RTP/AVP 0 101\r\n\
+        a=rtpmap:0 PCMU/8000\r\n\
+        a=rtpmap:101 telephone-event/8000\r\n\
+        a=sendrecv\r\n";
+
+    #[test]
+    fn test_ipv6_offer_rewrite() {
+        // Offer side: fake ICE injected and the origin address unwrapped
+        let offer = inject_ice_attributes(IPV6_OFFER);
+        assert!(offer.contains("o=- 1234 1 IN IP6 2001:db8::10\r\n"));
+        assert!(offer.contains("c=IN IP6 2001:db8::10\r\n"));
+        assert!(offer.contains("m=audio 40000 RTP/AVP 0 101\r\na=ice-ufrag:fake\r\n"));
+
+        // Answer side: local IPv4 host address replaced by an IPv6 public candidate
+        let answer = "v=0\r\n\
+            o=- 99 1 IN IP4 192.168.1.5\r\n\
+            s=-\r\n\
+            c=IN IP4 192.168.1.5\r\n\
+            t=0 0\r\n\
+            m=audio 50000 RTP/AVP 0 101\r\n\
+            a=ice-ufrag:abcd\r\n\
+            a=candidate:1 1 udp 1 192.168.1.5 50000 typ host\r\n\
+            a=rtcp-mux\r\n\
+            a=sendrecv\r\n";
+        let rewritten = replace_with_public_address(answer, "[2001:db8::20]", 42000, false);
+        assert_eq!(
+            rewritten,
+            "v=0\r\n\
+             o=- 99 1 IN IP6 2001:db8::20\r\n\
+             s=-\r\n\
+             c=IN IP6 2001:db8::20\r\n\
+             t=0 0\r\n\
+             m=audio 42000 RTP/AVP 0 101\r\n\
+             a=sendrecv\r\n"
+        );
+
+        // IPv6 answer rewritten to an IPv4 public address switches the address type back
+        let v4 = replace_with_public_address(&rewritten, "203.0.113.7", 42000, false);
+        assert!(v4.contains("o=- 99 1 IN IP4 203.0.113.7\r\n"));
+        assert!(v4.contains("c=IN IP4 203.0.113.7\r\n"));
+
+        // rtcp-mux kept when enabled
+        let muxed = replace_with_public_address(answer, "203.0.113.7", 42000, true);
+        assert!(muxed.contains("m=audio 42000 RTP/AVP 0 101\r\na=rtcp-mux\r\n"));
+        assert!(!muxed.contains("a=ice-ufrag"));
+    }
+
+    #[test]
+    fn test_video_declined_in_answer() {
+        let offer = "v=0\r\n\
+            o=- 1 1 IN IP4 10.0.0.1\r\n\
+            s=-\r\n\
+            c=IN IP4 10.0.0.1\r\n\
+            t=0 0\r\n\
+            m=audio 40000 RTP/AVP 0 101\r\n\
+            a=rtpmap:0 PCMU/8000\r\n\
+            a=rtpmap:101 telephone-event/8000\r\n\
+            m=video 40002 RTP/AVP 96\r\n\
+            a=rtpmap:96 H264/90000\r\n";
+        let audio = "m=audio 50000 RTP/AVP 0 101\r\n\
+            a=mid:0\r\n\
+            a=rtpmap:0 PCMU/8000\r\n\
+            a=sendrecv\r\n";
+        let header = "v=0\r\no=- 2 1 IN IP4 10.0.0.2\r\ns=-\r\nt=0 0\r\n";
+
+        // rustrtc answered video too: the section is replaced by a declined m-line
+        let answer = format!(
+            "{}a=group:BUNDLE 0 1\r\n{}m=video 50000 RTP/AVP 96\r\na=mid:1\r\na=sendrecv\r\n",
+            header, audio
+        );
+        let expected = format!(
+            "{}a=group:BUNDLE 0\r\n{}m=video 0 RTP/AVP 96\r\n",
+            header, audio
+        );
+        assert_eq!(decline_non_audio_streams(offer, &answer), expected);
+
+        // rustrtc left video out: a declined m-line is added in its place
+        let answer = format!("{}{}", header, audio);
+        let expected = format!("{}{}m=video 0 RTP/AVP 96\r\n", header, audio);
+        assert_eq!(decline_non_audio_streams(offer, &answer), expected);
+
+        // Audio-only offers are answered unchanged
+        assert_eq!(decline_non_audio_streams(IPV6_OFFER, &answer), answer);
+    }
+
+    #[test]
+    fn test_reinvite_answer_direction() {
+        assert_eq!(sdp_direction(IPV6_OFFER), "sendrecv");
+        let held = rewrite_sdp_direction(IPV6_OFFER, "sendonly");
+        assert_eq!(sdp_direction(&held), "sendonly");
+        assert_eq!(sdp_direction("v=0\r\nm=audio 1 RTP/AVP 0\r\n"), "sendrecv");
+
+        assert_eq!(answer_direction("sendonly", false), "recvonly");
+        assert_eq!(answer_direction("sendonly", true), "inactive");
+        assert_eq!(answer_direction("inactive", false), "inactive");
+        assert_eq!(answer_direction("sendrecv", false), "sendrecv");
+        assert_eq!(answer_direction("sendrecv", true), "sendonly");
+        assert_eq!(answer_direction("recvonly", false), "sendonly");
+    }
+
+    #[test]
+    fn test_listen_only_never_sends() {
+        for (remote, expected) in [
+            ("sendrecv", "recvonly"),
+            ("sendonly", "recvonly"),
+            ("recvonly", "inactive"),
+            ("inactive", "inactive"),
+        ] {
+            assert_eq!(receive_only(answer_direction(remote, false)), expected);
+        }
+        // Holding a listen-only call stops receiving as well
+        assert_eq!(receive_only(answer_direction("sendrecv", true)), "inactive");
+
+        let offer = rewrite_sdp_direction(IPV6_OFFER, "recvonly");
+        assert_eq!(sdp_direction(&offer), "recvonly");
+    }
+}
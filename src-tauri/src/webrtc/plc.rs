@@ -0,0 +1,202 @@
+//! Decoding and packet loss concealment for the playback path.
+//!
+//! One `FrameDecoder` lives for the whole call (stateful codecs such as G.722
+//! and Opus need their history). When the jitter buffer reports a lost frame,
+//! a replacement is synthesized:
+//!   Opus          → in-band FEC from the next packet if it is already buffered
+//!                   and carries it, otherwise the decoder's built-in PLC
+//!   other codecs  → repeat the last good frame, fading out over `FADE_FRAMES`
+
+use std::os::raw::c_int;
+use std::ptr::NonNull;
+
+use audio_codec::{create_decoder, CodecType, Decoder};
+use opusic_sys::{
+    opus_decode, opus_decoder_create, opus_decoder_destroy, opus_packet_has_lbrr, OPUS_OK,
+};
+use tracing::warn;
+
+use super::codec::{l16_decode, AudioCodec};
+
+/// Consecutive lost frames after which repeat-and-fade concealment is silent
+const FADE_FRAMES: u32 = 4;
+
+/// Largest Opus frame (120 ms at 48 kHz)
+const OPUS_MAX_FRAME: usize = 5760;
+
+pub struct FrameDecoder {
+    decoder: DecoderKind,
+    frame_samples: usize,
+    /// Last frame played, repeated (faded) for codecs without native PLC
+    last_frame: Vec<i16>,
+    /// Consecutive frames concealed so far
+    lost_run: u32,
+}
+
+enum DecoderKind {
+    Opus(OpusPlcDecoder),
+    L16,
+    Other(Box<dyn Decoder>),
+}
+
+impl FrameDecoder {
+    pub fn new(codec: impl Into<AudioCodec>, clock_rate: u32, frame_samples: usize) -> Self {
+        let decoder = match codec.into() {
+            AudioCodec::L16 => DecoderKind::L16,
+            AudioCodec::Native(CodecType::Opus) => match OpusPlcDecoder::new(clock_rate) {
+                Ok(opus) => DecoderKind::Opus(opus),
+                Err(e) => {
+                    warn!(error = %e, "Opus PLC decoder unavailable, falling back to repeat-and-fade");
+                    DecoderKind::Other(create_decoder(CodecType::Opus))
+                }
+            },
+            AudioCodec::Native(codec) => DecoderKind::Other(create_decoder(codec)),
+        };
+        Self {
+            decoder,
+            frame_samples,
+            last_frame: Vec::new(),
+            lost_run: 0,
+        }
+    }
+
+    /// Decode a received frame.
+    pub fn decode(&mut self, data: &[u8]) -> Vec<i16> {
+        let pcm = match self.decoder {
+            DecoderKind::Opus(ref mut opus) => opus.decode(data, OPUS_MAX_FRAME, false),
+            DecoderKind::L16 => l16_decode(data),
+            DecoderKind::Other(ref mut decoder) => decoder.decode(data),
+        };
+        self.lost_run = 0;
+        self.last_frame.clone_from(&pcm);
+        pcm
+    }
+
+    /// Synthesize one frame in place of a lost one. `next` is the following
+    /// packet if it has already arrived (used for Opus FEC).
+    ///
+    /// Returns the frame and whether it was recovered from in-band FEC rather
+    /// than concealed.
+    pub fn conceal(&mut self, next: Option<&[u8]>) -> (Vec<i16>, bool) {
+        let frame_samples = self.frame_samples;
+        let (pcm, recovered) = match self.decoder {
+            DecoderKind::Opus(ref mut opus) => match next.filter(|next| has_fec(next)) {
+                Some(next) => (opus.decode(next, frame_samples, true), true),
+                None => (opus.decode(&[], frame_samples, false), false),
+            },
+            DecoderKind::L16 | DecoderKind::Other(_) => {
+                (repeat_and_fade(&self.last_frame, self.lost_run), false)
+            }
+        };
+        self.lost_run += 1;
+
+        let mut pcm = pcm;
+        pcm.resize(frame_samples, 0);
+        (pcm, recovered)
+    }
+}
+
+/// The last good frame scaled by a gain ramp that reaches zero after `FADE_FRAMES` losses.
+fn repeat_and_fade(last_frame: &[i16], lost_run: u32) -> Vec<i16> {
+    let gain_at = |run: u32| (1.0 - run as f32 / FADE_FRAMES as f32).max(0.0);
+    let start = gain_at(lost_run);
+    let end = gain_at(lost_run + 1);
+    let len = last_frame.len().max(1) as f32;
+    last_frame
+        .iter()
+        .enumerate()
+        .map(|(i, &s)| {
+            let gain = start + (end - start) * i as f32 / len;
+            (s as f32 * gain) as i16
+        })
+        .collect()
+}
+
+/// Whether an Opus packet carries an in-band FEC (LBRR) copy of the frame before it.
+fn has_fec(packet: &[u8]) -> bool {
+    unsafe { opus_packet_has_lbrr(packet.as_ptr(), packet.len() as i32) > 0 }
+}
+
+/// Mono Opus decoder exposing the FEC and PLC modes of `opus_decode`,
+/// which the `audio_codec` decoder does not.
+struct OpusPlcDecoder {
+    decoder: NonNull<opusic_sys::OpusDecoder>,
+}
+
+// The decoder state is only touched from the playback task.
+unsafe impl Send for OpusPlcDecoder {}
+
+impl OpusPlcDecoder {
+    fn new(sample_rate: u32) -> Result<Self, String> {
+        let mut error: c_int = 0;
+        let ptr = unsafe { opus_decoder_create(sample_rate as i32, 1, &mut error) };
+        if error != OPUS_OK {
+            if !ptr.is_null() {
+                unsafe { opus_decoder_destroy(ptr) };
+            }
+            return Err(format!("opus_decoder_create failed ({})", error));
+        }
+        NonNull::new(ptr)
+            .map(|decoder| Self { decoder })
+            .ok_or_else(|| "opus_decoder_create returned null".to_string())
+    }
+
+    /// Decode `data` (empty → PLC). With `fec`, the in-band FEC copy of the
+    /// previous frame carried in `data` is decoded instead; `frame_size` must
+    /// then be exactly the missing duration.
+    fn decode(&mut self, data: &[u8], frame_size: usize, fec: bool) -> Vec<i16> {
+        let mut output = vec![0i16; frame_size];
+        let data_ptr = if data.is_empty() {
+            std::ptr::null()
+        } else {
+            data.as_ptr()
+        };
+        let len = unsafe {
+            opus_decode(
+                self.decoder.as_ptr(),
+                data_ptr,
+                data.len() as i32,
+                output.as_mut_ptr(),
+                frame_size as c_int,
+                fec as c_int,
+            )
+        };
+        output.truncate(len.max(0) as usize);
+        output
+    }
+}
+
+impl Drop for OpusPlcDecoder {
+    fn drop(&mut self) {
+        unsafe { opus_decoder_destroy(self.decoder.as_ptr()) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repeat_and_fade_ramps_down_to_silence() {
+        let last = vec![1000i16; 160];
+
+        let first = repeat_and_fade(&last, 0);
+        assert_eq!(first[0], 1000);
+        assert!(first[159] < first[0] && first[159] > 700);
+
+        // Continuous across frames: the next frame starts where the previous ended
+        let second = repeat_and_fade(&last, 1);
+        assert!((second[0] - first[159]).abs() <= 10);
+
+        let silent = repeat_and_fade(&last, FADE_FRAMES);
+        assert!(silent.iter().all(|&s| s == 0));
+    }
+
+    #[test]
+    fn test_conceal_without_history_is_silence() {
+        let mut decoder = FrameDecoder::new(CodecType::PCMU, 8000, 160);
+        let (pcm, recovered) = decoder.conceal(None);
+        assert_eq!(pcm, vec![0i16; 160]);
+        assert!(!recovered);
+    }
+}
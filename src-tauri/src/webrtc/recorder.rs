@@ -0,0 +1,139 @@
+//! Call recording to WAV.
+//!
+//! Both directions are recorded at the codec sample rate, after decoding /
+//! before encoding, so no extra resampling is needed. The capture task drives
+//! the recording: every frame it sends is written together with whatever
+//! far-end audio the playback task queued since, padded with silence when the
+//! far end had nothing for that frame.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::BufWriter;
+
+use serde::{Deserialize, Serialize};
+
+/// Far-end audio queued beyond this many frames is dropped, keeping both sides aligned
+const MAX_REMOTE_LAG_FRAMES: usize = 3;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordingMode {
+    /// Both sides mixed into one channel
+    #[default]
+    Mixed,
+    /// Local mic on the left channel, far end on the right
+    Stereo,
+}
+
+pub struct CallRecorder {
+    writer: hound::WavWriter<BufWriter<File>>,
+    mode: RecordingMode,
+    path: String,
+    /// Far-end samples not yet written
+    remote: VecDeque<i16>,
+}
+
+impl CallRecorder {
+    pub fn create(path: &str, mode: RecordingMode, sample_rate: u32) -> Result<Self, String> {
+        let spec = hound::WavSpec {
+            channels: match mode {
+                RecordingMode::Mixed => 1,
+                RecordingMode::Stereo => 2,
+            },
+            sample_rate,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let writer = hound::WavWriter::create(path, spec)
+            .map_err(|e| format!("Cannot create recording '{}': {}", path, e))?;
+        Ok(Self {
+            writer,
+            mode,
+            path: path.to_string(),
+            remote: VecDeque::new(),
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Queue decoded far-end audio (called from the playback task).
+    pub fn push_remote(&mut self, pcm: &[i16]) {
+        self.remote.extend(pcm);
+    }
+
+    /// Write one frame of local audio (called from the capture task) along
+    /// with the same number of queued far-end samples.
+    pub fn write_local(&mut self, pcm: &[i16]) -> Result<(), String> {
+        let max_remote = pcm.len() * MAX_REMOTE_LAG_FRAMES;
+        if self.remote.len() > max_remote {
+            self.remote.drain(..self.remote.len() - max_remote);
+        }
+        for &local in pcm {
+            let remote = self.remote.pop_front().unwrap_or(0);
+            let result = match self.mode {
+                RecordingMode::Mixed => self
+                    .writer
+                    .write_sample((local as i32 + remote as i32).clamp(-32768, 32767) as i16),
+                RecordingMode::Stereo => self
+                    .writer
+                    .write_sample(local)
+                    .and_then(|_| self.writer.write_sample(remote)),
+            };
+            result.map_err(|e| format!("Recording write failed: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// Flush and close the file, writing the final WAV header.
+    pub fn finish(self) -> Result<String, String> {
+        let path = self.path;
+        self.writer
+            .finalize()
+            .map_err(|e| format!("Failed to finish recording '{}': {}", path, e))?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_wav(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("{}-{}.wav", name, std::process::id()))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn test_stereo_recording_pads_missing_remote_with_silence() {
+        let path = temp_wav("stereo-recording");
+        let mut recorder = CallRecorder::create(&path, RecordingMode::Stereo, 8000).unwrap();
+        recorder.push_remote(&[7, 8]);
+        recorder.write_local(&[1, 2, 3]).unwrap();
+        recorder.finish().unwrap();
+
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.spec().channels, 2);
+        let samples: Vec<i16> = reader.samples::<i16>().map(|s| s.unwrap()).collect();
+        assert_eq!(samples, vec![1, 7, 2, 8, 3, 0]);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_mixed_recording_sums_and_clamps() {
+        let path = temp_wav("mixed-recording");
+        let mut recorder = CallRecorder::create(&path, RecordingMode::Mixed, 8000).unwrap();
+        recorder.push_remote(&[10, 30000]);
+        recorder.write_local(&[5, 30000]).unwrap();
+        recorder.finish().unwrap();
+
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.spec().channels, 1);
+        let samples: Vec<i16> = reader.samples::<i16>().map(|s| s.unwrap()).collect();
+        assert_eq!(samples, vec![15, 32767]);
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,247 @@
+//! Local ringtone for incoming calls.
+//!
+//! The ringtone plays on its own cpal output stream, owned by a dedicated
+//! thread, so it never touches the playback stream of an active call; the OS
+//! mixer combines both when a second call rings during a conversation.
+
+use std::sync::mpsc;
+use std::sync::Mutex;
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SampleFormat, SizedSample, StreamConfig};
+use serde::Serialize;
+use tracing::{debug, error, info, warn};
+
+use super::audio_bridge::find_device_by_id;
+
+/// Sample rate of the built-in ring pattern
+const BUILTIN_RATE: u32 = 16000;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct RingtoneConfig {
+    pub enabled: bool,
+    /// WAV file to loop; `None` uses the built-in ring pattern
+    pub path: Option<String>,
+}
+
+impl Default for RingtoneConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            path: None,
+        }
+    }
+}
+
+/// Mono samples (-1.0 - 1.0) looped by the ringtone stream (and by music on hold).
+pub(super) struct LoopSamples {
+    pub(super) samples: Vec<f32>,
+    pub(super) sample_rate: u32,
+}
+
+pub struct RingtonePlayer {
+    config: Mutex<RingtoneConfig>,
+    /// Dropping or signalling the sender stops the ringing thread
+    stop_tx: Mutex<Option<mpsc::Sender<()>>>,
+}
+
+impl Default for RingtonePlayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RingtonePlayer {
+    pub fn new() -> Self {
+        Self {
+            config: Mutex::new(RingtoneConfig::default()),
+            stop_tx: Mutex::new(None),
+        }
+    }
+
+    pub fn config(&self) -> RingtoneConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.config.lock().unwrap().enabled = enabled;
+        if !enabled {
+            self.stop();
+        }
+    }
+
+    /// Set the WAV file to use (`None` restores the built-in ring).
+    /// The file is decoded once here so a bad file is reported immediately.
+    pub fn set_path(&self, path: Option<String>) -> Result<(), String> {
+        if let Some(ref path) = path {
+            load_wav(path)?;
+        }
+        self.config.lock().unwrap().path = path;
+        Ok(())
+    }
+
+    /// Start ringing on `output_device` (default device if `None`) until `stop()`.
+    ///
+    /// `volume` scales the ringtone, e.g. to ring quietly while another call is
+    /// in progress. Does nothing when disabled or already ringing.
+    pub fn start(&self, output_device: Option<&str>, volume: f32) {
+        let config = self.config();
+        if !config.enabled {
+            return;
+        }
+        let mut stop_tx = self.stop_tx.lock().unwrap();
+        if stop_tx.is_some() {
+            return;
+        }
+
+        let ringtone = match config.path.as_deref() {
+            Some(path) => load_wav(path).unwrap_or_else(|e| {
+                warn!(path, error = %e, "Failed to load ringtone, using built-in ring");
+                builtin_ring()
+            }),
+            None => builtin_ring(),
+        };
+
+        let (tx, rx) = mpsc::channel::<()>();
+        let device_name = output_device.map(|s| s.to_string());
+        std::thread::spawn(move || {
+            match open_ringtone_stream(device_name.as_deref(), ringtone, volume) {
+                Ok(stream) => {
+                    // Block until stop() signals or drops the sender
+                    let _ = rx.recv();
+                    drop(stream);
+                    debug!("Ringtone stream closed");
+                }
+                Err(e) => warn!(error = %e, "Failed to play ringtone"),
+            }
+        });
+
+        *stop_tx = Some(tx);
+        info!(volume, "Ringtone started");
+    }
+
+    /// Stop ringing immediately (no-op if not ringing).
+    pub fn stop(&self) {
+        if let Some(tx) = self.stop_tx.lock().unwrap().take() {
+            let _ = tx.send(());
+            info!("Ringtone stopped");
+        }
+    }
+}
+
+/// Built-in ring: 440 + 480 Hz for 2 s, then 4 s of silence.
+fn builtin_ring() -> LoopSamples {
+    let rate = BUILTIN_RATE as usize;
+    let mut samples = vec![0.0f32; rate * 6];
+    for (i, s) in samples.iter_mut().take(rate * 2).enumerate() {
+        let t = i as f32 / BUILTIN_RATE as f32;
+        let tone = (2.0 * std::f32::consts::PI * 440.0 * t).sin()
+            + (2.0 * std::f32::consts::PI * 480.0 * t).sin();
+        *s = tone * 0.25;
+    }
+    LoopSamples {
+        samples,
+        sample_rate: BUILTIN_RATE,
+    }
+}
+
+/// Decode a WAV file into mono f32 samples.
+pub(super) fn load_wav(path: &str) -> Result<LoopSamples, String> {
+    let mut reader =
+        hound::WavReader::open(path).map_err(|e| format!("Invalid WAV file '{}': {}", path, e))?;
+    let spec = reader.spec();
+    let channels = spec.channels.max(1) as usize;
+
+    let interleaved: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader
+            .samples::<f32>()
+            .collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to decode '{}': {}", path, e))?,
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample.max(1) - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|s| s.map(|v| v as f32 / scale))
+                .collect::<Result<_, _>>()
+                .map_err(|e| format!("Failed to decode '{}': {}", path, e))?
+        }
+    };
+
+    let samples: Vec<f32> = interleaved
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect();
+    if samples.is_empty() {
+        return Err(format!("'{}' contains no audio", path));
+    }
+
+    Ok(LoopSamples {
+        samples,
+        sample_rate: spec.sample_rate,
+    })
+}
+
+/// Open an output stream that loops `ringtone` until dropped.
+fn open_ringtone_stream(
+    device_name: Option<&str>,
+    ringtone: LoopSamples,
+    volume: f32,
+) -> Result<cpal::Stream, String> {
+    let host = cpal::default_host();
+    let device = if let Some(name) = device_name {
+        find_device_by_id(&host, name)?
+    } else {
+        host.default_output_device()
+            .ok_or_else(|| "No default output device".to_string())?
+    };
+
+    let supported_config = device
+        .default_output_config()
+        .map_err(|e| format!("No output config: {}", e))?;
+    let stream_config = StreamConfig {
+        channels: supported_config.channels(),
+        sample_rate: supported_config.sample_rate(),
+        buffer_size: cpal::BufferSize::Default,
+    };
+
+    // Samples are produced as f32 and converted to the device's format
+    let stream = match supported_config.sample_format() {
+        SampleFormat::F32 => build_loop_stream::<f32>(&device, &stream_config, ringtone, volume),
+        SampleFormat::I16 => build_loop_stream::<i16>(&device, &stream_config, ringtone, volume),
+        SampleFormat::U16 => build_loop_stream::<u16>(&device, &stream_config, ringtone, volume),
+        SampleFormat::I32 => build_loop_stream::<i32>(&device, &stream_config, ringtone, volume),
+        fmt => return Err(format!("Unsupported ringtone sample format: {:?}", fmt)),
+    }
+    .map_err(|e| format!("Failed to build ringtone stream: {}", e))?;
+
+    stream
+        .play()
+        .map_err(|e| format!("Failed to start ringtone: {}", e))?;
+    Ok(stream)
+}
+
+fn build_loop_stream<T: SizedSample + FromSample<f32>>(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    ringtone: LoopSamples,
+    volume: f32,
+) -> Result<cpal::Stream, cpal::BuildStreamError> {
+    let channels = config.channels as usize;
+    // Step through the source at its own rate (nearest-sample resampling is fine for a ringtone)
+    let step = ringtone.sample_rate as f64 / config.sample_rate as f64;
+    let len = ringtone.samples.len();
+    let mut pos = 0.0f64;
+
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+            for frame in data.chunks_mut(channels) {
+                let s = ringtone.samples[pos as usize % len] * volume;
+                frame.fill(T::from_sample(s));
+                pos = (pos + step) % len as f64;
+            }
+        },
+        |err| error!("Ringtone stream error: {}", err),
+        None,
+    )
+}
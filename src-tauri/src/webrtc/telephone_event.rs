@@ -0,0 +1,172 @@
+//! Receiving RFC 4733 telephone-events (DTMF sent by the far end, e.g. an IVR).
+//!
+//! A keypress arrives as a series of packets sharing one RTP timestamp, with
+//! a growing duration and (usually three) final packets carrying the End bit.
+//! `DtmfDetector` turns that series into a single digit.
+
+/// telephone-event clock rate (timestamp units per second)
+const EVENT_CLOCK_RATE: u32 = 8000;
+
+/// One decoded telephone-event payload.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TelephoneEvent {
+    pub event: u8,
+    pub end: bool,
+    pub volume: u8,
+    /// In timestamp units
+    pub duration: u16,
+}
+
+/// Decode the 4-byte RFC 4733 payload (see `build_dtmf_payload` for the layout).
+pub fn parse(payload: &[u8]) -> Option<TelephoneEvent> {
+    if payload.len() < 4 {
+        return None;
+    }
+    Some(TelephoneEvent {
+        event: payload[0],
+        end: payload[1] & 0x80 != 0,
+        volume: payload[1] & 0x3F,
+        duration: u16::from_be_bytes([payload[2], payload[3]]),
+    })
+}
+
+/// DTMF digit of an event code (0-15); other events (e.g. fax tones) are ignored.
+pub fn event_digit(event: u8) -> Option<char> {
+    match event {
+        0..=9 => Some((b'0' + event) as char),
+        10 => Some('*'),
+        11 => Some('#'),
+        12..=15 => Some((b'A' + event - 12) as char),
+        _ => None,
+    }
+}
+
+/// A completed keypress.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReceivedDtmf {
+    pub digit: char,
+    pub duration_ms: u32,
+}
+
+/// Debounces telephone-event packets into one `ReceivedDtmf` per keypress.
+#[derive(Default)]
+pub struct DtmfDetector {
+    /// Event in progress: (RTP timestamp, digit, duration so far)
+    current: Option<(u32, char, u16)>,
+    /// Timestamp of the last reported event, so its End retransmissions are ignored
+    last_reported: Option<u32>,
+}
+
+impl DtmfDetector {
+    /// Feed one packet; returns the digit when a keypress completes (on its
+    /// first End packet, or when a new event starts before the End was seen).
+    pub fn process(&mut self, rtp_timestamp: u32, payload: &[u8]) -> Option<ReceivedDtmf> {
+        let event = parse(payload)?;
+        let digit = event_digit(event.event)?;
+        if self.last_reported == Some(rtp_timestamp) {
+            return None;
+        }
+
+        // A new event whose predecessor never sent End: report the predecessor now
+        let mut interrupted = None;
+        if let Some((ts, prev_digit, prev_duration)) = self.current {
+            if ts != rtp_timestamp {
+                self.current = None;
+                self.last_reported = Some(ts);
+                interrupted = Some(Self::completed(prev_digit, prev_duration));
+            }
+        }
+
+        // Only one digit can be returned at a time: after reporting an interrupted
+        // event, this one stays pending and is reported by its End retransmission
+        if event.end && interrupted.is_none() {
+            self.current = None;
+            self.last_reported = Some(rtp_timestamp);
+            return Some(Self::completed(digit, event.duration));
+        }
+        self.current = Some((rtp_timestamp, digit, event.duration));
+        interrupted
+    }
+
+    fn completed(digit: char, duration: u16) -> ReceivedDtmf {
+        ReceivedDtmf {
+            digit,
+            duration_ms: duration as u32 * 1000 / EVENT_CLOCK_RATE,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(event: u8, end: bool, duration: u16) -> Vec<u8> {
+        let d = duration.to_be_bytes();
+        vec![event, if end { 0x80 | 10 } else { 10 }, d[0], d[1]]
+    }
+
+    #[test]
+    fn test_parse_payload() {
+        assert_eq!(
+            parse(&packet(11, true, 1280)),
+            Some(TelephoneEvent {
+                event: 11,
+                end: true,
+                volume: 10,
+                duration: 1280,
+            })
+        );
+        assert_eq!(parse(&[1, 2]), None);
+        assert_eq!(event_digit(11), Some('#'));
+        assert_eq!(event_digit(13), Some('B'));
+        assert_eq!(event_digit(32), None);
+    }
+
+    #[test]
+    fn test_one_digit_per_keypress() {
+        let mut detector = DtmfDetector::default();
+        let mut digits = Vec::new();
+        for i in 1..=5u16 {
+            digits.extend(detector.process(1000, &packet(5, false, i * 160)));
+        }
+        // End packet sent three times
+        for _ in 0..3 {
+            digits.extend(detector.process(1000, &packet(5, true, 1280)));
+        }
+        // Same digit pressed again: new timestamp
+        digits.extend(detector.process(5000, &packet(5, false, 160)));
+        digits.extend(detector.process(5000, &packet(5, true, 800)));
+
+        assert_eq!(
+            digits,
+            vec![
+                ReceivedDtmf {
+                    digit: '5',
+                    duration_ms: 160
+                },
+                ReceivedDtmf {
+                    digit: '5',
+                    duration_ms: 100
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_missing_end_reported_when_next_event_starts() {
+        let mut detector = DtmfDetector::default();
+        assert_eq!(detector.process(1000, &packet(1, false, 480)), None);
+        let first = detector.process(2000, &packet(2, false, 160));
+        assert_eq!(first.map(|d| d.digit), Some('1'));
+        let second = detector.process(2000, &packet(2, true, 640));
+        assert_eq!(second.map(|d| d.digit), Some('2'));
+
+        // End of the next digit arriving with the interrupted one: reported on retransmission
+        assert_eq!(detector.process(3000, &packet(3, false, 160)), None);
+        let third = detector.process(4000, &packet(4, true, 320));
+        assert_eq!(third.map(|d| d.digit), Some('3'));
+        let fourth = detector.process(4000, &packet(4, true, 320));
+        assert_eq!(fourth.map(|d| d.digit), Some('4'));
+        assert_eq!(detector.process(4000, &packet(4, true, 320)), None);
+    }
+}
@@ -0,0 +1,162 @@
+//! Voice activity detection for the capture path.
+//!
+//! Frames whose energy stays below a threshold for `HANGOVER_MS` are not sent,
+//! saving bandwidth during silence. The first frame sent after a pause carries
+//! the RTP marker bit (RFC 3551 §4.1) so the far end resynchronises its
+//! playout. No comfort noise is sent; receivers play silence or conceal the gap.
+
+use serde::{Deserialize, Serialize};
+
+/// Silence that must last this long before sending stops, so word endings
+/// and short pauses inside speech still go out
+const HANGOVER_MS: u32 = 300;
+
+/// Threshold at sensitivity 0: only loud audio counts as speech
+const LEAST_SENSITIVE_DBFS: f32 = -30.0;
+/// Threshold at sensitivity 100: nearly anything above the noise floor counts
+const MOST_SENSITIVE_DBFS: f32 = -60.0;
+
+/// VAD settings for outgoing audio.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct VadConfig {
+    pub enabled: bool,
+    /// 0 - 100: higher values treat quieter audio as speech
+    pub sensitivity: u8,
+}
+
+impl Default for VadConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sensitivity: 50,
+        }
+    }
+}
+
+impl VadConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.sensitivity > 100 {
+            return Err("VAD sensitivity must be between 0 and 100".to_string());
+        }
+        Ok(())
+    }
+
+    /// RMS level (dBFS) below which a frame is silence
+    fn threshold_dbfs(&self) -> f32 {
+        let t = self.sensitivity.min(100) as f32 / 100.0;
+        LEAST_SENSITIVE_DBFS + (MOST_SENSITIVE_DBFS - LEAST_SENSITIVE_DBFS) * t
+    }
+}
+
+/// Decides, frame by frame, whether outgoing audio is sent.
+pub struct VoiceActivityDetector {
+    hangover_frames: u32,
+    /// Consecutive silent frames so far
+    silent_run: u32,
+    /// Sending is currently suppressed
+    suppressed: bool,
+}
+
+impl VoiceActivityDetector {
+    pub fn new(frame_duration_ms: u32) -> Self {
+        Self {
+            hangover_frames: HANGOVER_MS.div_ceil(frame_duration_ms.max(1)),
+            silent_run: 0,
+            suppressed: false,
+        }
+    }
+
+    /// `None` when the frame is to be dropped, otherwise `Some(marker)` with the
+    /// RTP marker bit. `force` (e.g. a DTMF tone in the frame) always sends.
+    pub fn gate(&mut self, pcm: &[f32], config: &VadConfig, force: bool) -> Option<bool> {
+        let speech = !config.enabled || force || rms_dbfs(pcm) >= config.threshold_dbfs();
+        if speech {
+            self.silent_run = 0;
+            let marker = self.suppressed;
+            self.suppressed = false;
+            return Some(marker);
+        }
+
+        self.silent_run = self.silent_run.saturating_add(1);
+        if self.silent_run > self.hangover_frames {
+            self.suppressed = true;
+            None
+        } else {
+            Some(false)
+        }
+    }
+}
+
+/// RMS level of `pcm` (full scale = 1.0) in dBFS
+fn rms_dbfs(pcm: &[f32]) -> f32 {
+    if pcm.is_empty() {
+        return f32::NEG_INFINITY;
+    }
+    let mean_square = pcm.iter().map(|s| s * s).sum::<f32>() / pcm.len() as f32;
+    10.0 * mean_square.log10()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENABLED: VadConfig = VadConfig {
+        enabled: true,
+        sensitivity: 50,
+    };
+
+    #[test]
+    fn test_silence_suppressed_after_hangover_then_marker_on_speech() {
+        let mut vad = VoiceActivityDetector::new(20);
+        let silence = vec![0.0f32; 160];
+        let speech = vec![0.3f32; 160];
+
+        // 300 ms hangover at 20 ms frames: 15 silent frames still go out
+        for _ in 0..15 {
+            assert_eq!(vad.gate(&silence, &ENABLED, false), Some(false));
+        }
+        assert_eq!(vad.gate(&silence, &ENABLED, false), None);
+        assert_eq!(vad.gate(&speech, &ENABLED, false), Some(true));
+        assert_eq!(vad.gate(&speech, &ENABLED, false), Some(false));
+    }
+
+    #[test]
+    fn test_forced_and_disabled_always_send() {
+        let mut vad = VoiceActivityDetector::new(20);
+        let silence = vec![0.0f32; 160];
+        for _ in 0..20 {
+            vad.gate(&silence, &ENABLED, false);
+        }
+        assert_eq!(vad.gate(&silence, &ENABLED, true), Some(true));
+
+        let disabled = VadConfig::default();
+        for _ in 0..20 {
+            assert!(vad.gate(&silence, &disabled, false).is_some());
+        }
+    }
+
+    #[test]
+    fn test_sensitivity_moves_threshold() {
+        let quiet = vec![0.005f32; 160]; // about -46 dBFS
+        let mut vad = VoiceActivityDetector::new(20);
+        let low = VadConfig {
+            enabled: true,
+            sensitivity: 0,
+        };
+        let high = VadConfig {
+            enabled: true,
+            sensitivity: 100,
+        };
+        for _ in 0..20 {
+            vad.gate(&quiet, &low, false);
+        }
+        assert_eq!(vad.gate(&quiet, &low, false), None);
+        assert_eq!(vad.gate(&quiet, &high, false), Some(true));
+        assert!(VadConfig {
+            enabled: true,
+            sensitivity: 101
+        }
+        .validate()
+        .is_err());
+    }
+}
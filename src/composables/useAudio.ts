@@ -1,5 +1,6 @@
 import { ref } from 'vue'
 import { invoke } from '@tauri-apps/api/core'
+import { errorMessage } from '@/utils/appError'
 
 export interface MediaDevice {
   name: string
@@ -91,7 +92,7 @@ export function useAudio() {
 
       console.debug('[Audio] Selected mic:', selectedMic.value, '| speaker:', selectedSpeaker.value)
     } catch (e) {
-      deviceError.value = `设备枚举失败: ${e}`
+      deviceError.value = `设备枚举失败: ${errorMessage(e)}`
       console.error('[Audio] enumerate_audio_devices failed:', e)
     }
   }
@@ -2,10 +2,12 @@ import { ref } from 'vue'
 import { invoke } from '@tauri-apps/api/core'
 import { listen } from '@tauri-apps/api/event'
 import { useAudio } from './useAudio'
+import { errorMessage } from '@/utils/appError'
 
-export type CallState = 'idle' | 'calling' | 'ringing' | 'connected' | 'incoming' | 'ended'
+export type CallState = 'idle' | 'calling' | 'trying' | 'progress' | 'ringing' | 'connected' | 'incoming' | 'ended'
 
 export interface IncomingCallPayload {
+  account_id: string
   call_id: string
   caller: string
   callee?: string
@@ -29,7 +31,7 @@ async function setupListeners() {
       (event) => {
         console.debug('[Call] call-state event:', event.payload)
         const s = event.payload.state
-        if (s === 'calling' || s === 'ringing' || s === 'connected' || s === 'ended' || s === 'incoming') {
+        if (s === 'calling' || s === 'trying' || s === 'progress' || s === 'ringing' || s === 'connected' || s === 'ended' || s === 'incoming') {
           callState.value = s as CallState
         }
         if (s === 'ended') {
@@ -83,7 +85,7 @@ export function useSipCall() {
       await invoke('sip_make_call', { callee: number })
       console.debug('[Call] Call established')
     } catch (e) {
-      error.value = String(e)
+      error.value = errorMessage(e)
       callState.value = 'idle'
       console.error('[Call] Dial failed:', e)
       throw e
@@ -119,7 +121,7 @@ export function useSipCall() {
       incomingCall.value = null
       callState.value = 'connected'
     } catch (e) {
-      error.value = String(e)
+      error.value = errorMessage(e)
       console.error('[Call] Answer failed:', e)
       throw e
     }
@@ -141,7 +143,7 @@ export function useSipCall() {
       incomingCall.value = null
       callState.value = 'idle'
     } catch (e) {
-      error.value = String(e)
+      error.value = errorMessage(e)
       console.error('[Call] Reject failed:', e)
       throw e
     }
@@ -153,7 +155,7 @@ export function useSipCall() {
       await invoke('send_dtmf', { digit })
       console.debug('[Call] DTMF sent:', digit)
     } catch (e) {
-      error.value = String(e)
+      error.value = errorMessage(e)
       console.error('[Call] DTMF failed:', e)
       throw e
     }
@@ -1,6 +1,7 @@
 import { ref } from 'vue'
 import { invoke } from '@tauri-apps/api/core'
 import { listen } from '@tauri-apps/api/event'
+import { errorMessage } from '@/utils/appError'
 
 const EXTENSION_STORAGE_KEY = 'sip-extension'
 
@@ -39,15 +40,19 @@ function clearExtension() {
 
 async function setupListener() {
   if (unlisten) return
-  unlisten = await listen<{ status: string; message?: string }>(
+  unlisten = await listen<{
+    status: string
+    message?: string
+    code?: number
+    reason?: string
+  }>(
     'sip://registration-status',
     (event) => {
       console.debug('[SIP] registration-status event:', event.payload)
-      if (event.payload.status === 'registered') {
-        isRegistered.value = true
-      } else {
-        isRegistered.value = false
-      }
+      // A refresh in progress does not end the current registration
+      isRegistered.value = ['registered', 'refreshing', 'refreshed'].includes(
+        event.payload.status
+      )
     }
   )
 }
@@ -86,7 +91,7 @@ export function useSipRegistration() {
       saveExtension(username) // 保存分机号
       console.debug('[SIP] Registration successful')
     } catch (e) {
-      error.value = String(e)
+      error.value = errorMessage(e)
       isRegistered.value = false
       console.error('[SIP] Registration failed:', e)
       throw e
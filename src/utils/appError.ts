@@ -0,0 +1,31 @@
+/**
+ * Rust 命令返回的错误 (AppError)
+ * code 供程序判断，message 用于显示
+ */
+export interface AppError {
+  code:
+    | 'not_registered'
+    | 'already_registered'
+    | 'no_active_call'
+    | 'call_not_found'
+    | 'device_not_found'
+    | 'registration_failed'
+    | 'auth_failed'
+    | 'timeout'
+    | 'transport'
+    | 'media_failed'
+    | 'call_rejected'
+    | 'error'
+  message: string
+  /** 导致错误的 SIP 响应码，如 403、408 */
+  status?: number
+}
+
+export function isAppError(e: unknown): e is AppError {
+  return typeof e === 'object' && e !== null && 'code' in e && 'message' in e
+}
+
+/** 取出可显示的错误信息 */
+export function errorMessage(e: unknown): string {
+  return isAppError(e) ? e.message : String(e)
+}